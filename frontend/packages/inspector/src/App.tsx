@@ -4,6 +4,7 @@ import {
   SandboxAgent,
   type AgentInfo,
   type CreateSessionRequest,
+  type InstallJob,
   type AgentModelInfo,
   type AgentModeInfo,
   type PermissionEventData,
@@ -22,7 +23,7 @@ import type { RequestLog } from "./types/requestLog";
 import { buildCurl } from "./utils/http";
 
 const logoUrl = `${import.meta.env.BASE_URL}logos/sandboxagent.svg`;
-const defaultAgents = ["claude", "codex", "opencode", "amp", "mock"];
+const defaultAgents = ["claude", "codex", "opencode", "amp", "gemini", "acp", "mock"];
 
 type ItemEventData = {
   item: UniversalItem;
@@ -353,9 +354,21 @@ export default function App() {
     }
   };
 
-  const installAgent = async (targetId: string, reinstall: boolean) => {
+  const installAgent = async (
+    targetId: string,
+    reinstall: boolean,
+    onProgress: (job: InstallJob) => void
+  ) => {
     try {
-      await getClient().installAgent(targetId, { reinstall });
+      let job = await getClient().startAgentInstall(targetId, { reinstall });
+      onProgress(job);
+      for await (const update of getClient().streamInstallJob(job.id)) {
+        job = update;
+        onProgress(job);
+      }
+      if (job.status === "failed") {
+        setConnectError(job.error?.detail ?? "Install failed");
+      }
       await refreshAgents();
     } catch (error) {
       setConnectError(getErrorMessage(error, "Install failed"));
@@ -939,6 +952,8 @@ export default function App() {
     codex: "Codex",
     opencode: "OpenCode",
     amp: "Amp",
+    gemini: "Gemini CLI",
+    acp: "ACP",
     mock: "Mock"
   };
   const agentLabel = agentDisplayNames[agentId] ?? agentId;
@@ -15,6 +15,8 @@ const agentLabels: Record<string, string> = {
   codex: "Codex",
   opencode: "OpenCode",
   amp: "Amp",
+  gemini: "Gemini CLI",
+  acp: "ACP",
   mock: "Mock"
 };
 
@@ -1,8 +1,35 @@
 import { Download, Loader2, RefreshCw } from "lucide-react";
 import { useState } from "react";
-import type { AgentInfo, AgentModeInfo } from "sandbox-agent";
+import type { AgentInfo, AgentModeInfo, InstallJob } from "sandbox-agent";
 import FeatureCoverageBadges from "../agents/FeatureCoverageBadges";
 import { emptyFeatureCoverage } from "../../types/agents";
+import { formatBytes } from "../../utils/format";
+
+const InstallProgress = ({ job }: { job: InstallJob }) => {
+  const progress = job.progress;
+  if (job.status === "queued") {
+    return <div className="card-meta install-progress">Waiting for another install to finish...</div>;
+  }
+  if (!progress) {
+    return null;
+  }
+  const total = progress.totalBytes ?? null;
+  const percent =
+    progress.phase === "extracting" ? 100 : total ? Math.min(100, (progress.downloadedBytes / total) * 100) : 0;
+  const size = total
+    ? `${formatBytes(progress.downloadedBytes)} / ${formatBytes(total)}`
+    : formatBytes(progress.downloadedBytes);
+  return (
+    <div className="install-progress">
+      <div className="install-progress-track">
+        <div className="install-progress-fill" style={{ width: `${percent}%` }} />
+      </div>
+      <div className="card-meta">
+        {progress.phase === "extracting" ? `Extracting (${size})` : `Downloading ${size}`}
+      </div>
+    </div>
+  );
+};
 
 const AgentsTab = ({
   agents,
@@ -17,18 +44,24 @@ const AgentsTab = ({
   defaultAgents: string[];
   modesByAgent: Record<string, AgentModeInfo[]>;
   onRefresh: () => void;
-  onInstall: (agentId: string, reinstall: boolean) => Promise<void>;
+  onInstall: (
+    agentId: string,
+    reinstall: boolean,
+    onProgress: (job: InstallJob) => void
+  ) => Promise<void>;
   loading: boolean;
   error: string | null;
 }) => {
   const [installingAgent, setInstallingAgent] = useState<string | null>(null);
+  const [installJob, setInstallJob] = useState<InstallJob | null>(null);
 
   const handleInstall = async (agentId: string, reinstall: boolean) => {
     setInstallingAgent(agentId);
     try {
-      await onInstall(agentId, reinstall);
+      await onInstall(agentId, reinstall, setInstallJob);
     } finally {
       setInstallingAgent(null);
+      setInstallJob(null);
     }
   };
 
@@ -85,6 +118,7 @@ const AgentsTab = ({
                 Modes: {modesByAgent[agent.id].map((mode) => mode.id).join(", ")}
               </div>
             )}
+            {isInstalling && installJob && <InstallProgress job={installJob} />}
             <div className="card-actions">
               <button
                 className="button secondary small"
@@ -1,5 +1,5 @@
 import { Cloud, PlayCircle, Terminal } from "lucide-react";
-import type { AgentInfo, AgentModeInfo, UniversalEvent } from "sandbox-agent";
+import type { AgentInfo, AgentModeInfo, InstallJob, UniversalEvent } from "sandbox-agent";
 import AgentsTab from "./AgentsTab";
 import EventsTab from "./EventsTab";
 import RequestLogTab from "./RequestLogTab";
@@ -40,7 +40,11 @@ const DebugPanel = ({
   defaultAgents: string[];
   modesByAgent: Record<string, AgentModeInfo[]>;
   onRefreshAgents: () => void;
-  onInstallAgent: (agentId: string, reinstall: boolean) => Promise<void>;
+  onInstallAgent: (
+    agentId: string,
+    reinstall: boolean,
+    onProgress: (job: InstallJob) => void
+  ) => Promise<void>;
   agentsLoading: boolean;
   agentsError: string | null;
 }) => {
@@ -15,4 +15,10 @@ export const formatTime = (value: string) => {
   return date.toLocaleTimeString();
 };
 
+export const formatBytes = (bytes: number) => {
+  if (bytes < 1024) return `${bytes} B`;
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`;
+};
+
 export const escapeSingleQuotes = (value: string) => value.replace(/'/g, `'\\''`);
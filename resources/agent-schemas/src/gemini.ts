@@ -0,0 +1,59 @@
+import { createNormalizedSchema, type NormalizedSchema } from "./normalize.js";
+import type { JSONSchema7 } from "json-schema";
+
+// Events printed by `gemini --output-format stream-json`, one JSON object per line.
+// Mirrors packages/core/src/output/types.ts in google-gemini/gemini-cli, which is not
+// published as a standalone type package.
+export async function extractGeminiSchema(): Promise<NormalizedSchema> {
+  console.log("Extracting Gemini CLI schema...");
+
+  const definitions: Record<string, JSONSchema7> = {
+    StreamJsonEvent: {
+      type: "object",
+      properties: {
+        type: {
+          type: "string",
+          enum: ["init", "message", "tool_use", "tool_result", "error", "result"],
+        },
+        timestamp: { type: "string" },
+        session_id: { type: "string" },
+        model: { type: "string" },
+        role: { type: "string", enum: ["user", "assistant"] },
+        content: { type: "string" },
+        delta: { type: "boolean" },
+        tool_name: { type: "string" },
+        tool_id: { type: "string" },
+        parameters: { type: "object" },
+        status: { type: "string", enum: ["success", "error"] },
+        output: { type: "string" },
+        severity: { type: "string", enum: ["warning", "error"] },
+        message: { type: "string" },
+        error: { $ref: "#/definitions/StreamJsonError" },
+        stats: { $ref: "#/definitions/StreamStats" },
+      },
+      required: ["type"],
+    },
+    StreamJsonError: {
+      type: "object",
+      properties: {
+        type: { type: "string" },
+        message: { type: "string" },
+      },
+      required: ["message"],
+    },
+    StreamStats: {
+      type: "object",
+      properties: {
+        total_tokens: { type: "integer", minimum: 0 },
+        input_tokens: { type: "integer", minimum: 0 },
+        output_tokens: { type: "integer", minimum: 0 },
+        duration_ms: { type: "integer", minimum: 0 },
+        tool_calls: { type: "integer", minimum: 0 },
+      },
+    },
+  };
+
+  console.log(`  [ok] Using embedded schema with ${Object.keys(definitions).length} definitions`);
+
+  return createNormalizedSchema("gemini", "Gemini CLI Schema", definitions);
+}
@@ -4,8 +4,13 @@ import type {
   AgentListResponse,
   AgentModelsResponse,
   AgentModesResponse,
+  AgentUninstallQuery,
+  AgentUpdatesResponse,
   CreateSessionRequest,
   CreateSessionResponse,
+  CreateSessionTokenRequest,
+  CreateSessionTokenResponse,
+  DiagnosticsResponse,
   EventsQuery,
   EventsResponse,
   FsActionResponse,
@@ -21,10 +26,15 @@ import type {
   FsUploadBatchResponse,
   FsWriteResponse,
   HealthResponse,
+  InstallJob,
+  InstallJobListResponse,
+  LogsQuery,
+  LogsResponse,
   MessageRequest,
   PermissionReplyRequest,
   ProblemDetails,
   QuestionReplyRequest,
+  ReadinessResponse,
   SessionListResponse,
   TurnStreamQuery,
   UniversalEvent,
@@ -118,12 +128,77 @@ export class SandboxAgent {
     return this.requestJson("GET", `${API_PREFIX}/health`);
   }
 
+  async getReadiness(): Promise<ReadinessResponse> {
+    try {
+      return await this.requestJson("GET", `${API_PREFIX}/health/ready`);
+    } catch (error) {
+      // Not ready is a 503 carrying the same body.
+      if (error instanceof SandboxAgentError && error.status === 503) {
+        return (await error.response.json()) as ReadinessResponse;
+      }
+      throw error;
+    }
+  }
+
+  async getDiagnostics(): Promise<DiagnosticsResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/diagnostics`);
+  }
+
+  async getServerLogs(query?: Omit<LogsQuery, "follow">): Promise<LogsResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/logs/server`, { query });
+  }
+
+  async getServerLogsSse(query?: Omit<LogsQuery, "follow">, signal?: AbortSignal): Promise<Response> {
+    return this.requestRaw("GET", `${API_PREFIX}/logs/server`, {
+      query: { ...query, follow: true },
+      accept: "text/event-stream",
+      signal,
+    });
+  }
+
   async installAgent(agent: string, request: AgentInstallRequest = {}): Promise<void> {
     await this.requestJson("POST", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/install`, {
-      body: request,
+      body: { ...request, background: false },
+    });
+  }
+
+  /** Refused with `agent_in_use` while sessions run what would be removed. */
+  async uninstallAgent(agent: string, query: AgentUninstallQuery = {}): Promise<void> {
+    await this.requestJson("DELETE", `${API_PREFIX}/agents/${encodeURIComponent(agent)}`, { query });
+  }
+
+  async getAgentUpdates(agent: string): Promise<AgentUpdatesResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/updates`);
+  }
+
+  /** Starts an install without waiting for it; follow it with `streamInstallJob`. */
+  async startAgentInstall(agent: string, request: AgentInstallRequest = {}): Promise<InstallJob> {
+    return this.requestJson("POST", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/install`, {
+      body: { ...request, background: true },
+    });
+  }
+
+  async listInstallJobs(): Promise<InstallJobListResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/install-jobs`);
+  }
+
+  async getInstallJob(jobId: string): Promise<InstallJob> {
+    return this.requestJson("GET", `${API_PREFIX}/install-jobs/${encodeURIComponent(jobId)}`);
+  }
+
+  async getInstallJobEventsSse(jobId: string, signal?: AbortSignal): Promise<Response> {
+    return this.requestRaw("GET", `${API_PREFIX}/install-jobs/${encodeURIComponent(jobId)}/events`, {
+      accept: "text/event-stream",
+      signal,
     });
   }
 
+  /** Yields the job each time its status or progress changes, ending once it has finished. */
+  async *streamInstallJob(jobId: string, signal?: AbortSignal): AsyncGenerator<InstallJob, void, void> {
+    const response = await this.getInstallJobEventsSse(jobId, signal);
+    yield* this.parseSseStream<InstallJob>(response);
+  }
+
   async getAgentModes(agent: string): Promise<AgentModesResponse> {
     return this.requestJson("GET", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/modes`);
   }
@@ -132,6 +207,22 @@ export class SandboxAgent {
     return this.requestJson("GET", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/models`);
   }
 
+  async getAgentLogs(agent: string, query?: Omit<LogsQuery, "follow">): Promise<LogsResponse> {
+    return this.requestJson("GET", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/logs`, { query });
+  }
+
+  async getAgentLogsSse(
+    agent: string,
+    query?: Omit<LogsQuery, "follow">,
+    signal?: AbortSignal,
+  ): Promise<Response> {
+    return this.requestRaw("GET", `${API_PREFIX}/agents/${encodeURIComponent(agent)}/logs`, {
+      query: { ...query, follow: true },
+      accept: "text/event-stream",
+      signal,
+    });
+  }
+
   async createSession(sessionId: string, request: CreateSessionRequest): Promise<CreateSessionResponse> {
     return this.requestJson("POST", `${API_PREFIX}/sessions/${encodeURIComponent(sessionId)}`, {
       body: request,
@@ -162,6 +253,13 @@ export class SandboxAgent {
     });
   }
 
+  async getDebugLog(sessionId: string): Promise<string> {
+    const response = await this.requestRaw("GET", `${API_PREFIX}/sessions/${encodeURIComponent(sessionId)}/debug-log`, {
+      accept: "application/x-ndjson",
+    });
+    return response.text();
+  }
+
   async postMessageStream(
     sessionId: string,
     request: MessageRequest,
@@ -230,6 +328,15 @@ export class SandboxAgent {
     await this.requestJson("POST", `${API_PREFIX}/sessions/${encodeURIComponent(sessionId)}/terminate`);
   }
 
+  async createSessionToken(
+    sessionId: string,
+    request: CreateSessionTokenRequest = {},
+  ): Promise<CreateSessionTokenResponse> {
+    return this.requestJson("POST", `${API_PREFIX}/sessions/${encodeURIComponent(sessionId)}/tokens`, {
+      body: request,
+    });
+  }
+
   async listFsEntries(query?: FsEntriesQuery): Promise<FsEntry[]> {
     return this.requestJson("GET", `${API_PREFIX}/fs/entries`, { query });
   }
@@ -373,7 +480,7 @@ export class SandboxAgent {
     }
   }
 
-  private async *parseSseStream(response: Response): AsyncGenerator<UniversalEvent, void, void> {
+  private async *parseSseStream<T = UniversalEvent>(response: Response): AsyncGenerator<T, void, void> {
     if (!response.body) {
       throw new Error("SSE stream is not readable in this environment.");
     }
@@ -401,7 +508,7 @@ export class SandboxAgent {
             .map((line) => line.slice(5).trim())
             .join("\n");
           if (payload) {
-            yield JSON.parse(payload) as UniversalEvent;
+            yield JSON.parse(payload) as T;
           }
         }
         index = buffer.indexOf("\n\n");
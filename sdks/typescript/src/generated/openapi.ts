@@ -12,13 +12,31 @@ export interface paths {
      */
     get: operations["list_agents"];
   };
+  "/v1/agents/{agent}": {
+    /**
+     * Uninstall Agent
+     * @description Removes the agent's installs, or one version of it. Refused while sessions that
+     * have not ended use what would be removed; a shared server with no sessions left is
+     * stopped first. Binaries found on `PATH` are kept.
+     */
+    delete: operations["uninstall_agent"];
+  };
   "/v1/agents/{agent}/install": {
     /**
      * Install Agent
-     * @description Installs or updates a coding agent (e.g. claude, codex, opencode, amp).
+     * @description Installs or updates a coding agent (e.g. claude, codex, opencode, amp). Waits for
+     * the install unless `background` is set, in which case the job is returned to follow.
      */
     post: operations["install_agent"];
   };
+  "/v1/agents/{agent}/logs": {
+    /**
+     * Get Agent Logs
+     * @description Returns the stderr log of an agent's shared server process (OpenCode, Codex),
+     * with secrets masked. With `follow=true` the response is an SSE stream of `log` events.
+     */
+    get: operations["get_agent_logs"];
+  };
   "/v1/agents/{agent}/models": {
     /**
      * List Agent Models
@@ -33,6 +51,22 @@ export interface paths {
      */
     get: operations["get_agent_modes"];
   };
+  "/v1/agents/{agent}/updates": {
+    /**
+     * Check Agent Updates
+     * @description Compares the installed version with the newest one the agent's release host
+     * publishes, through the configured mirror if there is one.
+     */
+    get: operations["get_agent_updates"];
+  };
+  "/v1/diagnostics": {
+    /**
+     * Diagnostics
+     * @description Returns installed agents and versions, credential availability per provider, shared
+     * server status, disk space, active sessions, recent errors and readiness in one call.
+     */
+    get: operations["get_diagnostics"];
+  };
   "/v1/fs/entries": {
     /**
      * List Directory
@@ -94,6 +128,42 @@ export interface paths {
      */
     get: operations["get_health"];
   };
+  "/v1/health/ready": {
+    /**
+     * Readiness Check
+     * @description Reports whether the server can take new sessions: enough free disk in the install
+     * and log directories, no shared agent server in an error state, and room under the
+     * session cap. Responds 503 when any check fails.
+     */
+    get: operations["get_readiness"];
+  };
+  "/v1/install-jobs": {
+    /**
+     * List Install Jobs
+     * @description Returns running and queued installs and the most recent finished ones.
+     */
+    get: operations["list_install_jobs"];
+  };
+  "/v1/install-jobs/{job_id}": {
+    /** Get Install Job */
+    get: operations["get_install_job"];
+  };
+  "/v1/install-jobs/{job_id}/events": {
+    /**
+     * Follow Install Job (SSE)
+     * @description Sends the job as a `job` event now and whenever its status or progress changes.
+     * The stream ends after the event for the finished job.
+     */
+    get: operations["get_install_job_events"];
+  };
+  "/v1/logs/server": {
+    /**
+     * Get Server Logs
+     * @description Returns the server's own rotated logs. With `follow=true` the response is an SSE
+     * stream of `log` events.
+     */
+    get: operations["get_server_logs"];
+  };
   "/v1/sessions": {
     /**
      * List Sessions
@@ -108,6 +178,14 @@ export interface paths {
      */
     post: operations["create_session"];
   };
+  "/v1/sessions/{session_id}/debug-log": {
+    /**
+     * Get Debug Log
+     * @description Returns every native line exchanged with the agent since the session was created
+     * with `debugLog: true`, one JSON object per line.
+     */
+    get: operations["get_debug_log"];
+  };
   "/v1/sessions/{session_id}/events": {
     /**
      * Get Events
@@ -164,6 +242,13 @@ export interface paths {
      */
     post: operations["terminate_session"];
   };
+  "/v1/sessions/{session_id}/tokens": {
+    /**
+     * Create Session Token
+     * @description Mints a short-lived signed token limited to one session, for handing to browser clients.
+     */
+    post: operations["create_session_token"];
+  };
 }
 
 export type webhooks = Record<string, never>;
@@ -204,12 +289,22 @@ export interface components {
       credentialsAvailable: boolean;
       id: string;
       installed: boolean;
+      /** @description Versions installed side by side, selectable with `agentVersion`. */
+      installedVersions?: string[];
       path?: string | null;
+      /** @description Version pinned in the server's lockfile. */
+      pinnedVersion?: string | null;
       serverStatus?: components["schemas"]["ServerStatusInfo"] | null;
       version?: string | null;
     };
     AgentInstallRequest: {
+      /** @description Return the install job right away (202) instead of waiting for it to finish. */
+      background?: boolean | null;
       reinstall?: boolean | null;
+      /** @description Expected SHA-256 of the downloaded release file, checked instead of the published one. */
+      sha256?: string | null;
+      /** @description Version to install next to the others. Defaults to the pinned version, then the latest. */
+      version?: string | null;
     };
     AgentListResponse: {
       agents: components["schemas"]["AgentInfo"][];
@@ -232,11 +327,23 @@ export interface components {
     AgentModesResponse: {
       modes: components["schemas"]["AgentModeInfo"][];
     };
+    AgentUninstallQuery: {
+      /** @description Remove only this side-by-side version. */
+      version?: string | null;
+    };
     AgentUnparsedData: {
       error: string;
       location: string;
       raw_hash?: string | null;
     };
+    AgentUpdatesResponse: {
+      /** @description Version the binary sessions run by default reports; unset when it is not installed. */
+      installedVersion?: string | null;
+      /** @description Newest published version, as `version` on install takes it. */
+      latestVersion: string;
+      /** @description Whether `latestVersion` is newer than the installed one. */
+      updateAvailable: boolean;
+    };
     ContentPart: {
       text: string;
       /** @enum {string} */
@@ -282,7 +389,13 @@ export interface components {
       agent: string;
       agentMode?: string | null;
       agentVersion?: string | null;
+      /** @description Capture every native line exchanged with the agent for `GET /v1/sessions/{id}/debug-log`. */
+      debugLog?: boolean | null;
       directory?: string | null;
+      egress?: components["schemas"]["SessionEgressPolicy"] | null;
+      /** @description Give the session its own HOME/XDG directories. Defaults to the server setting. */
+      isolateHome?: boolean | null;
+      limits?: components["schemas"]["SessionResourceLimits"] | null;
       mcp?: {
         [key: string]: components["schemas"]["McpServerConfig"];
       } | null;
@@ -295,15 +408,67 @@ export interface components {
     CreateSessionResponse: {
       error?: components["schemas"]["AgentError"] | null;
       healthy: boolean;
+      /**
+       * @description Set when the agent's resource limits cannot use a cgroup and fall back to
+       * rlimits, which cap each process or user rather than the process tree.
+       */
+      limitsFallback?: string | null;
       nativeSessionId?: string | null;
     };
+    CreateSessionTokenRequest: {
+      /** @description Scopes granted to the token. Defaults to `read`, `messages:send` and `permissions:reply`. */
+      scopes?: components["schemas"]["TokenScope"][] | null;
+      /**
+       * Format: int64
+       * @description Token lifetime in seconds. Defaults to 900, at most 86400.
+       */
+      ttlSeconds?: number | null;
+    };
+    CreateSessionTokenResponse: {
+      /**
+       * Format: int64
+       * @description Expiry as Unix milliseconds.
+       */
+      expiresAt: number;
+      scopes: components["schemas"]["TokenScope"][];
+      token: string;
+    };
+    CredentialStatus: {
+      /** @description `api_key` or `oauth` */
+      authType?: string | null;
+      available: boolean;
+      provider: string;
+      /** @description Where the credential was found, such as `environment` or a config file */
+      source?: string | null;
+    };
+    DiagnosticsResponse: {
+      activeSessions: number;
+      agents: components["schemas"]["AgentInfo"][];
+      credentials: components["schemas"]["CredentialStatus"][];
+      disks: components["schemas"]["DiskSpaceInfo"][];
+      readiness: components["schemas"]["ReadinessResponse"];
+      /** @description Newest first */
+      recentErrors: components["schemas"]["RecentError"][];
+      /** Format: int64 */
+      uptimeMs: number;
+      version: string;
+    };
+    DiskSpaceInfo: {
+      /** Format: int64 */
+      availableBytes?: number | null;
+      /** @description `install` or `logs` */
+      name: string;
+      path: string;
+      /** Format: int64 */
+      totalBytes?: number | null;
+    };
     ErrorData: {
       code?: string | null;
       details?: unknown;
       message: string;
     };
     /** @enum {string} */
-    ErrorType: "invalid_request" | "unsupported_agent" | "agent_not_installed" | "install_failed" | "agent_process_exited" | "token_invalid" | "permission_denied" | "session_not_found" | "session_already_exists" | "mode_not_supported" | "stream_error" | "timeout";
+    ErrorType: "invalid_request" | "unsupported_agent" | "agent_not_installed" | "install_failed" | "agent_process_exited" | "token_invalid" | "permission_denied" | "session_not_found" | "session_already_exists" | "mode_not_supported" | "stream_error" | "timeout" | "rate_limited" | "agent_in_use";
     /** @enum {string} */
     EventSource: "agent" | "daemon";
     EventsQuery: {
@@ -380,6 +545,37 @@ export interface components {
     HealthResponse: {
       status: string;
     };
+    /** @description An agent install. Installs of one agent run one at a time, in the order requested. */
+    InstallJob: {
+      agent: string;
+      error?: components["schemas"]["ProblemDetails"] | null;
+      id: string;
+      /** @description Version reported by the installed binary. */
+      installedVersion?: string | null;
+      progress?: components["schemas"]["InstallJobProgress"] | null;
+      status: components["schemas"]["InstallJobStatus"];
+      /** @description Version requested; unset installs the pinned or latest version. */
+      version?: string | null;
+    };
+    InstallJobListResponse: {
+      jobs: components["schemas"]["InstallJob"][];
+    };
+    /** @enum {string} */
+    InstallJobPhase: "downloading" | "extracting";
+    InstallJobProgress: {
+      /** Format: int64 */
+      downloadedBytes: number;
+      phase: components["schemas"]["InstallJobPhase"];
+      /**
+       * Format: int64
+       * @description Size of the download, when the server reports one.
+       */
+      totalBytes?: number | null;
+      /** @description File being downloaded. */
+      url?: string | null;
+    };
+    /** @enum {string} */
+    InstallJobStatus: "queued" | "running" | "succeeded" | "failed";
     ItemDeltaData: {
       delta: string;
       item_id: string;
@@ -394,6 +590,20 @@ export interface components {
     ItemRole: "user" | "assistant" | "system" | "tool";
     /** @enum {string} */
     ItemStatus: "in_progress" | "completed" | "failed";
+    LogsQuery: {
+      /** @description Stream the tail and then new lines as SSE `log` events */
+      follow?: boolean | null;
+      /** @description Only lines written at or after this RFC 3339 time */
+      since?: string | null;
+      /** @description Number of most recent lines to return (default 200, max 10000) */
+      tail?: number | null;
+    };
+    LogsResponse: {
+      /** @description Oldest first */
+      lines: string[];
+      /** @description Whether older matching lines were left out by `tail` */
+      truncated: boolean;
+    };
     McpCommand: string | string[];
     McpOAuthConfig: {
       clientId?: string | null;
@@ -475,8 +685,26 @@ export interface components {
     };
     /** @enum {string} */
     QuestionStatus: "requested" | "answered" | "rejected";
+    ReadinessCheck: {
+      message?: string | null;
+      /** @description `disk`, `agentServers` or `sessionCapacity` */
+      name: string;
+      ok: boolean;
+    };
+    ReadinessResponse: {
+      checks: components["schemas"]["ReadinessCheck"][];
+      ready: boolean;
+    };
     /** @enum {string} */
     ReasoningVisibility: "public" | "private";
+    RecentError: {
+      agent?: string | null;
+      message: string;
+      sessionId?: string | null;
+      /** @description `agent`, `agent_server` or `http` */
+      source: string;
+      time: string;
+    };
     /**
      * @description Status of a shared server process for an agent
      * @enum {string}
@@ -492,6 +720,12 @@ export interface components {
       uptimeMs?: number | null;
     };
     /** @enum {string} */
+    SessionEgressPolicy: {
+      /** @description Host patterns the session may reach: `example.com`, `*.example.com`, `example.com:8443` or `*`. */
+      allow?: string[];
+      /** @description Also allow the agent's model provider endpoints. Defaults to true. */
+      allowProviderHosts?: boolean | null;
+    };
     SessionEndReason: "completed" | "error" | "terminated";
     SessionEndedData: {
       /**
@@ -508,6 +742,7 @@ export interface components {
     SessionInfo: {
       agent: string;
       agentMode: string;
+      agentVersion?: string | null;
       /** Format: int64 */
       createdAt: number;
       directory?: string | null;
@@ -530,6 +765,16 @@ export interface components {
     SessionListResponse: {
       sessions: components["schemas"]["SessionInfo"][];
     };
+    SessionResourceLimits: {
+      /** Format: int32 */
+      cpuWeight?: number | null;
+      /** Format: int64 */
+      maxOpenFiles?: number | null;
+      /** Format: int64 */
+      maxPids?: number | null;
+      /** Format: int64 */
+      memoryBytes?: number | null;
+    };
     SessionStartedData: {
       metadata?: unknown;
     };
@@ -555,6 +800,11 @@ export interface components {
     };
     /** @enum {string} */
     TerminatedBy: "agent" | "daemon";
+    /**
+     * @description Permission granted to an API token.
+     * @enum {string}
+     */
+    TokenScope: "read" | "messages:send" | "permissions:reply" | "fs:read" | "fs:write" | "admin";
     TurnEventData: {
       metadata?: unknown;
       phase: components["schemas"]["TurnPhase"];
@@ -618,6 +868,48 @@ export interface operations {
       };
     };
   };
+  /**
+   * Uninstall Agent
+   * @description Removes the agent's installs, or one version of it. Refused while sessions that
+   * have not ended use what would be removed; a shared server with no sessions left is
+   * stopped first. Binaries found on `PATH` are kept.
+   */
+  uninstall_agent: {
+    parameters: {
+      query?: {
+        /** @description Remove only this side-by-side version */
+        version?: string | null;
+      };
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    responses: {
+      /** @description Agent uninstalled */
+      204: {
+        content: never;
+      };
+      /** @description Invalid request */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Agent or version not installed */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Sessions are using the agent */
+      409: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
   /**
    * Install Agent
    * @description Installs or updates a coding agent (e.g. claude, codex, opencode, amp).
@@ -635,6 +927,12 @@ export interface operations {
       };
     };
     responses: {
+      /** @description Install job started, with `background` */
+      202: {
+        content: {
+          "application/json": components["schemas"]["InstallJob"];
+        };
+      };
       /** @description Agent installed */
       204: {
         content: never;
@@ -659,6 +957,41 @@ export interface operations {
       };
     };
   };
+  /**
+   * Get Agent Logs
+   * @description Returns the stderr log of an agent's shared server process (OpenCode, Codex),
+   * with secrets masked. With `follow=true` the response is an SSE stream of `log` events.
+   */
+  get_agent_logs: {
+    parameters: {
+      query?: {
+        /** @description Number of most recent lines (default 200, max 10000) */
+        tail?: number | null;
+        /** @description Only lines written at or after this RFC 3339 time */
+        since?: string | null;
+        /** @description Stream as SSE and keep sending new lines */
+        follow?: boolean | null;
+      };
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    responses: {
+      /** @description Agent server log lines, or an SSE stream when following */
+      200: {
+        content: {
+          "application/json": components["schemas"]["LogsResponse"];
+        };
+      };
+      /** @description Invalid request */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
   /**
    * List Agent Models
    * @description Returns the available LLM models for an agent.
@@ -711,6 +1044,54 @@ export interface operations {
       };
     };
   };
+  /**
+   * Check Agent Updates
+   * @description Compares the installed version with the newest one the agent's release host
+   * publishes, through the configured mirror if there is one.
+   */
+  get_agent_updates: {
+    parameters: {
+      path: {
+        /** @description Agent id */
+        agent: string;
+      };
+    };
+    responses: {
+      /** @description Installed and latest published versions */
+      200: {
+        content: {
+          "application/json": components["schemas"]["AgentUpdatesResponse"];
+        };
+      };
+      /** @description Agent has no published releases */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Release host could not be reached */
+      502: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  /**
+   * Diagnostics
+   * @description Returns installed agents and versions, credential availability per provider, shared
+   * server status, disk space, active sessions, recent errors and readiness in one call.
+   */
+  get_diagnostics: {
+    responses: {
+      /** @description Server diagnostics */
+      200: {
+        content: {
+          "application/json": components["schemas"]["DiagnosticsResponse"];
+        };
+      };
+    };
+  };
   /**
    * List Directory
    * @description Lists files and directories at the given path.
@@ -916,6 +1297,121 @@ export interface operations {
       };
     };
   };
+  /**
+   * Readiness Check
+   * @description Reports whether the server can take new sessions: enough free disk in the install
+   * and log directories, no shared agent server in an error state, and room under the
+   * session cap. Responds 503 when any check fails.
+   */
+  get_readiness: {
+    responses: {
+      /** @description Server is ready for traffic */
+      200: {
+        content: {
+          "application/json": components["schemas"]["ReadinessResponse"];
+        };
+      };
+      /** @description Server is not ready */
+      503: {
+        content: {
+          "application/json": components["schemas"]["ReadinessResponse"];
+        };
+      };
+    };
+  };
+  /**
+   * List Install Jobs
+   * @description Returns running and queued installs and the most recent finished ones.
+   */
+  list_install_jobs: {
+    responses: {
+      /** @description Recent install jobs, oldest first */
+      200: {
+        content: {
+          "application/json": components["schemas"]["InstallJobListResponse"];
+        };
+      };
+    };
+  };
+  /** Get Install Job */
+  get_install_job: {
+    parameters: {
+      path: {
+        /** @description Install job id */
+        job_id: string;
+      };
+    };
+    responses: {
+      /** @description Install job */
+      200: {
+        content: {
+          "application/json": components["schemas"]["InstallJob"];
+        };
+      };
+      /** @description Unknown job */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  /**
+   * Follow Install Job (SSE)
+   * @description Sends the job as a `job` event now and whenever its status or progress changes.
+   * The stream ends after the event for the finished job.
+   */
+  get_install_job_events: {
+    parameters: {
+      path: {
+        /** @description Install job id */
+        job_id: string;
+      };
+    };
+    responses: {
+      /** @description SSE stream of `job` events, ending when the job finishes */
+      200: {
+        content: never;
+      };
+      /** @description Unknown job */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
+  /**
+   * Get Server Logs
+   * @description Returns the server's own rotated logs. With `follow=true` the response is an SSE
+   * stream of `log` events.
+   */
+  get_server_logs: {
+    parameters: {
+      query?: {
+        /** @description Number of most recent lines (default 200, max 10000) */
+        tail?: number | null;
+        /** @description Only lines written at or after this RFC 3339 time */
+        since?: string | null;
+        /** @description Stream as SSE and keep sending new lines */
+        follow?: boolean | null;
+      };
+    };
+    responses: {
+      /** @description Server log lines, or an SSE stream when following */
+      200: {
+        content: {
+          "application/json": components["schemas"]["LogsResponse"];
+        };
+      };
+      /** @description Invalid request */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
   /**
    * List Sessions
    * @description Returns all active sessions.
@@ -967,6 +1463,39 @@ export interface operations {
       };
     };
   };
+  /**
+   * Get Debug Log
+   * @description Returns every native line exchanged with the agent since the session was created
+   * with `debugLog: true`, one JSON object per line.
+   */
+  get_debug_log: {
+    parameters: {
+      path: {
+        /** @description Session id */
+        session_id: string;
+      };
+    };
+    responses: {
+      /** @description Captured native agent I/O as NDJSON */
+      200: {
+        content: {
+          "application/x-ndjson": string;
+        };
+      };
+      /** @description Session was created without debugLog */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Session not found */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
   /**
    * Get Events
    * @description Returns session events with optional offset-based pagination.
@@ -1199,4 +1728,41 @@ export interface operations {
       };
     };
   };
+  /**
+   * Create Session Token
+   * @description Mints a short-lived signed token limited to one session, for handing to browser clients.
+   */
+  create_session_token: {
+    parameters: {
+      path: {
+        /** @description Session id */
+        session_id: string;
+      };
+    };
+    requestBody: {
+      content: {
+        "application/json": components["schemas"]["CreateSessionTokenRequest"];
+      };
+    };
+    responses: {
+      /** @description Session token created */
+      200: {
+        content: {
+          "application/json": components["schemas"]["CreateSessionTokenResponse"];
+        };
+      };
+      /** @description Invalid scopes or lifetime */
+      400: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+      /** @description Session not found */
+      404: {
+        content: {
+          "application/json": components["schemas"]["ProblemDetails"];
+        };
+      };
+    };
+  };
 }
@@ -14,10 +14,17 @@ export type {
   AgentModelsResponse,
   AgentModeInfo,
   AgentModesResponse,
+  AgentUninstallQuery,
   AgentUnparsedData,
+  AgentUpdatesResponse,
   ContentPart,
   CreateSessionRequest,
   CreateSessionResponse,
+  CreateSessionTokenRequest,
+  CreateSessionTokenResponse,
+  CredentialStatus,
+  DiagnosticsResponse,
+  DiskSpaceInfo,
   ErrorData,
   EventSource,
   EventsQuery,
@@ -37,11 +44,18 @@ export type {
   FsUploadBatchResponse,
   FsWriteResponse,
   HealthResponse,
+  InstallJob,
+  InstallJobListResponse,
+  InstallJobPhase,
+  InstallJobProgress,
+  InstallJobStatus,
   ItemDeltaData,
   ItemEventData,
   ItemKind,
   ItemRole,
   ItemStatus,
+  LogsQuery,
+  LogsResponse,
   MessageAttachment,
   MessageRequest,
   PermissionEventData,
@@ -52,13 +66,17 @@ export type {
   QuestionEventData,
   QuestionReplyRequest,
   QuestionStatus,
+  ReadinessCheck,
+  ReadinessResponse,
   ReasoningVisibility,
+  RecentError,
   SessionEndReason,
   SessionEndedData,
   SessionInfo,
   SessionListResponse,
   SessionStartedData,
   TerminatedBy,
+  TokenScope,
   TurnStreamQuery,
   UniversalEvent,
   UniversalEventData,
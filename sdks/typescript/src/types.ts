@@ -10,10 +10,17 @@ export type AgentModelInfo = S["AgentModelInfo"];
 export type AgentModelsResponse = S["AgentModelsResponse"];
 export type AgentModeInfo = S["AgentModeInfo"];
 export type AgentModesResponse = S["AgentModesResponse"];
+export type AgentUninstallQuery = S["AgentUninstallQuery"];
 export type AgentUnparsedData = S["AgentUnparsedData"];
+export type AgentUpdatesResponse = S["AgentUpdatesResponse"];
 export type ContentPart = S["ContentPart"];
 export type CreateSessionRequest = S["CreateSessionRequest"];
 export type CreateSessionResponse = S["CreateSessionResponse"];
+export type CreateSessionTokenRequest = S["CreateSessionTokenRequest"];
+export type CreateSessionTokenResponse = S["CreateSessionTokenResponse"];
+export type CredentialStatus = S["CredentialStatus"];
+export type DiagnosticsResponse = S["DiagnosticsResponse"];
+export type DiskSpaceInfo = S["DiskSpaceInfo"];
 export type ErrorData = S["ErrorData"];
 export type EventSource = S["EventSource"];
 export type EventsQuery = S["EventsQuery"];
@@ -33,11 +40,18 @@ export type FsUploadBatchQuery = S["FsUploadBatchQuery"];
 export type FsUploadBatchResponse = S["FsUploadBatchResponse"];
 export type FsWriteResponse = S["FsWriteResponse"];
 export type HealthResponse = S["HealthResponse"];
+export type InstallJob = S["InstallJob"];
+export type InstallJobListResponse = S["InstallJobListResponse"];
+export type InstallJobPhase = S["InstallJobPhase"];
+export type InstallJobProgress = S["InstallJobProgress"];
+export type InstallJobStatus = S["InstallJobStatus"];
 export type ItemDeltaData = S["ItemDeltaData"];
 export type ItemEventData = S["ItemEventData"];
 export type ItemKind = S["ItemKind"];
 export type ItemRole = S["ItemRole"];
 export type ItemStatus = S["ItemStatus"];
+export type LogsQuery = S["LogsQuery"];
+export type LogsResponse = S["LogsResponse"];
 export type MessageRequest = S["MessageRequest"];
 export type MessageAttachment = S["MessageAttachment"];
 export type PermissionEventData = S["PermissionEventData"];
@@ -48,13 +62,17 @@ export type ProblemDetails = S["ProblemDetails"];
 export type QuestionEventData = S["QuestionEventData"];
 export type QuestionReplyRequest = S["QuestionReplyRequest"];
 export type QuestionStatus = S["QuestionStatus"];
+export type ReadinessCheck = S["ReadinessCheck"];
+export type ReadinessResponse = S["ReadinessResponse"];
 export type ReasoningVisibility = S["ReasoningVisibility"];
+export type RecentError = S["RecentError"];
 export type SessionEndReason = S["SessionEndReason"];
 export type SessionEndedData = S["SessionEndedData"];
 export type SessionInfo = S["SessionInfo"];
 export type SessionListResponse = S["SessionListResponse"];
 export type SessionStartedData = S["SessionStartedData"];
 export type TerminatedBy = S["TerminatedBy"];
+export type TokenScope = S["TokenScope"];
 export type TurnStreamQuery = S["TurnStreamQuery"];
 export type UniversalEvent = S["UniversalEvent"];
 export type UniversalEventData = S["UniversalEventData"];
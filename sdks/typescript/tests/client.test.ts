@@ -103,6 +103,58 @@ describe("SandboxAgent", () => {
     });
   });
 
+  describe("uninstallAgent", () => {
+    it("sends the version to remove", async () => {
+      const mockFetch = vi.fn().mockResolvedValue(
+        new Response(null, { status: 204 })
+      );
+      const client = await SandboxAgent.connect({
+        baseUrl: "http://localhost:8080",
+        fetch: mockFetch,
+      });
+
+      await client.uninstallAgent("claude", { version: "2.0.14" });
+
+      expect(mockFetch).toHaveBeenCalledWith(
+        "http://localhost:8080/v1/agents/claude?version=2.0.14",
+        expect.objectContaining({ method: "DELETE" })
+      );
+    });
+  });
+
+  describe("streamInstallJob", () => {
+    it("yields the job until it finishes", async () => {
+      const running = {
+        id: "install-1",
+        agent: "claude",
+        status: "running",
+        progress: { phase: "downloading", downloadedBytes: 512, totalBytes: 1024 },
+      };
+      const done = { id: "install-1", agent: "claude", status: "succeeded" };
+      const body = [running, done]
+        .map((job) => `event: job\ndata: ${JSON.stringify(job)}\n\n`)
+        .join("");
+      const mockFetch = vi.fn().mockResolvedValue(
+        new Response(body, { status: 200, headers: { "Content-Type": "text/event-stream" } })
+      );
+      const client = await SandboxAgent.connect({
+        baseUrl: "http://localhost:8080",
+        fetch: mockFetch,
+      });
+
+      const jobs = [];
+      for await (const job of client.streamInstallJob("install-1")) {
+        jobs.push(job);
+      }
+
+      expect(jobs).toEqual([running, done]);
+      expect(mockFetch).toHaveBeenCalledWith(
+        "http://localhost:8080/v1/install-jobs/install-1/events",
+        expect.objectContaining({ method: "GET" })
+      );
+    });
+  });
+
   describe("createSession", () => {
     it("creates session with agent", async () => {
       const response = { healthy: true, agentSessionId: "abc123" };
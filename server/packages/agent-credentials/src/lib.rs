@@ -267,6 +267,33 @@ pub fn extract_amp_credentials(
     None
 }
 
+/// Gemini CLI's Google login. API keys come from the environment; the CLI keeps no
+/// key of its own.
+pub fn extract_gemini_credentials(
+    options: &CredentialExtractionOptions,
+) -> Option<ProviderCredentials> {
+    if !options.include_oauth {
+        return None;
+    }
+    let home_dir = options.home_dir.clone().unwrap_or_else(default_home_dir);
+    let data = read_json_file(&home_dir.join(".gemini").join("oauth_creds.json"))?;
+    let token = read_string_field(&data, &["access_token"])?;
+    // A refresh token lets the CLI renew an expired access token itself.
+    let expired = data
+        .get("expiry_date")
+        .and_then(Value::as_i64)
+        .is_some_and(|expiry| expiry < current_epoch_millis());
+    if expired && read_string_field(&data, &["refresh_token"]).is_none() {
+        return None;
+    }
+    Some(ProviderCredentials {
+        api_key: token,
+        source: "gemini".to_string(),
+        auth_type: AuthType::Oauth,
+        provider: "google".to_string(),
+    })
+}
+
 pub fn extract_all_credentials(options: &CredentialExtractionOptions) -> ExtractedCredentials {
     let mut result = ExtractedCredentials::default();
 
@@ -318,6 +345,20 @@ pub fn extract_all_credentials(options: &CredentialExtractionOptions) -> Extract
         });
     }
 
+    let google = ["GEMINI_API_KEY", "GOOGLE_API_KEY"]
+        .into_iter()
+        .find_map(|key| std::env::var(key).ok())
+        .map(|value| ProviderCredentials {
+            api_key: value,
+            source: "environment".to_string(),
+            auth_type: AuthType::ApiKey,
+            provider: "google".to_string(),
+        })
+        .or_else(|| extract_gemini_credentials(options));
+    if let Some(google) = google {
+        result.other.insert("google".to_string(), google);
+    }
+
     if result.anthropic.is_none() {
         result.anthropic = extract_amp_credentials(options);
     }
@@ -406,12 +447,14 @@ mod tests {
 
     static ENV_LOCK: Mutex<()> = Mutex::new(());
 
-    const ANTHROPIC_ENV_KEYS: [&str; 5] = [
+    const ANTHROPIC_ENV_KEYS: [&str; 7] = [
         "ANTHROPIC_API_KEY",
         "CLAUDE_API_KEY",
         "CLAUDE_CODE_OAUTH_TOKEN",
         "ANTHROPIC_AUTH_TOKEN",
         "OPENAI_API_KEY",
+        "GEMINI_API_KEY",
+        "GOOGLE_API_KEY",
     ];
 
     fn with_env(mutations: &[(&str, Option<&str>)], test_fn: impl FnOnce()) {
@@ -524,4 +567,42 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn extract_all_credentials_reads_gemini_key_and_oauth() {
+        let home_dir = empty_home_dir();
+        fs::create_dir_all(home_dir.join(".gemini")).expect("create .gemini");
+        fs::write(
+            home_dir.join(".gemini").join("oauth_creds.json"),
+            r#"{"access_token": "ya29.token", "refresh_token": "1//refresh", "expiry_date": 1}"#,
+        )
+        .expect("write oauth creds");
+        let options = CredentialExtractionOptions {
+            home_dir: Some(home_dir),
+            include_oauth: true,
+        };
+
+        with_env(
+            &[("GEMINI_API_KEY", None), ("GOOGLE_API_KEY", None)],
+            || {
+                let google = extract_all_credentials(&options)
+                    .other
+                    .remove("google")
+                    .expect("expected google credentials from oauth file");
+                assert_eq!(google.api_key, "ya29.token");
+                assert_eq!(google.source, "gemini");
+                assert_eq!(google.auth_type, AuthType::Oauth);
+            },
+        );
+
+        with_env(&[("GEMINI_API_KEY", Some("gemini-key"))], || {
+            let google = extract_all_credentials(&options)
+                .other
+                .remove("google")
+                .expect("expected google credentials from env");
+            assert_eq!(google.api_key, "gemini-key");
+            assert_eq!(google.auth_type, AuthType::ApiKey);
+            assert_eq!(google.provider, "google");
+        });
+    }
 }
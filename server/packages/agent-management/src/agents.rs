@@ -4,8 +4,11 @@ use std::fs;
 use std::io::{self, BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use flate2::read::GzDecoder;
 use reqwest::blocking::Client;
 use sandbox_agent_extracted_agent_schemas::codex as codex_schema;
@@ -14,24 +17,51 @@ use serde_json::Value;
 use thiserror::Error;
 use url::Url;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+use crate::limits::{CgroupProbes, ResourceGuard, ResourceLimits, ResourceLimitsConfig};
+use crate::lockfile::{sha256_file, sha256_hex, AgentLockfile};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AgentId {
     Claude,
     Codex,
     Opencode,
     Amp,
+    Gemini,
+    /// Any Agent Client Protocol agent, started with the command from
+    /// [`AgentManager::with_agent_command`].
+    Acp,
     Mock,
+    /// Agent registered at runtime with [`AgentId::register_custom`].
+    Custom(CustomAgentId),
 }
 
+/// Name of a runtime-registered agent, interned so `AgentId` stays `Copy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CustomAgentId(&'static str);
+
+static CUSTOM_AGENTS: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
 impl AgentId {
+    pub const BUILTIN: [AgentId; 7] = [
+        AgentId::Claude,
+        AgentId::Codex,
+        AgentId::Opencode,
+        AgentId::Amp,
+        AgentId::Gemini,
+        AgentId::Acp,
+        AgentId::Mock,
+    ];
+
     pub fn as_str(self) -> &'static str {
         match self {
             AgentId::Claude => "claude",
             AgentId::Codex => "codex",
             AgentId::Opencode => "opencode",
             AgentId::Amp => "amp",
+            AgentId::Gemini => "gemini",
+            AgentId::Acp => "acp",
             AgentId::Mock => "mock",
+            AgentId::Custom(custom) => custom.0,
         }
     }
 
@@ -41,7 +71,10 @@ impl AgentId {
             AgentId::Codex => "codex",
             AgentId::Opencode => "opencode",
             AgentId::Amp => "amp",
+            AgentId::Gemini => "gemini",
+            AgentId::Acp => "acp",
             AgentId::Mock => "mock",
+            AgentId::Custom(custom) => custom.0,
         }
     }
 
@@ -51,9 +84,64 @@ impl AgentId {
             "codex" => Some(AgentId::Codex),
             "opencode" => Some(AgentId::Opencode),
             "amp" => Some(AgentId::Amp),
+            "gemini" => Some(AgentId::Gemini),
+            "acp" => Some(AgentId::Acp),
             "mock" => Some(AgentId::Mock),
-            _ => None,
+            _ => CUSTOM_AGENTS
+                .lock()
+                .expect("custom agents lock")
+                .iter()
+                .find(|name| **name == value)
+                .map(|name| AgentId::Custom(CustomAgentId(name))),
+        }
+    }
+
+    /// Makes `name` a valid agent id for the rest of the process. Registering a name
+    /// twice returns the same id; built-in names are rejected.
+    pub fn register_custom(name: &str) -> Result<Self, AgentError> {
+        let invalid = |reason: &str| AgentError::InvalidAgentName {
+            name: name.to_string(),
+            reason: reason.to_string(),
+        };
+        if name.is_empty() || name.len() > 64 {
+            return Err(invalid("must be 1 to 64 characters"));
+        }
+        if !name
+            .bytes()
+            .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-')
+        {
+            return Err(invalid(
+                "only lowercase letters, digits and '-' are allowed",
+            ));
+        }
+        if AgentId::BUILTIN.iter().any(|agent| agent.as_str() == name) {
+            return Err(invalid("name of a built-in agent"));
+        }
+        let mut custom = CUSTOM_AGENTS.lock().expect("custom agents lock");
+        if let Some(existing) = custom.iter().find(|existing| **existing == name) {
+            return Ok(AgentId::Custom(CustomAgentId(existing)));
         }
+        let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+        custom.push(interned);
+        Ok(AgentId::Custom(CustomAgentId(interned)))
+    }
+
+    pub fn is_custom(self) -> bool {
+        matches!(self, AgentId::Custom(_))
+    }
+}
+
+impl Serialize for AgentId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for AgentId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        AgentId::parse(&value)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown agent {value}")))
     }
 }
 
@@ -93,6 +181,28 @@ impl Platform {
         }
     }
 
+    /// Parses the names `as_str` returns, as taken by `bundle --platform`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "linux-x64" => Some(Self::LinuxX64),
+            "linux-x64-musl" => Some(Self::LinuxX64Musl),
+            "linux-arm64" => Some(Self::LinuxArm64),
+            "darwin-arm64" => Some(Self::MacosArm64),
+            "darwin-x64" => Some(Self::MacosX64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::LinuxX64 => "linux-x64",
+            Self::LinuxX64Musl => "linux-x64-musl",
+            Self::LinuxArm64 => "linux-arm64",
+            Self::MacosArm64 => "darwin-arm64",
+            Self::MacosX64 => "darwin-x64",
+        }
+    }
+
     /// Detect if the runtime environment uses musl libc by checking for musl dynamic linker
     fn detect_musl_runtime() -> bool {
         use std::path::Path;
@@ -102,10 +212,24 @@ impl Platform {
     }
 }
 
+/// Program and leading arguments that start an agent which is configured rather
+/// than installed, such as `acp`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentCommand {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct AgentManager {
     install_dir: PathBuf,
     platform: Platform,
+    resource_limits: ResourceLimitsConfig,
+    cgroup_probes: Arc<CgroupProbes>,
+    commands: HashMap<AgentId, AgentCommand>,
+    lockfile: AgentLockfile,
+    mirror: Option<Url>,
 }
 
 impl AgentManager {
@@ -113,6 +237,11 @@ impl AgentManager {
         Ok(Self {
             install_dir: install_dir.into(),
             platform: Platform::detect()?,
+            resource_limits: ResourceLimitsConfig::default(),
+            cgroup_probes: Arc::default(),
+            commands: HashMap::new(),
+            lockfile: AgentLockfile::default(),
+            mirror: None,
         })
     }
 
@@ -120,47 +249,251 @@ impl AgentManager {
         Self {
             install_dir: install_dir.into(),
             platform,
+            resource_limits: ResourceLimitsConfig::default(),
+            cgroup_probes: Arc::default(),
+            commands: HashMap::new(),
+            lockfile: AgentLockfile::default(),
+            mirror: None,
         }
     }
 
+    /// Starts `agent` with `command` instead of its installed binary.
+    pub fn with_agent_command(mut self, agent: AgentId, command: AgentCommand) -> Self {
+        self.commands.insert(agent, command);
+        self
+    }
+
+    pub fn agent_command(&self, agent: AgentId) -> Option<&AgentCommand> {
+        self.commands.get(&agent)
+    }
+
+    pub fn with_resource_limits(mut self, resource_limits: ResourceLimitsConfig) -> Self {
+        self.resource_limits = resource_limits;
+        self.cgroup_probes = Arc::default();
+        self
+    }
+
+    pub fn resource_limits(&self) -> &ResourceLimitsConfig {
+        &self.resource_limits
+    }
+
+    /// Installs and runs the versions pinned in `lockfile` unless a version is requested.
+    pub fn with_lockfile(mut self, lockfile: AgentLockfile) -> Self {
+        self.lockfile = lockfile;
+        self
+    }
+
+    pub fn lockfile(&self) -> &AgentLockfile {
+        &self.lockfile
+    }
+
+    pub fn pinned_version(&self, agent: AgentId) -> Option<&str> {
+        self.lockfile
+            .get(agent)
+            .map(|locked| locked.version.as_str())
+    }
+
+    /// Downloads release files from `mirror` instead of their public hosts; see
+    /// [`InstallOptions::mirror`].
+    pub fn with_mirror(mut self, mirror: Url) -> Self {
+        self.mirror = Some(mirror);
+        self
+    }
+
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn install_dir(&self) -> &Path {
+        &self.install_dir
+    }
+
+    /// Applies the agent's configured limits, overlaid with `overrides`, to
+    /// `command`. The returned guard must outlive the spawned process.
+    pub fn prepare_limits(
+        &self,
+        agent: AgentId,
+        command: &mut Command,
+        overrides: &ResourceLimits,
+    ) -> Option<ResourceGuard> {
+        let limits = self.resource_limits.for_agent(agent).merged(overrides);
+        ResourceGuard::prepare(
+            command,
+            agent.as_str(),
+            limits,
+            self.resource_limits.cgroup_root.as_deref(),
+        )
+    }
+
+    /// Why the limits [`AgentManager::prepare_limits`] applies fall back to rlimits
+    /// instead of a cgroup, or `None` when they do not. Probed once per set of
+    /// controllers.
+    pub fn limits_fallback(&self, agent: AgentId, overrides: &ResourceLimits) -> Option<String> {
+        let limits = self.resource_limits.for_agent(agent).merged(overrides);
+        self.cgroup_probes
+            .fallback(&limits, self.resource_limits.cgroup_root.as_deref())
+    }
+
     pub fn install(
         &self,
         agent: AgentId,
         options: InstallOptions,
     ) -> Result<InstallResult, AgentError> {
-        let install_path = self.binary_path(agent);
-        if !options.reinstall {
-            if let Ok(existing_path) = self.resolve_binary(agent) {
+        let downloader = Downloader {
+            mirror: options.mirror.clone().or_else(|| self.mirror.clone()),
+            keep: None,
+            progress: options.progress.clone(),
+        };
+        self.install_with(agent, options, &downloader)
+    }
+
+    /// Downloads everything installing `agent` for this manager's platform would,
+    /// into `dir` laid out as `<host>/<path>`, so `dir` can be used as a `file://`
+    /// mirror where the release hosts cannot be reached.
+    pub fn bundle(
+        &self,
+        agent: AgentId,
+        options: InstallOptions,
+        dir: &Path,
+    ) -> Result<InstallMetadata, AgentError> {
+        if agent.is_custom() || matches!(agent, AgentId::Acp | AgentId::Mock) {
+            return Err(AgentError::UnsupportedAgent {
+                agent: agent.as_str().to_string(),
+            });
+        }
+        // Install into a scratch directory; only the downloads are kept.
+        let staging_dir = tempfile::tempdir()?;
+        let staging = Self {
+            install_dir: staging_dir.path().to_path_buf(),
+            commands: HashMap::new(),
+            ..self.clone()
+        };
+        let downloader = Downloader {
+            mirror: options.mirror.clone().or_else(|| self.mirror.clone()),
+            keep: Some(dir.to_path_buf()),
+            progress: options.progress.clone(),
+        };
+        let options = InstallOptions {
+            reinstall: true,
+            from: None,
+            ..options
+        };
+        staging
+            .install_with(agent, options, &downloader)?
+            .metadata
+            .ok_or_else(|| AgentError::ExtractFailed(format!("nothing downloaded for {agent}")))
+    }
+
+    fn install_with(
+        &self,
+        agent: AgentId,
+        options: InstallOptions,
+        downloader: &Downloader,
+    ) -> Result<InstallResult, AgentError> {
+        if agent.is_custom() || agent == AgentId::Acp || self.commands.contains_key(&agent) {
+            // Custom and configured agents bring their own binary; adapters that can
+            // download one override `AgentAdapter::install`.
+            return Ok(InstallResult {
+                path: self.resolve_binary(agent)?,
+                version: self.version(agent).unwrap_or(None),
+                metadata: None,
+            });
+        }
+        let version = self.selected_version(agent, options.version.as_deref());
+        let install_path = match version.as_deref() {
+            Some(version) => self.versioned_binary_path(agent, version)?,
+            None => self.binary_path(agent),
+        };
+        if !options.reinstall && options.from.is_none() {
+            let existing = match version {
+                Some(_) => install_path.exists().then(|| install_path.clone()),
+                None => self.resolve_binary(agent).ok(),
+            };
+            if let Some(existing_path) = existing {
                 return Ok(InstallResult {
+                    version: binary_version(&existing_path).or(version),
+                    metadata: InstallMetadata::read(&existing_path),
                     path: existing_path,
-                    version: self.version(agent).unwrap_or(None),
                 });
             }
         }
 
-        fs::create_dir_all(&self.install_dir)?;
+        fs::create_dir_all(install_path.parent().unwrap_or(&self.install_dir))?;
 
-        match agent {
-            AgentId::Claude => {
-                install_claude(&install_path, self.platform, options.version.as_deref())?
-            }
-            AgentId::Codex => {
-                install_codex(&install_path, self.platform, options.version.as_deref())?
-            }
-            AgentId::Opencode => {
-                install_opencode(&install_path, self.platform, options.version.as_deref())?
-            }
-            AgentId::Amp => install_amp(&install_path, self.platform, options.version.as_deref())?,
-            AgentId::Mock => {
+        let download_sha256 = options.sha256.or_else(|| {
+            self.lockfile
+                .get(agent)
+                .filter(|locked| Some(locked.version.as_str()) == version.as_deref())
+                .and_then(|locked| locked.download_sha256.clone())
+        });
+        let download_sha256 = download_sha256.as_deref();
+        let platform = self.platform;
+        let requested = version.as_deref();
+        let metadata = match (agent, options.from.as_deref()) {
+            (AgentId::Mock, _) => {
                 if !install_path.exists() {
                     fs::write(&install_path, b"mock")?;
                 }
+                return Ok(InstallResult {
+                    path: install_path,
+                    version: Some("builtin".to_string()),
+                    metadata: None,
+                });
             }
+            (AgentId::Acp | AgentId::Custom(_), _) => {
+                unreachable!("configured and custom agents return early")
+            }
+            (_, Some(from)) => install_local(
+                downloader,
+                agent,
+                &install_path,
+                platform,
+                from,
+                requested,
+                download_sha256,
+            )?,
+            (AgentId::Claude, None) => install_claude(
+                downloader,
+                &install_path,
+                platform,
+                requested,
+                download_sha256,
+            )?,
+            (AgentId::Codex, None) => install_codex(
+                downloader,
+                &install_path,
+                platform,
+                requested,
+                download_sha256,
+            )?,
+            (AgentId::Opencode, None) => install_opencode(
+                downloader,
+                &install_path,
+                platform,
+                requested,
+                download_sha256,
+            )?,
+            (AgentId::Amp, None) => install_amp(
+                downloader,
+                &install_path,
+                platform,
+                requested,
+                download_sha256,
+            )?,
+            (AgentId::Gemini, None) => {
+                install_gemini(downloader, &install_path, requested, download_sha256)?
+            }
+        };
+
+        if let Some(version) = version.as_deref() {
+            self.verify_pinned_checksum(agent, version, &install_path)?;
         }
+        metadata.write(&install_path)?;
 
         Ok(InstallResult {
+            version: binary_version(&install_path).or(version),
             path: install_path,
-            version: self.version(agent).unwrap_or(None),
+            metadata: Some(metadata),
         })
     }
 
@@ -168,30 +501,171 @@ impl AgentManager {
         if agent == AgentId::Mock {
             return true;
         }
+        if agent == AgentId::Acp || self.commands.contains_key(&agent) {
+            return self.resolve_binary(agent).is_ok();
+        }
+        if self.pinned_version(agent).is_some() {
+            return self.resolve_binary(agent).is_ok();
+        }
         self.binary_path(agent).exists() || find_in_path(agent.binary_name()).is_some()
     }
 
+    /// Where an install without a version goes.
     pub fn binary_path(&self, agent: AgentId) -> PathBuf {
         self.install_dir.join(agent.binary_name())
     }
 
+    /// Where `version` of `agent` is installed, next to any other versions.
+    pub fn versioned_binary_path(
+        &self,
+        agent: AgentId,
+        version: &str,
+    ) -> Result<PathBuf, AgentError> {
+        let valid = !version.is_empty()
+            && !version.starts_with('.')
+            && version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '+'));
+        if !valid {
+            return Err(AgentError::InvalidVersion {
+                agent,
+                version: version.to_string(),
+            });
+        }
+        Ok(self
+            .versions_dir(agent)
+            .join(version)
+            .join(agent.binary_name()))
+    }
+
+    /// Versions installed side by side, sorted by name. Unversioned installs are not listed.
+    pub fn installed_versions(&self, agent: AgentId) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(self.versions_dir(agent)) else {
+            return Vec::new();
+        };
+        let mut versions: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().join(agent.binary_name()).exists())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        versions.sort();
+        versions
+    }
+
+    fn versions_dir(&self, agent: AgentId) -> PathBuf {
+        self.install_dir.join("versions").join(agent.as_str())
+    }
+
+    /// Removes `version` of `agent`, or when no version is given the unversioned install
+    /// and every side-by-side version. Binaries found on `PATH` are left alone.
+    pub fn uninstall(&self, agent: AgentId, version: Option<&str>) -> Result<(), AgentError> {
+        if agent.is_custom()
+            || matches!(agent, AgentId::Mock | AgentId::Acp)
+            || self.commands.contains_key(&agent)
+        {
+            return Err(AgentError::UnsupportedAgent {
+                agent: agent.as_str().to_string(),
+            });
+        }
+        if let Some(version) = version {
+            let path = self.versioned_binary_path(agent, version)?;
+            if !path.exists() {
+                return Err(AgentError::VersionNotInstalled {
+                    agent,
+                    version: version.to_string(),
+                });
+            }
+            fs::remove_dir_all(path.parent().unwrap_or(&path))?;
+            return Ok(());
+        }
+
+        let binary = self.binary_path(agent);
+        let versions = self.versions_dir(agent);
+        if !binary.exists() && self.installed_versions(agent).is_empty() {
+            return Err(AgentError::BinaryNotFound { agent });
+        }
+        for file in [InstallMetadata::path(&binary), binary.clone()] {
+            if file.exists() {
+                fs::remove_file(file)?;
+            }
+        }
+        if agent == AgentId::Gemini {
+            let package_dir = binary.with_file_name("gemini-cli");
+            if package_dir.exists() {
+                fs::remove_dir_all(package_dir)?;
+            }
+        }
+        if versions.exists() {
+            fs::remove_dir_all(versions)?;
+        }
+        Ok(())
+    }
+
+    /// The newest version `agent` publishes, in the form `InstallOptions::version` takes.
+    pub fn latest_version(&self, agent: AgentId) -> Result<String, AgentError> {
+        let downloader = Downloader {
+            mirror: self.mirror.clone(),
+            ..Downloader::default()
+        };
+        latest_release(&downloader, agent)
+    }
+
+    /// The version to install or run: `requested`, else the pinned one. `latest` is the
+    /// unversioned install. Agents that are configured rather than installed have no versions.
+    fn selected_version(&self, agent: AgentId, requested: Option<&str>) -> Option<String> {
+        if agent.is_custom()
+            || matches!(agent, AgentId::Mock | AgentId::Acp)
+            || self.commands.contains_key(&agent)
+        {
+            return None;
+        }
+        match requested {
+            Some("latest") => None,
+            Some(version) => Some(version.to_string()),
+            None => self.pinned_version(agent).map(str::to_string),
+        }
+    }
+
+    /// Removes a freshly installed pinned version whose binary does not match the lockfile.
+    fn verify_pinned_checksum(
+        &self,
+        agent: AgentId,
+        version: &str,
+        path: &Path,
+    ) -> Result<(), AgentError> {
+        let Some(expected) = self
+            .lockfile
+            .get(agent)
+            .filter(|locked| locked.version == version)
+            .and_then(|locked| locked.sha256.as_deref())
+        else {
+            return Ok(());
+        };
+        let actual = sha256_file(path)?;
+        if actual.eq_ignore_ascii_case(expected) {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            let _ = fs::remove_dir_all(dir);
+        }
+        Err(AgentError::ChecksumMismatch {
+            agent,
+            algorithm: "sha256",
+            expected: expected.to_string(),
+            actual,
+        })
+    }
+
     pub fn version(&self, agent: AgentId) -> Result<Option<String>, AgentError> {
         if agent == AgentId::Mock {
             return Ok(Some("builtin".to_string()));
         }
         let path = self.resolve_binary(agent)?;
-        let attempts = [vec!["--version"], vec!["version"], vec!["-V"]];
-        for args in attempts {
-            let output = Command::new(&path).args(args).output();
-            if let Ok(output) = output {
-                if output.status.success() {
-                    if let Some(version) = parse_version_output(&output) {
-                        return Ok(Some(version));
-                    }
-                }
-            }
+        if self.commands.contains_key(&agent) {
+            // A configured command may be a protocol server that never exits on its own.
+            return Ok(None);
         }
-        Ok(None)
+        Ok(binary_version(&path))
     }
 
     pub fn spawn(&self, agent: AgentId, options: SpawnOptions) -> Result<SpawnResult, AgentError> {
@@ -203,7 +677,7 @@ impl AgentManager {
         if agent == AgentId::Codex {
             return self.spawn_codex_app_server(options);
         }
-        let path = self.resolve_binary(agent)?;
+        let path = self.resolve_binary_version(agent, options.version.as_deref())?;
         let working_dir = options
             .working_dir
             .clone()
@@ -268,6 +742,7 @@ impl AgentManager {
                 }
                 command.arg(&options.prompt);
             }
+            AgentId::Gemini => gemini_args(&mut command, &options),
             AgentId::Amp => {
                 let output = spawn_amp(&path, &working_dir, &options)?;
                 let stdout = String::from_utf8_lossy(&output.stdout).to_string();
@@ -282,7 +757,7 @@ impl AgentManager {
                     events,
                 });
             }
-            AgentId::Mock => {
+            AgentId::Acp | AgentId::Mock | AgentId::Custom(_) => {
                 return Err(AgentError::UnsupportedAgent {
                     agent: agent.as_str().to_string(),
                 });
@@ -293,6 +768,7 @@ impl AgentManager {
             command.env(key, value);
         }
 
+        let _limits = self.prepare_limits(agent, &mut command, &options.limits);
         let output = command.output().map_err(AgentError::Io)?;
         let stdout = String::from_utf8_lossy(&output.stdout).to_string();
         let stderr = String::from_utf8_lossy(&output.stderr).to_string();
@@ -320,11 +796,28 @@ impl AgentManager {
         if agent == AgentId::Claude {
             options.streaming_input = true;
         }
-        let mut command = self.build_command(agent, &options)?;
-        if matches!(agent, AgentId::Codex | AgentId::Claude) {
+        let command = self.build_command(agent, &options)?;
+        let pipe_stdin = matches!(agent, AgentId::Codex | AgentId::Claude);
+        let mut spawn =
+            self.spawn_command_streaming(agent, command, &options.limits, pipe_stdin)?;
+        spawn.codex_options = codex_options;
+        Ok(spawn)
+    }
+
+    /// Spawns `command` with piped output and the agent's resource limits applied.
+    /// Custom agent adapters build their command with [`AgentManager::base_command`].
+    pub fn spawn_command_streaming(
+        &self,
+        agent: AgentId,
+        mut command: Command,
+        limits: &ResourceLimits,
+        pipe_stdin: bool,
+    ) -> Result<StreamingSpawn, AgentError> {
+        if pipe_stdin {
             command.stdin(Stdio::piped());
         }
         command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        let limits = self.prepare_limits(agent, &mut command, limits);
         let mut child = command.spawn().map_err(AgentError::Io)?;
         let stdin = child.stdin.take();
         let stdout = child.stdout.take();
@@ -334,10 +827,49 @@ impl AgentManager {
             stdin,
             stdout,
             stderr,
-            codex_options,
+            codex_options: None,
+            limits,
         })
     }
 
+    /// The agent's binary in the session's working directory with the session
+    /// environment, without any arguments.
+    pub fn base_command(
+        &self,
+        agent: AgentId,
+        options: &SpawnOptions,
+    ) -> Result<Command, AgentError> {
+        let mut command =
+            Command::new(self.resolve_binary_version(agent, options.version.as_deref())?);
+        if let Some(configured) = self.commands.get(&agent) {
+            command.args(&configured.args);
+        }
+        command.current_dir(
+            options
+                .working_dir
+                .clone()
+                .unwrap_or_else(|| std::env::current_dir().unwrap_or_default()),
+        );
+        command.envs(&options.env);
+        Ok(command)
+    }
+
+    /// Command for an agent that runs as one shared server over stdio: `codex
+    /// app-server`, or the configured command for other agents.
+    pub fn server_command(&self, agent: AgentId) -> Result<Command, AgentError> {
+        let mut command = Command::new(self.resolve_binary(agent)?);
+        match self.commands.get(&agent) {
+            Some(configured) => {
+                command.args(&configured.args);
+            }
+            None if agent == AgentId::Codex => {
+                command.arg("app-server");
+            }
+            None => {}
+        }
+        Ok(command)
+    }
+
     fn spawn_codex_app_server(&self, options: SpawnOptions) -> Result<SpawnResult, AgentError> {
         if options.session_id.is_some() {
             return Err(AgentError::ResumeUnsupported {
@@ -353,6 +885,7 @@ impl AgentManager {
             command.env(key, value);
         }
 
+        let _limits = self.prepare_limits(AgentId::Codex, &mut command, &options.limits);
         let mut child = command.spawn().map_err(AgentError::Io)?;
         let mut stdin = child.stdin.take().ok_or_else(|| {
             AgentError::Io(io::Error::new(io::ErrorKind::Other, "missing codex stdin"))
@@ -549,7 +1082,7 @@ impl AgentManager {
     }
 
     fn build_command(&self, agent: AgentId, options: &SpawnOptions) -> Result<Command, AgentError> {
-        let path = self.resolve_binary(agent)?;
+        let path = self.resolve_binary_version(agent, options.version.as_deref())?;
         let working_dir = options
             .working_dir
             .clone()
@@ -614,14 +1147,16 @@ impl AgentManager {
                 }
                 command.arg(&options.prompt);
             }
+            AgentId::Gemini => gemini_args(&mut command, options),
             AgentId::Amp => {
                 return Ok(build_amp_command(&path, &working_dir, options));
             }
-            AgentId::Mock => {
+            AgentId::Acp | AgentId::Mock => {
                 return Err(AgentError::UnsupportedAgent {
                     agent: agent.as_str().to_string(),
                 });
             }
+            AgentId::Custom(_) => {}
         }
 
         for (key, value) in &options.env {
@@ -631,7 +1166,36 @@ impl AgentManager {
         Ok(command)
     }
 
+    /// The binary for the pinned version, or the unversioned install or `PATH` when
+    /// the agent is not pinned.
     pub fn resolve_binary(&self, agent: AgentId) -> Result<PathBuf, AgentError> {
+        self.resolve_binary_version(agent, None)
+    }
+
+    /// Like [`AgentManager::resolve_binary`], preferring `version` over the pinned one.
+    /// A selected version must already be installed.
+    pub fn resolve_binary_version(
+        &self,
+        agent: AgentId,
+        version: Option<&str>,
+    ) -> Result<PathBuf, AgentError> {
+        if let Some(version) = self.selected_version(agent, version) {
+            let path = self.versioned_binary_path(agent, &version)?;
+            if path.exists() {
+                return Ok(path);
+            }
+            return Err(AgentError::VersionNotInstalled { agent, version });
+        }
+        if let Some(configured) = self.commands.get(&agent) {
+            let program = PathBuf::from(&configured.program);
+            if program.components().count() > 1 && program.exists() {
+                return Ok(program);
+            }
+            return find_in_path(&configured.program).ok_or(AgentError::BinaryNotFound { agent });
+        }
+        if agent == AgentId::Acp {
+            return Err(AgentError::BinaryNotFound { agent });
+        }
         let path = self.binary_path(agent);
         if path.exists() {
             return Ok(path);
@@ -647,6 +1211,17 @@ impl AgentManager {
 pub struct InstallOptions {
     pub reinstall: bool,
     pub version: Option<String>,
+    /// Expected SHA-256 of the downloaded release file, checked instead of the one
+    /// from the lockfile or the release manifest.
+    pub sha256: Option<String>,
+    /// Local release file or binary to install instead of downloading one. Implies
+    /// `reinstall`.
+    pub from: Option<PathBuf>,
+    /// Base URL replacing the release hosts: `https://<host>/<path>` is fetched from
+    /// `<mirror>/<host>/<path>`. `file://` and `http(s)://` mirrors are supported.
+    pub mirror: Option<Url>,
+    /// Called from the installing thread as the install proceeds.
+    pub progress: Option<InstallProgressSink>,
 }
 
 impl Default for InstallOptions {
@@ -654,14 +1229,99 @@ impl Default for InstallOptions {
         Self {
             reinstall: false,
             version: None,
+            sha256: None,
+            from: None,
+            mirror: None,
+            progress: None,
         }
     }
 }
 
+/// Progress of an install, reported through [`InstallOptions::progress`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallProgress {
+    /// `downloaded` bytes of `url` have arrived; `total` is the size the server
+    /// reported, if it did.
+    Downloading {
+        url: Url,
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    /// The release file was verified and is being unpacked into place.
+    Extracting,
+}
+
+#[derive(Clone)]
+pub struct InstallProgressSink(Arc<dyn Fn(InstallProgress) + Send + Sync>);
+
+impl InstallProgressSink {
+    pub fn new(report: impl Fn(InstallProgress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(report))
+    }
+
+    pub fn report(&self, progress: InstallProgress) {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for InstallProgressSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("InstallProgressSink")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct InstallResult {
     pub path: PathBuf,
     pub version: Option<String>,
+    /// What was downloaded, for installs made by this manager.
+    pub metadata: Option<InstallMetadata>,
+}
+
+/// Written next to an installed binary as `<binary>.install.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstallMetadata {
+    /// Release that was downloaded, when known before downloading.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub url: String,
+    /// Hex SHA-256 of the downloaded file.
+    pub sha256: String,
+    /// Where the checksum the download matched came from; `None` if it was not verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum_source: Option<ChecksumSource>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChecksumSource {
+    /// `InstallOptions::sha256` or the lockfile's `downloadSha256`.
+    Pinned,
+    /// Published with the release.
+    Manifest,
+}
+
+impl InstallMetadata {
+    pub fn read(binary: &Path) -> Option<Self> {
+        let text = fs::read_to_string(Self::path(binary)).ok()?;
+        serde_json::from_str(&text).ok()
+    }
+
+    fn write(&self, binary: &Path) -> Result<(), AgentError> {
+        let text = serde_json::to_string_pretty(self)
+            .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
+        fs::write(Self::path(binary), text)?;
+        Ok(())
+    }
+
+    fn path(binary: &Path) -> PathBuf {
+        let name = binary
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        binary.with_file_name(format!("{name}.install.json"))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -672,10 +1332,14 @@ pub struct SpawnOptions {
     pub agent_mode: Option<String>,
     pub permission_mode: Option<String>,
     pub session_id: Option<String>,
+    /// Installed version to run instead of the pinned or unversioned one.
+    pub version: Option<String>,
     pub working_dir: Option<PathBuf>,
     pub env: HashMap<String, String>,
     /// Use stream-json input via stdin (Claude only).
     pub streaming_input: bool,
+    /// Per-session limits layered over the agent's configured limits.
+    pub limits: ResourceLimits,
 }
 
 impl SpawnOptions {
@@ -687,9 +1351,11 @@ impl SpawnOptions {
             agent_mode: None,
             permission_mode: None,
             session_id: None,
+            version: None,
             working_dir: None,
             env: HashMap::new(),
             streaming_input: false,
+            limits: ResourceLimits::default(),
         }
     }
 }
@@ -711,6 +1377,7 @@ pub struct StreamingSpawn {
     pub stdout: Option<ChildStdout>,
     pub stderr: Option<ChildStderr>,
     pub codex_options: Option<SpawnOptions>,
+    pub limits: Option<ResourceGuard>,
 }
 
 #[derive(Debug, Error)]
@@ -733,6 +1400,40 @@ pub enum AgentError {
     ExtractFailed(String),
     #[error("resume unsupported for {agent}")]
     ResumeUnsupported { agent: AgentId },
+    #[error("invalid agent name {name:?}: {reason}")]
+    InvalidAgentName { name: String, reason: String },
+    #[error("invalid version {version:?} for {agent}")]
+    InvalidVersion { agent: AgentId, version: String },
+    #[error("{agent} {version} is not installed")]
+    VersionNotInstalled { agent: AgentId, version: String },
+    /// A download or installed binary did not match its pinned or published checksum.
+    #[error("checksum mismatch for {agent}: expected {algorithm} {expected}, got {actual}")]
+    ChecksumMismatch {
+        agent: AgentId,
+        algorithm: &'static str,
+        expected: String,
+        actual: String,
+    },
+    /// An agent that runs on another program (such as `node`) could not find it.
+    #[error("{agent} requires {program}, which was not found on PATH")]
+    RuntimeNotFound { agent: AgentId, program: String },
+    #[error("installing dependencies for {agent} failed: {stderr}")]
+    DependenciesFailed { agent: AgentId, stderr: String },
+}
+
+fn binary_version(path: &Path) -> Option<String> {
+    let attempts = [vec!["--version"], vec!["version"], vec!["-V"]];
+    for args in attempts {
+        let output = Command::new(path).args(args).output();
+        if let Ok(output) = output {
+            if output.status.success() {
+                if let Some(version) = parse_version_output(&output) {
+                    return Some(version);
+                }
+            }
+        }
+    }
+    None
 }
 
 fn parse_version_output(output: &std::process::Output) -> Option<String> {
@@ -881,7 +1582,7 @@ fn extract_nested_string(value: &Value, path: &[&str]) -> Option<String> {
 fn extract_session_id(agent: AgentId, events: &[Value]) -> Option<String> {
     for event in events {
         match agent {
-            AgentId::Claude | AgentId::Amp => {
+            AgentId::Claude | AgentId::Amp | AgentId::Gemini => {
                 if let Some(id) = event.get("session_id").and_then(Value::as_str) {
                     return Some(id.to_string());
                 }
@@ -940,7 +1641,7 @@ fn extract_session_id(agent: AgentId, events: &[Value]) -> Option<String> {
                     return Some(id);
                 }
             }
-            AgentId::Mock => {}
+            AgentId::Acp | AgentId::Mock | AgentId::Custom(_) => {}
         }
     }
     None
@@ -1022,7 +1723,22 @@ fn extract_result_text(agent: AgentId, events: &[Value]) -> Option<String> {
                 Some(buffer)
             }
         }
-        AgentId::Mock => None,
+        AgentId::Gemini => {
+            let text = events
+                .iter()
+                .filter(|event| {
+                    event.get("type").and_then(Value::as_str) == Some("message")
+                        && event.get("role").and_then(Value::as_str) == Some("assistant")
+                })
+                .filter_map(|event| event.get("content").and_then(Value::as_str))
+                .collect::<String>();
+            if text.is_empty() {
+                None
+            } else {
+                Some(text)
+            }
+        }
+        AgentId::Acp | AgentId::Mock | AgentId::Custom(_) => None,
     }
 }
 
@@ -1182,6 +1898,29 @@ fn spawn_amp_fallback(
     Ok(command.output().map_err(AgentError::Io)?)
 }
 
+fn gemini_args(command: &mut Command, options: &SpawnOptions) {
+    command.arg("--output-format").arg("stream-json");
+    if let Some(model) = options.model.as_deref() {
+        command.arg("--model").arg(model);
+    }
+    if let Some(session_id) = options.session_id.as_deref() {
+        command.arg("--resume").arg(session_id);
+    }
+    match options.permission_mode.as_deref() {
+        Some("plan") => {
+            command.arg("--approval-mode").arg("plan");
+        }
+        Some("bypass") => {
+            command.arg("--approval-mode").arg("yolo");
+        }
+        Some("acceptEdits") => {
+            command.arg("--approval-mode").arg("auto_edit");
+        }
+        _ => {}
+    }
+    command.arg("--prompt").arg(&options.prompt);
+}
+
 fn find_in_path(binary_name: &str) -> Option<PathBuf> {
     let path_var = std::env::var_os("PATH")?;
     for path in std::env::split_paths(&path_var) {
@@ -1193,32 +1932,357 @@ fn find_in_path(binary_name: &str) -> Option<PathBuf> {
     None
 }
 
-fn download_bytes(url: &Url) -> Result<Vec<u8>, AgentError> {
-    let client = Client::builder().build()?;
-    let mut response = client.get(url.clone()).send()?;
-    if !response.status().is_success() {
-        return Err(AgentError::DownloadFailed { url: url.clone() });
+/// Fetches release files, from a mirror when one is set, and copies each one into
+/// `keep` when bundling.
+#[derive(Debug, Default)]
+struct Downloader {
+    mirror: Option<Url>,
+    keep: Option<PathBuf>,
+    progress: Option<InstallProgressSink>,
+}
+
+/// Token sent with GitHub API requests, when set.
+const GITHUB_TOKEN_ENV: &str = "GITHUB_TOKEN";
+
+/// Minimum time between two download progress reports.
+const DOWNLOAD_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+impl Downloader {
+    fn report(&self, progress: InstallProgress) {
+        if let Some(sink) = &self.progress {
+            sink.report(progress);
+        }
+    }
+
+    /// Where `url` is fetched from.
+    fn source(&self, url: &Url) -> Result<Url, AgentError> {
+        let Some(mirror) = &self.mirror else {
+            return Ok(url.clone());
+        };
+        let host = url.host_str().unwrap_or_default();
+        Ok(Url::parse(&format!(
+            "{}/{host}{}",
+            mirror.as_str().trim_end_matches('/'),
+            url.path()
+        ))?)
+    }
+
+    fn get(&self, url: &Url) -> Result<Vec<u8>, AgentError> {
+        let source = self.source(url)?;
+        let bytes = if source.scheme() == "file" {
+            let path = source
+                .to_file_path()
+                .map_err(|_| AgentError::DownloadFailed {
+                    url: source.clone(),
+                })?;
+            let bytes = fs::read(path).map_err(|_| AgentError::DownloadFailed {
+                url: source.clone(),
+            })?;
+            let size = bytes.len() as u64;
+            self.report(InstallProgress::Downloading {
+                url: source,
+                downloaded: size,
+                total: Some(size),
+            });
+            bytes
+        } else {
+            let client = Client::builder().user_agent("sandbox-agent").build()?;
+            let mut request = client.get(source.clone());
+            // Unauthenticated GitHub API calls share a small per-address rate limit.
+            if source.host_str() == Some("api.github.com") {
+                if let Some(token) = std::env::var(GITHUB_TOKEN_ENV)
+                    .ok()
+                    .filter(|token| !token.is_empty())
+                {
+                    request = request.bearer_auth(token);
+                }
+            }
+            let mut response = request.send()?;
+            if !response.status().is_success() {
+                return Err(AgentError::DownloadFailed { url: source });
+            }
+            let total = response.content_length();
+            let mut bytes = Vec::new();
+            let mut buffer = [0u8; 64 * 1024];
+            let mut last_report = None::<Instant>;
+            loop {
+                let read = response.read(&mut buffer)?;
+                bytes.extend_from_slice(&buffer[..read]);
+                let due = last_report.is_none_or(|at| at.elapsed() >= DOWNLOAD_PROGRESS_INTERVAL);
+                if read == 0 || due {
+                    self.report(InstallProgress::Downloading {
+                        url: source.clone(),
+                        downloaded: bytes.len() as u64,
+                        total,
+                    });
+                    last_report = Some(Instant::now());
+                }
+                if read == 0 {
+                    break;
+                }
+            }
+            bytes
+        };
+        if let Some(dir) = &self.keep {
+            let kept = dir
+                .join(url.host_str().unwrap_or_default())
+                .join(url.path().trim_start_matches('/'));
+            if let Some(parent) = kept.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(kept, &bytes)?;
+        }
+        Ok(bytes)
+    }
+}
+
+/// Expected digest of a download and where it came from.
+struct ExpectedChecksum {
+    digest: ExpectedDigest,
+    source: ChecksumSource,
+}
+
+enum ExpectedDigest {
+    /// Hex SHA-256.
+    Sha256(String),
+    /// An npm `dist.integrity` value, `sha512-<base64>`.
+    NpmIntegrity(String),
+}
+
+/// The pinned checksum if there is one, otherwise the one the release publishes.
+fn expected_checksum(
+    pinned: Option<&str>,
+    published: impl FnOnce() -> Result<Option<String>, AgentError>,
+) -> Result<Option<ExpectedChecksum>, AgentError> {
+    if let Some(sha256) = pinned {
+        return Ok(Some(ExpectedChecksum {
+            digest: ExpectedDigest::Sha256(sha256.to_string()),
+            source: ChecksumSource::Pinned,
+        }));
+    }
+    Ok(published()?.map(|sha256| ExpectedChecksum {
+        digest: ExpectedDigest::Sha256(sha256),
+        source: ChecksumSource::Manifest,
+    }))
+}
+
+/// Downloads `url` and checks it against `expected` before any of it is written.
+fn download_verified(
+    downloader: &Downloader,
+    agent: AgentId,
+    url: &Url,
+    version: Option<&str>,
+    expected: Option<ExpectedChecksum>,
+) -> Result<(Vec<u8>, InstallMetadata), AgentError> {
+    let bytes = downloader.get(url)?;
+    let verified = verified(agent, downloader.source(url)?, bytes, version, expected)?;
+    downloader.report(InstallProgress::Extracting);
+    Ok(verified)
+}
+
+fn verified(
+    agent: AgentId,
+    url: Url,
+    bytes: Vec<u8>,
+    version: Option<&str>,
+    expected: Option<ExpectedChecksum>,
+) -> Result<(Vec<u8>, InstallMetadata), AgentError> {
+    let actual = sha256_hex(&bytes);
+    if let Some(expected) = &expected {
+        let mismatch = match &expected.digest {
+            ExpectedDigest::Sha256(sha256) => (!actual.eq_ignore_ascii_case(sha256))
+                .then(|| ("sha256", sha256.clone(), actual.clone())),
+            ExpectedDigest::NpmIntegrity(integrity) => {
+                let computed = npm_integrity(&bytes);
+                (computed != *integrity).then(|| ("sha512", integrity.clone(), computed))
+            }
+        };
+        if let Some((algorithm, expected, actual)) = mismatch {
+            return Err(AgentError::ChecksumMismatch {
+                agent,
+                algorithm,
+                expected,
+                actual,
+            });
+        }
+    }
+    let metadata = InstallMetadata {
+        version: version.map(str::to_string),
+        url: url.to_string(),
+        sha256: actual,
+        checksum_source: expected.map(|expected| expected.source),
+    };
+    Ok((bytes, metadata))
+}
+
+/// The npm `dist.integrity` value for `bytes`.
+fn npm_integrity(bytes: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA512, bytes);
+    format!("sha512-{}", BASE64.encode(digest.as_ref()))
+}
+
+/// A GitHub release: its tag and the SHA-256 it reports for one of its assets. The tag
+/// is `None` when the latest release could not be looked up.
+struct GithubRelease {
+    tag: Option<String>,
+    asset_sha256: Option<String>,
+}
+
+impl GithubRelease {
+    fn download_url(&self, repo: &str, asset: &str) -> Result<Url, AgentError> {
+        Ok(Url::parse(&match &self.tag {
+            Some(tag) => format!("https://github.com/{repo}/releases/download/{tag}/{asset}"),
+            None => format!("https://github.com/{repo}/releases/latest/download/{asset}"),
+        })?)
     }
-    let mut bytes = Vec::new();
-    response.read_to_end(&mut bytes)?;
-    Ok(bytes)
 }
 
+/// Looks up the release tagged `tag`, or the latest one. A single lookup gives both the
+/// tag to download and the checksum to check it against, so they cannot come from
+/// different releases. Releases published before GitHub recorded asset digests have no
+/// checksum.
+fn github_release(
+    downloader: &Downloader,
+    repo: &str,
+    tag: Option<&str>,
+    asset: &str,
+) -> Result<GithubRelease, AgentError> {
+    let release = match tag {
+        Some(tag) => format!("tags/{tag}"),
+        None => "latest".to_string(),
+    };
+    let url = Url::parse(&format!(
+        "https://api.github.com/repos/{repo}/releases/{release}"
+    ))?;
+    let release: Value = serde_json::from_slice(&downloader.get(&url)?)
+        .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
+    let tag = release
+        .get("tag_name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| AgentError::ExtractFailed(format!("missing {repo} tag_name")))?;
+    let asset_sha256 = release
+        .get("assets")
+        .and_then(Value::as_array)
+        .and_then(|assets| {
+            assets
+                .iter()
+                .find(|candidate| candidate.get("name").and_then(Value::as_str) == Some(asset))
+        })
+        .and_then(|asset| asset.get("digest"))
+        .and_then(Value::as_str)
+        .and_then(|digest| digest.strip_prefix("sha256:"))
+        .map(str::to_string);
+    Ok(GithubRelease {
+        tag: Some(tag.to_string()),
+        asset_sha256,
+    })
+}
+
+/// The tag to download from `repo` and the checksum it publishes for `asset`. A pinned
+/// version with a pinned checksum needs no lookup. When the latest release cannot be
+/// looked up, as when the API rate limit is used up, its direct download link is
+/// installed unverified instead.
+fn resolve_github_release(
+    downloader: &Downloader,
+    repo: &str,
+    version: Option<&str>,
+    pinned_sha256: Option<&str>,
+    asset: &str,
+) -> Result<GithubRelease, AgentError> {
+    match (version, pinned_sha256) {
+        (Some(version), Some(_)) => Ok(GithubRelease {
+            tag: Some(version.to_string()),
+            asset_sha256: None,
+        }),
+        (Some(version), None) => github_release(downloader, repo, Some(version), asset),
+        (None, _) => github_release(downloader, repo, None, asset).or_else(|err| {
+            tracing::warn!(
+                repo,
+                error = %err,
+                "could not look up the latest release; its download will not be verified"
+            );
+            Ok(GithubRelease {
+                tag: None,
+                asset_sha256: None,
+            })
+        }),
+    }
+}
+
+/// The version each agent's release host currently calls latest. Codex and OpenCode
+/// report their GitHub release tag, such as `rust-v0.46.0`.
+fn latest_release(downloader: &Downloader, agent: AgentId) -> Result<String, AgentError> {
+    let text = |url: &str| -> Result<String, AgentError> {
+        let bytes = downloader.get(&Url::parse(url)?)?;
+        String::from_utf8(bytes)
+            .map(|text| text.trim().to_string())
+            .map_err(|err| AgentError::ExtractFailed(err.to_string()))
+    };
+    let json_field = |url: &str, field: &str| -> Result<String, AgentError> {
+        let value: Value = serde_json::from_slice(&downloader.get(&Url::parse(url)?)?)
+            .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
+        value
+            .get(field)
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| AgentError::ExtractFailed(format!("missing {agent} {field}")))
+    };
+    match agent {
+        AgentId::Claude => text(&format!("{CLAUDE_RELEASES_URL}/latest")),
+        AgentId::Amp => {
+            text("https://storage.googleapis.com/amp-public-assets-prod-0/cli/cli-version.txt")
+        }
+        AgentId::Gemini => json_field(
+            "https://registry.npmjs.org/@google/gemini-cli/latest",
+            "version",
+        ),
+        AgentId::Codex => json_field(
+            "https://api.github.com/repos/openai/codex/releases/latest",
+            "tag_name",
+        ),
+        AgentId::Opencode => json_field(
+            "https://api.github.com/repos/anomalyco/opencode/releases/latest",
+            "tag_name",
+        ),
+        AgentId::Acp | AgentId::Mock | AgentId::Custom(_) => Err(AgentError::UnsupportedAgent {
+            agent: agent.as_str().to_string(),
+        }),
+    }
+}
+
+/// Whether `latest` is newer than `installed`. Both may carry text around the version
+/// number, as in `codex-cli 0.46.0` or the tag `rust-v0.46.0`; the first run of
+/// dot-separated numbers is compared. Versions without one compare by text.
+pub fn is_newer_version(latest: &str, installed: &str) -> bool {
+    match (version_numbers(latest), version_numbers(installed)) {
+        (Some(latest), Some(installed)) => latest > installed,
+        _ => latest.trim() != installed.trim(),
+    }
+}
+
+fn version_numbers(text: &str) -> Option<Vec<u64>> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let numbers: Vec<u64> = text[start..]
+        .split(|c: char| !c.is_ascii_digit() && c != '.')
+        .next()?
+        .split('.')
+        .map_while(|part| part.parse().ok())
+        .collect();
+    (!numbers.is_empty()).then_some(numbers)
+}
+
+const CLAUDE_RELEASES_URL: &str = "https://storage.googleapis.com/claude-code-dist-86c565f3-f756-42ad-8dfa-d59b1c096819/claude-code-releases";
+
 fn install_claude(
+    downloader: &Downloader,
     path: &Path,
     platform: Platform,
     version: Option<&str>,
-) -> Result<(), AgentError> {
+    pinned_sha256: Option<&str>,
+) -> Result<InstallMetadata, AgentError> {
     let version = match version {
         Some(version) => version.to_string(),
-        None => {
-            let url = Url::parse(
-                "https://storage.googleapis.com/claude-code-dist-86c565f3-f756-42ad-8dfa-d59b1c096819/claude-code-releases/latest",
-            )?;
-            let text = String::from_utf8(download_bytes(&url)?)
-                .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
-            text.trim().to_string()
-        }
+        None => latest_release(downloader, AgentId::Claude)?,
     };
 
     let platform_segment = match platform {
@@ -1229,25 +2293,36 @@ fn install_claude(
         Platform::MacosX64 => "darwin-x64",
     };
 
+    let expected = expected_checksum(pinned_sha256, || {
+        // Each release has a manifest with the checksum of every platform's binary.
+        let url = Url::parse(&format!("{CLAUDE_RELEASES_URL}/{version}/manifest.json"))?;
+        let manifest: Value = serde_json::from_slice(&downloader.get(&url)?)
+            .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
+        Ok(manifest
+            .pointer(&format!("/platforms/{platform_segment}/checksum"))
+            .and_then(Value::as_str)
+            .map(str::to_string))
+    })?;
     let url = Url::parse(&format!(
-        "https://storage.googleapis.com/claude-code-dist-86c565f3-f756-42ad-8dfa-d59b1c096819/claude-code-releases/{version}/{platform_segment}/claude"
+        "{CLAUDE_RELEASES_URL}/{version}/{platform_segment}/claude"
     ))?;
-    let bytes = download_bytes(&url)?;
+    let (bytes, metadata) =
+        download_verified(downloader, AgentId::Claude, &url, Some(&version), expected)?;
     write_executable(path, &bytes)?;
-    Ok(())
+    Ok(metadata)
 }
 
-fn install_amp(path: &Path, platform: Platform, version: Option<&str>) -> Result<(), AgentError> {
+/// Amp publishes no checksums, so only a pinned checksum is checked.
+fn install_amp(
+    downloader: &Downloader,
+    path: &Path,
+    platform: Platform,
+    version: Option<&str>,
+    pinned_sha256: Option<&str>,
+) -> Result<InstallMetadata, AgentError> {
     let version = match version {
         Some(version) => version.to_string(),
-        None => {
-            let url = Url::parse(
-                "https://storage.googleapis.com/amp-public-assets-prod-0/cli/cli-version.txt",
-            )?;
-            let text = String::from_utf8(download_bytes(&url)?)
-                .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
-            text.trim().to_string()
-        }
+        None => latest_release(downloader, AgentId::Amp)?,
     };
 
     let platform_segment = match platform {
@@ -1260,100 +2335,250 @@ fn install_amp(path: &Path, platform: Platform, version: Option<&str>) -> Result
     let url = Url::parse(&format!(
         "https://storage.googleapis.com/amp-public-assets-prod-0/cli/{version}/amp-{platform_segment}"
     ))?;
-    let bytes = download_bytes(&url)?;
+    let expected = expected_checksum(pinned_sha256, || Ok(None))?;
+    let (bytes, metadata) =
+        download_verified(downloader, AgentId::Amp, &url, Some(&version), expected)?;
     write_executable(path, &bytes)?;
-    Ok(())
+    Ok(metadata)
 }
 
-fn install_codex(path: &Path, platform: Platform, version: Option<&str>) -> Result<(), AgentError> {
-    let target = match platform {
-        Platform::LinuxX64 | Platform::LinuxX64Musl => "x86_64-unknown-linux-musl",
-        Platform::LinuxArm64 => "aarch64-unknown-linux-musl",
-        Platform::MacosArm64 => "aarch64-apple-darwin",
-        Platform::MacosX64 => "x86_64-apple-darwin",
+/// Gemini CLI ships as an npm package, so it is unpacked next to a wrapper script
+/// that runs its bundle with `node`, and its dependencies are installed with `npm`.
+/// Without a pinned checksum, the package is checked against the SHA-512 integrity
+/// hash the registry publishes for it.
+fn install_gemini(
+    downloader: &Downloader,
+    path: &Path,
+    version: Option<&str>,
+    pinned_sha256: Option<&str>,
+) -> Result<InstallMetadata, AgentError> {
+    find_runtime(AgentId::Gemini, "node")?;
+    let version = match version {
+        Some(version) => version.to_string(),
+        None => latest_release(downloader, AgentId::Gemini)?,
     };
+    let url = Url::parse(&format!(
+        "https://registry.npmjs.org/@google/gemini-cli/-/gemini-cli-{version}.tgz"
+    ))?;
 
-    let url = match version {
-        Some(version) => Url::parse(&format!(
-            "https://github.com/openai/codex/releases/download/{version}/codex-{target}.tar.gz"
-        ))?,
-        None => Url::parse(&format!(
-            "https://github.com/openai/codex/releases/latest/download/codex-{target}.tar.gz"
-        ))?,
+    let expected = match pinned_sha256 {
+        Some(_) => expected_checksum(pinned_sha256, || Ok(None))?,
+        None => {
+            let url = Url::parse(&format!(
+                "https://registry.npmjs.org/@google/gemini-cli/{version}"
+            ))?;
+            let package: Value = serde_json::from_slice(&downloader.get(&url)?)
+                .map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
+            package
+                .pointer("/dist/integrity")
+                .and_then(Value::as_str)
+                .filter(|integrity| integrity.starts_with("sha512-"))
+                .map(|integrity| ExpectedChecksum {
+                    digest: ExpectedDigest::NpmIntegrity(integrity.to_string()),
+                    source: ChecksumSource::Manifest,
+                })
+        }
     };
+    let (bytes, metadata) =
+        download_verified(downloader, AgentId::Gemini, &url, Some(&version), expected)?;
+    install_gemini_package(path, bytes)?;
+    Ok(metadata)
+}
 
-    let bytes = download_bytes(&url)?;
-    let temp_dir = tempfile::tempdir()?;
-    let cursor = io::Cursor::new(bytes);
-    let mut archive = tar::Archive::new(GzDecoder::new(cursor));
-    archive.unpack(temp_dir.path())?;
+/// Unpacks a Gemini CLI package and points the wrapper at the `node` found now, so
+/// a missing runtime fails the install rather than every session. A package that
+/// already carries `node_modules` is used as is, which keeps offline bundles working.
+fn install_gemini_package(path: &Path, bytes: Vec<u8>) -> Result<(), AgentError> {
+    let node = find_runtime(AgentId::Gemini, "node")?;
+    let package_dir = path.with_file_name("gemini-cli");
+    if package_dir.exists() {
+        fs::remove_dir_all(&package_dir)?;
+    }
+    let mut archive = tar::Archive::new(GzDecoder::new(io::Cursor::new(bytes)));
+    archive.unpack(&package_dir)?;
+    let root = package_dir.join("package");
+    let entry = root.join("bundle").join("gemini.js");
+    if !entry.exists() {
+        return Err(AgentError::ExtractFailed(
+            "missing bundle/gemini.js".to_string(),
+        ));
+    }
+    if !root.join("node_modules").exists() && has_npm_dependencies(&root)? {
+        install_npm_dependencies(AgentId::Gemini, &root)?;
+    }
+    let script = format!(
+        "#!/bin/sh\nexec \"{}\" \"{}\" \"$@\"\n",
+        node.display(),
+        entry.display()
+    );
+    write_executable(path, script.as_bytes())
+}
+
+fn find_runtime(agent: AgentId, program: &str) -> Result<PathBuf, AgentError> {
+    find_in_path(program).ok_or_else(|| AgentError::RuntimeNotFound {
+        agent,
+        program: program.to_string(),
+    })
+}
 
-    let expected = format!("codex-{target}");
-    let binary = find_file_recursive(temp_dir.path(), &expected)?
-        .ok_or_else(|| AgentError::ExtractFailed(format!("missing {expected}")))?;
-    move_executable(&binary, path)?;
+fn has_npm_dependencies(package_root: &Path) -> Result<bool, AgentError> {
+    let manifest: Value = serde_json::from_slice(&fs::read(package_root.join("package.json"))?)
+        .map_err(|err| AgentError::ExtractFailed(format!("invalid package.json: {err}")))?;
+    Ok(manifest
+        .get("dependencies")
+        .and_then(Value::as_object)
+        .is_some_and(|deps| !deps.is_empty()))
+}
+
+fn install_npm_dependencies(agent: AgentId, package_root: &Path) -> Result<(), AgentError> {
+    let npm = find_runtime(agent, "npm")?;
+    let output = Command::new(npm)
+        .args([
+            "install",
+            "--omit=dev",
+            "--no-audit",
+            "--no-fund",
+            "--no-package-lock",
+        ])
+        .current_dir(package_root)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(AgentError::DependenciesFailed {
+            agent,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
     Ok(())
 }
 
-fn install_opencode(
+fn install_codex(
+    downloader: &Downloader,
     path: &Path,
     platform: Platform,
     version: Option<&str>,
-) -> Result<(), AgentError> {
+    pinned_sha256: Option<&str>,
+) -> Result<InstallMetadata, AgentError> {
+    let target = codex_target(platform);
+    let asset = format!("codex-{target}.tar.gz");
+    let release =
+        resolve_github_release(downloader, "openai/codex", version, pinned_sha256, &asset)?;
+    let url = release.download_url("openai/codex", &asset)?;
+    let expected = expected_checksum(pinned_sha256, || Ok(release.asset_sha256))?;
+    let (bytes, metadata) = download_verified(
+        downloader,
+        AgentId::Codex,
+        &url,
+        release.tag.as_deref(),
+        expected,
+    )?;
+    install_tar_binary(path, bytes, &[&format!("codex-{target}")])?;
+    Ok(metadata)
+}
+
+/// Codex release archives name the binary after its target triple.
+fn codex_target(platform: Platform) -> &'static str {
     match platform {
-        Platform::MacosArm64 => {
-            let url = match version {
-                Some(version) => Url::parse(&format!(
-                    "https://github.com/anomalyco/opencode/releases/download/{version}/opencode-darwin-arm64.zip"
-                ))?,
-                None => Url::parse(
-                    "https://github.com/anomalyco/opencode/releases/latest/download/opencode-darwin-arm64.zip",
-                )?,
-            };
-            install_zip_binary(path, &url, "opencode")
-        }
-        Platform::MacosX64 => {
-            let url = match version {
-                Some(version) => Url::parse(&format!(
-                    "https://github.com/anomalyco/opencode/releases/download/{version}/opencode-darwin-x64.zip"
-                ))?,
-                None => Url::parse(
-                    "https://github.com/anomalyco/opencode/releases/latest/download/opencode-darwin-x64.zip",
-                )?,
-            };
-            install_zip_binary(path, &url, "opencode")
-        }
-        _ => {
-            let platform_segment = match platform {
-                Platform::LinuxX64 => "linux-x64",
-                Platform::LinuxX64Musl => "linux-x64-musl",
-                Platform::LinuxArm64 => "linux-arm64",
-                Platform::MacosArm64 | Platform::MacosX64 => unreachable!(),
-            };
-            let url = match version {
-                Some(version) => Url::parse(&format!(
-                    "https://github.com/anomalyco/opencode/releases/download/{version}/opencode-{platform_segment}.tar.gz"
-                ))?,
-                None => Url::parse(&format!(
-                    "https://github.com/anomalyco/opencode/releases/latest/download/opencode-{platform_segment}.tar.gz"
-                ))?,
-            };
+        Platform::LinuxX64 | Platform::LinuxX64Musl => "x86_64-unknown-linux-musl",
+        Platform::LinuxArm64 => "aarch64-unknown-linux-musl",
+        Platform::MacosArm64 => "aarch64-apple-darwin",
+        Platform::MacosX64 => "x86_64-apple-darwin",
+    }
+}
 
-            let bytes = download_bytes(&url)?;
-            let temp_dir = tempfile::tempdir()?;
-            let cursor = io::Cursor::new(bytes);
-            let mut archive = tar::Archive::new(GzDecoder::new(cursor));
-            archive.unpack(temp_dir.path())?;
-            let binary = find_file_recursive(temp_dir.path(), "opencode")?
-                .ok_or_else(|| AgentError::ExtractFailed("missing opencode".to_string()))?;
-            move_executable(&binary, path)?;
-            Ok(())
+fn install_opencode(
+    downloader: &Downloader,
+    path: &Path,
+    platform: Platform,
+    version: Option<&str>,
+    pinned_sha256: Option<&str>,
+) -> Result<InstallMetadata, AgentError> {
+    let asset = match platform {
+        Platform::MacosArm64 => "opencode-darwin-arm64.zip",
+        Platform::MacosX64 => "opencode-darwin-x64.zip",
+        Platform::LinuxX64 => "opencode-linux-x64.tar.gz",
+        Platform::LinuxX64Musl => "opencode-linux-x64-musl.tar.gz",
+        Platform::LinuxArm64 => "opencode-linux-arm64.tar.gz",
+    };
+    let release = resolve_github_release(
+        downloader,
+        "anomalyco/opencode",
+        version,
+        pinned_sha256,
+        asset,
+    )?;
+    let url = release.download_url("anomalyco/opencode", asset)?;
+    let expected = expected_checksum(pinned_sha256, || Ok(release.asset_sha256))?;
+    let (bytes, metadata) = download_verified(
+        downloader,
+        AgentId::Opencode,
+        &url,
+        release.tag.as_deref(),
+        expected,
+    )?;
+
+    if asset.ends_with(".zip") {
+        install_zip_binary(path, bytes, "opencode")?;
+    } else {
+        install_tar_binary(path, bytes, &["opencode"])?;
+    }
+    Ok(metadata)
+}
+
+/// Installs a release file that was copied in rather than downloaded. Its format is
+/// detected from its contents: a `.tar.gz` or `.zip` release archive, or the binary
+/// itself.
+fn install_local(
+    downloader: &Downloader,
+    agent: AgentId,
+    path: &Path,
+    platform: Platform,
+    from: &Path,
+    version: Option<&str>,
+    pinned_sha256: Option<&str>,
+) -> Result<InstallMetadata, AgentError> {
+    let bytes = fs::read(from)?;
+    let url = Url::from_file_path(fs::canonicalize(from)?)
+        .map_err(|_| AgentError::ExtractFailed(format!("invalid path {}", from.display())))?;
+    let expected = expected_checksum(pinned_sha256, || Ok(None))?;
+    let (bytes, metadata) = verified(agent, url, bytes, version, expected)?;
+    downloader.report(InstallProgress::Extracting);
+    let binary_name = agent.binary_name();
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        match agent {
+            AgentId::Gemini => install_gemini_package(path, bytes)?,
+            AgentId::Codex => install_tar_binary(
+                path,
+                bytes,
+                &[&format!("codex-{}", codex_target(platform)), binary_name],
+            )?,
+            _ => install_tar_binary(path, bytes, &[binary_name])?,
         }
+    } else if bytes.starts_with(b"PK\x03\x04") {
+        install_zip_binary(path, bytes, binary_name)?;
+    } else {
+        write_executable(path, &bytes)?;
     }
+    Ok(metadata)
 }
 
-fn install_zip_binary(path: &Path, url: &Url, binary_name: &str) -> Result<(), AgentError> {
-    let bytes = download_bytes(url)?;
+/// Unpacks a `.tar.gz` and installs the first of `names` found in it.
+fn install_tar_binary(path: &Path, bytes: Vec<u8>, names: &[&str]) -> Result<(), AgentError> {
+    let temp_dir = tempfile::tempdir()?;
+    let mut archive = tar::Archive::new(GzDecoder::new(io::Cursor::new(bytes)));
+    archive.unpack(temp_dir.path())?;
+    for name in names {
+        if let Some(binary) = find_file_recursive(temp_dir.path(), name)? {
+            return move_executable(&binary, path);
+        }
+    }
+    Err(AgentError::ExtractFailed(format!(
+        "missing {}",
+        names.join(" or ")
+    )))
+}
+
+fn install_zip_binary(path: &Path, bytes: Vec<u8>, binary_name: &str) -> Result<(), AgentError> {
     let reader = io::Cursor::new(bytes);
     let mut archive =
         zip::ZipArchive::new(reader).map_err(|err| AgentError::ExtractFailed(err.to_string()))?;
@@ -1425,3 +2650,380 @@ fn find_file_recursive(dir: &Path, filename: &str) -> Result<Option<PathBuf>, Ag
     }
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves `body` to one request and returns its URL.
+    fn serve_once(body: &'static [u8]) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let url =
+            Url::parse(&format!("http://{}/agent", listener.local_addr().unwrap())).expect("url");
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("accept");
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            let head = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).expect("head");
+            stream.write_all(body).expect("body");
+        });
+        url
+    }
+
+    #[test]
+    fn downloads_are_checked_against_the_pinned_checksum() {
+        let sha256 = sha256_hex(b"agent");
+        let expected =
+            expected_checksum(Some(&sha256), || panic!("manifest not needed")).expect("expected");
+        let (bytes, metadata) = download_verified(
+            &Downloader::default(),
+            AgentId::Amp,
+            &serve_once(b"agent"),
+            Some("1.0"),
+            expected,
+        )
+        .expect("verified");
+        assert_eq!(bytes, b"agent");
+        assert_eq!(metadata.sha256, sha256);
+        assert_eq!(metadata.checksum_source, Some(ChecksumSource::Pinned));
+
+        let expected = expected_checksum(None, || Ok(Some(sha256.clone()))).expect("expected");
+        let err = download_verified(
+            &Downloader::default(),
+            AgentId::Amp,
+            &serve_once(b"tampered"),
+            None,
+            expected,
+        )
+        .expect_err("mismatch");
+        assert!(matches!(
+            err,
+            AgentError::ChecksumMismatch { expected, .. } if expected == sha256
+        ));
+    }
+
+    #[test]
+    fn npm_packages_are_checked_against_their_integrity() {
+        let url = Url::parse("https://registry.npmjs.org/gemini-cli.tgz").expect("url");
+        let expected = || {
+            Some(ExpectedChecksum {
+                digest: ExpectedDigest::NpmIntegrity(npm_integrity(b"package")),
+                source: ChecksumSource::Manifest,
+            })
+        };
+        let (_, metadata) = verified(
+            AgentId::Gemini,
+            url.clone(),
+            b"package".to_vec(),
+            Some("1.0.0"),
+            expected(),
+        )
+        .expect("verified");
+        assert_eq!(metadata.checksum_source, Some(ChecksumSource::Manifest));
+
+        let err = verified(
+            AgentId::Gemini,
+            url,
+            b"tampered".to_vec(),
+            Some("1.0.0"),
+            expected(),
+        )
+        .expect_err("mismatch");
+        assert!(matches!(
+            err,
+            AgentError::ChecksumMismatch {
+                algorithm: "sha512",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn mirrors_serve_installs_and_bundles_replay_them() {
+        let mirror = tempfile::tempdir().expect("mirror");
+        let release = mirror
+            .path()
+            .join("storage.googleapis.com/amp-public-assets-prod-0/cli/1.0/amp-linux-x64");
+        fs::create_dir_all(release.parent().expect("parent")).expect("mkdir");
+        fs::write(&release, b"amp").expect("write");
+        let mirror_url = Url::from_directory_path(mirror.path()).expect("url");
+
+        let install_dir = tempfile::tempdir().expect("install dir");
+        let manager = AgentManager::with_platform(install_dir.path(), Platform::LinuxX64)
+            .with_mirror(mirror_url.clone());
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let options = InstallOptions {
+            version: Some("1.0".to_string()),
+            progress: Some(InstallProgressSink::new(move |progress| {
+                sink.lock().unwrap().push(progress)
+            })),
+            ..InstallOptions::default()
+        };
+        let result = manager
+            .install(AgentId::Amp, options.clone())
+            .expect("install");
+        assert_eq!(fs::read(&result.path).expect("read"), b"amp");
+        assert_eq!(
+            *reported.lock().unwrap(),
+            [
+                InstallProgress::Downloading {
+                    url: Url::from_file_path(&release).unwrap(),
+                    downloaded: 3,
+                    total: Some(3),
+                },
+                InstallProgress::Extracting,
+            ]
+        );
+        let metadata = result.metadata.expect("metadata");
+        assert_eq!(
+            metadata.url,
+            Url::from_file_path(&release).unwrap().as_str()
+        );
+
+        let bundle = tempfile::tempdir().expect("bundle");
+        manager
+            .bundle(AgentId::Amp, options, bundle.path())
+            .expect("bundle");
+        let bundled = bundle
+            .path()
+            .join("storage.googleapis.com/amp-public-assets-prod-0/cli/1.0/amp-linux-x64");
+        assert_eq!(fs::read(bundled).expect("bundled"), b"amp");
+        assert!(matches!(
+            manager.bundle(AgentId::Mock, InstallOptions::default(), bundle.path()),
+            Err(AgentError::UnsupportedAgent { .. })
+        ));
+    }
+
+    #[test]
+    fn uninstalls_versions_and_reads_the_latest_release() {
+        let mirror = tempfile::tempdir().expect("mirror");
+        let cli = mirror
+            .path()
+            .join("storage.googleapis.com/amp-public-assets-prod-0/cli");
+        fs::create_dir_all(cli.join("1.1")).expect("mkdir");
+        fs::write(cli.join("cli-version.txt"), b"1.1\n").expect("write");
+        fs::write(cli.join("1.1/amp-linux-x64"), b"amp").expect("write");
+        let install_dir = tempfile::tempdir().expect("install dir");
+        let manager = AgentManager::with_platform(install_dir.path(), Platform::LinuxX64)
+            .with_mirror(Url::from_directory_path(mirror.path()).expect("url"));
+
+        assert_eq!(manager.latest_version(AgentId::Amp).expect("latest"), "1.1");
+        let latest = manager
+            .install(AgentId::Amp, InstallOptions::default())
+            .expect("install latest");
+        let pinned = manager
+            .install(
+                AgentId::Amp,
+                InstallOptions {
+                    version: Some("1.1".to_string()),
+                    ..InstallOptions::default()
+                },
+            )
+            .expect("install version");
+
+        manager
+            .uninstall(AgentId::Amp, Some("1.1"))
+            .expect("uninstall version");
+        assert!(!pinned.path.exists());
+        assert!(latest.path.exists());
+        assert!(matches!(
+            manager.uninstall(AgentId::Amp, Some("1.1")),
+            Err(AgentError::VersionNotInstalled { .. })
+        ));
+        manager.uninstall(AgentId::Amp, None).expect("uninstall");
+        assert!(!latest.path.exists());
+        assert!(InstallMetadata::read(&latest.path).is_none());
+        assert!(matches!(
+            manager.uninstall(AgentId::Amp, None),
+            Err(AgentError::BinaryNotFound { .. })
+        ));
+        assert!(matches!(
+            manager.uninstall(AgentId::Mock, None),
+            Err(AgentError::UnsupportedAgent { .. })
+        ));
+    }
+
+    /// Packs `files` under `package/`, the way npm tarballs are laid out.
+    fn npm_tarball(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            Vec::new(),
+            flate2::Compression::default(),
+        ));
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, format!("package/{name}"), contents.as_bytes())
+                .expect("append");
+        }
+        builder.into_inner().expect("tar").finish().expect("gzip")
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn gemini_packages_run_on_the_node_found_at_install() {
+        let install_dir = tempfile::tempdir().expect("install dir");
+        let path = install_dir.path().join("gemini");
+        let bundled = npm_tarball(&[
+            ("package.json", r#"{"dependencies":{"left-pad":"1.3.0"}}"#),
+            ("bundle/gemini.js", "console.log(process.argv[2]);"),
+            ("node_modules/left-pad/package.json", "{}"),
+        ]);
+        match find_in_path("node") {
+            Some(node) => {
+                install_gemini_package(&path, bundled).expect("install");
+                let script = fs::read_to_string(&path).expect("wrapper");
+                assert!(script.contains(&format!("exec \"{}\"", node.display())));
+                let output = Command::new(&path).arg("hello").output().expect("run");
+                assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "hello");
+            }
+            None => assert!(matches!(
+                install_gemini_package(&path, bundled),
+                Err(AgentError::RuntimeNotFound { program, .. }) if program == "node"
+            )),
+        }
+
+        if find_in_path("node").is_some() {
+            let missing = npm_tarball(&[("package.json", "{}")]);
+            assert!(matches!(
+                install_gemini_package(&path, missing),
+                Err(AgentError::ExtractFailed(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn compares_version_numbers_inside_version_text() {
+        assert!(is_newer_version("rust-v0.47.0", "codex-cli 0.46.0"));
+        assert!(is_newer_version("2.0.10", "2.0.9 (Claude Code)"));
+        assert!(!is_newer_version("v1.0.0", "1.0.0"));
+        assert!(!is_newer_version("1.0.0", "1.1.0"));
+        assert!(is_newer_version("nightly", "stable"));
+    }
+
+    #[test]
+    fn installs_release_archives_from_local_files() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let archive_path = dir.path().join("codex.tar.gz");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            fs::File::create(&archive_path).expect("create"),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(
+                &mut header,
+                "codex-x86_64-unknown-linux-musl",
+                &b"codex"[..],
+            )
+            .expect("append");
+        builder.into_inner().expect("tar").finish().expect("gzip");
+
+        let manager = AgentManager::with_platform(dir.path().join("bin"), Platform::LinuxX64);
+        let result = manager
+            .install(
+                AgentId::Codex,
+                InstallOptions {
+                    from: Some(archive_path.clone()),
+                    ..InstallOptions::default()
+                },
+            )
+            .expect("install");
+        assert_eq!(fs::read(&result.path).expect("read"), b"codex");
+        let metadata = result.metadata.expect("metadata");
+        assert_eq!(metadata.sha256, sha256_file(&archive_path).expect("hash"));
+
+        let err = manager
+            .install(
+                AgentId::Codex,
+                InstallOptions {
+                    from: Some(archive_path),
+                    sha256: Some(sha256_hex(b"other")),
+                    ..InstallOptions::default()
+                },
+            )
+            .expect_err("mismatch");
+        assert!(matches!(err, AgentError::ChecksumMismatch { .. }));
+    }
+
+    #[test]
+    fn latest_github_installs_download_the_release_they_checked() {
+        let mirror = tempfile::tempdir().expect("mirror");
+        let asset = "codex-x86_64-unknown-linux-musl.tar.gz";
+        let archive_path = mirror
+            .path()
+            .join("github.com/openai/codex/releases/download/rust-v1.0.0")
+            .join(asset);
+        fs::create_dir_all(archive_path.parent().expect("parent")).expect("mkdir");
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+            fs::File::create(&archive_path).expect("create"),
+            flate2::Compression::default(),
+        ));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(
+                &mut header,
+                "codex-x86_64-unknown-linux-musl",
+                &b"codex"[..],
+            )
+            .expect("append");
+        builder.into_inner().expect("tar").finish().expect("gzip");
+        let sha256 = sha256_file(&archive_path).expect("hash");
+
+        let install_dir = tempfile::tempdir().expect("install dir");
+        let manager = AgentManager::with_platform(install_dir.path(), Platform::LinuxX64)
+            .with_mirror(Url::from_directory_path(mirror.path()).expect("url"));
+        // Without the release lookup, the latest release is downloaded unverified.
+        let latest_path = mirror
+            .path()
+            .join("github.com/openai/codex/releases/latest/download")
+            .join(asset);
+        fs::create_dir_all(latest_path.parent().expect("parent")).expect("mkdir");
+        fs::copy(&archive_path, &latest_path).expect("copy");
+        let result = manager
+            .install(AgentId::Codex, InstallOptions::default())
+            .expect("install unverified");
+        let metadata = result.metadata.expect("metadata");
+        assert_eq!(metadata.version, None);
+        assert_eq!(metadata.checksum_source, None);
+
+        let releases = mirror
+            .path()
+            .join("api.github.com/repos/openai/codex/releases");
+        fs::create_dir_all(&releases).expect("mkdir");
+        let release = serde_json::json!({
+            "tag_name": "rust-v1.0.0",
+            "assets": [{ "name": asset, "digest": format!("sha256:{sha256}") }],
+        });
+        fs::write(releases.join("latest"), release.to_string()).expect("write");
+        let result = manager
+            .install(
+                AgentId::Codex,
+                InstallOptions {
+                    reinstall: true,
+                    ..InstallOptions::default()
+                },
+            )
+            .expect("install latest");
+        assert_eq!(fs::read(&result.path).expect("read"), b"codex");
+        let metadata = result.metadata.expect("metadata");
+        assert_eq!(metadata.version.as_deref(), Some("rust-v1.0.0"));
+        assert_eq!(metadata.sha256, sha256);
+        assert_eq!(metadata.checksum_source, Some(ChecksumSource::Manifest));
+    }
+}
@@ -1,3 +1,5 @@
 pub mod agents;
 pub mod credentials;
+pub mod limits;
+pub mod lockfile;
 pub mod testing;
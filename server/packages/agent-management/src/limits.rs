@@ -0,0 +1,620 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::agents::AgentId;
+
+const CGROUP_MOUNT: &str = "/sys/fs/cgroup";
+/// Environment variable naming the parent cgroup when none is configured.
+pub const CGROUP_ROOT_ENV: &str = "SANDBOX_AGENT_CGROUP_ROOT";
+/// Leaf the daemon moves into when its own cgroup is the root for agent cgroups.
+const DAEMON_CGROUP_LEAF: &str = "daemon";
+static CGROUP_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+/// Resource limits applied to a spawned agent process and its descendants.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_bytes: Option<u64>,
+    /// cgroup v2 `cpu.weight` (1-10000, default 100).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_weight: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_pids: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_open_files: Option<u64>,
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        self.memory_bytes.is_none()
+            && self.cpu_weight.is_none()
+            && self.max_pids.is_none()
+            && self.max_open_files.is_none()
+    }
+
+    /// Whether a limit is set that is only enforced per process tree with a cgroup.
+    fn wants_cgroup(&self) -> bool {
+        self.memory_bytes.is_some() || self.cpu_weight.is_some() || self.max_pids.is_some()
+    }
+
+    /// Returns `self` with every limit set in `overrides` replaced.
+    pub fn merged(&self, overrides: &ResourceLimits) -> ResourceLimits {
+        ResourceLimits {
+            memory_bytes: overrides.memory_bytes.or(self.memory_bytes),
+            cpu_weight: overrides.cpu_weight.or(self.cpu_weight),
+            max_pids: overrides.max_pids.or(self.max_pids),
+            max_open_files: overrides.max_open_files.or(self.max_open_files),
+        }
+    }
+}
+
+/// Daemon-wide limit configuration: defaults plus per-agent overrides.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceLimitsConfig {
+    #[serde(default)]
+    pub default: ResourceLimits,
+    #[serde(default)]
+    pub agents: HashMap<AgentId, ResourceLimits>,
+    /// Parent cgroup for per-process cgroups. Defaults to `SANDBOX_AGENT_CGROUP_ROOT`;
+    /// the server fills in its own cgroup at startup with [`adopt_daemon_cgroup`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cgroup_root: Option<PathBuf>,
+}
+
+impl ResourceLimitsConfig {
+    pub fn from_file(path: &Path) -> Result<Self, io::Error> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn for_agent(&self, agent: AgentId) -> ResourceLimits {
+        match self.agents.get(&agent) {
+            Some(limits) => self.default.merged(limits),
+            None => self.default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    Memory,
+    Pids,
+}
+
+impl LimitKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LimitKind::Memory => "memory",
+            LimitKind::Pids => "pids",
+        }
+    }
+}
+
+/// A limit the process ran into, read back from the cgroup event counters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LimitExceeded {
+    pub kind: LimitKind,
+    pub limit: u64,
+    pub events: u64,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            LimitKind::Memory => write!(
+                f,
+                "agent process exceeded memory limit of {} bytes ({} oom kills)",
+                self.limit, self.events
+            ),
+            LimitKind::Pids => write!(
+                f,
+                "agent process exceeded process limit of {} ({} forks refused)",
+                self.limit, self.events
+            ),
+        }
+    }
+}
+
+/// Holds the cgroup created for a spawned process. Dropping it kills any
+/// leftover processes in the cgroup and removes it.
+///
+/// When cgroup v2 is unavailable the limits are applied with `setrlimit`
+/// instead. Only cgroup-backed limits can report hits; rlimits surface as
+/// failed allocations or forks inside the agent.
+#[derive(Debug)]
+pub struct ResourceGuard {
+    limits: ResourceLimits,
+    cgroup: Option<PathBuf>,
+    #[cfg(unix)]
+    _procs: Option<fs::File>,
+}
+
+impl ResourceGuard {
+    /// Configures `command` so the spawned process starts inside its own
+    /// cgroup (or under rlimits). Returns `None` when no limits are set.
+    pub fn prepare(
+        command: &mut Command,
+        name: &str,
+        limits: ResourceLimits,
+        cgroup_root: Option<&Path>,
+    ) -> Option<ResourceGuard> {
+        if limits.is_empty() {
+            return None;
+        }
+        let cgroup = match open_cgroup(name, &limits, cgroup_root) {
+            Ok(dir) => Some(dir),
+            Err(err) => {
+                if limits.wants_cgroup() {
+                    tracing::warn!(agent = name, error = %err, "falling back to rlimits");
+                }
+                None
+            }
+        };
+        Some(Self::configure(command, limits, cgroup))
+    }
+
+    /// Why `limits` would fall back to rlimits instead of a cgroup, or `None` when
+    /// a cgroup can be created for them. Creates and removes a probe cgroup; see
+    /// [`CgroupProbes`] to do that once.
+    pub fn cgroup_fallback(limits: &ResourceLimits, cgroup_root: Option<&Path>) -> Option<String> {
+        if !limits.wants_cgroup() {
+            return None;
+        }
+        match open_cgroup("probe", limits, cgroup_root) {
+            Ok(dir) => {
+                let _ = fs::remove_dir(&dir);
+                None
+            }
+            Err(err) => Some(err.to_string()),
+        }
+    }
+
+    pub fn limits(&self) -> ResourceLimits {
+        self.limits
+    }
+
+    pub fn cgroup_path(&self) -> Option<&Path> {
+        self.cgroup.as_deref()
+    }
+
+    /// Limits that were hit since the process started.
+    pub fn exceeded(&self) -> Vec<LimitExceeded> {
+        let Some(dir) = self.cgroup.as_ref() else {
+            return Vec::new();
+        };
+        let mut exceeded = Vec::new();
+        if let Some(limit) = self.limits.memory_bytes {
+            let events = read_event_counter(&dir.join("memory.events"), "oom_kill");
+            if events > 0 {
+                exceeded.push(LimitExceeded {
+                    kind: LimitKind::Memory,
+                    limit,
+                    events,
+                });
+            }
+        }
+        if let Some(limit) = self.limits.max_pids {
+            let events = read_event_counter(&dir.join("pids.events"), "max");
+            if events > 0 {
+                exceeded.push(LimitExceeded {
+                    kind: LimitKind::Pids,
+                    limit,
+                    events,
+                });
+            }
+        }
+        exceeded
+    }
+
+    #[cfg(unix)]
+    fn configure(
+        command: &mut Command,
+        limits: ResourceLimits,
+        cgroup: Option<PathBuf>,
+    ) -> ResourceGuard {
+        use std::os::unix::io::AsRawFd;
+        use std::os::unix::process::CommandExt;
+
+        let procs = cgroup.as_ref().and_then(|dir| {
+            fs::OpenOptions::new()
+                .write(true)
+                .open(dir.join("cgroup.procs"))
+                .ok()
+        });
+        let procs_fd = procs.as_ref().map(|file| file.as_raw_fd());
+        // Without a cgroup every limit falls back to rlimits; open files always
+        // use rlimits since cgroups have no equivalent.
+        let rlimits = if procs_fd.is_some() {
+            ResourceLimits {
+                max_open_files: limits.max_open_files,
+                ..ResourceLimits::default()
+            }
+        } else {
+            limits
+        };
+        let cgroup = match (cgroup, procs_fd) {
+            (Some(dir), None) => {
+                let _ = fs::remove_dir(&dir);
+                None
+            }
+            (cgroup, _) => cgroup,
+        };
+
+        // SAFETY: the closure only issues async-signal-safe syscalls.
+        unsafe {
+            command.pre_exec(move || {
+                if let Some(fd) = procs_fd {
+                    // Writing "0" moves the calling process into the cgroup
+                    // before exec, so no descendants can escape it.
+                    if libc::write(fd, b"0".as_ptr().cast(), 1) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                }
+                apply_rlimits(&rlimits)
+            });
+        }
+
+        ResourceGuard {
+            limits,
+            cgroup,
+            _procs: procs,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn configure(
+        _command: &mut Command,
+        limits: ResourceLimits,
+        _cgroup: Option<PathBuf>,
+    ) -> ResourceGuard {
+        ResourceGuard {
+            limits,
+            cgroup: None,
+        }
+    }
+}
+
+impl Drop for ResourceGuard {
+    fn drop(&mut self) {
+        let Some(dir) = self.cgroup.take() else {
+            return;
+        };
+        let _ = fs::write(dir.join("cgroup.kill"), "1");
+        for _ in 0..20 {
+            if fs::remove_dir(&dir).is_ok() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+/// Caches [`ResourceGuard::cgroup_fallback`] per set of controllers, so the probe
+/// cgroup is created once rather than for every session.
+#[derive(Debug, Default)]
+pub struct CgroupProbes {
+    results: Mutex<HashMap<Vec<&'static str>, Option<String>>>,
+}
+
+impl CgroupProbes {
+    pub fn fallback(&self, limits: &ResourceLimits, cgroup_root: Option<&Path>) -> Option<String> {
+        if !limits.wants_cgroup() {
+            return None;
+        }
+        let controllers = wanted_controllers(limits);
+        if let Some(result) = self.results.lock().unwrap().get(&controllers) {
+            return result.clone();
+        }
+        let result = ResourceGuard::cgroup_fallback(limits, cgroup_root);
+        self.results
+            .lock()
+            .unwrap()
+            .insert(controllers, result.clone());
+        result
+    }
+}
+
+/// Makes the daemon's own cgroup usable as the parent of agent cgroups and returns
+/// it. cgroup v2 only enables controllers below a cgroup with no processes of its
+/// own, so the daemon moves itself into a `daemon` child. This changes the whole
+/// process's cgroup, so call it once at startup. Returns `None`, leaving the daemon
+/// where it is, when cgroup v2 is unavailable or the move fails.
+pub fn adopt_daemon_cgroup() -> Option<PathBuf> {
+    let mount = Path::new(CGROUP_MOUNT);
+    if !mount.join("cgroup.controllers").exists() {
+        return None;
+    }
+    let membership = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let relative = membership
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))?
+        .trim_start_matches('/');
+    // The root cgroup may hold processes and still delegate controllers.
+    if relative.is_empty() {
+        return Some(mount.to_path_buf());
+    }
+    let root = mount.join(relative);
+    match move_into_leaf(&root, std::process::id()) {
+        Ok(()) => {
+            tracing::info!(
+                cgroup = %root.join(DAEMON_CGROUP_LEAF).display(),
+                "moved the daemon into a leaf cgroup so agent cgroups can be created beside it"
+            );
+            Some(root)
+        }
+        Err(err) => {
+            tracing::warn!(
+                cgroup = %root.display(),
+                error = %err,
+                "cannot move the daemon into a leaf cgroup; resource limits will use rlimits"
+            );
+            None
+        }
+    }
+}
+
+fn open_cgroup(
+    name: &str,
+    limits: &ResourceLimits,
+    cgroup_root: Option<&Path>,
+) -> io::Result<PathBuf> {
+    let root = cgroup_root
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::var_os(CGROUP_ROOT_ENV).map(PathBuf::from))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "cgroup v2 is not available"))?;
+    create_cgroup(&root, name, limits).map_err(|err| {
+        io::Error::new(
+            err.kind(),
+            format!("cannot create a cgroup under {}: {err}", root.display()),
+        )
+    })
+}
+
+/// cgroup v2 only enables controllers for the children of a cgroup with no
+/// processes of its own, so the daemon moves into a leaf child first.
+fn move_into_leaf(root: &Path, pid: u32) -> io::Result<()> {
+    let leaf = root.join(DAEMON_CGROUP_LEAF);
+    match fs::create_dir(&leaf) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(err) => return Err(err),
+    }
+    fs::write(leaf.join("cgroup.procs"), pid.to_string())
+}
+
+fn wanted_controllers(limits: &ResourceLimits) -> Vec<&'static str> {
+    let mut wanted = Vec::new();
+    if limits.memory_bytes.is_some() {
+        wanted.push("memory");
+    }
+    if limits.cpu_weight.is_some() {
+        wanted.push("cpu");
+    }
+    if limits.max_pids.is_some() {
+        wanted.push("pids");
+    }
+    wanted
+}
+
+fn create_cgroup(root: &Path, name: &str, limits: &ResourceLimits) -> io::Result<PathBuf> {
+    let available = fs::read_to_string(root.join("cgroup.controllers"))?;
+    let enabled = fs::read_to_string(root.join("cgroup.subtree_control")).unwrap_or_default();
+    for controller in wanted_controllers(limits) {
+        if !available.split_whitespace().any(|c| c == controller) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("{controller} controller not available"),
+            ));
+        }
+        if !enabled.split_whitespace().any(|c| c == controller) {
+            fs::write(
+                root.join("cgroup.subtree_control"),
+                format!("+{controller}"),
+            )?;
+        }
+    }
+
+    let dir = root.join(format!(
+        "sandbox-agent-{name}-{}-{}",
+        std::process::id(),
+        CGROUP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir(&dir)?;
+    let result = (|| {
+        if let Some(memory) = limits.memory_bytes {
+            fs::write(dir.join("memory.max"), memory.to_string())?;
+            let _ = fs::write(dir.join("memory.swap.max"), "0");
+        }
+        if let Some(weight) = limits.cpu_weight {
+            fs::write(dir.join("cpu.weight"), weight.clamp(1, 10_000).to_string())?;
+        }
+        if let Some(pids) = limits.max_pids {
+            fs::write(dir.join("pids.max"), pids.to_string())?;
+        }
+        Ok(())
+    })();
+    if let Err(err) = result {
+        let _ = fs::remove_dir(&dir);
+        return Err(err);
+    }
+    Ok(dir)
+}
+
+fn read_event_counter(path: &Path, key: &str) -> u64 {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| {
+            text.lines().find_map(|line| {
+                let (name, value) = line.split_once(' ')?;
+                (name == key).then(|| value.trim().parse().ok()).flatten()
+            })
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(all(unix, not(all(target_os = "linux", target_env = "gnu"))))]
+type RlimitResource = libc::c_int;
+
+#[cfg(unix)]
+fn apply_rlimits(limits: &ResourceLimits) -> io::Result<()> {
+    // RLIMIT_DATA rather than RLIMIT_AS: JS runtimes reserve far more address
+    // space than they ever touch.
+    if let Some(memory) = limits.memory_bytes {
+        set_rlimit(libc::RLIMIT_DATA, memory)?;
+    }
+    // RLIMIT_NPROC counts every process of the user, so it is only a rough cap.
+    if let Some(pids) = limits.max_pids {
+        set_rlimit(libc::RLIMIT_NPROC, pids)?;
+    }
+    if let Some(files) = limits.max_open_files {
+        set_rlimit(libc::RLIMIT_NOFILE, files)?;
+    }
+    if let Some(weight) = limits.cpu_weight {
+        // Map weights below the default of 100 onto nice 1-19.
+        if weight < 100 {
+            let nice = 19 - (weight.max(1) * 19 / 100) as libc::c_int;
+            // SAFETY: setpriority is async-signal-safe.
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_rlimit(resource: RlimitResource, value: u64) -> io::Result<()> {
+    let mut current = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: getrlimit/setrlimit are async-signal-safe and only touch `current`.
+    unsafe {
+        if libc::getrlimit(resource, &mut current) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let value = (value as libc::rlim_t).min(current.rlim_max);
+        let limit = libc::rlimit {
+            rlim_cur: value,
+            rlim_max: value,
+        };
+        if libc::setrlimit(resource, &limit) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn agent_limits_override_defaults() {
+        let mut config = ResourceLimitsConfig {
+            default: ResourceLimits {
+                memory_bytes: Some(1024),
+                max_pids: Some(64),
+                ..ResourceLimits::default()
+            },
+            ..ResourceLimitsConfig::default()
+        };
+        config.agents.insert(
+            AgentId::Claude,
+            ResourceLimits {
+                memory_bytes: Some(2048),
+                ..ResourceLimits::default()
+            },
+        );
+
+        let claude = config.for_agent(AgentId::Claude);
+        assert_eq!(claude.memory_bytes, Some(2048));
+        assert_eq!(claude.max_pids, Some(64));
+        assert_eq!(config.for_agent(AgentId::Codex).memory_bytes, Some(1024));
+    }
+
+    #[test]
+    fn moves_the_daemon_into_a_leaf_cgroup() {
+        let root = tempfile::tempdir().expect("tempdir");
+        move_into_leaf(root.path(), 42).expect("move");
+        let leaf = root.path().join(DAEMON_CGROUP_LEAF);
+        assert_eq!(fs::read_to_string(leaf.join("cgroup.procs")).unwrap(), "42");
+        move_into_leaf(root.path(), 43).expect("leaf already exists");
+        assert_eq!(fs::read_to_string(leaf.join("cgroup.procs")).unwrap(), "43");
+    }
+
+    #[test]
+    fn reports_why_cgroups_cannot_be_used() {
+        let root = tempfile::tempdir().expect("tempdir");
+        let limits = ResourceLimits {
+            memory_bytes: Some(1024),
+            ..ResourceLimits::default()
+        };
+        fs::write(root.path().join("cgroup.controllers"), "cpu pids").expect("write");
+        let reason = ResourceGuard::cgroup_fallback(&limits, Some(root.path()))
+            .expect("no memory controller");
+        assert!(
+            reason.contains("memory controller not available"),
+            "{reason}"
+        );
+
+        let open_files = ResourceLimits {
+            max_open_files: Some(64),
+            ..ResourceLimits::default()
+        };
+        assert_eq!(
+            ResourceGuard::cgroup_fallback(&open_files, Some(root.path())),
+            None
+        );
+
+        fs::write(root.path().join("cgroup.controllers"), "memory").expect("write");
+        assert_eq!(
+            ResourceGuard::cgroup_fallback(&limits, Some(root.path())),
+            None
+        );
+    }
+
+    #[test]
+    fn probes_each_set_of_controllers_once() {
+        let root = tempfile::tempdir().expect("tempdir");
+        fs::write(root.path().join("cgroup.controllers"), "cpu").expect("write");
+        let memory = ResourceLimits {
+            memory_bytes: Some(1024),
+            ..ResourceLimits::default()
+        };
+        let cpu = ResourceLimits {
+            cpu_weight: Some(50),
+            ..ResourceLimits::default()
+        };
+        let probes = CgroupProbes::default();
+        assert!(probes.fallback(&memory, Some(root.path())).is_some());
+        assert_eq!(probes.fallback(&cpu, Some(root.path())), None);
+
+        fs::write(root.path().join("cgroup.controllers"), "memory").expect("write");
+        assert!(probes.fallback(&memory, Some(root.path())).is_some());
+        assert_eq!(probes.fallback(&cpu, Some(root.path())), None);
+    }
+
+    #[test]
+    fn reads_cgroup_event_counters() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("memory.events");
+        fs::write(&path, "low 0\nhigh 0\nmax 12\noom 1\noom_kill 1\n").expect("write");
+        assert_eq!(read_event_counter(&path, "oom_kill"), 1);
+        assert_eq!(read_event_counter(&path, "max"), 12);
+        assert_eq!(read_event_counter(&path, "missing"), 0);
+    }
+}
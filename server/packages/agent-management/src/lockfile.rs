@@ -0,0 +1,159 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use ring::digest::{Context, SHA256};
+use serde::{Deserialize, Serialize};
+
+use crate::agents::AgentId;
+
+/// Versions agents are pinned to, with the checksum of each pinned binary. Installs
+/// without an explicit version install the pinned one, and sessions run it unless
+/// they ask for another installed version.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentLockfile {
+    /// Keyed by agent id.
+    #[serde(default)]
+    pub agents: BTreeMap<String, LockedAgent>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockedAgent {
+    pub version: String,
+    /// Hex SHA-256 of the installed binary, checked when the version is installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Hex SHA-256 of the downloaded release file, checked before it is installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_sha256: Option<String>,
+}
+
+impl AgentLockfile {
+    /// Reads `path`, or returns an empty lockfile if it does not exist yet.
+    pub fn from_file(path: &Path) -> Result<Self, io::Error> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), io::Error> {
+        let mut text = serde_json::to_string_pretty(self)?;
+        text.push('\n');
+        fs::write(path, text)
+    }
+
+    pub fn get(&self, agent: AgentId) -> Option<&LockedAgent> {
+        self.agents.get(agent.as_str())
+    }
+
+    pub fn pin(&mut self, agent: AgentId, locked: LockedAgent) {
+        self.agents.insert(agent.as_str().to_string(), locked);
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex(ring::digest::digest(&SHA256, bytes).as_ref())
+}
+
+/// Hex SHA-256 of a file's contents.
+pub fn sha256_file(path: &Path) -> Result<String, io::Error> {
+    let mut file = fs::File::open(path)?;
+    let mut context = Context::new(&SHA256);
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+    Ok(hex(context.finish().as_ref()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::{AgentError, AgentManager, Platform};
+
+    #[test]
+    fn missing_file_is_empty_and_pins_round_trip() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("agents.lock.json");
+        let mut lockfile = AgentLockfile::from_file(&path).expect("missing lockfile");
+        assert!(lockfile.agents.is_empty());
+
+        fs::write(dir.path().join("binary"), b"abc").expect("write binary");
+        let sha256 = sha256_file(&dir.path().join("binary")).expect("hash");
+        assert_eq!(
+            sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(sha256_hex(b"abc"), sha256);
+        lockfile.pin(
+            AgentId::Claude,
+            LockedAgent {
+                version: "2.0.14".to_string(),
+                sha256: Some(sha256.clone()),
+                download_sha256: None,
+            },
+        );
+        lockfile.write(&path).expect("write");
+
+        let read = AgentLockfile::from_file(&path).expect("read");
+        let locked = read.get(AgentId::Claude).expect("claude pinned");
+        assert_eq!(locked.version, "2.0.14");
+        assert_eq!(locked.sha256.as_deref(), Some(sha256.as_str()));
+        assert!(read.get(AgentId::Codex).is_none());
+    }
+
+    #[test]
+    fn sessions_resolve_pinned_and_requested_versions() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let manager = AgentManager::with_platform(dir.path(), Platform::LinuxX64);
+        for version in ["1.0.0", "1.1.0"] {
+            let path = manager
+                .versioned_binary_path(AgentId::Claude, version)
+                .expect("path");
+            fs::create_dir_all(path.parent().expect("parent")).expect("mkdir");
+            fs::write(&path, version).expect("write");
+        }
+        assert_eq!(
+            manager.installed_versions(AgentId::Claude),
+            ["1.0.0", "1.1.0"]
+        );
+
+        let mut lockfile = AgentLockfile::default();
+        lockfile.pin(
+            AgentId::Claude,
+            LockedAgent {
+                version: "1.0.0".to_string(),
+                sha256: None,
+                download_sha256: None,
+            },
+        );
+        let manager = manager.with_lockfile(lockfile);
+        let pinned = manager.resolve_binary(AgentId::Claude).expect("pinned");
+        assert!(pinned.ends_with("versions/claude/1.0.0/claude"));
+        let requested = manager
+            .resolve_binary_version(AgentId::Claude, Some("1.1.0"))
+            .expect("requested");
+        assert!(requested.ends_with("versions/claude/1.1.0/claude"));
+        assert!(matches!(
+            manager.resolve_binary_version(AgentId::Claude, Some("2.0.0")),
+            Err(AgentError::VersionNotInstalled { .. })
+        ));
+        assert!(matches!(
+            manager.versioned_binary_path(AgentId::Claude, "../evil"),
+            Err(AgentError::InvalidVersion { .. })
+        ));
+    }
+}
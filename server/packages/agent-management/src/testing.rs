@@ -36,6 +36,7 @@ pub enum TestAgentConfigError {
 const AGENTS_ENV: &str = "SANDBOX_TEST_AGENTS";
 const ANTHROPIC_ENV: &str = "SANDBOX_TEST_ANTHROPIC_API_KEY";
 const OPENAI_ENV: &str = "SANDBOX_TEST_OPENAI_API_KEY";
+const GEMINI_ENV: &str = "SANDBOX_TEST_GEMINI_API_KEY";
 const ANTHROPIC_MODELS_URL: &str = "https://api.anthropic.com/v1/models";
 const OPENAI_MODELS_URL: &str = "https://api.openai.com/v1/models";
 const ANTHROPIC_VERSION: &str = "2023-06-01";
@@ -63,6 +64,7 @@ pub fn test_agents_from_env() -> Result<Vec<TestAgentConfig>, TestAgentConfigErr
                     AgentId::Codex,
                     AgentId::Opencode,
                     AgentId::Amp,
+                    AgentId::Gemini,
                 ]);
                 continue;
             }
@@ -97,6 +99,14 @@ pub fn test_agents_from_env() -> Result<Vec<TestAgentConfig>, TestAgentConfigErr
             provider: "openai".to_string(),
         })
         .or_else(|| extracted.openai.clone());
+    let google_cred = read_env_key(GEMINI_ENV)
+        .map(|key| ProviderCredentials {
+            api_key: key,
+            source: "sandbox-test-env".to_string(),
+            auth_type: AuthType::ApiKey,
+            provider: "google".to_string(),
+        })
+        .or_else(|| extracted.other.get("google").cloned());
     let mut health_cache = HealthCheckCache::default();
 
     let mut configs = Vec::new();
@@ -137,7 +147,18 @@ pub fn test_agents_from_env() -> Result<Vec<TestAgentConfig>, TestAgentConfigErr
                 }
                 credentials_with(anthropic_cred.clone(), openai_cred.clone())
             }
-            AgentId::Mock => credentials_with(None, None),
+            AgentId::Gemini => {
+                let google_cred = google_cred.clone().ok_or_else(|| {
+                    TestAgentConfigError::MissingCredentials {
+                        agent,
+                        missing: GEMINI_ENV.to_string(),
+                    }
+                })?;
+                let mut credentials = credentials_with(None, None);
+                credentials.other.insert("google".to_string(), google_cred);
+                credentials
+            }
+            AgentId::Acp | AgentId::Mock | AgentId::Custom(_) => credentials_with(None, None),
         };
         configs.push(TestAgentConfig { agent, credentials });
     }
@@ -303,6 +324,7 @@ fn detect_system_agents() -> Vec<AgentId> {
         AgentId::Codex,
         AgentId::Opencode,
         AgentId::Amp,
+        AgentId::Gemini,
     ];
     let install_dir = default_install_dir();
     candidates
@@ -19,6 +19,8 @@ pub enum ErrorType {
     ModeNotSupported,
     StreamError,
     Timeout,
+    RateLimited,
+    AgentInUse,
 }
 
 impl ErrorType {
@@ -36,6 +38,8 @@ impl ErrorType {
             Self::ModeNotSupported => "urn:sandbox-agent:error:mode_not_supported",
             Self::StreamError => "urn:sandbox-agent:error:stream_error",
             Self::Timeout => "urn:sandbox-agent:error:timeout",
+            Self::RateLimited => "urn:sandbox-agent:error:rate_limited",
+            Self::AgentInUse => "urn:sandbox-agent:error:agent_in_use",
         }
     }
 
@@ -53,6 +57,8 @@ impl ErrorType {
             Self::ModeNotSupported => "Mode Not Supported",
             Self::StreamError => "Stream Error",
             Self::Timeout => "Timeout",
+            Self::RateLimited => "Rate Limited",
+            Self::AgentInUse => "Agent In Use",
         }
     }
 
@@ -70,6 +76,8 @@ impl ErrorType {
             Self::ModeNotSupported => 400,
             Self::StreamError => 502,
             Self::Timeout => 504,
+            Self::RateLimited => 429,
+            Self::AgentInUse => 409,
         }
     }
 }
@@ -147,6 +155,16 @@ pub enum SandboxError {
     StreamError { message: String },
     #[error("timeout")]
     Timeout { message: Option<String> },
+    #[error("rate limited: {message}")]
+    RateLimited {
+        message: String,
+        retry_after_secs: u64,
+    },
+    #[error("agent in use: {agent}")]
+    AgentInUse {
+        agent: String,
+        session_ids: Vec<String>,
+    },
 }
 
 impl SandboxError {
@@ -164,6 +182,8 @@ impl SandboxError {
             Self::ModeNotSupported { .. } => ErrorType::ModeNotSupported,
             Self::StreamError { .. } => ErrorType::StreamError,
             Self::Timeout { .. } => ErrorType::Timeout,
+            Self::RateLimited { .. } => ErrorType::RateLimited,
+            Self::AgentInUse { .. } => ErrorType::AgentInUse,
         }
     }
 
@@ -248,6 +268,26 @@ impl SandboxError {
                 });
                 (None, None, details)
             }
+            Self::RateLimited {
+                message,
+                retry_after_secs,
+            } => {
+                let mut map = Map::new();
+                map.insert("message".to_string(), Value::String(message.clone()));
+                map.insert(
+                    "retryAfterSecs".to_string(),
+                    Value::Number(serde_json::Number::from(*retry_after_secs)),
+                );
+                (None, None, Some(Value::Object(map)))
+            }
+            Self::AgentInUse { agent, session_ids } => {
+                let mut map = Map::new();
+                map.insert(
+                    "sessionIds".to_string(),
+                    Value::Array(session_ids.iter().cloned().map(Value::String).collect()),
+                );
+                (Some(agent.clone()), None, Some(Value::Object(map)))
+            }
         };
 
         AgentError {
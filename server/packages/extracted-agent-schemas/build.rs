@@ -11,6 +11,7 @@ fn main() {
         ("claude", "claude.json"),
         ("codex", "codex.json"),
         ("amp", "amp.json"),
+        ("gemini", "gemini.json"),
     ];
 
     for (name, file) in schemas {
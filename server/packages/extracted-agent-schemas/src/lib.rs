@@ -5,6 +5,7 @@
 //! - Claude Code SDK
 //! - Codex SDK
 //! - AMP Code SDK
+//! - Gemini CLI
 
 pub mod opencode {
     //! OpenCode SDK types extracted from OpenAPI 3.1.1 spec.
@@ -25,3 +26,8 @@ pub mod amp {
     //! AMP Code SDK types.
     include!(concat!(env!("OUT_DIR"), "/amp.rs"));
 }
+
+pub mod gemini {
+    //! Gemini CLI stream-json types.
+    include!(concat!(env!("OUT_DIR"), "/gemini.rs"));
+}
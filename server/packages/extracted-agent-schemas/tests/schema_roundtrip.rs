@@ -1,4 +1,4 @@
-use sandbox_agent_extracted_agent_schemas::{amp, claude, codex};
+use sandbox_agent_extracted_agent_schemas::{amp, claude, codex, gemini};
 
 #[test]
 fn test_claude_bash_input() {
@@ -73,3 +73,12 @@ fn test_amp_message() {
     assert!(json.contains("user"));
     assert!(json.contains("Hello"));
 }
+
+#[test]
+fn test_gemini_stream_event() {
+    let line = r#"{"type":"tool_use","timestamp":"2025-01-01T00:00:00Z","tool_name":"read_file","tool_id":"read-1","parameters":{"path":"a.txt"}}"#;
+    let event: gemini::StreamJsonEvent = serde_json::from_str(line).unwrap();
+    assert_eq!(event.type_, gemini::StreamJsonEventType::ToolUse);
+    assert_eq!(event.tool_id.as_deref(), Some("read-1"));
+    assert_eq!(event.parameters["path"], "a.txt");
+}
@@ -0,0 +1,1605 @@
+//! Per-agent behavior behind a trait, so agents beyond the built-in ones can be
+//! added from other crates by registering an [`AgentAdapter`] at startup.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+use futures::future::BoxFuture;
+use sandbox_agent_error::SandboxError;
+
+use sandbox_agent_agent_management::agents::{
+    AgentError, AgentId, AgentManager, InstallOptions, InstallResult, SpawnOptions, StreamingSpawn,
+};
+use sandbox_agent_agent_management::credentials::{
+    AuthType, ExtractedCredentials, ProviderCredentials,
+};
+use sandbox_agent_universal_agent_schema::{
+    convert_amp, convert_claude, convert_codex, convert_gemini, convert_opencode, gemini,
+    AgentUnparsedData, EventConversion, UniversalEventData, UniversalEventType,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use utoipa::ToSchema;
+
+use crate::router::{
+    apply_amp_mcp_config, claude_fallback_models, claude_user_message_line,
+    codex_permission_response_line, fetch_anthropic_models, is_file_change_action,
+    write_claude_mcp_config, write_codex_mcp_config, write_gemini_mcp_config,
+};
+
+/// Everything the server needs to know about one agent. Only `id`, `capabilities`,
+/// `modes` and `parse_line` are required; the defaults describe a per-turn
+/// subprocess that is spawned with its bare binary and never asks for input.
+pub trait AgentAdapter: Send + Sync {
+    fn id(&self) -> AgentId;
+
+    fn capabilities(&self) -> AgentCapabilities;
+
+    fn modes(&self) -> Vec<AgentModeInfo>;
+
+    /// Models to offer when the agent has no way to list them itself.
+    fn models(&self) -> AgentModelsResponse {
+        AgentModelsResponse {
+            models: Vec::new(),
+            default_model: None,
+        }
+    }
+
+    fn accepts_agent_mode(&self, mode: &str) -> bool {
+        self.modes().iter().any(|info| info.id == mode)
+    }
+
+    /// Permission modes the agent honours, out of `default`, `plan`, `bypass`
+    /// and `acceptEdits`.
+    fn permission_modes(&self) -> &'static [&'static str] {
+        &["default"]
+    }
+
+    /// Rejects a supported permission mode the agent cannot run in on this host.
+    fn check_permission_mode(&self, _mode: &str) -> Result<(), SandboxError> {
+        Ok(())
+    }
+
+    /// Whether a permission request is approved without asking under the
+    /// session's permission mode, for agents that do not apply the mode themselves.
+    fn auto_approves(&self, _permission_mode: &str, _action: &str) -> bool {
+        false
+    }
+
+    /// Whether a finished session can take another message, resuming the
+    /// conversation in a new process.
+    fn supports_resume(&self) -> bool {
+        false
+    }
+
+    /// Whether the agent reports turn starts itself; otherwise one is synthesized.
+    fn emits_turn_started(&self) -> bool {
+        false
+    }
+
+    /// How the agent's process runs and how sessions talk to it.
+    fn server_kind(&self) -> ServerKind {
+        ServerKind::PerTurn
+    }
+
+    fn credentials_available(&self, _credentials: &ExtractedCredentials) -> bool {
+        true
+    }
+
+    /// Environment that hands the agent extracted Anthropic credentials. Variables
+    /// the request already sets are kept.
+    fn anthropic_env(&self, anthropic: &ProviderCredentials) -> Vec<(&'static str, String)> {
+        vec![
+            ("ANTHROPIC_API_KEY", anthropic.api_key.clone()),
+            ("CLAUDE_API_KEY", anthropic.api_key.clone()),
+        ]
+    }
+
+    /// Hosts sessions with an egress allowlist may always reach.
+    fn provider_hosts(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn install(
+        &self,
+        manager: &AgentManager,
+        options: InstallOptions,
+    ) -> Result<InstallResult, AgentError> {
+        manager.install(self.id(), options)
+    }
+
+    /// Starts one turn. Adapters for agents the manager has no command line for
+    /// build one from [`AgentManager::base_command`].
+    fn spawn(
+        &self,
+        manager: &AgentManager,
+        options: SpawnOptions,
+    ) -> Result<StreamingSpawn, AgentError> {
+        manager.spawn_streaming(self.id(), options)
+    }
+
+    /// Lists models live, for agents whose models depend on the account. Empty or
+    /// failed listings fall back to `models`.
+    fn fetch_models(
+        &self,
+        _client: reqwest::Client,
+        _credentials: ExtractedCredentials,
+    ) -> Option<BoxFuture<'static, Result<AgentModelsResponse, SandboxError>>> {
+        None
+    }
+
+    /// Whether a model listing can be reused for later sessions.
+    fn cache_models(&self, _models: &AgentModelsResponse) -> bool {
+        true
+    }
+
+    /// Adds MCP servers to the agent's configuration before its session starts.
+    /// `home` is the session's isolated home, when it has one.
+    fn write_mcp_config(
+        &self,
+        _manager: &AgentManager,
+        _mcp: &BTreeMap<String, McpServerConfig>,
+        _home: Option<&SessionHomeRef<'_>>,
+    ) -> Result<(), SandboxError> {
+        Err(SandboxError::InvalidRequest {
+            message: format!("MCP servers are not supported for {}", self.id().as_str()),
+        })
+    }
+
+    /// Line written to stdin after spawning a turn, for agents that read the
+    /// prompt there rather than from their arguments. `session_id` is the agent's
+    /// session id, or ours when it has not reported one.
+    fn initial_input(&self, _prompt: &str, _session_id: &str) -> Option<String> {
+        None
+    }
+
+    /// Whether an output line is the turn's last. Stdin is closed after it so
+    /// agents that read it until EOF exit.
+    fn ends_turn(&self, _event: &Value) -> bool {
+        false
+    }
+
+    /// Converts one line of the agent's stdout or stderr into universal events.
+    fn parse_line(&self, line: &str, session_id: &str) -> Vec<EventConversion>;
+
+    /// Called once a session's per-turn process has exited, however it ended, so
+    /// state kept for it between lines can be dropped.
+    fn process_exited(&self, _session_id: &str) {}
+
+    /// Called when a session is deleted, so state kept for it across turns can be
+    /// dropped.
+    fn session_removed(&self, _session_id: &str) {}
+
+    /// Line to write to the agent's stdin to answer a permission request, for
+    /// agents that read replies there. Shared stdio servers get it on theirs.
+    fn permission_reply_line(&self, _reply: &PermissionReplyContext<'_>) -> Option<String> {
+        None
+    }
+
+    /// Line to write to the agent's stdin to answer or reject a question.
+    fn question_reply_line(&self, _reply: &QuestionReplyContext<'_>) -> Option<String> {
+        None
+    }
+}
+
+/// How sessions reach an agent's process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerKind {
+    /// A new process for every turn, started by `spawn`.
+    PerTurn,
+    /// One Codex `app-server` speaking JSON-RPC over stdio, shared by all sessions.
+    CodexAppServer,
+    /// One OpenCode HTTP server, shared by all sessions.
+    OpencodeHttp,
+    /// One Agent Client Protocol server over stdio, shared by all sessions.
+    Acp,
+}
+
+/// A session's isolated home directory and the environment that points the agent at it.
+pub struct SessionHomeRef<'a> {
+    pub path: &'a Path,
+    pub env: Vec<(String, String)>,
+}
+
+pub struct PermissionReplyContext<'a> {
+    pub permission_id: &'a str,
+    pub action: &'a str,
+    pub metadata: Option<&'a Value>,
+    pub reply: PermissionReply,
+}
+
+pub struct QuestionReplyContext<'a> {
+    /// The agent's session id, or ours when it has not reported one.
+    pub native_session_id: &'a str,
+    pub question_id: &'a str,
+    /// `None` when the question was rejected.
+    pub answers: Option<&'a [Vec<String>]>,
+    /// Permission request that carried the question, for agents that ask
+    /// questions through a tool call.
+    pub linked_permission_id: Option<&'a str>,
+    pub linked_permission_metadata: Option<&'a Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentModeInfo {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentModelInfo {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variants: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_variant: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentModelsResponse {
+    pub models: Vec<AgentModelInfo>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentCapabilities {
+    // TODO: add agent-agnostic tests that cover every capability flag here.
+    pub plan_mode: bool,
+    pub permissions: bool,
+    pub questions: bool,
+    pub tool_calls: bool,
+    pub tool_results: bool,
+    pub text_messages: bool,
+    pub images: bool,
+    pub file_attachments: bool,
+    pub session_lifecycle: bool,
+    pub error_events: bool,
+    pub reasoning: bool,
+    pub status: bool,
+    pub command_execution: bool,
+    pub file_changes: bool,
+    pub mcp_tools: bool,
+    pub streaming_deltas: bool,
+    pub item_started: bool,
+    /// Whether this agent uses a shared long-running server process (vs per-turn subprocess)
+    pub shared_process: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(untagged)]
+pub enum McpCommand {
+    Command(String),
+    CommandWithArgs(Vec<String>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum McpRemoteTransport {
+    Http,
+    Sse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct McpOAuthConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(untagged)]
+pub enum McpOAuthConfigOrDisabled {
+    Config(McpOAuthConfig),
+    Disabled(bool),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum McpServerConfig {
+    #[serde(rename = "local", alias = "stdio")]
+    Local {
+        command: McpCommand,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            alias = "environment"
+        )]
+        env: Option<BTreeMap<String, String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        enabled: Option<bool>,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            rename = "timeoutMs",
+            alias = "timeout"
+        )]
+        #[schema(rename = "timeoutMs")]
+        timeout_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cwd: Option<String>,
+    },
+    #[serde(rename = "remote", alias = "http")]
+    Remote {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        headers: Option<BTreeMap<String, String>>,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            rename = "bearerTokenEnvVar",
+            alias = "bearerTokenEnvVar",
+            alias = "bearer_token_env_var"
+        )]
+        #[schema(rename = "bearerTokenEnvVar")]
+        bearer_token_env_var: Option<String>,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            rename = "envHeaders",
+            alias = "envHttpHeaders",
+            alias = "env_http_headers"
+        )]
+        #[schema(rename = "envHeaders")]
+        env_headers: Option<BTreeMap<String, String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        oauth: Option<McpOAuthConfigOrDisabled>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        enabled: Option<bool>,
+        #[serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            rename = "timeoutMs",
+            alias = "timeout"
+        )]
+        #[schema(rename = "timeoutMs")]
+        timeout_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        transport: Option<McpRemoteTransport>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionReply {
+    Once,
+    Always,
+    Reject,
+}
+
+impl std::str::FromStr for PermissionReply {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "once" => Ok(Self::Once),
+            "always" => Ok(Self::Always),
+            "reject" => Ok(Self::Reject),
+            _ => Err(format!("invalid permission reply: {value}")),
+        }
+    }
+}
+
+static REGISTRY: OnceLock<AgentRegistry> = OnceLock::new();
+
+/// Process-wide set of agent adapters, starting with the built-in agents.
+pub fn agent_registry() -> &'static AgentRegistry {
+    REGISTRY.get_or_init(AgentRegistry::with_builtins)
+}
+
+pub struct AgentRegistry {
+    adapters: RwLock<Vec<Arc<dyn AgentAdapter>>>,
+}
+
+impl AgentRegistry {
+    fn with_builtins() -> Self {
+        let adapters: Vec<Arc<dyn AgentAdapter>> = vec![
+            Arc::new(ClaudeAdapter::default()),
+            Arc::new(CodexAdapter),
+            Arc::new(OpencodeAdapter),
+            Arc::new(AmpAdapter),
+            Arc::new(GeminiAdapter::default()),
+            Arc::new(AcpAdapter),
+            Arc::new(MockAdapter),
+        ];
+        Self {
+            adapters: RwLock::new(adapters),
+        }
+    }
+
+    /// Adds an adapter for an id from [`AgentId::register_custom`].
+    pub fn register(&self, adapter: Arc<dyn AgentAdapter>) -> Result<(), AgentError> {
+        let agent = adapter.id();
+        let invalid = |reason: &str| AgentError::InvalidAgentName {
+            name: agent.as_str().to_string(),
+            reason: reason.to_string(),
+        };
+        if !agent.is_custom() {
+            return Err(invalid("name of a built-in agent"));
+        }
+        let mut adapters = self.adapters.write().expect("agent registry lock");
+        if adapters.iter().any(|existing| existing.id() == agent) {
+            return Err(invalid("already registered"));
+        }
+        adapters.push(adapter);
+        Ok(())
+    }
+
+    pub fn get(&self, agent: AgentId) -> Option<Arc<dyn AgentAdapter>> {
+        self.adapters
+            .read()
+            .expect("agent registry lock")
+            .iter()
+            .find(|adapter| adapter.id() == agent)
+            .cloned()
+    }
+
+    /// Registered agents, built-in agents first.
+    pub fn agents(&self) -> Vec<AgentId> {
+        self.adapters
+            .read()
+            .expect("agent registry lock")
+            .iter()
+            .map(|adapter| adapter.id())
+            .collect()
+    }
+}
+
+/// The agent's adapter; custom ids registered without one get an adapter that
+/// reports every line as unparsed.
+pub(crate) fn adapter(agent: AgentId) -> Arc<dyn AgentAdapter> {
+    agent_registry()
+        .get(agent)
+        .unwrap_or_else(|| Arc::new(UnregisteredAdapter(agent)))
+}
+
+/// Parses one output line as JSON. Blank lines give `Ok(None)`; anything else that
+/// is not JSON gives the `agent.unparsed` event to record instead.
+pub fn parse_json_line(agent: AgentId, line: &str) -> Result<Option<Value>, Vec<EventConversion>> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_str(trimmed).map(Some).map_err(|err| {
+        vec![agent_unparsed(
+            agent.as_str(),
+            &err.to_string(),
+            Value::String(trimmed.to_string()),
+        )]
+    })
+}
+
+pub fn agent_unparsed(location: &str, error: &str, raw: Value) -> EventConversion {
+    EventConversion::new(
+        UniversalEventType::AgentUnparsed,
+        UniversalEventData::AgentUnparsed(AgentUnparsedData {
+            error: error.to_string(),
+            location: location.to_string(),
+            raw_hash: None,
+        }),
+    )
+    .synthetic()
+    .with_raw(Some(raw))
+}
+
+/// Runs `convert` on a JSON line, reporting parse and conversion failures as
+/// `agent.unparsed` events.
+pub(crate) fn convert_json_line(
+    agent: AgentId,
+    line: &str,
+    convert: impl FnOnce(&Value) -> Result<Vec<EventConversion>, String>,
+) -> Vec<EventConversion> {
+    match parse_json_line(agent, line) {
+        Ok(Some(value)) => {
+            convert(&value).unwrap_or_else(|err| vec![agent_unparsed(agent.as_str(), &err, value)])
+        }
+        Ok(None) => Vec::new(),
+        Err(unparsed) => unparsed,
+    }
+}
+
+fn current_dir() -> Result<std::path::PathBuf, SandboxError> {
+    std::env::current_dir().map_err(|err| SandboxError::StreamError {
+        message: err.to_string(),
+    })
+}
+
+/// Check if the current process is running as root (uid 0)
+fn is_running_as_root() -> bool {
+    #[cfg(unix)]
+    {
+        unsafe { libc::getuid() == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        false
+    }
+}
+
+pub(crate) fn mode(id: &str, name: &str, description: &str) -> AgentModeInfo {
+    AgentModeInfo {
+        id: id.to_string(),
+        name: name.to_string(),
+        description: description.to_string(),
+    }
+}
+
+fn single_model(id: &str, name: &str) -> AgentModelsResponse {
+    AgentModelsResponse {
+        models: vec![AgentModelInfo {
+            id: id.to_string(),
+            name: Some(name.to_string()),
+            variants: None,
+            default_variant: None,
+        }],
+        default_model: Some(id.to_string()),
+    }
+}
+
+/// Claude leaves the message id off some assistant and result lines, so ids are
+/// generated per session and a turn's result is tied to its last message.
+#[derive(Default)]
+struct ClaudeAdapter {
+    messages: Mutex<HashMap<String, ClaudeMessages>>,
+}
+
+#[derive(Default)]
+struct ClaudeMessages {
+    counter: u64,
+    last_id: Option<String>,
+}
+
+impl ClaudeMessages {
+    fn next_id(&mut self, session_id: &str) -> String {
+        self.counter += 1;
+        format!("{session_id}_message_{}", self.counter)
+    }
+}
+
+impl ClaudeAdapter {
+    fn fill_message_ids(&self, value: &mut Value, session_id: &str) {
+        let event_type = value.get("type").and_then(Value::as_str).unwrap_or("");
+        if event_type != "assistant" && event_type != "result" {
+            return;
+        }
+        let mut messages = self.messages.lock().expect("claude messages lock");
+        let messages = messages.entry(session_id.to_string()).or_default();
+        if event_type == "assistant" {
+            let id = match value
+                .get("message")
+                .and_then(|message| message.get("id"))
+                .and_then(Value::as_str)
+            {
+                Some(id) => id.to_string(),
+                None => {
+                    let generated = messages.next_id(session_id);
+                    if let Some(message) = value.get_mut("message").and_then(Value::as_object_mut) {
+                        message.insert("id".to_string(), Value::String(generated.clone()));
+                    } else if let Some(map) = value.as_object_mut() {
+                        map.insert("message".to_string(), json!({ "id": generated }));
+                    }
+                    generated
+                }
+            };
+            messages.last_id = Some(id);
+        } else if value.get("message_id").is_some() || value.get("messageId").is_some() {
+            messages.last_id = None;
+        } else {
+            let id = messages
+                .last_id
+                .take()
+                .unwrap_or_else(|| messages.next_id(session_id));
+            if let Some(map) = value.as_object_mut() {
+                map.insert("message_id".to_string(), Value::String(id));
+            }
+        }
+    }
+}
+
+impl AgentAdapter for ClaudeAdapter {
+    fn id(&self) -> AgentId {
+        AgentId::Claude
+    }
+
+    // Claude CLI supports tool calls/results and permission prompts via the SDK control protocol,
+    // but we still emit synthetic item.started events.
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            plan_mode: false,
+            permissions: true,
+            questions: true,
+            tool_calls: true,
+            tool_results: true,
+            text_messages: true,
+            images: false,
+            file_attachments: false,
+            session_lifecycle: false,
+            error_events: false,
+            reasoning: false,
+            status: false,
+            command_execution: false,
+            file_changes: false,
+            mcp_tools: true,
+            streaming_deltas: true,
+            item_started: false,
+            shared_process: false, // per-turn subprocess with --resume
+        }
+    }
+
+    fn modes(&self) -> Vec<AgentModeInfo> {
+        vec![
+            mode("build", "Build", "Default build mode"),
+            mode("plan", "Plan", "Plan mode (prompt-only)"),
+        ]
+    }
+
+    fn permission_modes(&self) -> &'static [&'static str] {
+        &["default", "plan", "bypass", "acceptEdits"]
+    }
+
+    // Claude refuses --dangerously-skip-permissions when running as root, which is
+    // common in container environments (Docker, Daytona, E2B).
+    fn check_permission_mode(&self, mode: &str) -> Result<(), SandboxError> {
+        if mode == "bypass" && is_running_as_root() {
+            return Err(SandboxError::InvalidRequest {
+                message: "permission mode 'bypass' is not supported when running as root (Claude refuses --dangerously-skip-permissions with root privileges)".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn supports_resume(&self) -> bool {
+        true
+    }
+
+    fn credentials_available(&self, credentials: &ExtractedCredentials) -> bool {
+        credentials.anthropic.is_some()
+    }
+
+    // Claude Code finds its own login; only credentials from elsewhere are passed on.
+    fn anthropic_env(&self, anthropic: &ProviderCredentials) -> Vec<(&'static str, String)> {
+        if anthropic.source == "claude-code" && anthropic.provider == "anthropic" {
+            return Vec::new();
+        }
+        if anthropic.auth_type == AuthType::Oauth {
+            return vec![
+                ("CLAUDE_CODE_OAUTH_TOKEN", anthropic.api_key.clone()),
+                ("ANTHROPIC_AUTH_TOKEN", anthropic.api_key.clone()),
+            ];
+        }
+        vec![
+            ("ANTHROPIC_API_KEY", anthropic.api_key.clone()),
+            ("CLAUDE_API_KEY", anthropic.api_key.clone()),
+        ]
+    }
+
+    fn provider_hosts(&self) -> &'static [&'static str] {
+        &["*.anthropic.com", "claude.ai"]
+    }
+
+    // Claude Code accepts model aliases, listed when the Anthropic API cannot be asked.
+    fn models(&self) -> AgentModelsResponse {
+        claude_fallback_models()
+    }
+
+    fn fetch_models(
+        &self,
+        client: reqwest::Client,
+        credentials: ExtractedCredentials,
+    ) -> Option<BoxFuture<'static, Result<AgentModelsResponse, SandboxError>>> {
+        let anthropic = credentials.anthropic?;
+        Some(Box::pin(fetch_anthropic_models(client, anthropic)))
+    }
+
+    // An isolated home takes user-scoped servers; otherwise use the project's .mcp.json.
+    fn write_mcp_config(
+        &self,
+        _manager: &AgentManager,
+        mcp: &BTreeMap<String, McpServerConfig>,
+        home: Option<&SessionHomeRef<'_>>,
+    ) -> Result<(), SandboxError> {
+        let path = match home {
+            Some(home) => home.path.join(".claude.json"),
+            None => current_dir()?.join(".mcp.json"),
+        };
+        write_claude_mcp_config(&path, mcp)
+    }
+
+    fn initial_input(&self, prompt: &str, session_id: &str) -> Option<String> {
+        Some(claude_user_message_line(session_id, prompt))
+    }
+
+    fn ends_turn(&self, event: &Value) -> bool {
+        event.get("type").and_then(Value::as_str) == Some("result")
+    }
+
+    fn parse_line(&self, line: &str, session_id: &str) -> Vec<EventConversion> {
+        convert_json_line(AgentId::Claude, line, |value| {
+            let mut value = value.clone();
+            self.fill_message_ids(&mut value, session_id);
+            let native_session_id = value
+                .get("session_id")
+                .or_else(|| value.get("sessionId"))
+                .and_then(Value::as_str)
+                .map(str::to_string);
+            let conversions =
+                convert_claude::event_to_universal_with_session(&value, session_id.to_string())?;
+            Ok(match native_session_id {
+                Some(native) => conversions
+                    .into_iter()
+                    .map(|conversion| conversion.with_native_session(Some(native.clone())))
+                    .collect(),
+                None => conversions,
+            })
+        })
+    }
+
+    // The counter survives the process so resumed turns get fresh ids.
+    fn process_exited(&self, session_id: &str) {
+        let mut messages = self.messages.lock().expect("claude messages lock");
+        if let Some(messages) = messages.get_mut(session_id) {
+            messages.last_id = None;
+        }
+    }
+
+    fn session_removed(&self, session_id: &str) {
+        self.messages
+            .lock()
+            .expect("claude messages lock")
+            .remove(session_id);
+    }
+
+    fn permission_reply_line(&self, reply: &PermissionReplyContext<'_>) -> Option<String> {
+        let updated_input = reply
+            .metadata
+            .and_then(Value::as_object)
+            .and_then(|map| map.get("input"))
+            .cloned()
+            .unwrap_or(Value::Null);
+
+        let mut response_map = serde_json::Map::new();
+        let behavior = match reply.reply {
+            PermissionReply::Reject => {
+                response_map.insert(
+                    "message".to_string(),
+                    Value::String("Permission denied.".to_string()),
+                );
+                "deny"
+            }
+            PermissionReply::Once | PermissionReply::Always => {
+                if !updated_input.is_null() {
+                    response_map.insert("updatedInput".to_string(), updated_input);
+                }
+                "allow"
+            }
+        };
+        Some(claude_control_response_line(
+            reply.permission_id,
+            behavior,
+            Value::Object(response_map),
+        ))
+    }
+
+    fn question_reply_line(&self, reply: &QuestionReplyContext<'_>) -> Option<String> {
+        let line = match (reply.linked_permission_id, reply.answers) {
+            // Deliver the answer through the AskUserQuestion/ExitPlanMode permission,
+            // adding an answers map ({"0": "selected option", ...}) to the tool input.
+            (Some(permission_id), Some(answers)) => {
+                let mut updated = match reply
+                    .linked_permission_metadata
+                    .and_then(|metadata| metadata.get("input"))
+                {
+                    Some(Value::Object(map)) => map.clone(),
+                    _ => serde_json::Map::new(),
+                };
+                let answers_map: serde_json::Map<String, Value> = answers
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, inner)| {
+                        inner
+                            .first()
+                            .map(|v| (i.to_string(), Value::String(v.clone())))
+                    })
+                    .collect();
+                updated.insert("answers".to_string(), Value::Object(answers_map));
+
+                let mut response_map = serde_json::Map::new();
+                response_map.insert("updatedInput".to_string(), Value::Object(updated));
+                claude_control_response_line(permission_id, "allow", Value::Object(response_map))
+            }
+            (Some(permission_id), None) => {
+                let mut response_map = serde_json::Map::new();
+                response_map.insert(
+                    "message".to_string(),
+                    Value::String("Permission denied.".to_string()),
+                );
+                claude_control_response_line(permission_id, "deny", Value::Object(response_map))
+            }
+            // No linked permission: answer with a tool result.
+            (None, Some(answers)) => {
+                let response = answers
+                    .first()
+                    .and_then(|inner| inner.first())
+                    .cloned()
+                    .unwrap_or_default();
+                claude_tool_result_line(
+                    reply.native_session_id,
+                    reply.question_id,
+                    &response,
+                    false,
+                )
+            }
+            (None, None) => claude_tool_result_line(
+                reply.native_session_id,
+                reply.question_id,
+                "User rejected the question.",
+                true,
+            ),
+        };
+        Some(line)
+    }
+}
+
+fn claude_tool_result_line(
+    session_id: &str,
+    tool_use_id: &str,
+    content: &str,
+    is_error: bool,
+) -> String {
+    serde_json::json!({
+        "type": "user",
+        "message": {
+            "role": "user",
+            "content": [{
+                "type": "tool_result",
+                "tool_use_id": tool_use_id,
+                "content": content,
+                "is_error": is_error,
+            }],
+        },
+        "parent_tool_use_id": null,
+        "session_id": session_id,
+    })
+    .to_string()
+}
+
+fn claude_control_response_line(request_id: &str, behavior: &str, response: Value) -> String {
+    let mut response_obj = serde_json::Map::new();
+    response_obj.insert("behavior".to_string(), Value::String(behavior.to_string()));
+    if let Some(message) = response.get("message") {
+        response_obj.insert("message".to_string(), message.clone());
+    }
+    if let Some(updated_input) = response.get("updatedInput") {
+        response_obj.insert("updatedInput".to_string(), updated_input.clone());
+    }
+    if let Some(updated_permissions) = response.get("updatedPermissions") {
+        response_obj.insert(
+            "updatedPermissions".to_string(),
+            updated_permissions.clone(),
+        );
+    }
+    if let Some(interrupt) = response.get("interrupt") {
+        response_obj.insert("interrupt".to_string(), interrupt.clone());
+    }
+
+    serde_json::json!({
+        "type": "control_response",
+        "response": {
+            "subtype": "success",
+            "request_id": request_id,
+            "response": Value::Object(response_obj),
+        }
+    })
+    .to_string()
+}
+
+struct CodexAdapter;
+
+impl AgentAdapter for CodexAdapter {
+    fn id(&self) -> AgentId {
+        AgentId::Codex
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            plan_mode: true,
+            permissions: true,
+            questions: false,
+            tool_calls: true,
+            tool_results: true,
+            text_messages: true,
+            images: true,
+            file_attachments: true,
+            session_lifecycle: true,
+            error_events: true,
+            reasoning: true,
+            status: true,
+            command_execution: true,
+            file_changes: true,
+            mcp_tools: true,
+            streaming_deltas: true,
+            item_started: true,
+            shared_process: true, // shared app-server via JSON-RPC
+        }
+    }
+
+    fn modes(&self) -> Vec<AgentModeInfo> {
+        vec![
+            mode("build", "Build", "Default build mode"),
+            mode("plan", "Plan", "Planning mode via prompt prefix"),
+        ]
+    }
+
+    fn permission_modes(&self) -> &'static [&'static str] {
+        &["default", "plan", "bypass", "acceptEdits"]
+    }
+
+    // Codex has no acceptEdits mode of its own, so its file changes are approved here.
+    fn auto_approves(&self, permission_mode: &str, action: &str) -> bool {
+        permission_mode == "acceptEdits" && is_file_change_action(action)
+    }
+
+    fn supports_resume(&self) -> bool {
+        true
+    }
+
+    fn emits_turn_started(&self) -> bool {
+        true
+    }
+
+    fn credentials_available(&self, credentials: &ExtractedCredentials) -> bool {
+        credentials.openai.is_some()
+    }
+
+    fn provider_hosts(&self) -> &'static [&'static str] {
+        &["*.openai.com", "chatgpt.com"]
+    }
+
+    fn server_kind(&self) -> ServerKind {
+        ServerKind::CodexAppServer
+    }
+
+    fn write_mcp_config(
+        &self,
+        _manager: &AgentManager,
+        mcp: &BTreeMap<String, McpServerConfig>,
+        _home: Option<&SessionHomeRef<'_>>,
+    ) -> Result<(), SandboxError> {
+        write_codex_mcp_config(mcp)
+    }
+
+    fn permission_reply_line(&self, reply: &PermissionReplyContext<'_>) -> Option<String> {
+        codex_permission_response_line(reply.permission_id, reply.metadata, reply.reply.clone())
+            .ok()
+    }
+
+    fn parse_line(&self, line: &str, _session_id: &str) -> Vec<EventConversion> {
+        convert_json_line(AgentId::Codex, line, |value| {
+            let notification =
+                serde_json::from_value(value.clone()).map_err(|err| err.to_string())?;
+            convert_codex::notification_to_universal(&notification)
+        })
+    }
+}
+
+struct OpencodeAdapter;
+
+impl AgentAdapter for OpencodeAdapter {
+    fn id(&self) -> AgentId {
+        AgentId::Opencode
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            plan_mode: false,
+            permissions: false,
+            questions: false,
+            tool_calls: true,
+            tool_results: true,
+            text_messages: true,
+            images: true,
+            file_attachments: true,
+            session_lifecycle: true,
+            error_events: true,
+            reasoning: false,
+            status: false,
+            command_execution: false,
+            file_changes: false,
+            mcp_tools: true,
+            streaming_deltas: true,
+            item_started: true,
+            shared_process: true, // shared HTTP server
+        }
+    }
+
+    fn modes(&self) -> Vec<AgentModeInfo> {
+        vec![
+            mode("build", "Build", "Default build mode"),
+            mode("plan", "Plan", "Planning mode"),
+            mode("custom", "Custom", "Any user-defined OpenCode agent name"),
+        ]
+    }
+
+    // Any OpenCode agent name is a mode.
+    fn accepts_agent_mode(&self, _mode: &str) -> bool {
+        true
+    }
+
+    fn supports_resume(&self) -> bool {
+        true
+    }
+
+    fn emits_turn_started(&self) -> bool {
+        true
+    }
+
+    fn credentials_available(&self, credentials: &ExtractedCredentials) -> bool {
+        credentials.anthropic.is_some() || credentials.openai.is_some()
+    }
+
+    fn provider_hosts(&self) -> &'static [&'static str] {
+        &[
+            "*.anthropic.com",
+            "*.openai.com",
+            "opencode.ai",
+            "*.opencode.ai",
+            "models.dev",
+        ]
+    }
+
+    fn server_kind(&self) -> ServerKind {
+        ServerKind::OpencodeHttp
+    }
+
+    // The server's provider list is empty until it has loaded its config.
+    fn cache_models(&self, models: &AgentModelsResponse) -> bool {
+        !models.models.is_empty()
+    }
+
+    fn parse_line(&self, line: &str, _session_id: &str) -> Vec<EventConversion> {
+        convert_json_line(AgentId::Opencode, line, |value| {
+            let event = convert_opencode::parse_event(value.clone())?;
+            convert_opencode::event_to_universal(&event)
+        })
+    }
+}
+
+struct AmpAdapter;
+
+impl AgentAdapter for AmpAdapter {
+    fn id(&self) -> AgentId {
+        AgentId::Amp
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            plan_mode: false,
+            permissions: false,
+            questions: false,
+            tool_calls: true,
+            tool_results: true,
+            text_messages: true,
+            images: false,
+            file_attachments: false,
+            session_lifecycle: false,
+            error_events: true,
+            reasoning: false,
+            status: false,
+            command_execution: false,
+            file_changes: false,
+            mcp_tools: true,
+            streaming_deltas: false,
+            item_started: false,
+            shared_process: false, // per-turn subprocess with --continue
+        }
+    }
+
+    fn modes(&self) -> Vec<AgentModeInfo> {
+        vec![mode("build", "Build", "Default build mode")]
+    }
+
+    fn models(&self) -> AgentModelsResponse {
+        single_model("amp-default", "Amp Default")
+    }
+
+    fn permission_modes(&self) -> &'static [&'static str] {
+        &["default", "bypass"]
+    }
+
+    fn supports_resume(&self) -> bool {
+        true
+    }
+
+    fn credentials_available(&self, credentials: &ExtractedCredentials) -> bool {
+        credentials.anthropic.is_some()
+    }
+
+    fn provider_hosts(&self) -> &'static [&'static str] {
+        &["ampcode.com", "*.ampcode.com"]
+    }
+
+    fn write_mcp_config(
+        &self,
+        manager: &AgentManager,
+        mcp: &BTreeMap<String, McpServerConfig>,
+        home: Option<&SessionHomeRef<'_>>,
+    ) -> Result<(), SandboxError> {
+        apply_amp_mcp_config(manager, mcp, home.map(|home| home.env.as_slice()))
+    }
+
+    fn parse_line(&self, line: &str, _session_id: &str) -> Vec<EventConversion> {
+        convert_json_line(AgentId::Amp, line, |value| {
+            let event = serde_json::from_value(value.clone()).map_err(|err| err.to_string())?;
+            convert_amp::event_to_universal(&event)
+        })
+    }
+}
+
+/// Gemini streams message chunks without ids, so conversion keeps per-session state
+/// for the open message until the turn's `result` line.
+#[derive(Default)]
+struct GeminiAdapter {
+    streams: Mutex<HashMap<String, convert_gemini::GeminiStream>>,
+}
+
+impl AgentAdapter for GeminiAdapter {
+    fn id(&self) -> AgentId {
+        AgentId::Gemini
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            plan_mode: true,
+            permissions: false,
+            questions: false,
+            tool_calls: true,
+            tool_results: true,
+            text_messages: true,
+            images: false,
+            file_attachments: false,
+            session_lifecycle: false,
+            error_events: true,
+            reasoning: false,
+            status: false,
+            command_execution: false,
+            file_changes: false,
+            mcp_tools: true,
+            streaming_deltas: true,
+            item_started: true,
+            shared_process: false, // per-turn subprocess with --resume
+        }
+    }
+
+    fn modes(&self) -> Vec<AgentModeInfo> {
+        vec![
+            mode("build", "Build", "Default build mode"),
+            mode("plan", "Plan", "Planning mode (read-only)"),
+        ]
+    }
+
+    fn models(&self) -> AgentModelsResponse {
+        let model = |id: &str, name: &str| AgentModelInfo {
+            id: id.to_string(),
+            name: Some(name.to_string()),
+            variants: None,
+            default_variant: None,
+        };
+        AgentModelsResponse {
+            models: vec![
+                model("gemini-2.5-pro", "Gemini 2.5 Pro"),
+                model("gemini-2.5-flash", "Gemini 2.5 Flash"),
+                model("gemini-2.5-flash-lite", "Gemini 2.5 Flash Lite"),
+            ],
+            default_model: Some("gemini-2.5-pro".to_string()),
+        }
+    }
+
+    fn permission_modes(&self) -> &'static [&'static str] {
+        &["default", "plan", "bypass", "acceptEdits"]
+    }
+
+    fn supports_resume(&self) -> bool {
+        true
+    }
+
+    fn credentials_available(&self, credentials: &ExtractedCredentials) -> bool {
+        credentials.other.contains_key("google")
+    }
+
+    fn provider_hosts(&self) -> &'static [&'static str] {
+        &[
+            "generativelanguage.googleapis.com",
+            "cloudcode-pa.googleapis.com",
+            "oauth2.googleapis.com",
+            "accounts.google.com",
+        ]
+    }
+
+    // User settings under an isolated home, project settings otherwise.
+    fn write_mcp_config(
+        &self,
+        _manager: &AgentManager,
+        mcp: &BTreeMap<String, McpServerConfig>,
+        home: Option<&SessionHomeRef<'_>>,
+    ) -> Result<(), SandboxError> {
+        let root = match home {
+            Some(home) => home.path.to_path_buf(),
+            None => current_dir()?,
+        };
+        write_gemini_mcp_config(&root.join(".gemini").join("settings.json"), mcp)
+    }
+
+    fn parse_line(&self, line: &str, session_id: &str) -> Vec<EventConversion> {
+        convert_json_line(AgentId::Gemini, line, |value| {
+            let event: gemini::StreamJsonEvent =
+                serde_json::from_value(value.clone()).map_err(|err| err.to_string())?;
+            let mut streams = self.streams.lock().expect("gemini streams lock");
+            if event.type_ == gemini::StreamJsonEventType::Init {
+                streams.remove(session_id);
+            }
+            let stream = streams.entry(session_id.to_string()).or_default();
+            let conversions = stream.event_to_universal(&event);
+            if event.type_ == gemini::StreamJsonEventType::Result {
+                streams.remove(session_id);
+            }
+            conversions
+        })
+    }
+}
+
+struct AcpAdapter;
+
+impl AgentAdapter for AcpAdapter {
+    fn id(&self) -> AgentId {
+        AgentId::Acp
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            permissions: true,
+            tool_calls: true,
+            tool_results: true,
+            text_messages: true,
+            error_events: true,
+            reasoning: true,
+            status: true,
+            mcp_tools: true,
+            streaming_deltas: true,
+            item_started: true,
+            shared_process: true, // shared ACP server over stdio
+            ..AgentCapabilities::default()
+        }
+    }
+
+    fn modes(&self) -> Vec<AgentModeInfo> {
+        vec![mode("build", "Build", "The agent's default mode")]
+    }
+
+    // Modes are defined by each ACP agent and passed through to `session/set_mode`.
+    fn accepts_agent_mode(&self, _mode: &str) -> bool {
+        true
+    }
+
+    fn server_kind(&self) -> ServerKind {
+        ServerKind::Acp
+    }
+
+    // Servers are passed in `session/new`.
+    fn write_mcp_config(
+        &self,
+        _manager: &AgentManager,
+        _mcp: &BTreeMap<String, McpServerConfig>,
+        _home: Option<&SessionHomeRef<'_>>,
+    ) -> Result<(), SandboxError> {
+        Ok(())
+    }
+
+    fn parse_line(&self, line: &str, _session_id: &str) -> Vec<EventConversion> {
+        convert_json_line(AgentId::Acp, line, |_| {
+            Err("acp agents stream through the shared server".to_string())
+        })
+    }
+
+    fn permission_reply_line(&self, reply: &PermissionReplyContext<'_>) -> Option<String> {
+        acp_permission_response_line(reply)
+    }
+}
+
+/// JSON-RPC response to an ACP `session/request_permission` request, picking the
+/// offered option whose kind matches the reply.
+fn acp_permission_response_line(reply: &PermissionReplyContext<'_>) -> Option<String> {
+    let metadata = reply.metadata?;
+    let request_id = metadata.get("acpRequestId")?.clone();
+    let kinds: &[&str] = match reply.reply {
+        PermissionReply::Once => &["allow_once", "allow_always"],
+        PermissionReply::Always => &["allow_always", "allow_once"],
+        PermissionReply::Reject => &["reject_once", "reject_always"],
+    };
+    let options = metadata
+        .get("options")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let option_id = kinds.iter().find_map(|kind| {
+        options
+            .iter()
+            .find(|option| option.get("kind").and_then(Value::as_str) == Some(kind))
+            .and_then(|option| option.get("optionId").cloned())
+    });
+    let outcome = match option_id {
+        Some(option_id) => json!({ "outcome": "selected", "optionId": option_id }),
+        None => json!({ "outcome": "cancelled" }),
+    };
+    Some(
+        json!({
+            "jsonrpc": "2.0",
+            "id": request_id,
+            "result": { "outcome": outcome },
+        })
+        .to_string(),
+    )
+}
+
+struct MockAdapter;
+
+impl AgentAdapter for MockAdapter {
+    fn id(&self) -> AgentId {
+        AgentId::Mock
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            plan_mode: true,
+            permissions: true,
+            questions: true,
+            tool_calls: true,
+            tool_results: true,
+            text_messages: true,
+            images: true,
+            file_attachments: true,
+            session_lifecycle: true,
+            error_events: true,
+            reasoning: true,
+            status: true,
+            command_execution: true,
+            file_changes: true,
+            mcp_tools: true,
+            streaming_deltas: true,
+            item_started: true,
+            shared_process: false, // in-memory mock (no subprocess)
+        }
+    }
+
+    fn modes(&self) -> Vec<AgentModeInfo> {
+        vec![
+            mode("build", "Build", "Mock agent for UI testing"),
+            mode("plan", "Plan", "Plan-only mock mode"),
+        ]
+    }
+
+    fn models(&self) -> AgentModelsResponse {
+        single_model("mock", "Mock")
+    }
+
+    fn permission_modes(&self) -> &'static [&'static str] {
+        &["default", "plan", "bypass"]
+    }
+
+    fn write_mcp_config(
+        &self,
+        _manager: &AgentManager,
+        _mcp: &BTreeMap<String, McpServerConfig>,
+        _home: Option<&SessionHomeRef<'_>>,
+    ) -> Result<(), SandboxError> {
+        Ok(())
+    }
+
+    fn parse_line(&self, line: &str, _session_id: &str) -> Vec<EventConversion> {
+        convert_json_line(AgentId::Mock, line, |_| {
+            Err("mock agent does not parse streaming output".to_string())
+        })
+    }
+}
+
+/// Stands in for a custom agent id nobody registered an adapter for.
+struct UnregisteredAdapter(AgentId);
+
+impl AgentAdapter for UnregisteredAdapter {
+    fn id(&self) -> AgentId {
+        self.0
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        AgentCapabilities {
+            text_messages: true,
+            ..AgentCapabilities::default()
+        }
+    }
+
+    fn modes(&self) -> Vec<AgentModeInfo> {
+        vec![mode("build", "Build", "Default build mode")]
+    }
+
+    fn parse_line(&self, line: &str, _session_id: &str) -> Vec<EventConversion> {
+        convert_json_line(self.0, line, |_| {
+            Err("no adapter registered for this agent".to_string())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct EchoAdapter(AgentId);
+
+    impl AgentAdapter for EchoAdapter {
+        fn id(&self) -> AgentId {
+            self.0
+        }
+
+        fn capabilities(&self) -> AgentCapabilities {
+            AgentCapabilities::default()
+        }
+
+        fn modes(&self) -> Vec<AgentModeInfo> {
+            vec![mode("build", "Build", "Echo")]
+        }
+
+        fn parse_line(&self, line: &str, _session_id: &str) -> Vec<EventConversion> {
+            convert_json_line(self.0, line, |_| Ok(Vec::new()))
+        }
+    }
+
+    #[test]
+    fn registers_custom_adapters_once() {
+        let registry = AgentRegistry::with_builtins();
+        assert_eq!(registry.agents(), AgentId::BUILTIN);
+
+        let err = registry
+            .register(Arc::new(EchoAdapter(AgentId::Claude)))
+            .unwrap_err();
+        assert!(matches!(err, AgentError::InvalidAgentName { .. }));
+
+        let echo = AgentId::register_custom("echo-adapter-test").expect("register id");
+        registry
+            .register(Arc::new(EchoAdapter(echo)))
+            .expect("register adapter");
+        assert!(registry.register(Arc::new(EchoAdapter(echo))).is_err());
+        assert_eq!(registry.agents().last(), Some(&echo));
+
+        let adapter = registry.get(echo).expect("adapter");
+        assert!(adapter.parse_line("{}", "s").is_empty());
+        assert!(adapter.parse_line("  ", "s").is_empty());
+        let unparsed = adapter.parse_line("not json", "s");
+        assert_eq!(unparsed[0].event_type, UniversalEventType::AgentUnparsed);
+        assert!(adapter.accepts_agent_mode("build"));
+        assert!(!adapter.accepts_agent_mode("plan"));
+    }
+
+    #[test]
+    fn turn_hooks_default_to_a_plain_per_turn_process() {
+        let echo = EchoAdapter(AgentId::Mock);
+        assert_eq!(echo.server_kind(), ServerKind::PerTurn);
+        assert!(echo.initial_input("hi", "s").is_none());
+        assert!(!echo.ends_turn(&json!({"type": "result"})));
+        assert!(echo.cache_models(&AgentModelsResponse {
+            models: Vec::new(),
+            default_model: None,
+        }));
+
+        let claude_adapter = ClaudeAdapter::default();
+        let claude: Value = serde_json::from_str(
+            &claude_adapter
+                .initial_input("hi", "native-1")
+                .expect("claude reads the prompt on stdin"),
+        )
+        .expect("json");
+        assert_eq!(claude["session_id"], "native-1");
+        assert_eq!(claude["message"]["content"], "hi");
+        assert!(claude_adapter.ends_turn(&json!({"type": "result"})));
+
+        assert_eq!(CodexAdapter.server_kind(), ServerKind::CodexAppServer);
+        assert_eq!(OpencodeAdapter.server_kind(), ServerKind::OpencodeHttp);
+        assert_eq!(AcpAdapter.server_kind(), ServerKind::Acp);
+        assert!(!OpencodeAdapter.cache_models(&AgentModelsResponse {
+            models: Vec::new(),
+            default_model: None,
+        }));
+    }
+
+    #[test]
+    fn acp_permission_reply_selects_matching_option() {
+        let metadata = json!({
+            "acpRequestId": 4,
+            "options": [
+                {"optionId": "yes", "kind": "allow_once"},
+                {"optionId": "no", "kind": "reject_once"},
+            ],
+        });
+        let line = |reply| {
+            let line = AcpAdapter
+                .permission_reply_line(&PermissionReplyContext {
+                    permission_id: "4",
+                    action: "edit",
+                    metadata: Some(&metadata),
+                    reply,
+                })
+                .expect("reply line");
+            serde_json::from_str::<Value>(&line).expect("json")
+        };
+
+        let always = line(PermissionReply::Always);
+        assert_eq!(always["id"], 4);
+        assert_eq!(
+            always["result"]["outcome"],
+            json!({"outcome": "selected", "optionId": "yes"})
+        );
+        assert_eq!(
+            line(PermissionReply::Reject)["result"]["outcome"]["optionId"],
+            "no"
+        );
+
+        let metadata = json!({ "acpRequestId": "r1", "options": [] });
+        let cancelled = AcpAdapter
+            .permission_reply_line(&PermissionReplyContext {
+                permission_id: "r1",
+                action: "edit",
+                metadata: Some(&metadata),
+                reply: PermissionReply::Once,
+            })
+            .expect("reply line");
+        assert!(cancelled.contains(r#""outcome":"cancelled""#));
+    }
+
+    #[test]
+    fn claude_results_take_the_id_of_the_turns_last_message() {
+        let claude = ClaudeAdapter::default();
+        let message_id = |conversions: Vec<EventConversion>| {
+            conversions
+                .into_iter()
+                .find_map(|conversion| match conversion.data {
+                    UniversalEventData::Item(data) => data.item.native_item_id,
+                    _ => None,
+                })
+        };
+        let assistant = r#"{"type":"assistant","session_id":"native-1","message":{"content":[{"type":"text","text":"hi"}]}}"#;
+        let result = r#"{"type":"result","session_id":"native-1","result":"hi"}"#;
+
+        let conversions = claude.parse_line(assistant, "s1");
+        assert!(conversions
+            .iter()
+            .all(|conversion| conversion.native_session_id.as_deref() == Some("native-1")));
+        assert_eq!(message_id(conversions).as_deref(), Some("s1_message_1"));
+        assert_eq!(
+            message_id(claude.parse_line(result, "s1")).as_deref(),
+            Some("s1_message_1")
+        );
+
+        claude.process_exited("s1");
+        assert_eq!(
+            message_id(claude.parse_line(result, "s1")).as_deref(),
+            Some("s1_message_2")
+        );
+        claude.session_removed("s1");
+        assert_eq!(
+            message_id(claude.parse_line(assistant, "s1")).as_deref(),
+            Some("s1_message_1")
+        );
+    }
+}
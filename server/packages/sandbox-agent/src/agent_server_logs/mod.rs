@@ -1,3 +1,10 @@
+use std::fs::File;
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::logs::TimestampedLines;
+use crate::redaction::Redactor;
+
 #[cfg(unix)]
 mod unix;
 #[cfg(windows)]
@@ -7,3 +14,9 @@ mod windows;
 pub use unix::AgentServerLogs;
 #[cfg(windows)]
 pub use windows::AgentServerLogs;
+
+/// Appends `output`, a server's piped stderr, to `log` with secrets masked until
+/// the server closes it.
+pub fn copy_output(log: File, output: impl Read + Send + 'static, redactor: Arc<Redactor>) {
+    std::thread::spawn(move || redactor.copy_lines(output, TimestampedLines::new(log)));
+}
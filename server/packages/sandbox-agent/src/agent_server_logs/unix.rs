@@ -6,6 +6,8 @@ use sandbox_agent_error::SandboxError;
 use sandbox_agent_universal_agent_schema::StderrOutput;
 use time::{Duration, OffsetDateTime};
 
+use crate::logs::{strip_timestamp, LogFiles};
+
 const LOG_RETENTION_DAYS: i64 = 7;
 const LOG_HEAD_LINES: usize = 20;
 const LOG_TAIL_LINES: usize = 50;
@@ -24,7 +26,8 @@ impl AgentServerLogs {
         }
     }
 
-    pub fn open(&self) -> Result<std::process::Stdio, SandboxError> {
+    /// Opens today's log for appending. Server output goes through [`copy_output`](super::copy_output).
+    pub fn open(&self) -> Result<File, SandboxError> {
         let log_dir = self.base_dir.join(&self.agent);
         std::fs::create_dir_all(&log_dir).map_err(|err| SandboxError::StreamError {
             message: err.to_string(),
@@ -50,7 +53,12 @@ impl AgentServerLogs {
             })?;
 
         eprintln!("{} server logs: {}", self.agent, path.display());
-        Ok(file.into())
+        Ok(file)
+    }
+
+    /// This agent's daily log files, for the logs API.
+    pub(crate) fn log_files(&self) -> LogFiles {
+        LogFiles::new(self.base_dir.join(&self.agent), format!("{}-", self.agent))
     }
 
     fn prune_logs(&self, log_dir: &Path, now: OffsetDateTime) -> Result<(), SandboxError> {
@@ -109,7 +117,7 @@ impl AgentServerLogs {
 
         for line_result in reader.lines() {
             let line: String = match line_result {
-                Ok(l) => l,
+                Ok(l) => strip_timestamp(&l).to_string(),
                 Err(_) => break,
             };
             let truncated_line = if line.len() > LOG_MAX_LINE_LENGTH {
@@ -1,10 +1,12 @@
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
 use std::path::{Path, PathBuf};
 
 use sandbox_agent_error::SandboxError;
 use sandbox_agent_universal_agent_schema::StderrOutput;
 use time::{Duration, OffsetDateTime};
 
+use crate::logs::LogFiles;
+
 const LOG_RETENTION_DAYS: i64 = 7;
 
 pub struct AgentServerLogs {
@@ -20,7 +22,8 @@ impl AgentServerLogs {
         }
     }
 
-    pub fn open(&self) -> Result<std::process::Stdio, SandboxError> {
+    /// Opens today's log for appending. Server output goes through [`copy_output`](super::copy_output).
+    pub fn open(&self) -> Result<File, SandboxError> {
         let log_dir = self.base_dir.join(&self.agent);
         std::fs::create_dir_all(&log_dir).map_err(|err| SandboxError::StreamError {
             message: err.to_string(),
@@ -46,7 +49,12 @@ impl AgentServerLogs {
             })?;
 
         eprintln!("{} server logs: {}", self.agent, path.display());
-        Ok(file.into())
+        Ok(file)
+    }
+
+    /// This agent's daily log files, for the logs API.
+    pub(crate) fn log_files(&self) -> LogFiles {
+        LogFiles::new(self.base_dir.join(&self.agent), format!("{}-", self.agent))
     }
 
     fn prune_logs(&self, log_dir: &Path, now: OffsetDateTime) -> Result<(), SandboxError> {
@@ -6,33 +6,47 @@ use std::process::{Command as ProcessCommand, Stdio};
 use std::sync::Arc;
 use std::time::Duration;
 
+use axum::body::Bytes;
 use clap::{Args, Parser, Subcommand};
 
 // Include the generated version constant
 mod build_version {
     include!(concat!(env!("OUT_DIR"), "/version.rs"));
 }
+use crate::custom_agents::CustomAgents;
+use crate::mock_scenarios::MockScenarios;
+use crate::otel::{self, OtlpConfig};
+use crate::rate_limit::RateLimitConfig;
+use crate::replay::ReplayFixture;
 use crate::router::{build_router_with_state, shutdown_servers};
 use crate::router::{
     AgentInstallRequest, AppState, AuthConfig, BrandingMode, CreateSessionRequest, McpServerConfig,
-    MessageRequest, PermissionReply, PermissionReplyRequest, QuestionReplyRequest, SkillSource,
-    SkillsConfig,
+    MessageRequest, PermissionReply, PermissionReplyRequest, QuestionReplyRequest, SessionConfig,
+    SessionEgressPolicy, SessionResourceLimits, SkillSource, SkillsConfig, TokenScope,
 };
 use crate::router::{
-    AgentListResponse, AgentModelsResponse, AgentModesResponse, CreateSessionResponse,
-    EventsResponse, FsActionResponse, FsEntry, FsMoveRequest, FsMoveResponse, FsStat,
-    FsUploadBatchResponse, FsWriteResponse, SessionListResponse,
+    AgentListResponse, AgentModelsResponse, AgentModesResponse, AgentUpdatesResponse,
+    CreateSessionResponse, CreateSessionTokenRequest, CreateSessionTokenResponse, EventsResponse,
+    FsActionResponse, FsEntry, FsMoveRequest, FsMoveResponse, FsStat, FsUploadBatchResponse,
+    FsWriteResponse, InstallJob, SessionListResponse,
 };
-use crate::server_logs::ServerLogs;
+use crate::server_logs::{default_log_dir, ServerLogs};
 use crate::telemetry;
+use crate::transport;
 use crate::ui;
 use reqwest::blocking::Client as HttpClient;
-use reqwest::Method;
-use sandbox_agent_agent_management::agents::{AgentId, AgentManager, InstallOptions};
+use reqwest::{Method, Url};
+use sandbox_agent_agent_management::agents::{
+    AgentCommand, AgentId, AgentManager, InstallOptions, Platform,
+};
 use sandbox_agent_agent_management::credentials::{
     extract_all_credentials, AuthType, CredentialExtractionOptions, ExtractedCredentials,
     ProviderCredentials,
 };
+use sandbox_agent_agent_management::limits::{
+    adopt_daemon_cgroup, ResourceLimitsConfig, CGROUP_ROOT_ENV,
+};
+use sandbox_agent_agent_management::lockfile::{sha256_file, AgentLockfile, LockedAgent};
 use serde::Serialize;
 use serde_json::{json, Value};
 use thiserror::Error;
@@ -80,7 +94,7 @@ pub struct GigacodeCli {
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// Run the sandbox agent HTTP server.
-    Server(ServerArgs),
+    Server(Box<ServerArgs>),
     /// Call the HTTP API without writing client code.
     Api(ApiArgs),
     /// EXPERIMENTAL: Start a sandbox-agent server and attach an OpenCode session.
@@ -89,6 +103,8 @@ pub enum Command {
     Daemon(DaemonArgs),
     /// Install or reinstall an agent without running the server.
     InstallAgent(InstallAgentArgs),
+    /// Download agents for a platform into a directory to install from offline.
+    Bundle(BundleArgs),
     /// Inspect locally discovered credentials.
     Credentials(CredentialsArgs),
 }
@@ -115,6 +131,91 @@ pub struct ServerArgs {
 
     #[arg(long = "no-telemetry")]
     no_telemetry: bool,
+
+    /// PEM certificate chain; serves HTTPS instead of HTTP (requires --tls-key).
+    #[arg(long = "tls-cert", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key for --tls-cert.
+    #[arg(long = "tls-key", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Also listen on a Unix domain socket, created with owner-only permissions.
+    #[arg(long = "unix-socket")]
+    unix_socket: Option<PathBuf>,
+
+    /// Do not listen on --host/--port (requires --unix-socket).
+    #[arg(long = "no-tcp", requires = "unix_socket")]
+    no_tcp: bool,
+
+    /// JSON file of scoped API tokens, accepted alongside --token.
+    #[arg(long = "tokens-file")]
+    tokens_file: Option<PathBuf>,
+
+    /// File holding the HMAC secret for session tokens (random per process if unset).
+    #[arg(long = "session-token-secret-file")]
+    session_token_secret_file: Option<PathBuf>,
+
+    /// JSON file with default and per-agent resource limits for agent processes.
+    #[arg(long = "resource-limits")]
+    resource_limits: Option<PathBuf>,
+
+    /// JSON file with request rates and session/turn caps, server-wide and per token.
+    #[arg(long = "rate-limits")]
+    rate_limits: Option<PathBuf>,
+
+    /// Export OpenTelemetry spans to this OTLP/HTTP collector endpoint.
+    #[arg(long = "otlp-endpoint")]
+    otlp_endpoint: Option<String>,
+
+    /// Give each session its own HOME and XDG directories under the state directory.
+    #[arg(long = "isolate-session-home")]
+    isolate_session_home: bool,
+
+    /// Directory for per-session state (defaults to the platform data directory).
+    #[arg(long = "state-dir")]
+    state_dir: Option<PathBuf>,
+
+    /// Route every session's network access through the egress allowlist proxy.
+    #[arg(long = "restrict-egress")]
+    restrict_egress: bool,
+
+    /// Host pattern every restricted session may reach (repeatable).
+    #[arg(long = "egress-allow")]
+    egress_allow: Vec<String>,
+
+    /// Environment variable whose value is masked in events and agent logs (repeatable).
+    #[arg(long = "redact-env")]
+    redact_env: Vec<String>,
+
+    /// Regex masked in events and agent logs (repeatable).
+    #[arg(long = "redact-pattern")]
+    redact_pattern: Vec<String>,
+
+    /// JSON or YAML file, or a directory of them, with scripted mock agent scenarios.
+    #[arg(long = "mock-scenarios")]
+    mock_scenarios: Option<PathBuf>,
+
+    /// Command that starts the `acp` agent, an Agent Client Protocol server over stdio.
+    #[arg(long = "acp-command")]
+    acp_command: Option<String>,
+
+    /// Argument for --acp-command (repeatable).
+    #[arg(long = "acp-arg", requires = "acp_command", allow_hyphen_values = true)]
+    acp_arg: Vec<String>,
+
+    /// TOML file declaring custom agents that print JSON lines.
+    #[arg(long = "custom-agents")]
+    custom_agents: Option<PathBuf>,
+
+    /// JSON lockfile pinning agent versions, written by `install-agent --lockfile`.
+    #[arg(long = "lockfile")]
+    lockfile: Option<PathBuf>,
+
+    /// Base URL agents are downloaded from instead of their release hosts
+    /// (file:// or http(s)://), such as a directory written by `bundle`.
+    #[arg(long = "agent-mirror")]
+    agent_mirror: Option<Url>,
 }
 
 #[derive(Args, Debug)]
@@ -183,6 +284,10 @@ pub struct DaemonStartArgs {
     /// If the daemon is already running but outdated, stop and restart it.
     #[arg(long, default_value_t = false)]
     upgrade: bool,
+
+    /// Also serve on this Unix socket and health-check the daemon through it.
+    #[arg(long = "unix-socket")]
+    unix_socket: Option<PathBuf>,
 }
 
 #[derive(Args, Debug)]
@@ -201,6 +306,10 @@ pub struct DaemonStatusArgs {
 
     #[arg(long, short = 'p', default_value_t = DEFAULT_PORT)]
     port: u16,
+
+    /// Health-check the daemon through this Unix socket.
+    #[arg(long = "unix-socket")]
+    unix_socket: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -246,6 +355,10 @@ pub enum AgentsCommand {
     List(ClientArgs),
     /// Install or reinstall an agent.
     Install(ApiInstallAgentArgs),
+    /// Remove an installed agent, or one version of it.
+    Uninstall(ApiUninstallAgentArgs),
+    /// Compare the installed version with the latest published one.
+    Updates(AgentUpdatesArgs),
     /// Show available modes for an agent.
     Modes(AgentModesArgs),
     /// Show available models for an agent.
@@ -257,7 +370,7 @@ pub enum SessionsCommand {
     /// List active sessions.
     List(ClientArgs),
     /// Create a new session for an agent.
-    Create(CreateSessionArgs),
+    Create(Box<CreateSessionArgs>),
     #[command(name = "send-message")]
     /// Send a message to an existing session.
     SendMessage(SessionMessageArgs),
@@ -267,6 +380,9 @@ pub enum SessionsCommand {
     #[command(name = "terminate")]
     /// Terminate a session.
     Terminate(SessionTerminateArgs),
+    #[command(name = "create-token")]
+    /// Mint a short-lived token limited to one session.
+    CreateToken(SessionTokenArgs),
     #[command(name = "get-messages")]
     /// Alias for events; returns session events.
     GetMessages(SessionEventsArgs),
@@ -285,6 +401,12 @@ pub enum SessionsCommand {
     #[command(name = "reply-permission")]
     /// Reply to a permission request.
     ReplyPermission(PermissionReplyArgs),
+    #[command(name = "debug-log")]
+    /// Download the native agent I/O captured for a session created with --debug-log.
+    DebugLog(SessionDebugLogArgs),
+    #[command(name = "record")]
+    /// Save a debug-logged session's native agent I/O as a replay fixture.
+    Record(SessionRecordArgs),
 }
 
 #[derive(Subcommand, Debug)]
@@ -319,6 +441,25 @@ pub struct ApiInstallAgentArgs {
     agent: String,
     #[arg(long, short = 'r')]
     reinstall: bool,
+    /// Version to install next to any others (defaults to the server's pinned version).
+    #[arg(long = "version")]
+    version: Option<String>,
+    /// Expected SHA-256 of the downloaded release file.
+    #[arg(long = "sha256")]
+    sha256: Option<String>,
+    /// Print the install job and return without waiting for it.
+    #[arg(long = "background")]
+    background: bool,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ApiUninstallAgentArgs {
+    agent: String,
+    /// Remove only this side-by-side version.
+    #[arg(long = "version")]
+    version: Option<String>,
     #[command(flatten)]
     client: ClientArgs,
 }
@@ -328,6 +469,41 @@ pub struct InstallAgentArgs {
     agent: String,
     #[arg(long, short = 'r')]
     reinstall: bool,
+    /// Version to install next to any others (defaults to the pinned version).
+    #[arg(long = "version")]
+    version: Option<String>,
+    /// Expected SHA-256 of the downloaded release file, checked instead of the
+    /// lockfile's or the one published with the release.
+    #[arg(long = "sha256")]
+    sha256: Option<String>,
+    /// Lockfile to install the pinned version from; the installed version and its
+    /// checksum are written back.
+    #[arg(long = "lockfile")]
+    lockfile: Option<PathBuf>,
+    /// Release archive or binary to install instead of downloading one.
+    #[arg(long = "from", conflicts_with = "mirror")]
+    from: Option<PathBuf>,
+    /// Base URL to download from instead of the release hosts (file:// or http(s)://).
+    #[arg(long = "mirror")]
+    mirror: Option<Url>,
+}
+
+#[derive(Args, Debug)]
+pub struct BundleArgs {
+    /// Directory to download into; usable as a file:// mirror afterwards.
+    out: PathBuf,
+    /// Agents to bundle, as `agent` or `agent@version` (default: every installable agent).
+    agents: Vec<String>,
+    /// Platform to bundle for: linux-x64, linux-x64-musl, linux-arm64, darwin-arm64
+    /// or darwin-x64 (default: this machine's).
+    #[arg(long = "platform")]
+    platform: Option<String>,
+    /// Lockfile whose pinned versions are bundled for agents without a version.
+    #[arg(long = "lockfile")]
+    lockfile: Option<PathBuf>,
+    /// Base URL to download from instead of the release hosts.
+    #[arg(long = "mirror")]
+    mirror: Option<Url>,
 }
 
 #[derive(Args, Debug)]
@@ -337,6 +513,13 @@ pub struct AgentModesArgs {
     client: ClientArgs,
 }
 
+#[derive(Args, Debug)]
+pub struct AgentUpdatesArgs {
+    agent: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
 #[derive(Args, Debug)]
 pub struct AgentModelsArgs {
     agent: String,
@@ -363,6 +546,20 @@ pub struct CreateSessionArgs {
     mcp_config: Option<PathBuf>,
     #[arg(long)]
     skill: Vec<PathBuf>,
+    #[arg(long)]
+    memory_limit: Option<u64>,
+    #[arg(long)]
+    cpu_weight: Option<u32>,
+    #[arg(long)]
+    max_pids: Option<u64>,
+    #[arg(long)]
+    max_open_files: Option<u64>,
+    #[arg(long)]
+    isolate_home: bool,
+    #[arg(long)]
+    egress_allow: Vec<String>,
+    #[arg(long)]
+    debug_log: bool,
     #[command(flatten)]
     client: ClientArgs,
 }
@@ -418,6 +615,34 @@ pub struct SessionTerminateArgs {
     client: ClientArgs,
 }
 
+#[derive(Args, Debug)]
+pub struct SessionDebugLogArgs {
+    session_id: String,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct SessionRecordArgs {
+    session_id: String,
+    #[arg(long, short = 'o')]
+    out: PathBuf,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct SessionTokenArgs {
+    session_id: String,
+    /// Scope to grant, e.g. read or messages:send (repeatable).
+    #[arg(long = "scope")]
+    scope: Vec<String>,
+    #[arg(long = "ttl-seconds")]
+    ttl_seconds: Option<u64>,
+    #[command(flatten)]
+    client: ClientArgs,
+}
+
 #[derive(Args, Debug)]
 pub struct QuestionReplyArgs {
     session_id: String,
@@ -608,6 +833,18 @@ pub fn init_logging(command: &Command) -> Result<(), CliError> {
         maybe_redirect_server_logs();
     }
 
+    let otlp = match command {
+        Command::Server(args) => OtlpConfig::resolve(args.otlp_endpoint.as_deref()),
+        Command::Opencode(_) => OtlpConfig::resolve(None),
+        _ => None,
+    }
+    .map(otel::layer);
+    let (otlp, otlp_error) = match otlp {
+        Some(Ok(layer)) => (Some(layer), None),
+        Some(Err(err)) => (None, Some(err)),
+        None => (None, None),
+    };
+
     let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
     tracing_subscriber::registry()
         .with(filter)
@@ -616,7 +853,11 @@ pub fn init_logging(command: &Command) -> Result<(), CliError> {
                 .layer()
                 .with_writer(std::io::stderr),
         )
+        .with(otlp)
         .init();
+    if let Some(err) = otlp_error {
+        tracing::warn!(error = %err, "failed to start OTLP exporter");
+    }
     Ok(())
 }
 
@@ -627,36 +868,120 @@ pub fn run_command(command: &Command, cli: &CliConfig) -> Result<(), CliError> {
         Command::Opencode(args) => run_opencode(cli, args),
         Command::Daemon(subcommand) => run_daemon(&subcommand.command, cli),
         Command::InstallAgent(args) => install_agent_local(args),
+        Command::Bundle(args) => bundle_agents(args),
         Command::Credentials(subcommand) => run_credentials(&subcommand.command),
     }
 }
 
 fn run_server(cli: &CliConfig, server: &ServerArgs) -> Result<(), CliError> {
-    let auth = if let Some(token) = cli.token.clone() {
+    let mut auth = if let Some(token) = cli.token.clone() {
         AuthConfig::with_token(token)
     } else {
         AuthConfig::disabled()
     };
+    if let Some(path) = &server.tokens_file {
+        auth = auth.with_tokens_file(path)?;
+    }
+    if let Some(path) = &server.session_token_secret_file {
+        let secret = std::fs::read_to_string(path)?;
+        let secret = secret.trim();
+        if secret.is_empty() {
+            return Err(CliError::Server(format!(
+                "session token secret file {} is empty",
+                path.display()
+            )));
+        }
+        auth = auth.with_session_token_secret(secret.as_bytes());
+    }
 
     let branding = if cli.gigacode {
         BrandingMode::Gigacode
     } else {
         BrandingMode::SandboxAgent
     };
-    let agent_manager = AgentManager::new(default_install_dir())
+    let mut agent_manager = AgentManager::new(default_install_dir())
         .map_err(|err| CliError::Server(err.to_string()))?;
-    let state = Arc::new(AppState::with_branding(auth, agent_manager, branding));
+    let mut resource_limits = match &server.resource_limits {
+        Some(path) => ResourceLimitsConfig::from_file(path)?,
+        None => ResourceLimitsConfig::default(),
+    };
+    if resource_limits.cgroup_root.is_none() && std::env::var_os(CGROUP_ROOT_ENV).is_none() {
+        resource_limits.cgroup_root = adopt_daemon_cgroup();
+    }
+    agent_manager = agent_manager.with_resource_limits(resource_limits);
+    if let Some(program) = &server.acp_command {
+        agent_manager = agent_manager.with_agent_command(
+            AgentId::Acp,
+            AgentCommand {
+                program: program.clone(),
+                args: server.acp_arg.clone(),
+            },
+        );
+    }
+    if let Some(path) = &server.custom_agents {
+        agent_manager = CustomAgents::from_file(path)?
+            .register(agent_manager)
+            .map_err(|err| CliError::Server(err.to_string()))?;
+    }
+    if let Some(path) = &server.lockfile {
+        agent_manager = agent_manager.with_lockfile(AgentLockfile::from_file(path)?);
+    }
+    if let Some(mirror) = &server.agent_mirror {
+        agent_manager = agent_manager.with_mirror(mirror.clone());
+    }
+    let session_config = SessionConfig {
+        isolate_home: server.isolate_session_home,
+        state_dir: server.state_dir.clone(),
+        restrict_egress: server.restrict_egress,
+        egress_allow: server.egress_allow.clone(),
+        redact_env: server.redact_env.clone(),
+        redact_patterns: server
+            .redact_pattern
+            .iter()
+            .map(|pattern| {
+                regex::Regex::new(pattern).map_err(|err| {
+                    CliError::Server(format!("invalid --redact-pattern {pattern:?}: {err}"))
+                })
+            })
+            .collect::<Result<_, _>>()?,
+        rate_limits: match &server.rate_limits {
+            Some(path) => RateLimitConfig::from_file(path)?,
+            None => RateLimitConfig::default(),
+        },
+        mock_scenarios: match &server.mock_scenarios {
+            Some(path) => MockScenarios::from_path(path)?,
+            None => MockScenarios::default(),
+        },
+    };
+    let state = Arc::new(AppState::with_session_config(
+        auth,
+        agent_manager,
+        branding,
+        session_config,
+    ));
     let (mut router, state) = build_router_with_state(state);
 
     let cors = build_cors_layer(server)?;
     router = router.layer(cors);
 
+    let tls = match (&server.tls_cert, &server.tls_key) {
+        (Some(cert), Some(key)) => Some(transport::load_tls_acceptor(cert, key)?),
+        _ => None,
+    };
+    #[cfg(windows)]
+    if server.unix_socket.is_some() {
+        return Err(CliError::Server(
+            "--unix-socket is not supported on Windows".to_string(),
+        ));
+    }
+
     let addr = format!("{}:{}", server.host, server.port);
     let display_host = match server.host.as_str() {
         "0.0.0.0" | "::" => "localhost",
         other => other,
     };
-    let inspector_url = format!("http://{}:{}/ui", display_host, server.port);
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    let inspector_url = format!("{scheme}://{}:{}/ui", display_host, server.port);
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
@@ -669,20 +994,70 @@ fn run_server(cli: &CliConfig, server: &ServerArgs) -> Result<(), CliError> {
             telemetry::log_enabled_message();
             telemetry::spawn_telemetry_task();
         }
-        let listener = tokio::net::TcpListener::bind(&addr).await?;
-        tracing::info!(addr = %addr, "server listening");
-        if ui::is_enabled() {
-            tracing::info!(url = %inspector_url, "inspector ui available");
+        let tcp_listener = if server.no_tcp {
+            None
         } else {
-            tracing::info!("inspector ui not embedded; set SANDBOX_AGENT_SKIP_INSPECTOR=1 to skip embedding during builds");
-        }
+            let listener = tokio::net::TcpListener::bind(&addr).await?;
+            tracing::info!(addr = %addr, scheme, "server listening");
+            if ui::is_enabled() {
+                tracing::info!(url = %inspector_url, "inspector ui available");
+            } else {
+                tracing::info!("inspector ui not embedded; set SANDBOX_AGENT_SKIP_INSPECTOR=1 to skip embedding during builds");
+            }
+            Some(listener)
+        };
+        #[cfg(unix)]
+        let unix_listener = match &server.unix_socket {
+            Some(path) => {
+                let listener = transport::bind_unix_socket(path)?;
+                tracing::info!(path = %path.display(), "server listening on unix socket");
+                Some((listener, path.clone()))
+            }
+            None => None,
+        };
+
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let shutdown_signal = move || {
+            let mut shutdown_rx = shutdown_rx.clone();
+            async move {
+                let _ = shutdown_rx.wait_for(|stop| *stop).await;
+            }
+        };
         let shutdown_state = state.clone();
-        axum::serve(listener, router)
-            .with_graceful_shutdown(async move {
-                let _ = tokio::signal::ctrl_c().await;
-                shutdown_servers(&shutdown_state).await;
-            })
-            .await
+        tokio::spawn(async move {
+            let _ = tokio::signal::ctrl_c().await;
+            shutdown_servers(&shutdown_state).await;
+            let _ = shutdown_tx.send(true);
+        });
+
+        let tcp_server = async {
+            match (tcp_listener, tls) {
+                (None, _) => Ok(()),
+                (Some(listener), Some(acceptor)) => {
+                    transport::serve_tls(listener, acceptor, router.clone(), shutdown_signal())
+                        .await
+                }
+                (Some(listener), None) => {
+                    axum::serve(listener, router.clone())
+                        .with_graceful_shutdown(shutdown_signal())
+                        .await
+                }
+            }
+        };
+        #[cfg(unix)]
+        let unix_server = async {
+            match unix_listener {
+                Some((listener, path)) => {
+                    transport::serve_unix(listener, path, router.clone(), shutdown_signal()).await
+                }
+                None => Ok(()),
+            }
+        };
+        #[cfg(windows)]
+        let unix_server = async { Ok(()) };
+
+        tokio::try_join!(tcp_server, unix_server)
+            .map(|_| ())
             .map_err(|err| CliError::Server(err.to_string()))
     })
 }
@@ -693,21 +1068,12 @@ fn default_install_dir() -> PathBuf {
         .unwrap_or_else(|| PathBuf::from(".").join(".sandbox-agent").join("bin"))
 }
 
-fn default_server_log_dir() -> PathBuf {
-    if let Ok(dir) = std::env::var("SANDBOX_AGENT_LOG_DIR") {
-        return PathBuf::from(dir);
-    }
-    dirs::data_dir()
-        .map(|dir| dir.join("sandbox-agent").join("logs"))
-        .unwrap_or_else(|| PathBuf::from(".").join(".sandbox-agent").join("logs"))
-}
-
 fn maybe_redirect_server_logs() {
     if std::env::var("SANDBOX_AGENT_LOG_STDOUT").is_ok() {
         return;
     }
 
-    let log_dir = default_server_log_dir();
+    let log_dir = default_log_dir();
     if let Err(err) = ServerLogs::new(log_dir, LOGS_RETENTION).start_sync() {
         eprintln!("failed to redirect logs: {err}");
     }
@@ -745,7 +1111,7 @@ fn run_opencode(cli: &CliConfig, args: &OpencodeArgs) -> Result<(), CliError> {
         if has_proxy_env { "set" } else { "unset" },
         if has_no_proxy_env { "set" } else { "unset" }
     ))?;
-    crate::daemon::ensure_running(cli, &args.host, args.port, token.as_deref())?;
+    crate::daemon::ensure_running(cli, &args.host, args.port, None, token.as_deref())?;
     write_stderr_line("gigacode startup: daemon is healthy")?;
 
     let attach_session_id = if args.session_title.is_some() || yolo {
@@ -800,13 +1166,24 @@ fn run_opencode(cli: &CliConfig, args: &OpencodeArgs) -> Result<(), CliError> {
 fn run_daemon(command: &DaemonCommand, cli: &CliConfig) -> Result<(), CliError> {
     let token = cli.token.as_deref();
     match command {
-        DaemonCommand::Start(args) if args.upgrade => {
-            crate::daemon::ensure_running(cli, &args.host, args.port, token)
-        }
-        DaemonCommand::Start(args) => crate::daemon::start(cli, &args.host, args.port, token),
+        DaemonCommand::Start(args) if args.upgrade => crate::daemon::ensure_running(
+            cli,
+            &args.host,
+            args.port,
+            args.unix_socket.as_deref(),
+            token,
+        ),
+        DaemonCommand::Start(args) => crate::daemon::start(
+            cli,
+            &args.host,
+            args.port,
+            args.unix_socket.as_deref(),
+            token,
+        ),
         DaemonCommand::Stop(args) => crate::daemon::stop(&args.host, args.port),
         DaemonCommand::Status(args) => {
-            let st = crate::daemon::status(&args.host, args.port, token)?;
+            let st =
+                crate::daemon::status(&args.host, args.port, args.unix_socket.as_deref(), token)?;
             write_stderr_line(&st.to_string())?;
             Ok(())
         }
@@ -824,11 +1201,29 @@ fn run_agents(command: &AgentsCommand, cli: &CliConfig) -> Result<(), CliError>
             let ctx = ClientContext::new(cli, &args.client)?;
             let body = AgentInstallRequest {
                 reinstall: if args.reinstall { Some(true) } else { None },
+                version: args.version.clone(),
+                sha256: args.sha256.clone(),
+                background: args.background.then_some(true),
             };
             let path = format!("{API_PREFIX}/agents/{}/install", args.agent);
             let response = ctx.post(&path, &body)?;
+            if args.background {
+                return print_json_response::<InstallJob>(response);
+            }
+            print_empty_response(response)
+        }
+        AgentsCommand::Uninstall(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = format!("{API_PREFIX}/agents/{}", args.agent);
+            let response = ctx.delete_with_query(&path, &[("version", args.version.clone())])?;
             print_empty_response(response)
         }
+        AgentsCommand::Updates(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = format!("{API_PREFIX}/agents/{}/updates", args.agent);
+            let response = ctx.get(&path)?;
+            print_json_response::<AgentUpdatesResponse>(response)
+        }
         AgentsCommand::Modes(args) => {
             let ctx = ClientContext::new(cli, &args.client)?;
             let path = format!("{API_PREFIX}/agents/{}/modes", args.agent);
@@ -879,6 +1274,25 @@ fn run_sessions(command: &SessionsCommand, cli: &CliConfig) -> Result<(), CliErr
                         .collect(),
                 })
             };
+            let limits = SessionResourceLimits {
+                memory_bytes: args.memory_limit,
+                cpu_weight: args.cpu_weight,
+                max_pids: args.max_pids,
+                max_open_files: args.max_open_files,
+            };
+            let limits = if limits.memory_bytes.is_none()
+                && limits.cpu_weight.is_none()
+                && limits.max_pids.is_none()
+                && limits.max_open_files.is_none()
+            {
+                None
+            } else {
+                Some(limits)
+            };
+            let egress = (!args.egress_allow.is_empty()).then(|| SessionEgressPolicy {
+                allow: args.egress_allow.clone(),
+                allow_provider_hosts: None,
+            });
             let body = CreateSessionRequest {
                 agent: args.agent.clone(),
                 agent_mode: args.agent_mode.clone(),
@@ -890,6 +1304,10 @@ fn run_sessions(command: &SessionsCommand, cli: &CliConfig) -> Result<(), CliErr
                 title: None,
                 mcp,
                 skills,
+                limits,
+                isolate_home: args.isolate_home.then_some(true),
+                egress,
+                debug_log: args.debug_log.then_some(true),
             };
             let path = format!("{API_PREFIX}/sessions/{}", args.session_id);
             let response = ctx.post(&path, &body)?;
@@ -932,6 +1350,28 @@ fn run_sessions(command: &SessionsCommand, cli: &CliConfig) -> Result<(), CliErr
             let response = ctx.post_empty(&path)?;
             print_empty_response(response)
         }
+        SessionsCommand::CreateToken(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let scopes = if args.scope.is_empty() {
+                None
+            } else {
+                Some(
+                    args.scope
+                        .iter()
+                        .map(|scope| {
+                            serde_json::from_value::<TokenScope>(Value::String(scope.clone()))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?,
+                )
+            };
+            let body = CreateSessionTokenRequest {
+                scopes,
+                ttl_seconds: args.ttl_seconds,
+            };
+            let path = format!("{API_PREFIX}/sessions/{}/tokens", args.session_id);
+            let response = ctx.post(&path, &body)?;
+            print_json_response::<CreateSessionTokenResponse>(response)
+        }
         SessionsCommand::GetMessages(args) | SessionsCommand::Events(args) => {
             let ctx = ClientContext::new(cli, &args.client)?;
             let path = format!("{API_PREFIX}/sessions/{}/events", args.session_id);
@@ -1003,6 +1443,33 @@ fn run_sessions(command: &SessionsCommand, cli: &CliConfig) -> Result<(), CliErr
             let response = ctx.post(&path, &body)?;
             print_empty_response(response)
         }
+        SessionsCommand::DebugLog(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let path = format!("{API_PREFIX}/sessions/{}/debug-log", args.session_id);
+            let response = ctx.get(&path)?;
+            print_text_response(response)
+        }
+        SessionsCommand::Record(args) => {
+            let ctx = ClientContext::new(cli, &args.client)?;
+            let sessions: SessionListResponse =
+                serde_json::from_str(&response_text(ctx.get(&format!("{API_PREFIX}/sessions"))?)?)?;
+            let session = sessions
+                .sessions
+                .into_iter()
+                .find(|session| session.session_id == args.session_id)
+                .ok_or_else(|| {
+                    CliError::Server(format!("session not found: {}", args.session_id))
+                })?;
+            let agent = AgentId::parse(&session.agent)
+                .ok_or_else(|| CliError::Server(format!("unknown agent: {}", session.agent)))?;
+            let path = format!("{API_PREFIX}/sessions/{}/debug-log", args.session_id);
+            let debug_log = response_text(ctx.get(&path)?)?;
+            let fixture =
+                ReplayFixture::from_debug_log(agent, session.native_session_id, &debug_log)
+                    .map_err(|err| CliError::Server(err.to_string()))?;
+            std::fs::write(&args.out, fixture.to_ndjson())?;
+            Ok(())
+        }
     }
 }
 
@@ -1114,10 +1581,10 @@ fn run_fs(command: &FsCommand, cli: &CliConfig) -> Result<(), CliError> {
         }
         FsCommand::UploadBatch(args) => {
             let ctx = ClientContext::new(cli, &args.client)?;
-            let file = File::open(&args.tar_path)?;
-            let response = ctx.post_raw_with_query(
+            let archive = File::open(&args.tar_path)?;
+            let response = ctx.post_file_with_query(
                 &format!("{API_PREFIX}/fs/upload-batch"),
-                file,
+                archive,
                 "application/x-tar",
                 &[
                     ("path", args.path.clone()),
@@ -1178,6 +1645,10 @@ fn resolve_opencode_bin() -> Result<PathBuf, CliError> {
         InstallOptions {
             reinstall: false,
             version: None,
+            sha256: None,
+            from: None,
+            mirror: None,
+            progress: None,
         },
     ) {
         Ok(result) => Ok(result.path),
@@ -1315,17 +1786,106 @@ fn redact_key(key: &str) -> String {
 fn install_agent_local(args: &InstallAgentArgs) -> Result<(), CliError> {
     let agent_id = AgentId::parse(&args.agent)
         .ok_or_else(|| CliError::Server(format!("unsupported agent: {}", args.agent)))?;
-    let manager = AgentManager::new(default_install_dir())
+    let mut manager = AgentManager::new(default_install_dir())
         .map_err(|err| CliError::Server(err.to_string()))?;
-    manager
+    let mut lockfile = match &args.lockfile {
+        Some(path) => Some(AgentLockfile::from_file(path)?),
+        None => None,
+    };
+    if let Some(lockfile) = &lockfile {
+        manager = manager.with_lockfile(lockfile.clone());
+    }
+    let version = args
+        .version
+        .clone()
+        .or_else(|| manager.pinned_version(agent_id).map(str::to_string));
+    let result = manager
         .install(
             agent_id,
             InstallOptions {
                 reinstall: args.reinstall,
-                version: None,
+                version: version.clone(),
+                sha256: args.sha256.clone(),
+                from: args.from.clone(),
+                mirror: args.mirror.clone(),
+                progress: None,
             },
         )
         .map_err(|err| CliError::Server(err.to_string()))?;
+    // Only versioned installs can be pinned; a plain install tracks the latest release.
+    if let (Some(lockfile), Some(path), Some(version)) =
+        (lockfile.as_mut(), args.lockfile.as_deref(), version)
+    {
+        lockfile.pin(
+            agent_id,
+            LockedAgent {
+                version,
+                sha256: Some(sha256_file(&result.path)?),
+                download_sha256: result.metadata.map(|metadata| metadata.sha256),
+            },
+        );
+        lockfile.write(path)?;
+    }
+    Ok(())
+}
+
+/// Agents `bundle` downloads when none are named.
+const BUNDLED_AGENTS: [AgentId; 5] = [
+    AgentId::Claude,
+    AgentId::Codex,
+    AgentId::Opencode,
+    AgentId::Amp,
+    AgentId::Gemini,
+];
+
+fn bundle_agents(args: &BundleArgs) -> Result<(), CliError> {
+    let platform = match &args.platform {
+        Some(name) => Platform::parse(name)
+            .ok_or_else(|| CliError::Server(format!("unsupported platform: {name}")))?,
+        None => Platform::detect().map_err(|err| CliError::Server(err.to_string()))?,
+    };
+    // Only versions are taken from the lockfile: its checksums are of one platform's
+    // files, and the bundle may be for another.
+    let lockfile = match &args.lockfile {
+        Some(path) => AgentLockfile::from_file(path)?,
+        None => AgentLockfile::default(),
+    };
+    let mut agents = Vec::new();
+    for spec in &args.agents {
+        let (name, version) = match spec.split_once('@') {
+            Some((name, version)) => (name, Some(version.to_string())),
+            None => (spec.as_str(), None),
+        };
+        let agent = AgentId::parse(name)
+            .ok_or_else(|| CliError::Server(format!("unsupported agent: {name}")))?;
+        agents.push((agent, version));
+    }
+    if agents.is_empty() {
+        agents = BUNDLED_AGENTS.iter().map(|agent| (*agent, None)).collect();
+    }
+
+    let manager = AgentManager::with_platform(default_install_dir(), platform);
+    std::fs::create_dir_all(&args.out)?;
+    for (agent, version) in agents {
+        let version = version.or_else(|| lockfile.get(agent).map(|locked| locked.version.clone()));
+        let metadata = manager
+            .bundle(
+                agent,
+                InstallOptions {
+                    version,
+                    mirror: args.mirror.clone(),
+                    ..InstallOptions::default()
+                },
+                &args.out,
+            )
+            .map_err(|err| CliError::Server(format!("{agent}: {err}")))?;
+        println!(
+            "{agent} {} {} sha256:{}",
+            metadata.version.as_deref().unwrap_or("latest"),
+            metadata.url,
+            metadata.sha256
+        );
+    }
     Ok(())
 }
 
@@ -1482,10 +2042,31 @@ fn build_cors_layer(server: &ServerArgs) -> Result<CorsLayer, CliError> {
 
 struct ClientContext {
     endpoint: String,
+    unix_socket: Option<PathBuf>,
     token: Option<String>,
     client: HttpClient,
 }
 
+/// Buffered HTTP response, shared by the TCP and Unix socket transports.
+struct ClientResponse {
+    status: reqwest::StatusCode,
+    body: Bytes,
+}
+
+impl ClientResponse {
+    fn status(&self) -> reqwest::StatusCode {
+        self.status
+    }
+
+    fn text(self) -> Result<String, CliError> {
+        Ok(String::from_utf8_lossy(&self.body).into_owned())
+    }
+
+    fn bytes(self) -> Result<Bytes, CliError> {
+        Ok(self.body)
+    }
+}
+
 impl ClientContext {
     fn new(cli: &CliConfig, args: &ClientArgs) -> Result<Self, CliError> {
         let endpoint = args
@@ -1497,14 +2078,79 @@ impl ClientContext {
         } else {
             cli.token.clone()
         };
+        let unix_socket = transport::unix_socket_path(&endpoint);
+        let endpoint = if unix_socket.is_some() {
+            "http://localhost".to_string()
+        } else {
+            endpoint
+        };
         let client = HttpClient::builder().build()?;
         Ok(Self {
             endpoint,
+            unix_socket,
             token,
             client,
         })
     }
 
+    fn send(&self, request: reqwest::blocking::RequestBuilder) -> Result<ClientResponse, CliError> {
+        self.send_with_file(request, None)
+    }
+
+    /// Sends `request`, streaming `file` as its body when given.
+    fn send_with_file(
+        &self,
+        request: reqwest::blocking::RequestBuilder,
+        file: Option<File>,
+    ) -> Result<ClientResponse, CliError> {
+        let Some(socket) = &self.unix_socket else {
+            let request = match file {
+                Some(file) => request.body(file),
+                None => request,
+            };
+            let response = request.send()?;
+            return Ok(ClientResponse {
+                status: response.status(),
+                body: response.bytes()?,
+            });
+        };
+        let request = request.build()?;
+        let url = request.url();
+        let target = match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        };
+        let body = match (file, request.body()) {
+            (Some(file), _) => transport::UnixRequestBody::File(file),
+            (None, Some(body)) => body
+                .as_bytes()
+                .map(|bytes| Bytes::copy_from_slice(bytes).into())
+                .ok_or_else(|| {
+                    CliError::Server(
+                        "streaming request bodies are not supported over unix sockets".to_string(),
+                    )
+                })?,
+            (None, None) => Bytes::new().into(),
+        };
+        let mut builder = axum::http::Request::builder()
+            .method(request.method().as_str())
+            .uri(target)
+            .header("host", "localhost");
+        for (name, value) in request.headers() {
+            builder = builder.header(name.as_str(), value.as_bytes());
+        }
+        let request = builder
+            .body(body)
+            .map_err(|err| CliError::Server(err.to_string()))?;
+        let response = transport::send_unix_request(socket, request, None)?;
+        let status = reqwest::StatusCode::from_u16(response.status().as_u16())
+            .map_err(|err| CliError::Server(err.to_string()))?;
+        Ok(ClientResponse {
+            status,
+            body: response.into_body(),
+        })
+    }
+
     fn url(&self, path: &str) -> String {
         format!("{}{}", self.endpoint.trim_end_matches('/'), path)
     }
@@ -1518,30 +2164,26 @@ impl ClientContext {
         builder
     }
 
-    fn get(&self, path: &str) -> Result<reqwest::blocking::Response, CliError> {
-        Ok(self.request(Method::GET, path).send()?)
+    fn get(&self, path: &str) -> Result<ClientResponse, CliError> {
+        self.send(self.request(Method::GET, path))
     }
 
     fn get_with_query(
         &self,
         path: &str,
         query: &[(&str, Option<String>)],
-    ) -> Result<reqwest::blocking::Response, CliError> {
+    ) -> Result<ClientResponse, CliError> {
         let mut request = self.request(Method::GET, path);
         for (key, value) in query {
             if let Some(value) = value {
                 request = request.query(&[(key, value)]);
             }
         }
-        Ok(request.send()?)
+        self.send(request)
     }
 
-    fn post<T: Serialize>(
-        &self,
-        path: &str,
-        body: &T,
-    ) -> Result<reqwest::blocking::Response, CliError> {
-        Ok(self.request(Method::POST, path).json(body).send()?)
+    fn post<T: Serialize>(&self, path: &str, body: &T) -> Result<ClientResponse, CliError> {
+        self.send(self.request(Method::POST, path).json(body))
     }
 
     fn post_with_query<T: Serialize>(
@@ -1549,14 +2191,14 @@ impl ClientContext {
         path: &str,
         body: &T,
         query: &[(&str, Option<String>)],
-    ) -> Result<reqwest::blocking::Response, CliError> {
+    ) -> Result<ClientResponse, CliError> {
         let mut request = self.request(Method::POST, path).json(body);
         for (key, value) in query {
             if let Some(value) = value {
                 request = request.query(&[(key, value)]);
             }
         }
-        Ok(request.send()?)
+        self.send(request)
     }
 
     fn put_raw_with_query<B: Into<reqwest::blocking::Body>>(
@@ -1565,7 +2207,7 @@ impl ClientContext {
         body: B,
         content_type: &str,
         query: &[(&str, Option<String>)],
-    ) -> Result<reqwest::blocking::Response, CliError> {
+    ) -> Result<ClientResponse, CliError> {
         let mut request = self
             .request(Method::PUT, path)
             .header(reqwest::header::CONTENT_TYPE, content_type)
@@ -1575,48 +2217,49 @@ impl ClientContext {
                 request = request.query(&[(key, value)]);
             }
         }
-        Ok(request.body(body).send()?)
+        self.send(request.body(body))
     }
 
-    fn post_empty(&self, path: &str) -> Result<reqwest::blocking::Response, CliError> {
-        Ok(self.request(Method::POST, path).send()?)
+    fn post_empty(&self, path: &str) -> Result<ClientResponse, CliError> {
+        self.send(self.request(Method::POST, path))
     }
 
     fn post_empty_with_query(
         &self,
         path: &str,
         query: &[(&str, Option<String>)],
-    ) -> Result<reqwest::blocking::Response, CliError> {
+    ) -> Result<ClientResponse, CliError> {
         let mut request = self.request(Method::POST, path);
         for (key, value) in query {
             if let Some(value) = value {
                 request = request.query(&[(key, value)]);
             }
         }
-        Ok(request.send()?)
+        self.send(request)
     }
 
     fn delete_with_query(
         &self,
         path: &str,
         query: &[(&str, Option<String>)],
-    ) -> Result<reqwest::blocking::Response, CliError> {
+    ) -> Result<ClientResponse, CliError> {
         let mut request = self.request(Method::DELETE, path);
         for (key, value) in query {
             if let Some(value) = value {
                 request = request.query(&[(key, value)]);
             }
         }
-        Ok(request.send()?)
+        self.send(request)
     }
 
-    fn post_raw_with_query<B: Into<reqwest::blocking::Body>>(
+    /// Posts a file as the request body without reading it into memory.
+    fn post_file_with_query(
         &self,
         path: &str,
-        body: B,
+        file: File,
         content_type: &str,
         query: &[(&str, Option<String>)],
-    ) -> Result<reqwest::blocking::Response, CliError> {
+    ) -> Result<ClientResponse, CliError> {
         let mut request = self
             .request(Method::POST, path)
             .header(reqwest::header::CONTENT_TYPE, content_type)
@@ -1626,12 +2269,12 @@ impl ClientContext {
                 request = request.query(&[(key, value)]);
             }
         }
-        Ok(request.body(body).send()?)
+        self.send_with_file(request, Some(file))
     }
 }
 
 fn print_json_response<T: serde::de::DeserializeOwned + Serialize>(
-    response: reqwest::blocking::Response,
+    response: ClientResponse,
 ) -> Result<(), CliError> {
     let status = response.status();
     let text = response.text()?;
@@ -1647,7 +2290,18 @@ fn print_json_response<T: serde::de::DeserializeOwned + Serialize>(
     Ok(())
 }
 
-fn print_text_response(response: reqwest::blocking::Response) -> Result<(), CliError> {
+/// Body of a successful response; error bodies are printed to stderr.
+fn response_text(response: ClientResponse) -> Result<String, CliError> {
+    let status = response.status();
+    let text = response.text()?;
+    if !status.is_success() {
+        print_error_body(&text)?;
+        return Err(CliError::HttpStatus(status));
+    }
+    Ok(text)
+}
+
+fn print_text_response(response: ClientResponse) -> Result<(), CliError> {
     let status = response.status();
     let text = response.text()?;
 
@@ -1660,7 +2314,7 @@ fn print_text_response(response: reqwest::blocking::Response) -> Result<(), CliE
     Ok(())
 }
 
-fn print_binary_response(response: reqwest::blocking::Response) -> Result<(), CliError> {
+fn print_binary_response(response: ClientResponse) -> Result<(), CliError> {
     let status = response.status();
     let bytes = response.bytes()?;
 
@@ -1679,7 +2333,7 @@ fn print_binary_response(response: reqwest::blocking::Response) -> Result<(), Cl
     Ok(())
 }
 
-fn print_empty_response(response: reqwest::blocking::Response) -> Result<(), CliError> {
+fn print_empty_response(response: ClientResponse) -> Result<(), CliError> {
     let status = response.status();
     if status.is_success() {
         return Ok(());
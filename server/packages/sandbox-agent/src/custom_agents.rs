@@ -0,0 +1,349 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use sandbox_agent_agent_management::agents::{
+    AgentCommand, AgentError, AgentId, AgentManager, SpawnOptions, StreamingSpawn,
+};
+use sandbox_agent_universal_agent_schema::convert_jsonl::{
+    JsonlEventKind, JsonlEventMap, JsonlStream,
+};
+use sandbox_agent_universal_agent_schema::{EventConversion, UniversalEventType};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::agent_adapter::{agent_registry, convert_json_line, mode, AgentAdapter};
+use crate::agent_adapter::{AgentCapabilities, AgentModeInfo, AgentModelInfo, AgentModelsResponse};
+
+/// Agents declared in a TOML file: a binary that runs one turn per process and
+/// prints JSON lines, described by a [`JsonlEventMap`].
+#[derive(Debug, Clone, Default)]
+pub struct CustomAgents {
+    agents: Vec<CustomAgent>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomAgent {
+    pub name: String,
+    pub command: AgentCommand,
+    /// Added when the session has a model; `{model}` is replaced.
+    pub model_args: Vec<String>,
+    /// Added when resuming; `{sessionId}` is replaced with the agent's session id.
+    pub resume_args: Vec<String>,
+    /// Added last; `{prompt}` is replaced.
+    pub prompt_args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub models: Vec<String>,
+    pub capabilities: AgentCapabilities,
+    pub events: JsonlEventMap,
+}
+
+#[derive(Debug, Deserialize)]
+struct AgentsFile {
+    #[serde(default)]
+    agents: BTreeMap<String, AgentSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct AgentSpec {
+    /// Program on `PATH` or a path to it.
+    command: String,
+    /// Leading arguments, passed as is.
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    model_args: Vec<String>,
+    #[serde(default)]
+    resume_args: Vec<String>,
+    #[serde(default = "default_prompt_args")]
+    prompt_args: Vec<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    #[serde(default)]
+    models: Vec<String>,
+    /// Overrides for the capabilities implied by `events`, keyed as in `GET /v1/agents`.
+    #[serde(default)]
+    capabilities: BTreeMap<String, bool>,
+    #[serde(default)]
+    events: JsonlEventMap,
+}
+
+fn default_prompt_args() -> Vec<String> {
+    vec!["{prompt}".to_string()]
+}
+
+impl CustomAgents {
+    pub fn from_file(path: &Path) -> Result<Self, io::Error> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, io::Error> {
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let file: AgentsFile =
+            toml_edit::de::from_str(text).map_err(|err| invalid(err.to_string()))?;
+        let agents = file
+            .agents
+            .into_iter()
+            .map(|(name, spec)| {
+                let capabilities = capabilities_for(&spec)
+                    .map_err(|err| invalid(format!("agents.{name}: {err}")))?;
+                Ok(CustomAgent {
+                    command: AgentCommand {
+                        program: spec.command,
+                        args: spec.args,
+                    },
+                    model_args: spec.model_args,
+                    resume_args: spec.resume_args,
+                    prompt_args: spec.prompt_args,
+                    env: spec.env,
+                    models: spec.models,
+                    capabilities,
+                    events: spec.events,
+                    name,
+                })
+            })
+            .collect::<Result<_, io::Error>>()?;
+        Ok(Self { agents })
+    }
+
+    pub fn agents(&self) -> &[CustomAgent] {
+        &self.agents
+    }
+
+    /// Registers every agent's id and adapter, and returns `manager` with their commands.
+    pub fn register(&self, mut manager: AgentManager) -> Result<AgentManager, AgentError> {
+        for agent in &self.agents {
+            let id = AgentId::register_custom(&agent.name)?;
+            agent_registry().register(Arc::new(CustomAgentAdapter {
+                id,
+                agent: agent.clone(),
+                streams: Mutex::new(HashMap::new()),
+            }))?;
+            manager = manager.with_agent_command(id, agent.command.clone());
+        }
+        Ok(manager)
+    }
+}
+
+/// Capabilities the event mapping can produce, with the file's overrides applied.
+fn capabilities_for(spec: &AgentSpec) -> Result<AgentCapabilities, String> {
+    let has = |kind| spec.events.types.values().any(|rule| rule.kind == kind);
+    let implied = AgentCapabilities {
+        text_messages: true,
+        item_started: true,
+        streaming_deltas: has(JsonlEventKind::Delta),
+        reasoning: has(JsonlEventKind::Reasoning),
+        tool_calls: has(JsonlEventKind::ToolCall),
+        tool_results: has(JsonlEventKind::ToolResult),
+        error_events: has(JsonlEventKind::Error),
+        ..AgentCapabilities::default()
+    };
+    let mut fields = match serde_json::to_value(implied) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    };
+    for (key, value) in &spec.capabilities {
+        if !fields.contains_key(key) {
+            return Err(format!("unknown capability {key}"));
+        }
+        fields.insert(key.clone(), Value::Bool(*value));
+    }
+    serde_json::from_value(Value::Object(fields)).map_err(|err| err.to_string())
+}
+
+struct CustomAgentAdapter {
+    id: AgentId,
+    agent: CustomAgent,
+    /// Conversion state per session, dropped when a turn ends or its process exits.
+    streams: Mutex<HashMap<String, JsonlStream>>,
+}
+
+impl CustomAgentAdapter {
+    fn command_args(&self, options: &SpawnOptions) -> Vec<String> {
+        let expand = |templates: &[String], placeholder: &str, value: &str| {
+            templates
+                .iter()
+                .map(|arg| arg.replace(placeholder, value))
+                .collect::<Vec<_>>()
+        };
+        let mut args = Vec::new();
+        if let Some(model) = options.model.as_deref() {
+            args.extend(expand(&self.agent.model_args, "{model}", model));
+        }
+        if let Some(session_id) = options.session_id.as_deref() {
+            args.extend(expand(&self.agent.resume_args, "{sessionId}", session_id));
+        }
+        args.extend(expand(&self.agent.prompt_args, "{prompt}", &options.prompt));
+        args
+    }
+}
+
+impl AgentAdapter for CustomAgentAdapter {
+    fn id(&self) -> AgentId {
+        self.id
+    }
+
+    fn capabilities(&self) -> AgentCapabilities {
+        self.agent.capabilities.clone()
+    }
+
+    fn modes(&self) -> Vec<AgentModeInfo> {
+        vec![mode("build", "Build", "Default build mode")]
+    }
+
+    fn models(&self) -> AgentModelsResponse {
+        AgentModelsResponse {
+            models: self
+                .agent
+                .models
+                .iter()
+                .map(|id| AgentModelInfo {
+                    id: id.clone(),
+                    name: None,
+                    variants: None,
+                    default_variant: None,
+                })
+                .collect(),
+            default_model: self.agent.models.first().cloned(),
+        }
+    }
+
+    fn supports_resume(&self) -> bool {
+        !self.agent.resume_args.is_empty() && self.agent.events.session_id.is_some()
+    }
+
+    fn spawn(
+        &self,
+        manager: &AgentManager,
+        options: SpawnOptions,
+    ) -> Result<StreamingSpawn, AgentError> {
+        let mut command = manager.base_command(self.id, &options)?;
+        command.args(self.command_args(&options));
+        // Session variables, such as an isolated HOME, win over the file's.
+        for (key, value) in &self.agent.env {
+            if !options.env.contains_key(key) {
+                command.env(key, value);
+            }
+        }
+        manager.spawn_command_streaming(self.id, command, &options.limits, false)
+    }
+
+    fn parse_line(&self, line: &str, session_id: &str) -> Vec<EventConversion> {
+        convert_json_line(self.id, line, |value| {
+            let mut streams = self.streams.lock().expect("custom agent streams lock");
+            let stream = streams.entry(session_id.to_string()).or_default();
+            let conversions = stream.event_to_universal(&self.agent.events, value)?;
+            if conversions
+                .iter()
+                .any(|conversion| conversion.event_type == UniversalEventType::TurnEnded)
+            {
+                streams.remove(session_id);
+            }
+            Ok(conversions)
+        })
+    }
+
+    fn process_exited(&self, session_id: &str) {
+        self.streams
+            .lock()
+            .expect("custom agent streams lock")
+            .remove(session_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+[agents.acme]
+command = "/opt/acme/bin/acme"
+args = ["run", "--json"]
+modelArgs = ["--model", "{model}"]
+resumeArgs = ["--session={sessionId}"]
+env = { ACME_COLOR = "never" }
+models = ["acme-large", "acme-small"]
+
+[agents.acme.capabilities]
+images = true
+
+[agents.acme.events]
+sessionId = "session"
+
+[agents.acme.events.types.delta]
+kind = "delta"
+text = "text"
+
+[agents.acme.events.types.tool]
+kind = "toolCall"
+name = "tool"
+"#;
+
+    #[test]
+    fn parses_agents_and_builds_arguments() {
+        let agents = CustomAgents::parse(CONFIG).expect("parse");
+        let [agent] = agents.agents() else {
+            panic!("expected one agent");
+        };
+        assert_eq!(agent.name, "acme");
+        assert_eq!(agent.command.args, ["run", "--json"]);
+        assert_eq!(agent.env["ACME_COLOR"], "never");
+        assert!(agent.capabilities.streaming_deltas);
+        assert!(agent.capabilities.tool_calls);
+        assert!(!agent.capabilities.tool_results);
+        assert!(agent.capabilities.images);
+
+        let adapter = CustomAgentAdapter {
+            id: AgentId::Mock,
+            agent: agent.clone(),
+            streams: Mutex::new(HashMap::new()),
+        };
+        assert!(adapter.supports_resume());
+        assert_eq!(
+            adapter.models().default_model.as_deref(),
+            Some("acme-large")
+        );
+        let mut options = SpawnOptions::new("fix it");
+        assert_eq!(adapter.command_args(&options), ["fix it"]);
+        options.model = Some("acme-small".to_string());
+        options.session_id = Some("s1".to_string());
+        assert_eq!(
+            adapter.command_args(&options),
+            ["--model", "acme-small", "--session=s1", "fix it"]
+        );
+    }
+
+    #[test]
+    fn drops_session_state_when_the_process_exits() {
+        let agents = CustomAgents::parse(CONFIG).expect("parse");
+        let adapter = CustomAgentAdapter {
+            id: AgentId::Mock,
+            agent: agents.agents()[0].clone(),
+            streams: Mutex::new(HashMap::new()),
+        };
+        adapter.parse_line(r#"{"type":"delta","text":"hi"}"#, "s1");
+        adapter.parse_line(r#"{"type":"delta","text":"hi"}"#, "s2");
+        adapter.process_exited("s1");
+        let streams = adapter.streams.lock().expect("streams");
+        assert_eq!(streams.keys().collect::<Vec<_>>(), ["s2"]);
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_capabilities() {
+        let err = CustomAgents::parse("[agents.a]\ncommand = \"a\"\nbinary = \"b\"\n").unwrap_err();
+        assert!(err.to_string().contains("binary"));
+        let err =
+            CustomAgents::parse("[agents.a]\ncommand = \"a\"\nargs = \"--json\"\n").unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{err}");
+        let err = CustomAgents::parse(
+            "[agents.a]\ncommand = \"a\"\ncapabilities = { teleport = true }\n",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("unknown capability teleport"));
+    }
+}
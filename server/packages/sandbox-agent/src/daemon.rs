@@ -3,9 +3,11 @@ use std::path::{Path, PathBuf};
 use std::process::{Child, Command as ProcessCommand, Stdio};
 use std::time::{Duration, Instant};
 
+use axum::body::Bytes;
 use reqwest::blocking::Client as HttpClient;
 
 use crate::cli::{CliConfig, CliError};
+use crate::transport;
 
 mod build_id {
     include!(concat!(env!("OUT_DIR"), "/build_id.rs"));
@@ -144,28 +146,55 @@ pub fn is_process_running(pid: u32) -> bool {
 // Health checks
 // ---------------------------------------------------------------------------
 
+/// Base URL the daemon client talks to: the Unix socket when one is configured.
+fn daemon_base_url(host: &str, port: u16, unix_socket: Option<&Path>) -> String {
+    match unix_socket {
+        Some(path) => format!("{}{}", transport::UNIX_SCHEME, path.display()),
+        None => format!("http://{host}:{port}"),
+    }
+}
+
+/// Requests `/v1/health` over HTTP, or over the socket for `unix://` base URLs.
+fn health_status(client: &HttpClient, base_url: &str, token: Option<&str>) -> Result<u16, String> {
+    if let Some(socket) = transport::unix_socket_path(base_url) {
+        let mut request = axum::http::Request::get("/v1/health").header("host", "localhost");
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {token}"));
+        }
+        let request = request
+            .body(Bytes::new().into())
+            .map_err(|err| err.to_string())?;
+        return transport::send_unix_request(&socket, request, Some(HEALTH_CHECK_REQUEST_TIMEOUT))
+            .map(|response| response.status().as_u16())
+            .map_err(|err| err.to_string());
+    }
+    let mut request = client.get(format!("{base_url}/v1/health"));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request
+        .send()
+        .map(|response| response.status().as_u16())
+        .map_err(|err| err.to_string())
+}
+
 pub fn check_health(base_url: &str, token: Option<&str>) -> Result<bool, CliError> {
-    let url = format!("{base_url}/v1/health");
     let started_at = Instant::now();
     let client = HttpClient::builder()
         .connect_timeout(HEALTH_CHECK_CONNECT_TIMEOUT)
         .timeout(HEALTH_CHECK_REQUEST_TIMEOUT)
         .build()?;
-    let mut request = client.get(url);
-    if let Some(token) = token {
-        request = request.bearer_auth(token);
-    }
-    match request.send() {
-        Ok(response) if response.status().is_success() => {
+    match health_status(&client, base_url, token) {
+        Ok(status) if (200..300).contains(&status) => {
             tracing::info!(
                 elapsed_ms = started_at.elapsed().as_millis(),
                 "daemon health check succeeded"
             );
             Ok(true)
         }
-        Ok(response) => {
+        Ok(status) => {
             tracing::warn!(
-                status = %response.status(),
+                status,
                 elapsed_ms = started_at.elapsed().as_millis(),
                 "daemon health check returned non-success status"
             );
@@ -205,13 +234,8 @@ pub fn wait_for_health(
             }
         }
 
-        let url = format!("{base_url}/v1/health");
-        let mut request = client.get(&url);
-        if let Some(token) = token {
-            request = request.bearer_auth(token);
-        }
-        match request.send() {
-            Ok(response) if response.status().is_success() => {
+        match health_status(&client, base_url, token) {
+            Ok(status) if (200..300).contains(&status) => {
                 tracing::info!(
                     attempts,
                     elapsed_ms =
@@ -220,13 +244,9 @@ pub fn wait_for_health(
                 );
                 return Ok(());
             }
-            Ok(response) => {
+            Ok(status) => {
                 if attempts % 10 == 0 {
-                    tracing::info!(
-                        attempts,
-                        status = %response.status(),
-                        "daemon still not healthy; waiting"
-                    );
+                    tracing::info!(attempts, status, "daemon still not healthy; waiting");
                 }
                 std::thread::sleep(Duration::from_millis(200));
             }
@@ -261,6 +281,7 @@ pub fn spawn_sandbox_agent_daemon(
     _cli: &CliConfig,
     host: &str,
     port: u16,
+    unix_socket: Option<&Path>,
     token: Option<&str>,
     log_path: &Path,
 ) -> Result<Child, CliError> {
@@ -282,6 +303,9 @@ pub fn spawn_sandbox_agent_daemon(
         .stdout(Stdio::from(log_file))
         .stderr(Stdio::from(log_file_err));
 
+    if let Some(path) = unix_socket {
+        cmd.arg("--unix-socket").arg(path);
+    }
     if let Some(token) = token {
         cmd.arg("--token").arg(token);
     }
@@ -335,7 +359,12 @@ impl std::fmt::Display for DaemonStatus {
 // High-level commands
 // ---------------------------------------------------------------------------
 
-pub fn status(host: &str, port: u16, token: Option<&str>) -> Result<DaemonStatus, CliError> {
+pub fn status(
+    host: &str,
+    port: u16,
+    unix_socket: Option<&Path>,
+    token: Option<&str>,
+) -> Result<DaemonStatus, CliError> {
     let pid_path = daemon_pid_path(host, port);
     let log_path = daemon_log_path(host, port);
 
@@ -356,7 +385,7 @@ pub fn status(host: &str, port: u16, token: Option<&str>) -> Result<DaemonStatus
     }
 
     // Also try a health check in case the daemon is running but we lost the PID file
-    let base_url = format!("http://{host}:{port}");
+    let base_url = daemon_base_url(host, port, unix_socket);
     if check_health(&base_url, token)? {
         return Ok(DaemonStatus::Running {
             pid: 0,
@@ -369,8 +398,14 @@ pub fn status(host: &str, port: u16, token: Option<&str>) -> Result<DaemonStatus
     Ok(DaemonStatus::NotRunning)
 }
 
-pub fn start(cli: &CliConfig, host: &str, port: u16, token: Option<&str>) -> Result<(), CliError> {
-    let base_url = format!("http://{host}:{port}");
+pub fn start(
+    cli: &CliConfig,
+    host: &str,
+    port: u16,
+    unix_socket: Option<&Path>,
+    token: Option<&str>,
+) -> Result<(), CliError> {
+    let base_url = daemon_base_url(host, port, unix_socket);
     let pid_path = daemon_pid_path(host, port);
     let log_path = daemon_log_path(host, port);
 
@@ -394,7 +429,7 @@ pub fn start(cli: &CliConfig, host: &str, port: u16, token: Option<&str>) -> Res
         log_path.display()
     );
 
-    let mut child = spawn_sandbox_agent_daemon(cli, host, port, token, &log_path)?;
+    let mut child = spawn_sandbox_agent_daemon(cli, host, port, unix_socket, token, &log_path)?;
     let pid = child.id();
     write_pid(&pid_path, pid)?;
     write_daemon_version(host, port)?;
@@ -535,9 +570,10 @@ pub fn ensure_running(
     cli: &CliConfig,
     host: &str,
     port: u16,
+    unix_socket: Option<&Path>,
     token: Option<&str>,
 ) -> Result<(), CliError> {
-    let base_url = format!("http://{host}:{port}");
+    let base_url = daemon_base_url(host, port, unix_socket);
     let pid_path = daemon_pid_path(host, port);
     eprintln!(
         "checking daemon health at {base_url} (token: {})...",
@@ -554,7 +590,7 @@ pub fn ensure_running(
                 BUILD_ID
             );
             stop(host, port)?;
-            return start(cli, host, port, token);
+            return start(cli, host, port, unix_socket, token);
         }
         let log_path = daemon_log_path(host, port);
         if let Some(pid) = read_pid(&pid_path) {
@@ -578,5 +614,5 @@ pub fn ensure_running(
         let _ = remove_version_file(host, port);
     }
 
-    start(cli, host, port, token)
+    start(cli, host, port, unix_socket, token)
 }
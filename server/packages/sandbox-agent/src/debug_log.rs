@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+use crate::redaction::Redactor;
+
+/// Bytes of captured lines kept per session; the oldest entries are dropped first.
+const MAX_DEBUG_LOG_BYTES: usize = 8 * 1024 * 1024;
+
+/// Where a captured line came from, relative to the daemon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DebugDirection {
+    /// Written to the agent process stdin.
+    Stdin,
+    /// Read from the agent process stdout.
+    Stdout,
+    /// Read from the agent process stderr.
+    Stderr,
+    /// HTTP request body sent to an agent server.
+    Request,
+    /// Server-sent event received from an agent server.
+    Event,
+}
+
+#[derive(Debug, Serialize)]
+struct DebugLogEntry<'a> {
+    seq: u64,
+    time: String,
+    direction: DebugDirection,
+    line: &'a str,
+}
+
+/// Native agent I/O captured for one session, kept as NDJSON lines in memory.
+#[derive(Debug)]
+pub(crate) struct DebugLog {
+    redactor: Arc<Redactor>,
+    entries: Mutex<DebugLogEntries>,
+}
+
+#[derive(Debug, Default)]
+struct DebugLogEntries {
+    lines: VecDeque<String>,
+    bytes: usize,
+    next_seq: u64,
+}
+
+impl DebugLog {
+    pub(crate) fn new(redactor: Arc<Redactor>) -> Self {
+        Self {
+            redactor,
+            entries: Mutex::new(DebugLogEntries::default()),
+        }
+    }
+
+    pub(crate) fn record(&self, direction: DebugDirection, line: &str) {
+        let line = self.redactor.redact_str(line);
+        let mut entries = self.entries.lock().expect("debug log lock");
+        let entry = DebugLogEntry {
+            seq: entries.next_seq,
+            time: OffsetDateTime::now_utc()
+                .format(&Rfc3339)
+                .unwrap_or_else(|_| "1970-01-01T00:00:00Z".to_string()),
+            direction,
+            line: &line,
+        };
+        let Ok(encoded) = serde_json::to_string(&entry) else {
+            return;
+        };
+        entries.next_seq += 1;
+        entries.bytes += encoded.len() + 1;
+        entries.lines.push_back(encoded);
+        while entries.bytes > MAX_DEBUG_LOG_BYTES {
+            let Some(dropped) = entries.lines.pop_front() else {
+                break;
+            };
+            entries.bytes -= dropped.len() + 1;
+        }
+    }
+
+    pub(crate) fn record_json(&self, direction: DebugDirection, value: &impl Serialize) {
+        if let Ok(line) = serde_json::to_string(value) {
+            self.record(direction, &line);
+        }
+    }
+
+    /// Captured entries, oldest first, one JSON object per line.
+    pub(crate) fn to_ndjson(&self) -> String {
+        let entries = self.entries.lock().expect("debug log lock");
+        let mut body = String::with_capacity(entries.bytes);
+        for line in &entries.lines {
+            body.push_str(line);
+            body.push('\n');
+        }
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_and_drops_the_oldest_lines() {
+        let redactor = Arc::new(Redactor::new(&[], &[]));
+        redactor.add_secret("hunter2-secret-value");
+        let log = DebugLog::new(redactor);
+        log.record(DebugDirection::Stdin, "password hunter2-secret-value");
+        let first: serde_json::Value =
+            serde_json::from_str(log.to_ndjson().lines().next().unwrap()).unwrap();
+        assert_eq!(first["seq"], 0);
+        assert_eq!(first["direction"], "stdin");
+        assert!(!first["line"].as_str().unwrap().contains("hunter2"));
+
+        let big = "x".repeat(1024 * 1024);
+        for _ in 0..10 {
+            log.record(DebugDirection::Stdout, &big);
+        }
+        let body = log.to_ndjson();
+        assert!(body.len() <= MAX_DEBUG_LOG_BYTES);
+        let first: serde_json::Value = serde_json::from_str(body.lines().next().unwrap()).unwrap();
+        assert!(first["seq"].as_u64().unwrap() > 1);
+        assert_eq!(first["direction"], "stdout");
+    }
+}
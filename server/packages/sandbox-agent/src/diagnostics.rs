@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+/// Errors kept for `GET /v1/diagnostics`; older entries are dropped first.
+const MAX_RECENT_ERRORS: usize = 50;
+
+static RECENT_ERRORS: OnceLock<RecentErrors> = OnceLock::new();
+
+/// Process-wide log of recent agent and server errors.
+pub(crate) fn recent_errors() -> &'static RecentErrors {
+    RECENT_ERRORS.get_or_init(RecentErrors::default)
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ErrorRecord {
+    pub(crate) time: String,
+    pub(crate) source: &'static str,
+    pub(crate) agent: Option<String>,
+    pub(crate) session_id: Option<String>,
+    pub(crate) message: String,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct RecentErrors {
+    entries: Mutex<VecDeque<ErrorRecord>>,
+}
+
+impl RecentErrors {
+    pub(crate) fn record(
+        &self,
+        source: &'static str,
+        agent: Option<&str>,
+        session_id: Option<&str>,
+        message: &str,
+    ) {
+        let record = ErrorRecord {
+            time: time::OffsetDateTime::now_utc()
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_else(|_| "1970-01-01T00:00:00Z".to_string()),
+            source,
+            agent: agent.map(str::to_string),
+            session_id: session_id.map(str::to_string),
+            message: message.to_string(),
+        };
+        let mut entries = self.entries.lock().expect("recent errors lock");
+        if entries.len() == MAX_RECENT_ERRORS {
+            entries.pop_front();
+        }
+        entries.push_back(record);
+    }
+
+    /// Recorded errors, newest first.
+    pub(crate) fn snapshot(&self) -> Vec<ErrorRecord> {
+        let entries = self.entries.lock().expect("recent errors lock");
+        entries.iter().rev().cloned().collect()
+    }
+}
+
+/// Available and total bytes on the filesystem holding `path`. Paths that do not
+/// exist yet are measured at their nearest existing ancestor.
+pub(crate) fn disk_space(path: &Path) -> Option<(u64, u64)> {
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+    filesystem_space(existing)
+}
+
+#[cfg(unix)]
+fn filesystem_space(path: &Path) -> Option<(u64, u64)> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let block = stat.f_frsize as u64;
+    Some((stat.f_bavail as u64 * block, stat.f_blocks as u64 * block))
+}
+
+#[cfg(windows)]
+fn filesystem_space(path: &Path) -> Option<(u64, u64)> {
+    use std::ffi::CString;
+    use windows::core::PCSTR;
+    use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExA;
+
+    let path = CString::new(path.to_str()?).ok()?;
+    let mut available = 0u64;
+    let mut total = 0u64;
+    unsafe {
+        GetDiskFreeSpaceExA(
+            PCSTR(path.as_ptr() as *const u8),
+            Some(&mut available),
+            Some(&mut total),
+            None,
+        )
+        .ok()?;
+    }
+    Some((available, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_newest_errors_first() {
+        let errors = RecentErrors::default();
+        for index in 0..MAX_RECENT_ERRORS + 2 {
+            errors.record("agent", Some("mock"), None, &format!("error {index}"));
+        }
+        let snapshot = errors.snapshot();
+        assert_eq!(snapshot.len(), MAX_RECENT_ERRORS);
+        assert_eq!(
+            snapshot[0].message,
+            format!("error {}", MAX_RECENT_ERRORS + 1)
+        );
+        assert_eq!(snapshot.last().unwrap().message, "error 2");
+    }
+
+    #[test]
+    fn measures_missing_paths_at_an_existing_ancestor() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let (available, total) =
+            disk_space(&dir.path().join("not/created/yet")).expect("disk space");
+        assert!(total > 0);
+        assert!(available <= total);
+    }
+}
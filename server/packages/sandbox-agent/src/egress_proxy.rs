@@ -0,0 +1,493 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use base64::Engine;
+use sandbox_agent_agent_management::agents::AgentId;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use crate::agent_adapter::adapter;
+
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Model provider endpoints each agent needs to function.
+pub(crate) fn provider_hosts(agent: AgentId) -> &'static [&'static str] {
+    adapter(agent).provider_hosts()
+}
+
+/// A connection attempt seen by the proxy.
+#[derive(Debug, Clone)]
+pub(crate) struct EgressEvent {
+    pub session_id: String,
+    pub method: String,
+    pub target: String,
+    pub allowed: bool,
+}
+
+/// A session's registration with the proxy.
+#[derive(Debug, Clone)]
+pub(crate) struct EgressLease {
+    token: String,
+    proxy_url: String,
+}
+
+impl EgressLease {
+    /// Proxy variables injected into the agent's environment. `NO_PROXY` is cleared
+    /// so loopback traffic, including the daemon's own API, goes through the allowlist.
+    pub(crate) fn env(&self) -> Vec<(String, String)> {
+        let no_proxy = String::new();
+        vec![
+            ("HTTP_PROXY".to_string(), self.proxy_url.clone()),
+            ("HTTPS_PROXY".to_string(), self.proxy_url.clone()),
+            ("ALL_PROXY".to_string(), self.proxy_url.clone()),
+            ("http_proxy".to_string(), self.proxy_url.clone()),
+            ("https_proxy".to_string(), self.proxy_url.clone()),
+            ("all_proxy".to_string(), self.proxy_url.clone()),
+            ("NO_PROXY".to_string(), no_proxy.clone()),
+            ("no_proxy".to_string(), no_proxy),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct HostPattern {
+    host: String,
+    wildcard: bool,
+    port: Option<u16>,
+}
+
+impl HostPattern {
+    /// Parses `example.com`, `*.example.com`, `example.com:8443` or `*`.
+    pub(crate) fn parse(pattern: &str) -> Result<Self, String> {
+        let pattern = pattern.trim().to_ascii_lowercase();
+        if pattern == "*" {
+            return Ok(Self {
+                host: String::new(),
+                wildcard: true,
+                port: None,
+            });
+        }
+        let (host, port) = split_host_port(&pattern)
+            .ok_or_else(|| format!("invalid egress host pattern: {pattern}"))?;
+        let (host, wildcard) = match host.strip_prefix("*.") {
+            Some(rest) => (rest.to_string(), true),
+            None => (host, false),
+        };
+        if host.is_empty() || host.contains('*') || host.contains('/') {
+            return Err(format!("invalid egress host pattern: {pattern}"));
+        }
+        Ok(Self {
+            host,
+            wildcard,
+            port,
+        })
+    }
+
+    /// `*.example.com` matches `example.com` and any of its subdomains.
+    fn matches(&self, host: &str, port: u16) -> bool {
+        if self.port.is_some_and(|expected| expected != port) {
+            return false;
+        }
+        if self.wildcard && self.host.is_empty() {
+            return true;
+        }
+        let host = host.trim_end_matches('.');
+        if host.eq_ignore_ascii_case(&self.host) {
+            return true;
+        }
+        self.wildcard
+            && host.len() > self.host.len()
+            && host
+                .to_ascii_lowercase()
+                .ends_with(&format!(".{}", self.host))
+    }
+}
+
+#[derive(Debug)]
+struct SessionPolicy {
+    session_id: String,
+    allow: Vec<HostPattern>,
+}
+
+/// HTTP(S) forward proxy that restricts agent egress to per-session allowlists.
+///
+/// Agents opt in through the standard proxy variables, so this limits well-behaved
+/// clients; it does not stop a process that opens raw sockets.
+#[derive(Debug)]
+pub(crate) struct EgressProxy {
+    addr: SocketAddr,
+    policies: Mutex<HashMap<String, SessionPolicy>>,
+    events: mpsc::UnboundedSender<EgressEvent>,
+}
+
+impl EgressProxy {
+    /// Binds the proxy on a loopback port and starts accepting connections.
+    pub(crate) async fn start() -> io::Result<(Arc<Self>, mpsc::UnboundedReceiver<EgressEvent>)> {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+        let (events, receiver) = mpsc::unbounded_channel();
+        let proxy = Arc::new(Self {
+            addr: listener.local_addr()?,
+            policies: Mutex::new(HashMap::new()),
+            events,
+        });
+        let weak = Arc::downgrade(&proxy);
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    continue;
+                };
+                let Some(proxy) = weak.upgrade() else {
+                    break;
+                };
+                tokio::spawn(async move {
+                    if let Err(err) = proxy.handle(stream).await {
+                        tracing::debug!(error = %err, "egress proxy connection failed");
+                    }
+                });
+            }
+        });
+        Ok((proxy, receiver))
+    }
+
+    pub(crate) fn register(
+        &self,
+        session_id: &str,
+        allow: Vec<HostPattern>,
+    ) -> io::Result<EgressLease> {
+        let mut bytes = [0u8; 16];
+        getrandom::getrandom(&mut bytes).map_err(|err| io::Error::other(err.to_string()))?;
+        let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        self.policies.lock().unwrap().insert(
+            token.clone(),
+            SessionPolicy {
+                session_id: session_id.to_string(),
+                allow,
+            },
+        );
+        Ok(EgressLease {
+            proxy_url: format!("http://session:{token}@{}", self.addr),
+            token,
+        })
+    }
+
+    pub(crate) fn unregister(&self, lease: &EgressLease) {
+        self.policies.lock().unwrap().remove(&lease.token);
+    }
+
+    async fn handle(&self, mut client: TcpStream) -> io::Result<()> {
+        let (head, rest) = read_head(&mut client).await?;
+        let Some(request) = ProxyRequest::parse(&head) else {
+            return respond(&mut client, "400 Bad Request").await;
+        };
+        let policy = request.token().and_then(|token| {
+            let policies = self.policies.lock().unwrap();
+            policies
+                .get(&token)
+                .map(|policy| (policy.session_id.clone(), policy.allow.clone()))
+        });
+        let Some((session_id, allow)) = policy else {
+            client
+                .write_all(
+                    b"HTTP/1.1 407 Proxy Authentication Required\r\n\
+                      Proxy-Authenticate: Basic realm=\"sandbox-agent\"\r\n\
+                      Content-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .await?;
+            return Ok(());
+        };
+        let Some((host, port, path)) = request.target() else {
+            return respond(&mut client, "400 Bad Request").await;
+        };
+        let allowed = allow.iter().any(|pattern| pattern.matches(&host, port));
+        let target = format!("{host}:{port}");
+        tracing::info!(
+            session_id = %session_id,
+            method = %request.method,
+            target = %target,
+            allowed,
+            "egress connection"
+        );
+        let _ = self.events.send(EgressEvent {
+            session_id,
+            method: request.method.clone(),
+            target,
+            allowed,
+        });
+        if !allowed {
+            return respond(&mut client, "403 Forbidden").await;
+        }
+
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        let upstream =
+            tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host, port))).await;
+        let mut upstream = match upstream {
+            Ok(Ok(stream)) => stream,
+            _ => return respond(&mut client, "502 Bad Gateway").await,
+        };
+        if request.method == "CONNECT" {
+            client
+                .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+                .await?;
+        } else {
+            upstream
+                .write_all(request.origin_form_head(&path).as_bytes())
+                .await?;
+        }
+        if !rest.is_empty() {
+            upstream.write_all(&rest).await?;
+        }
+        tokio::io::copy_bidirectional(&mut client, &mut upstream).await?;
+        Ok(())
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str) -> io::Result<()> {
+    let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    stream.write_all(response.as_bytes()).await
+}
+
+/// Reads up to the end of the request head, returning the head and any bytes read past it.
+async fn read_head(stream: &mut TcpStream) -> io::Result<(String, Vec<u8>)> {
+    let mut buf = Vec::with_capacity(4096);
+    let mut chunk = [0u8; 4096];
+    loop {
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "connection closed before request head",
+            ));
+        }
+        buf.extend_from_slice(&chunk[..read]);
+        if let Some(end) = buf.windows(4).position(|window| window == b"\r\n\r\n") {
+            let rest = buf.split_off(end + 4);
+            return Ok((String::from_utf8_lossy(&buf).to_string(), rest));
+        }
+        if buf.len() > MAX_HEAD_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request head too large",
+            ));
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ProxyRequest {
+    method: String,
+    uri: String,
+    version: String,
+    headers: Vec<(String, String)>,
+}
+
+impl ProxyRequest {
+    fn parse(head: &str) -> Option<Self> {
+        let mut lines = head.split("\r\n");
+        let mut request_line = lines.next()?.split_whitespace();
+        let method = request_line.next()?.to_ascii_uppercase();
+        let uri = request_line.next()?.to_string();
+        let version = request_line.next()?.to_string();
+        let headers = lines
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| {
+                let (name, value) = line.split_once(':')?;
+                Some((name.trim().to_string(), value.trim().to_string()))
+            })
+            .collect();
+        Some(Self {
+            method,
+            uri,
+            version,
+            headers,
+        })
+    }
+
+    /// The password (or bare username) from `Proxy-Authorization: Basic ...`.
+    fn token(&self) -> Option<String> {
+        let value = self
+            .headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("proxy-authorization"))
+            .map(|(_, value)| value)?;
+        let (scheme, encoded) = value.split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .ok()?;
+        let credentials = String::from_utf8(decoded).ok()?;
+        match credentials.split_once(':') {
+            Some((_, password)) if !password.is_empty() => Some(password.to_string()),
+            Some((user, _)) => Some(user.to_string()),
+            None => Some(credentials),
+        }
+    }
+
+    /// Host, port and origin-form path. HTTPS must be tunnelled with CONNECT.
+    fn target(&self) -> Option<(String, u16, String)> {
+        if self.method == "CONNECT" {
+            let (host, port) = split_host_port(&self.uri)?;
+            return Some((host, port?, String::new()));
+        }
+        let rest = self.uri.strip_prefix("http://")?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = split_host_port(authority)?;
+        Some((host, port.unwrap_or(80), path.to_string()))
+    }
+
+    /// Rewrites an absolute-form request for the origin server, one request per connection.
+    fn origin_form_head(&self, path: &str) -> String {
+        let mut head = format!("{} {} {}\r\n", self.method, path, self.version);
+        for (name, value) in &self.headers {
+            let lower = name.to_ascii_lowercase();
+            if lower.starts_with("proxy-") || lower == "connection" || lower == "keep-alive" {
+                continue;
+            }
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("Connection: close\r\n\r\n");
+        head
+    }
+}
+
+fn split_host_port(authority: &str) -> Option<(String, Option<u16>)> {
+    if authority.contains('@') {
+        return None;
+    }
+    if let Some(rest) = authority.strip_prefix('[') {
+        let (host, after) = rest.split_once(']')?;
+        let port = match after.strip_prefix(':') {
+            Some(port) => Some(port.parse().ok()?),
+            None if after.is_empty() => None,
+            None => return None,
+        };
+        return Some((format!("[{}]", host.to_ascii_lowercase()), port));
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) => Some((host.to_ascii_lowercase(), Some(port.parse().ok()?))),
+        None => Some((authority.to_ascii_lowercase(), None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(values: &[&str]) -> Vec<HostPattern> {
+        values
+            .iter()
+            .map(|value| HostPattern::parse(value).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn host_patterns_match_hosts_and_ports() {
+        let allow = patterns(&["*.anthropic.com", "example.com:8443", "[::1]"]);
+        let allowed = |host: &str, port: u16| allow.iter().any(|p| p.matches(host, port));
+
+        assert!(allowed("api.anthropic.com", 443));
+        assert!(allowed("anthropic.com", 443));
+        assert!(allowed("API.Anthropic.com.", 443));
+        assert!(!allowed("evilanthropic.com", 443));
+        assert!(allowed("example.com", 8443));
+        assert!(!allowed("example.com", 443));
+        assert!(allowed("[::1]", 80));
+        assert!(HostPattern::parse("*").unwrap().matches("anything.test", 1));
+        assert!(HostPattern::parse("foo.*.com").is_err());
+        assert!(HostPattern::parse("user@example.com").is_err());
+    }
+
+    #[test]
+    fn lease_env_proxies_loopback() {
+        let lease = EgressLease {
+            token: "t".to_string(),
+            proxy_url: "http://session:t@127.0.0.1:9".to_string(),
+        };
+        let env: HashMap<_, _> = lease.env().into_iter().collect();
+        assert_eq!(env["HTTPS_PROXY"], "http://session:t@127.0.0.1:9");
+        assert_eq!(env["NO_PROXY"], "");
+        assert_eq!(env["no_proxy"], "");
+    }
+
+    async fn send(proxy: &EgressProxy, head: String) -> (TcpStream, String) {
+        let mut stream = TcpStream::connect(proxy.addr).await.unwrap();
+        stream.write_all(head.as_bytes()).await.unwrap();
+        let mut buf = vec![0u8; 1024];
+        let read = stream.read(&mut buf).await.unwrap();
+        let status = String::from_utf8_lossy(&buf[..read])
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        (stream, status)
+    }
+
+    #[tokio::test]
+    async fn connect_enforces_session_allowlist() {
+        let upstream = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let upstream_port = upstream.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = upstream.accept().await.unwrap();
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&buf).await.unwrap();
+        });
+
+        let (proxy, mut events) = EgressProxy::start().await.unwrap();
+        let lease = proxy.register("sess-1", patterns(&["127.0.0.1"])).unwrap();
+        let auth =
+            base64::engine::general_purpose::STANDARD.encode(format!("session:{}", lease.token));
+
+        let (_, status) = send(
+            &proxy,
+            format!("CONNECT 127.0.0.1:{upstream_port} HTTP/1.1\r\n\r\n"),
+        )
+        .await;
+        assert!(status.contains("407"), "{status}");
+
+        let (_, status) = send(
+            &proxy,
+            format!(
+                "CONNECT example.com:443 HTTP/1.1\r\nProxy-Authorization: Basic {auth}\r\n\r\n"
+            ),
+        )
+        .await;
+        assert!(status.contains("403"), "{status}");
+        let denied = events.recv().await.unwrap();
+        assert_eq!(denied.session_id, "sess-1");
+        assert_eq!(denied.target, "example.com:443");
+        assert!(!denied.allowed);
+
+        let (mut tunnel, status) = send(
+            &proxy,
+            format!(
+                "CONNECT 127.0.0.1:{upstream_port} HTTP/1.1\r\nProxy-Authorization: Basic {auth}\r\n\r\n"
+            ),
+        )
+        .await;
+        assert!(status.contains("200"), "{status}");
+        assert!(events.recv().await.unwrap().allowed);
+        tunnel.write_all(b"ping").await.unwrap();
+        let mut echoed = [0u8; 4];
+        tunnel.read_exact(&mut echoed).await.unwrap();
+        assert_eq!(&echoed, b"ping");
+
+        proxy.unregister(&lease);
+        let (_, status) = send(
+            &proxy,
+            format!("CONNECT 127.0.0.1:{upstream_port} HTTP/1.1\r\nProxy-Authorization: Basic {auth}\r\n\r\n"),
+        )
+        .await;
+        assert!(status.contains("407"), "{status}");
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use sandbox_agent_agent_management::agents::{
+    AgentId, InstallProgress, InstallProgressSink, InstallResult,
+};
+use sandbox_agent_error::SandboxError;
+use tokio::sync::{watch, OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::router::{InstallJob, InstallJobPhase, InstallJobProgress, InstallJobStatus};
+
+/// Jobs listed by `GET /v1/install-jobs`; beyond this the oldest finished ones are dropped.
+const JOBS_KEPT: usize = 50;
+
+/// Agent installs, run one agent at a time so concurrent requests for the same agent
+/// do not write over each other's download. Sessions starting an agent hold its lock
+/// shared, so an uninstall cannot remove the binary from under them.
+#[derive(Debug, Default)]
+pub(crate) struct InstallJobs {
+    next_id: AtomicU64,
+    jobs: Mutex<VecDeque<watch::Receiver<InstallJob>>>,
+    agent_locks: Mutex<HashMap<AgentId, Arc<RwLock<()>>>>,
+}
+
+impl InstallJobs {
+    /// Queues `install` behind other installs of `agent` and runs it on a blocking
+    /// thread. The handle resolves when it finishes; dropping it leaves the job running.
+    pub(crate) fn start<F>(
+        &self,
+        agent: AgentId,
+        version: Option<String>,
+        install: F,
+    ) -> (InstallJob, JoinHandle<Result<InstallResult, SandboxError>>)
+    where
+        F: FnOnce(InstallProgressSink) -> Result<InstallResult, SandboxError> + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let job = InstallJob {
+            id: format!("install-{id}"),
+            agent: agent.as_str().to_string(),
+            version,
+            status: InstallJobStatus::Queued,
+            progress: None,
+            installed_version: None,
+            error: None,
+        };
+        let (sender, receiver) = watch::channel(job.clone());
+        self.keep(receiver);
+        let lock = self.agent_lock(agent);
+
+        let handle = tokio::spawn(async move {
+            let _turn = lock.write().await;
+            let sender = Arc::new(sender);
+            sender.send_modify(|job| job.status = InstallJobStatus::Running);
+            let reporter = sender.clone();
+            let sink = InstallProgressSink::new(move |progress| {
+                reporter.send_modify(|job| apply_progress(job, progress));
+            });
+            let result = tokio::task::spawn_blocking(move || install(sink))
+                .await
+                .unwrap_or_else(|err| {
+                    Err(SandboxError::InstallFailed {
+                        agent: agent.as_str().to_string(),
+                        stderr: Some(err.to_string()),
+                    })
+                });
+            sender.send_modify(|job| match &result {
+                Ok(installed) => {
+                    job.status = InstallJobStatus::Succeeded;
+                    job.installed_version = installed.version.clone();
+                }
+                Err(err) => {
+                    job.status = InstallJobStatus::Failed;
+                    job.error = Some(err.to_problem_details());
+                }
+            });
+            result
+        });
+        (job, handle)
+    }
+
+    /// Waits for installs and session starts of `agent` to finish; others queue until
+    /// the guard is dropped.
+    pub(crate) async fn exclusive(&self, agent: AgentId) -> OwnedRwLockWriteGuard<()> {
+        self.agent_lock(agent).write_owned().await
+    }
+
+    /// Waits for installs of `agent` to finish; sessions may start alongside each other,
+    /// but installs and uninstalls queue until the guard is dropped.
+    pub(crate) async fn shared(&self, agent: AgentId) -> OwnedRwLockReadGuard<()> {
+        self.agent_lock(agent).read_owned().await
+    }
+
+    fn agent_lock(&self, agent: AgentId) -> Arc<RwLock<()>> {
+        self.agent_locks
+            .lock()
+            .expect("install locks poisoned")
+            .entry(agent)
+            .or_default()
+            .clone()
+    }
+
+    /// Follows a job; the receiver sees every state it moves through from now on.
+    pub(crate) fn subscribe(&self, id: &str) -> Option<watch::Receiver<InstallJob>> {
+        self.jobs
+            .lock()
+            .expect("install jobs poisoned")
+            .iter()
+            .find(|job| job.borrow().id == id)
+            .cloned()
+    }
+
+    pub(crate) fn list(&self) -> Vec<InstallJob> {
+        self.jobs
+            .lock()
+            .expect("install jobs poisoned")
+            .iter()
+            .map(|job| job.borrow().clone())
+            .collect()
+    }
+
+    fn keep(&self, job: watch::Receiver<InstallJob>) {
+        let mut jobs = self.jobs.lock().expect("install jobs poisoned");
+        jobs.push_back(job);
+        while jobs.len() > JOBS_KEPT {
+            let Some(oldest) = jobs
+                .iter()
+                .position(|job| job.borrow().status.is_finished())
+            else {
+                break;
+            };
+            jobs.remove(oldest);
+        }
+    }
+}
+
+fn apply_progress(job: &mut InstallJob, progress: InstallProgress) {
+    match progress {
+        InstallProgress::Downloading {
+            url,
+            downloaded,
+            total,
+        } => {
+            job.progress = Some(InstallJobProgress {
+                phase: InstallJobPhase::Downloading,
+                url: Some(url.to_string()),
+                downloaded_bytes: downloaded,
+                total_bytes: total,
+            });
+        }
+        // Keeps the download's byte counts so a progress bar stays full.
+        InstallProgress::Extracting => {
+            job.progress
+                .get_or_insert(InstallJobProgress {
+                    phase: InstallJobPhase::Extracting,
+                    url: None,
+                    downloaded_bytes: 0,
+                    total_bytes: None,
+                })
+                .phase = InstallJobPhase::Extracting;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn installs_of_one_agent_run_in_turn() {
+        let jobs = InstallJobs::default();
+        let (gate, opened) = std::sync::mpsc::channel::<()>();
+        let (first, first_done) = jobs.start(AgentId::Claude, None, move |sink| {
+            sink.report(InstallProgress::Downloading {
+                url: reqwest::Url::parse("https://example.com/claude").expect("url"),
+                downloaded: 10,
+                total: Some(10),
+            });
+            sink.report(InstallProgress::Extracting);
+            opened.recv().ok();
+            Ok(InstallResult {
+                path: PathBuf::from("claude"),
+                version: Some("1.0.0".to_string()),
+                metadata: None,
+            })
+        });
+        let (second, second_done) = jobs.start(AgentId::Claude, None, |_| {
+            Err(SandboxError::InstallFailed {
+                agent: "claude".to_string(),
+                stderr: None,
+            })
+        });
+        assert_ne!(first.id, second.id);
+
+        let mut following = jobs.subscribe(&first.id).expect("first job");
+        following
+            .wait_for(|job| {
+                job.progress.as_ref().map(|p| p.phase) == Some(InstallJobPhase::Extracting)
+            })
+            .await
+            .expect("progress");
+        let queued = jobs.subscribe(&second.id).expect("second job");
+        assert_eq!(queued.borrow().status, InstallJobStatus::Queued);
+
+        gate.send(()).expect("open gate");
+        first_done.await.expect("join").expect("first install");
+        assert!(second_done.await.expect("join").is_err());
+        let listed = jobs.list();
+        assert_eq!(listed[0].status, InstallJobStatus::Succeeded);
+        assert_eq!(listed[0].installed_version.as_deref(), Some("1.0.0"));
+        let progress = listed[0].progress.as_ref().expect("progress");
+        assert_eq!(progress.phase, InstallJobPhase::Extracting);
+        assert_eq!(progress.total_bytes, Some(10));
+        assert_eq!(listed[1].status, InstallJobStatus::Failed);
+        assert!(listed[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn session_starts_hold_off_uninstalls() {
+        let jobs = Arc::new(InstallJobs::default());
+        let starting = jobs.shared(AgentId::Codex).await;
+        let other = jobs.shared(AgentId::Codex).await;
+        let uninstall = tokio::spawn({
+            let jobs = jobs.clone();
+            async move {
+                let _uninstall = jobs.exclusive(AgentId::Codex).await;
+            }
+        });
+        tokio::task::yield_now().await;
+        assert!(!uninstall.is_finished());
+        drop(starting);
+        drop(other);
+        uninstall.await.expect("uninstall");
+    }
+}
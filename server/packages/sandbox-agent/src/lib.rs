@@ -1,11 +1,27 @@
 //! Sandbox agent core utilities.
 
+pub mod agent_adapter;
 mod agent_server_logs;
 pub mod cli;
 pub mod credentials;
+pub mod custom_agents;
 pub mod daemon;
+mod debug_log;
+mod diagnostics;
+mod egress_proxy;
+mod install_jobs;
+mod logs;
+mod metrics;
+pub mod mock_scenarios;
 pub mod opencode_compat;
+mod otel;
+pub mod rate_limit;
+mod redaction;
+pub mod replay;
 pub mod router;
 pub mod server_logs;
+mod session_home;
+mod session_tokens;
 pub mod telemetry;
+mod transport;
 pub mod ui;
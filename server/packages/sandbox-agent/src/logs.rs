@@ -0,0 +1,307 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+pub(crate) const DEFAULT_TAIL_LINES: usize = 200;
+pub(crate) const MAX_TAIL_LINES: usize = 10_000;
+
+/// Rotated log files in one directory, recognized by their file name prefix.
+#[derive(Debug, Clone)]
+pub(crate) struct LogFiles {
+    dir: PathBuf,
+    prefix: String,
+}
+
+impl LogFiles {
+    pub(crate) fn new(dir: PathBuf, prefix: impl Into<String>) -> Self {
+        Self {
+            dir,
+            prefix: prefix.into(),
+        }
+    }
+
+    /// Log files, oldest first by modification time.
+    fn list(&self) -> Vec<(PathBuf, SystemTime)> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut files: Vec<(PathBuf, SystemTime)> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(&self.prefix)
+            })
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                metadata
+                    .is_file()
+                    .then(|| Some((entry.path(), metadata.modified().ok()?)))?
+            })
+            .collect();
+        files.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        files
+    }
+
+    /// Follower positioned at the current end of the newest file.
+    pub(crate) fn follow(&self) -> LogFollower {
+        let path = self.list().pop().map(|(path, _)| path);
+        let offset = path
+            .as_ref()
+            .and_then(|path| std::fs::metadata(path).ok())
+            .map_or(0, |metadata| metadata.len());
+        LogFollower {
+            files: self.clone(),
+            path,
+            offset,
+            partial: Vec::new(),
+        }
+    }
+
+    /// The last `tail` lines written at or after `since`, stopping where `follower`
+    /// starts so a follow stream neither repeats nor skips lines. Returns the lines and
+    /// whether older matching lines were left out.
+    pub(crate) fn read(
+        &self,
+        tail: usize,
+        since: Option<OffsetDateTime>,
+        follower: Option<&LogFollower>,
+    ) -> (Vec<String>, bool) {
+        let mut lines = VecDeque::with_capacity(tail.min(MAX_TAIL_LINES));
+        let mut truncated = false;
+        for (path, modified) in self.list() {
+            if since.is_some_and(|since| OffsetDateTime::from(modified) < since) {
+                continue;
+            }
+            let limit = follower
+                .filter(|follower| follower.path.as_deref() == Some(path.as_path()))
+                .map(|follower| follower.offset);
+            let Ok(file) = File::open(&path) else {
+                continue;
+            };
+            let reader: Box<dyn Read> = match limit {
+                Some(limit) => Box::new(file.take(limit)),
+                None => Box::new(file),
+            };
+            // Lines without their own timestamp belong to the last stamped line above them.
+            let mut include = true;
+            for line in BufReader::new(reader).split(b'\n').map_while(Result::ok) {
+                let line = decode_line(&line);
+                if let (Some(since), Some(at)) = (since, line_timestamp(&line)) {
+                    include = at >= since;
+                }
+                if !include {
+                    continue;
+                }
+                if lines.len() == tail {
+                    lines.pop_front();
+                    truncated = true;
+                }
+                if tail > 0 {
+                    lines.push_back(line);
+                }
+            }
+        }
+        (lines.into(), truncated)
+    }
+}
+
+/// Reads lines appended to the newest log file, moving to a new file when the log rotates.
+#[derive(Debug)]
+pub(crate) struct LogFollower {
+    files: LogFiles,
+    path: Option<PathBuf>,
+    offset: u64,
+    partial: Vec<u8>,
+}
+
+impl LogFollower {
+    /// Complete lines written since the last poll.
+    pub(crate) fn poll(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(path) = self.path.clone() {
+            self.read_new(&path, &mut lines);
+        }
+        let newest = self.files.list().pop().map(|(path, _)| path);
+        if newest.is_some() && newest != self.path {
+            // Flush what is left of the previous file before switching.
+            if !self.partial.is_empty() {
+                lines.push(decode_line(&self.partial));
+                self.partial.clear();
+            }
+            self.path = newest.clone();
+            self.offset = 0;
+            if let Some(path) = newest {
+                self.read_new(&path, &mut lines);
+            }
+        }
+        lines
+    }
+
+    fn read_new(&mut self, path: &Path, lines: &mut Vec<String>) {
+        let Ok(mut file) = File::open(path) else {
+            return;
+        };
+        let Ok(len) = file.metadata().map(|metadata| metadata.len()) else {
+            return;
+        };
+        if len < self.offset {
+            // Truncated in place; start over.
+            self.offset = 0;
+            self.partial.clear();
+        }
+        if len == self.offset || file.seek(SeekFrom::Start(self.offset)).is_err() {
+            return;
+        }
+        let mut buf = Vec::new();
+        if file.take(len - self.offset).read_to_end(&mut buf).is_err() {
+            return;
+        }
+        self.offset += buf.len() as u64;
+        self.partial.extend_from_slice(&buf);
+        while let Some(end) = self.partial.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            lines.push(decode_line(&line[..end]));
+        }
+    }
+}
+
+/// Carriage returns are dropped: CRLF endings are noise here and cannot be sent over SSE.
+fn decode_line(line: &[u8]) -> String {
+    String::from_utf8_lossy(line).replace('\r', "")
+}
+
+/// Time a log line was written: a leading RFC 3339 timestamp as written to agent server
+/// logs, or the `ts=` field of the daemon's logfmt lines.
+pub(crate) fn line_timestamp(line: &str) -> Option<OffsetDateTime> {
+    let first = line.split_whitespace().next()?;
+    if let Ok(at) = OffsetDateTime::parse(first, &Rfc3339) {
+        return Some(at);
+    }
+    let ts = line
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("ts="))?;
+    OffsetDateTime::parse(ts.trim_matches('"'), &Rfc3339).ok()
+}
+
+/// Removes the timestamp `TimestampedLines` prepends.
+pub(crate) fn strip_timestamp(line: &str) -> &str {
+    match line.split_once(' ') {
+        Some((first, rest)) if OffsetDateTime::parse(first, &Rfc3339).is_ok() => rest,
+        _ => line,
+    }
+}
+
+/// Writer that prefixes every line with the UTC time it was written.
+pub(crate) struct TimestampedLines<W> {
+    inner: W,
+    at_line_start: bool,
+}
+
+impl<W: Write> TimestampedLines<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self {
+            inner,
+            at_line_start: true,
+        }
+    }
+}
+
+impl<W: Write> Write for TimestampedLines<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while !rest.is_empty() {
+            if self.at_line_start {
+                let now = OffsetDateTime::now_utc()
+                    .format(&Rfc3339)
+                    .unwrap_or_else(|_| "1970-01-01T00:00:00Z".to_string());
+                self.inner.write_all(now.as_bytes())?;
+                self.inner.write_all(b" ")?;
+                self.at_line_start = false;
+            }
+            let end = rest
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .map_or(rest.len(), |newline| newline + 1);
+            self.inner.write_all(&rest[..end])?;
+            self.at_line_start = rest[end - 1] == b'\n';
+            rest = &rest[end..];
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tails_filters_by_time_and_follows_rotation() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let files = LogFiles::new(dir.path().to_path_buf(), "codex-");
+        std::fs::write(
+            dir.path().join("codex-2026-01-01.log"),
+            "2026-01-01T10:00:00Z old\n2026-01-01T12:00:00Z kept\r\ncontinued\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("other.log"), "ignored\n").unwrap();
+
+        let (lines, truncated) = files.read(10, None, None);
+        assert_eq!(lines.len(), 3);
+        assert!(!truncated);
+        let (lines, truncated) = files.read(1, None, None);
+        assert_eq!(lines, ["continued"]);
+        assert!(truncated);
+        let since = OffsetDateTime::parse("2026-01-01T11:00:00Z", &Rfc3339).unwrap();
+        let (lines, _) = files.read(10, Some(since), None);
+        assert_eq!(lines, ["2026-01-01T12:00:00Z kept", "continued"]);
+
+        let mut follower = files.follow();
+        assert!(follower.poll().is_empty());
+        let mut writer = TimestampedLines::new(
+            std::fs::OpenOptions::new()
+                .append(true)
+                .open(dir.path().join("codex-2026-01-01.log"))
+                .unwrap(),
+        );
+        writer.write_all(b"first\nsec").unwrap();
+        let polled = follower.poll();
+        assert_eq!(polled.len(), 1);
+        assert_eq!(strip_timestamp(&polled[0]), "first");
+        assert!(line_timestamp(&polled[0]).is_some());
+        writer.write_all(b"ond\n").unwrap();
+        assert_eq!(
+            follower
+                .poll()
+                .iter()
+                .map(|line| strip_timestamp(line))
+                .collect::<Vec<_>>(),
+            ["second"]
+        );
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(dir.path().join("codex-2026-01-02.log"), "next day\n").unwrap();
+        assert_eq!(follower.poll(), ["next day"]);
+    }
+
+    #[test]
+    fn reads_logfmt_timestamps() {
+        let line = "ts=2026-03-04T05:06:07.123456Z level=info target=sandbox_agent message=hi";
+        assert_eq!(
+            line_timestamp(line),
+            Some(OffsetDateTime::parse("2026-03-04T05:06:07.123456Z", &Rfc3339).unwrap())
+        );
+        assert_eq!(line_timestamp("plain stderr"), None);
+        assert_eq!(strip_timestamp("plain stderr"), "plain stderr");
+    }
+}
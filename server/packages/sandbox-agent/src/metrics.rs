@@ -0,0 +1,161 @@
+use std::sync::OnceLock;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+/// Buckets for HTTP handler latency, in seconds.
+const HTTP_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// Buckets for agent turns and permission waits, which take seconds to minutes.
+const WAIT_BUCKETS: &[f64] = &[
+    0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0,
+];
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Process-wide metrics registry rendered by `GET /metrics`.
+pub(crate) fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+#[derive(Debug)]
+pub(crate) struct Metrics {
+    registry: Registry,
+    pub(crate) sessions_created: IntCounterVec,
+    pub(crate) sessions_ended: IntCounterVec,
+    pub(crate) turns: IntCounterVec,
+    pub(crate) turn_duration: HistogramVec,
+    pub(crate) events: IntCounterVec,
+    pub(crate) permission_wait: HistogramVec,
+    pub(crate) http_requests: HistogramVec,
+    pub(crate) broadcast_lagged: IntCounterVec,
+    pub(crate) agent_server_restarts: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).expect("valid counter");
+            registry
+                .register(Box::new(counter.clone()))
+                .expect("metric registered once");
+            counter
+        };
+        let histogram = |name: &str, help: &str, labels: &[&str], buckets: &[f64]| {
+            let opts = HistogramOpts::new(name, help).buckets(buckets.to_vec());
+            let histogram = HistogramVec::new(opts, labels).expect("valid histogram");
+            registry
+                .register(Box::new(histogram.clone()))
+                .expect("metric registered once");
+            histogram
+        };
+        Self {
+            sessions_created: counter(
+                "sandbox_agent_sessions_created_total",
+                "Sessions created.",
+                &["agent"],
+            ),
+            sessions_ended: counter(
+                "sandbox_agent_sessions_ended_total",
+                "Sessions ended, by end reason.",
+                &["agent", "reason"],
+            ),
+            turns: counter(
+                "sandbox_agent_turns_total",
+                "Turns started by a message.",
+                &["agent"],
+            ),
+            turn_duration: histogram(
+                "sandbox_agent_turn_duration_seconds",
+                "Time from sending a message until the turn ends.",
+                &["agent"],
+                WAIT_BUCKETS,
+            ),
+            events: counter(
+                "sandbox_agent_events_total",
+                "Universal events emitted, by event type.",
+                &["agent", "type"],
+            ),
+            permission_wait: histogram(
+                "sandbox_agent_permission_wait_seconds",
+                "Time from a permission request until it is resolved.",
+                &["agent"],
+                WAIT_BUCKETS,
+            ),
+            http_requests: histogram(
+                "sandbox_agent_http_request_duration_seconds",
+                "HTTP request latency, by matched route.",
+                &["method", "route", "status"],
+                HTTP_BUCKETS,
+            ),
+            broadcast_lagged: counter(
+                "sandbox_agent_broadcast_lagged_events_total",
+                "Events dropped because a stream subscriber fell behind.",
+                &["stream"],
+            ),
+            agent_server_restarts: counter(
+                "sandbox_agent_agent_server_restarts_total",
+                "Restarts of shared agent server processes.",
+                &["agent"],
+            ),
+            registry,
+        }
+    }
+
+    /// Renders every family in the Prometheus text format.
+    pub(crate) fn render(&self) -> String {
+        let mut out = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut out)
+            .expect("text encoding into memory");
+        String::from_utf8(out).expect("text format is UTF-8")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_prometheus_text_format() {
+        let metrics = Metrics::new();
+        metrics.sessions_created.with_label_values(&["mock"]).inc();
+        metrics.sessions_created.with_label_values(&["mock"]).inc();
+        metrics
+            .broadcast_lagged
+            .with_label_values(&["sse"])
+            .inc_by(7);
+        metrics
+            .http_requests
+            .with_label_values(&["GET", "/v1/sessions/:session_id", "200"])
+            .observe(0.03);
+        metrics
+            .events
+            .with_label_values(&["mock", "say \"hi\"\n"])
+            .inc();
+        metrics
+            .agent_server_restarts
+            .with_label_values(&["codex"])
+            .inc_by(2);
+
+        let text = metrics.render();
+        assert!(text.contains("# TYPE sandbox_agent_sessions_created_total counter"));
+        assert!(text.contains("sandbox_agent_sessions_created_total{agent=\"mock\"} 2\n"));
+        assert!(text.contains("sandbox_agent_broadcast_lagged_events_total{stream=\"sse\"} 7\n"));
+        assert!(text.contains(
+            "sandbox_agent_http_request_duration_seconds_bucket{method=\"GET\",route=\"/v1/sessions/:session_id\",status=\"200\",le=\"0.025\"} 0\n"
+        ));
+        assert!(text.contains(
+            "sandbox_agent_http_request_duration_seconds_bucket{method=\"GET\",route=\"/v1/sessions/:session_id\",status=\"200\",le=\"0.05\"} 1\n"
+        ));
+        assert!(text.contains(
+            "sandbox_agent_http_request_duration_seconds_count{method=\"GET\",route=\"/v1/sessions/:session_id\",status=\"200\"} 1\n"
+        ));
+        assert!(text.contains("type=\"say \\\"hi\\\"\\n\""));
+        assert!(text.contains("# TYPE sandbox_agent_agent_server_restarts_total counter"));
+        assert!(text.contains("sandbox_agent_agent_server_restarts_total{agent=\"codex\"} 2\n"));
+    }
+}
@@ -0,0 +1,262 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Scripted replies for the mock agent, loaded from JSON or YAML files. A prompt runs the
+/// first scenario whose pattern matches it; prompts no scenario matches fall through
+/// to the built-in mock commands.
+#[derive(Debug, Clone, Default)]
+pub struct MockScenarios {
+    scenarios: Vec<MockScenario>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockScenario {
+    pub name: String,
+    pattern: Regex,
+    pub steps: Vec<MockStep>,
+}
+
+/// One thing the mock agent does, in order. Events are spaced by the mock agent's
+/// usual delay; `delay` steps add to it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum MockStep {
+    /// Assistant message, streamed as `deltas` when given. `text` defaults to the
+    /// joined deltas.
+    Message {
+        #[serde(default)]
+        text: String,
+        #[serde(default)]
+        deltas: Vec<String>,
+    },
+    /// Tool call item followed by its result unless `output` is unset.
+    ToolCall {
+        name: String,
+        #[serde(default)]
+        arguments: Value,
+        #[serde(default)]
+        output: Option<String>,
+        #[serde(default)]
+        failed: bool,
+    },
+    /// Pending permission request, resolved with the reply endpoint.
+    Permission {
+        action: String,
+        #[serde(default)]
+        metadata: Option<Value>,
+    },
+    /// Pending question, resolved with the reply or reject endpoint.
+    Question {
+        prompt: String,
+        #[serde(default)]
+        options: Vec<String>,
+    },
+    Delay {
+        ms: u64,
+    },
+    /// `error` event; the turn carries on.
+    Error {
+        message: String,
+        #[serde(default)]
+        code: Option<String>,
+    },
+    /// Ends the session with an error, as if the agent process died.
+    Crash {
+        #[serde(default)]
+        message: Option<String>,
+        #[serde(default)]
+        exit_code: Option<i32>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+struct ScenarioFile {
+    scenarios: Vec<ScenarioSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScenarioSpec {
+    #[serde(default)]
+    name: Option<String>,
+    /// Regex searched for in the trimmed prompt.
+    pattern: String,
+    steps: Vec<MockStep>,
+}
+
+impl MockScenarios {
+    /// Loads a scenario file, or every `*.json`, `*.yaml` and `*.yml` file in a
+    /// directory in name order. YAML is chosen by extension; anything else is JSON.
+    pub fn from_path(path: &Path) -> Result<Self, io::Error> {
+        let mut files = if path.is_dir() {
+            fs::read_dir(path)?
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|ext| ext == "json" || ext == "yaml" || ext == "yml")
+                })
+                .collect::<Vec<_>>()
+        } else {
+            vec![path.to_path_buf()]
+        };
+        files.sort();
+        let mut scenarios = Self::default();
+        for file in files {
+            let text = fs::read_to_string(&file)?;
+            let source = file.file_stem().unwrap_or_default().to_string_lossy();
+            let is_yaml = file
+                .extension()
+                .is_some_and(|ext| ext == "yaml" || ext == "yml");
+            let parsed = if is_yaml {
+                Self::parse_yaml(&text, &source)
+            } else {
+                Self::parse(&text, &source)
+            };
+            let parsed = parsed
+                .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", file.display())))?;
+            scenarios.scenarios.extend(parsed.scenarios);
+        }
+        Ok(scenarios)
+    }
+
+    /// Parses one JSON scenario file. Unnamed scenarios are called `{source}#{index}`.
+    pub fn parse(text: &str, source: &str) -> Result<Self, io::Error> {
+        let file: ScenarioFile = serde_json::from_str(text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Self::from_file(file, source)
+    }
+
+    /// Parses one YAML scenario file, named as [`parse`](Self::parse) does.
+    pub fn parse_yaml(text: &str, source: &str) -> Result<Self, io::Error> {
+        let file: ScenarioFile = serde_yaml::from_str(text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Self::from_file(file, source)
+    }
+
+    fn from_file(file: ScenarioFile, source: &str) -> Result<Self, io::Error> {
+        let scenarios = file
+            .scenarios
+            .into_iter()
+            .enumerate()
+            .map(|(index, spec)| {
+                let name = spec.name.unwrap_or_else(|| format!("{source}#{index}"));
+                let pattern = Regex::new(&spec.pattern).map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("scenario {name}: invalid pattern: {err}"),
+                    )
+                })?;
+                Ok(MockScenario {
+                    name,
+                    pattern,
+                    steps: spec.steps,
+                })
+            })
+            .collect::<Result<_, io::Error>>()?;
+        Ok(Self { scenarios })
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenarios.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenarios.is_empty()
+    }
+
+    pub(crate) fn find(&self, prompt: &str) -> Option<&MockScenario> {
+        self.scenarios
+            .iter()
+            .find(|scenario| scenario.pattern.is_match(prompt))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_in_file_order_and_names_unnamed_scenarios() {
+        let scenarios = MockScenarios::parse(
+            r#"{"scenarios": [
+                {"pattern": "^crash", "steps": [{"type": "crash", "exitCode": 137}]},
+                {"name": "catch-all", "pattern": ".", "steps": [
+                    {"type": "message", "deltas": ["a", "b"]},
+                    {"type": "delay", "ms": 5}
+                ]}
+            ]}"#,
+            "edge",
+        )
+        .expect("parse");
+        assert_eq!(scenarios.len(), 2);
+        let crash = scenarios.find("crash now").expect("crash scenario");
+        assert_eq!(crash.name, "edge#0");
+        assert_eq!(
+            crash.steps,
+            [MockStep::Crash {
+                message: None,
+                exit_code: Some(137)
+            }]
+        );
+        assert_eq!(scenarios.find("hello").expect("fallback").name, "catch-all");
+        assert!(scenarios.find("").is_none());
+
+        let err = MockScenarios::parse(
+            r#"{"scenarios": [{"name": "bad", "pattern": "(", "steps": []}]}"#,
+            "edge",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("scenario bad"), "{err}");
+    }
+
+    #[test]
+    fn loads_yaml_and_json_files_by_extension() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        fs::write(
+            dir.path().join("a.yaml"),
+            "scenarios:\n  - pattern: ^ask\n    steps:\n      - type: question\n        prompt: Which?\n        options: [a, b]\n      - type: toolCall\n        name: ls\n        output: done\n",
+        )
+        .expect("write yaml");
+        fs::write(
+            dir.path().join("b.json"),
+            r#"{"scenarios": [{"name": "json", "pattern": ".", "steps": []}]}"#,
+        )
+        .expect("write json");
+        fs::write(dir.path().join("c.yml"), "scenarios: [").expect("write yml");
+        fs::write(dir.path().join("notes.txt"), "not a scenario").expect("write txt");
+
+        let err = MockScenarios::from_path(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("c.yml"), "{err}");
+        fs::remove_file(dir.path().join("c.yml")).expect("remove");
+
+        let scenarios = MockScenarios::from_path(dir.path()).expect("load");
+        assert_eq!(scenarios.len(), 2);
+        let yaml = scenarios.find("ask me").expect("yaml scenario");
+        assert_eq!(yaml.name, "a#0");
+        assert_eq!(
+            yaml.steps,
+            [
+                MockStep::Question {
+                    prompt: "Which?".to_string(),
+                    options: vec!["a".to_string(), "b".to_string()],
+                },
+                MockStep::ToolCall {
+                    name: "ls".to_string(),
+                    arguments: Value::Null,
+                    output: Some("done".to_string()),
+                    failed: false,
+                },
+            ]
+        );
+        assert_eq!(scenarios.find("hello").expect("json scenario").name, "json");
+    }
+}
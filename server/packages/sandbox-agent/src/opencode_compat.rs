@@ -27,6 +27,8 @@ use tokio::time::interval;
 use tracing::{info, warn};
 use utoipa::{IntoParams, OpenApi, ToSchema};
 
+use crate::agent_adapter::adapter;
+use crate::metrics::metrics;
 use crate::router::{
     is_question_tool_action, AgentModelInfo, AppState, CreateSessionRequest, PermissionReply,
     SessionInfo,
@@ -526,10 +528,14 @@ async fn ensure_backing_session(
         title,
         mcp: None,
         skills: None,
+        limits: None,
+        isolate_home: None,
+        egress: None,
+        debug_log: None,
     };
     let manager = state.inner.session_manager();
     match manager
-        .create_session(session_id.to_string(), request.clone())
+        .create_session(session_id.to_string(), request.clone(), None)
         .await
     {
         Ok(_) => Ok(()),
@@ -542,7 +548,7 @@ async fn ensure_backing_session(
             if should_recreate {
                 manager.delete_session(session_id).await?;
                 match manager
-                    .create_session(session_id.to_string(), request.clone())
+                    .create_session(session_id.to_string(), request.clone(), None)
                     .await
                 {
                     Ok(_) => Ok(()),
@@ -559,7 +565,7 @@ async fn ensure_backing_session(
                                     "backing session vanished while applying overrides; retrying create_session"
                                 );
                                 match manager
-                                    .create_session(session_id.to_string(), request.clone())
+                                    .create_session(session_id.to_string(), request.clone(), None)
                                     .await
                                 {
                                     Ok(_) | Err(SandboxError::SessionAlreadyExists { .. }) => {
@@ -586,7 +592,7 @@ async fn ensure_backing_session(
                             "backing session missing while setting overrides; retrying create_session"
                         );
                         match manager
-                            .create_session(session_id.to_string(), request.clone())
+                            .create_session(session_id.to_string(), request.clone(), None)
                             .await
                         {
                             Ok(_) | Err(SandboxError::SessionAlreadyExists { .. }) => Ok(()),
@@ -639,7 +645,13 @@ async fn ensure_session_stream(state: Arc<OpenCodeAppState>, session_id: String)
                 Ok(event) => {
                     apply_universal_event(state.clone(), event).await;
                 }
-                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    metrics()
+                        .broadcast_lagged
+                        .with_label_values(&["opencode_session"])
+                        .inc_by(skipped);
+                    continue;
+                }
                 Err(broadcast::error::RecvError::Closed) => break,
             }
         }
@@ -1017,6 +1029,7 @@ async fn build_opencode_model_cache(state: &OpenCodeAppState) -> OpenCodeModelCa
                 }
             }
             Some(AgentId::Mock) => true,
+            Some(agent) => adapter(*agent).credentials_available(&credentials),
             None => false,
         };
         if is_connected {
@@ -1159,7 +1172,10 @@ fn agent_display_name(agent: AgentId) -> &'static str {
         AgentId::Codex => "Codex",
         AgentId::Opencode => "OpenCode",
         AgentId::Amp => "Amp",
+        AgentId::Gemini => "Gemini CLI",
+        AgentId::Acp => "ACP",
         AgentId::Mock => "Mock",
+        AgentId::Custom(_) => agent.as_str(),
     }
 }
 
@@ -1358,7 +1374,7 @@ async fn proxy_native_opencode(
     let base_url = if let Some(base_url) = state.opencode.proxy_base_url() {
         base_url.to_string()
     } else {
-        match state.inner.ensure_opencode_server().await {
+        match state.inner.ensure_opencode_server(AgentId::Opencode).await {
             Ok(base_url) => base_url,
             Err(err) => {
                 warn!(path, ?err, "failed to lazily start native opencode server");
@@ -3365,6 +3381,7 @@ async fn oc_event_subscribe(
                                 }
                                 Err(broadcast::error::RecvError::Lagged(skipped)) => {
                                     warn!(skipped, "opencode event stream lagged");
+                                    metrics().broadcast_lagged.with_label_values(&["opencode_events"]).inc_by(skipped);
                                     return Some((Ok(Event::default().comment("lagged")), (rx, ticker, replay, replay_cursor)));
                                 }
                                 Err(broadcast::error::RecvError::Closed) => return None,
@@ -3463,6 +3480,7 @@ async fn oc_global_event(
                                 }
                                 Err(broadcast::error::RecvError::Lagged(skipped)) => {
                                     warn!(skipped, "opencode global event stream lagged");
+                                    metrics().broadcast_lagged.with_label_values(&["opencode_events"]).inc_by(skipped);
                                     return Some((Ok(Event::default().comment("lagged")), (rx, ticker, replay, replay_cursor)));
                                 }
                                 Err(broadcast::error::RecvError::Closed) => return None,
@@ -4266,7 +4284,7 @@ async fn oc_session_message_create(
         if let Err(err) = state
             .inner
             .session_manager()
-            .send_message(session_id.clone(), prompt_text, Vec::new())
+            .send_message(session_id.clone(), prompt_text, Vec::new(), None)
             .await
         {
             let mut should_emit_idle = false;
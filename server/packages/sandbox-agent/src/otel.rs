@@ -0,0 +1,165 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing::{Metadata, Subscriber};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+const ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+const TRACES_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT";
+const SERVICE_NAME_ENV: &str = "OTEL_SERVICE_NAME";
+const DEFAULT_SERVICE_NAME: &str = "sandbox-agent";
+/// Only spans from this crate are exported; dependencies' spans are ignored.
+const TARGET_PREFIX: &str = "sandbox_agent";
+
+/// Where to send spans, resolved from `--otlp-endpoint` and the standard `OTEL_*`
+/// environment variables.
+#[derive(Debug, Clone)]
+pub(crate) struct OtlpConfig {
+    /// Traces URL from `--otlp-endpoint`; without it the exporter reads the
+    /// environment itself.
+    traces_url: Option<String>,
+    service_name: String,
+}
+
+impl OtlpConfig {
+    /// Returns `None` when neither the flag nor the environment names an endpoint.
+    pub(crate) fn resolve(endpoint: Option<&str>) -> Option<Self> {
+        let from_env = [TRACES_ENDPOINT_ENV, ENDPOINT_ENV]
+            .iter()
+            .any(|name| std::env::var(name).is_ok_and(|value| !value.is_empty()));
+        if endpoint.is_none() && !from_env {
+            return None;
+        }
+        let service_name = std::env::var(SERVICE_NAME_ENV)
+            .ok()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+        Some(Self {
+            traces_url: endpoint.map(traces_url),
+            service_name,
+        })
+    }
+}
+
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{endpoint}/v1/traces")
+    }
+}
+
+/// `tracing` layer that exports this crate's spans over OTLP/HTTP, batched on a
+/// background thread.
+///
+/// Span fields become attributes. `otel.kind` (`server`, `client`, `internal`) and
+/// `otel.status_code` (`ok`, `error`) set the span kind and status instead.
+pub(crate) fn layer<S>(config: OtlpConfig) -> Result<impl Layer<S>, ExporterBuildError>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let mut exporter = SpanExporter::builder().with_http();
+    if let Some(url) = config.traces_url {
+        exporter = exporter.with_endpoint(url);
+    }
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter.build()?)
+        .with_resource(resource(config.service_name))
+        .build();
+    Ok(layer_for(&provider))
+}
+
+fn resource(service_name: String) -> Resource {
+    Resource::builder_empty()
+        .with_service_name(service_name)
+        .with_attribute(opentelemetry::KeyValue::new(
+            "service.version",
+            env!("CARGO_PKG_VERSION"),
+        ))
+        .build()
+}
+
+fn layer_for<S>(provider: &SdkTracerProvider) -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer(TARGET_PREFIX))
+        .with_filter(filter_fn(exported))
+}
+
+/// Spans only: log events are written to stderr, not attached to spans.
+fn exported(metadata: &Metadata<'_>) -> bool {
+    metadata.is_span() && metadata.target().starts_with(TARGET_PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::{SpanKind, Status};
+    use opentelemetry::Value;
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn exports_nested_spans_with_attributes() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry().with(layer_for(&provider));
+        tracing::subscriber::with_default(subscriber, || {
+            let session = tracing::info_span!("session", agent = "mock", otel.kind = "server");
+            let turn = tracing::info_span!(
+                parent: &session,
+                "turn",
+                gen_ai.usage.input_tokens = tracing::field::Empty,
+                otel.status_code = tracing::field::Empty
+            );
+            turn.record("gen_ai.usage.input_tokens", 42i64);
+            turn.record("otel.status_code", "error");
+            drop(turn);
+            drop(session);
+        });
+
+        let spans = exporter.get_finished_spans().expect("spans");
+        let [turn, session] = spans.as_slice() else {
+            panic!("expected two spans, got {spans:?}");
+        };
+        assert_eq!(turn.name, "turn");
+        assert_eq!(
+            turn.span_context.trace_id(),
+            session.span_context.trace_id()
+        );
+        assert_eq!(turn.parent_span_id, session.span_context.span_id());
+        assert_eq!(session.span_kind, SpanKind::Server);
+        assert!(matches!(turn.status, Status::Error { .. }));
+        let attribute = |span: &opentelemetry_sdk::trace::SpanData, key: &str| {
+            span.attributes
+                .iter()
+                .find(|attribute| attribute.key.as_str() == key)
+                .map(|attribute| attribute.value.clone())
+        };
+        assert_eq!(
+            attribute(turn, "gen_ai.usage.input_tokens"),
+            Some(Value::I64(42))
+        );
+        assert_eq!(attribute(session, "agent"), Some(Value::from("mock")));
+    }
+
+    #[test]
+    fn resolves_traces_url() {
+        assert_eq!(
+            traces_url("http://localhost:4318/"),
+            "http://localhost:4318/v1/traces"
+        );
+        assert_eq!(
+            traces_url("http://collector/v1/traces"),
+            "http://collector/v1/traces"
+        );
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use sandbox_agent_error::SandboxError;
+use serde::{Deserialize, Serialize};
+
+/// Suggested wait when a session or turn cap is hit; slots free up as work finishes.
+const CONCURRENCY_RETRY_AFTER_SECS: u64 = 5;
+
+/// How often idle per-caller buckets are swept.
+const BUCKET_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Request rate and concurrency limits for one token or the whole server.
+/// Unset fields are unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimits {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_second: Option<f64>,
+    /// Requests allowed in a burst above the steady rate (defaults to one second's worth).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub burst: Option<u32>,
+    /// Sessions that have not ended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_sessions: Option<usize>,
+    /// Sessions with a turn in progress.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_active_turns: Option<usize>,
+}
+
+impl RateLimits {
+    pub fn is_empty(&self) -> bool {
+        self.requests_per_second.is_none()
+            && self.max_sessions.is_none()
+            && self.max_active_turns.is_none()
+    }
+}
+
+/// Server-wide limits plus the default applied to each API token.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RateLimitConfig {
+    #[serde(default)]
+    pub global: RateLimits,
+    /// Applies to every token without its own `limits`.
+    #[serde(default)]
+    pub per_token: RateLimits,
+}
+
+impl RateLimitConfig {
+    pub fn from_file(path: &Path) -> Result<Self, io::Error> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.global.is_empty() || !self.per_token.is_empty()
+    }
+}
+
+/// Token that authenticated a request, attached to the request by the auth middleware.
+/// `key` is a stable identity, so every token minted for one session shares it.
+#[derive(Debug, Clone)]
+pub(crate) struct Caller {
+    pub(crate) key: String,
+    pub(crate) limits: RateLimits,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Cap {
+    Sessions,
+    ActiveTurns,
+}
+
+impl Cap {
+    fn describe(self) -> &'static str {
+        match self {
+            Cap::Sessions => "active sessions",
+            Cap::ActiveTurns => "active turns",
+        }
+    }
+
+    fn limit(self, limits: &RateLimits) -> Option<usize> {
+        match self {
+            Cap::Sessions => limits.max_sessions,
+            Cap::ActiveTurns => limits.max_active_turns,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+    /// When the bucket is full again; past this it is no different from a new one.
+    full_at: Instant,
+}
+
+impl TokenBucket {
+    /// Takes one request from the bucket, or returns the seconds until one is available.
+    fn take(&mut self, rate: f64, burst: f64, now: Instant) -> Result<(), u64> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.updated = now;
+        let taken = self.tokens >= 1.0;
+        if taken {
+            self.tokens -= 1.0;
+        }
+        self.full_at = now + Duration::from_secs_f64((burst - self.tokens).max(0.0) / rate);
+        if taken {
+            return Ok(());
+        }
+        Err(((1.0 - self.tokens) / rate).ceil().max(1.0) as u64)
+    }
+}
+
+/// Enforces request rates in the HTTP middleware and session/turn caps in the
+/// session manager. Buckets are keyed by caller; the global bucket covers every caller.
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    config: RateLimitConfig,
+    global_bucket: Mutex<Option<TokenBucket>>,
+    buckets: Mutex<CallerBuckets>,
+    /// Sessions being created, by caller key, so parallel creates cannot overshoot a cap.
+    pending_sessions: Mutex<HashMap<Option<String>, usize>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.config.is_enabled()
+    }
+
+    /// Caller identity for a resolved token; tokens without their own limits get the default.
+    pub(crate) fn caller(&self, key: &str, limits: Option<&RateLimits>) -> Caller {
+        Caller {
+            key: key.to_string(),
+            limits: limits
+                .cloned()
+                .unwrap_or_else(|| self.config.per_token.clone()),
+        }
+    }
+
+    pub(crate) fn check_request(&self, caller: Option<&Caller>) -> Result<(), SandboxError> {
+        let now = Instant::now();
+        if let Some(caller) = caller {
+            if let Some((rate, burst)) = bucket_params(&caller.limits) {
+                let mut buckets = self.buckets.lock().expect("rate limit buckets");
+                buckets.sweep(now);
+                buckets
+                    .by_caller
+                    .entry(caller.key.clone())
+                    .or_insert_with(|| full_bucket(burst, now))
+                    .take(rate, burst, now)
+                    .map_err(|retry_after| {
+                        rate_limited("token request rate exceeded", retry_after)
+                    })?;
+            }
+        }
+        if let Some((rate, burst)) = bucket_params(&self.config.global) {
+            let mut bucket = self.global_bucket.lock().expect("rate limit bucket");
+            bucket
+                .get_or_insert_with(|| full_bucket(burst, now))
+                .take(rate, burst, now)
+                .map_err(|retry_after| rate_limited("server request rate exceeded", retry_after))?;
+        }
+        Ok(())
+    }
+
+    /// Checks a concurrency cap given the current totals, server-wide and for the caller.
+    pub(crate) fn check_cap(
+        &self,
+        cap: Cap,
+        caller: Option<&Caller>,
+        total: usize,
+        for_caller: usize,
+    ) -> Result<(), SandboxError> {
+        if let Some(caller) = caller {
+            if let Some(limit) = cap.limit(&caller.limits) {
+                if for_caller >= limit {
+                    return Err(rate_limited(
+                        &format!("token reached its limit of {limit} {}", cap.describe()),
+                        CONCURRENCY_RETRY_AFTER_SECS,
+                    ));
+                }
+            }
+        }
+        if let Some(limit) = cap.limit(&self.config.global) {
+            if total >= limit {
+                return Err(rate_limited(
+                    &format!("server reached its limit of {limit} {}", cap.describe()),
+                    CONCURRENCY_RETRY_AFTER_SECS,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the session cap, counting creations still in flight, and holds a slot
+    /// until the returned guard is dropped.
+    pub(crate) fn reserve_session(
+        &self,
+        caller: Option<&Caller>,
+        active: usize,
+        active_for_caller: usize,
+    ) -> Result<PendingSession<'_>, SandboxError> {
+        let key = caller.map(|caller| caller.key.clone());
+        let mut pending = self.pending_sessions.lock().expect("pending sessions");
+        let pending_total: usize = pending.values().sum();
+        let pending_for_caller = match &key {
+            Some(_) => pending.get(&key).copied().unwrap_or(0),
+            None => 0,
+        };
+        self.check_cap(
+            Cap::Sessions,
+            caller,
+            active + pending_total,
+            active_for_caller + pending_for_caller,
+        )?;
+        *pending.entry(key.clone()).or_insert(0) += 1;
+        Ok(PendingSession { limiter: self, key })
+    }
+}
+
+/// Per-caller buckets. Buckets that have refilled are dropped, since a fresh bucket
+/// behaves the same, so callers that stop sending requests do not accumulate.
+#[derive(Debug, Default)]
+struct CallerBuckets {
+    by_caller: HashMap<String, TokenBucket>,
+    swept: Option<Instant>,
+}
+
+impl CallerBuckets {
+    fn sweep(&mut self, now: Instant) {
+        if self
+            .swept
+            .is_some_and(|swept| now.saturating_duration_since(swept) < BUCKET_SWEEP_INTERVAL)
+        {
+            return;
+        }
+        self.swept = Some(now);
+        self.by_caller.retain(|_, bucket| bucket.full_at > now);
+    }
+}
+
+/// Session slot held while a session is being created.
+pub(crate) struct PendingSession<'a> {
+    limiter: &'a RateLimiter,
+    key: Option<String>,
+}
+
+impl Drop for PendingSession<'_> {
+    fn drop(&mut self) {
+        let mut pending = self
+            .limiter
+            .pending_sessions
+            .lock()
+            .expect("pending sessions");
+        if let Some(count) = pending.get_mut(&self.key) {
+            *count -= 1;
+            if *count == 0 {
+                pending.remove(&self.key);
+            }
+        }
+    }
+}
+
+fn bucket_params(limits: &RateLimits) -> Option<(f64, f64)> {
+    let rate = limits.requests_per_second.filter(|rate| *rate > 0.0)?;
+    let burst = limits.burst.map(f64::from).unwrap_or(rate).max(1.0);
+    Some((rate, burst))
+}
+
+fn full_bucket(burst: f64, now: Instant) -> TokenBucket {
+    TokenBucket {
+        tokens: burst,
+        updated: now,
+        full_at: now,
+    }
+}
+
+fn rate_limited(message: &str, retry_after_secs: u64) -> SandboxError {
+    SandboxError::RateLimited {
+        message: message.to_string(),
+        retry_after_secs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket_refills_at_the_configured_rate() {
+        let start = Instant::now();
+        let mut bucket = full_bucket(2.0, start);
+        assert!(bucket.take(1.0, 2.0, start).is_ok());
+        assert!(bucket.take(1.0, 2.0, start).is_ok());
+        assert_eq!(bucket.take(1.0, 2.0, start), Err(1));
+        assert!(bucket
+            .take(1.0, 2.0, start + Duration::from_millis(1100))
+            .is_ok());
+
+        let mut slow = full_bucket(1.0, start);
+        assert!(slow.take(0.1, 1.0, start).is_ok());
+        assert_eq!(slow.take(0.1, 1.0, start), Err(10));
+    }
+
+    #[test]
+    fn idle_caller_buckets_are_swept() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            per_token: RateLimits {
+                requests_per_second: Some(1.0),
+                ..RateLimits::default()
+            },
+            ..RateLimitConfig::default()
+        });
+        for caller in ["alice", "bob"] {
+            limiter
+                .check_request(Some(&limiter.caller(caller, None)))
+                .expect("first request");
+        }
+        let mut buckets = limiter.buckets.lock().expect("buckets");
+        assert_eq!(buckets.by_caller.len(), 2);
+        let later = Instant::now() + BUCKET_SWEEP_INTERVAL + Duration::from_secs(1);
+        buckets.sweep(later);
+        assert!(buckets.by_caller.is_empty());
+    }
+
+    #[test]
+    fn session_reservations_count_toward_caps() {
+        let limiter = RateLimiter::new(RateLimitConfig {
+            global: RateLimits {
+                max_sessions: Some(3),
+                ..RateLimits::default()
+            },
+            per_token: RateLimits {
+                max_sessions: Some(1),
+                ..RateLimits::default()
+            },
+        });
+        let alice = limiter.caller("alice", None);
+        let bob = limiter.caller("bob", None);
+
+        let first = limiter.reserve_session(Some(&alice), 0, 0).expect("first");
+        let err = limiter
+            .reserve_session(Some(&alice), 0, 0)
+            .err()
+            .expect("per-token cap");
+        assert!(matches!(
+            err,
+            SandboxError::RateLimited {
+                retry_after_secs: CONCURRENCY_RETRY_AFTER_SECS,
+                ..
+            }
+        ));
+        let _second = limiter.reserve_session(Some(&bob), 0, 0).expect("bob");
+        assert!(limiter.reserve_session(None, 1, 0).is_err());
+        drop(first);
+        assert!(limiter.reserve_session(Some(&alice), 1, 0).is_ok());
+    }
+}
@@ -0,0 +1,259 @@
+use std::borrow::Cow;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::sync::RwLock;
+
+use regex::Regex;
+use sandbox_agent_agent_management::credentials::ExtractedCredentials;
+use sandbox_agent_universal_agent_schema::{EventConversion, UniversalEventData};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::agent_adapter::agent_unparsed;
+
+const MASK: &str = "[REDACTED]";
+/// Shorter values are too likely to appear in ordinary output.
+const MIN_SECRET_LEN: usize = 8;
+
+/// Token formats masked even when the value is not known to the daemon.
+const BUILTIN_PATTERNS: [&str; 5] = [
+    r"sk-[A-Za-z0-9_\-]{20,}",
+    r"gh[pousr]_[A-Za-z0-9]{36,}",
+    r"github_pat_[A-Za-z0-9_]{22,}",
+    r"AKIA[0-9A-Z]{16}",
+    r"xox[abprs]-[A-Za-z0-9\-]{10,}",
+];
+
+/// Masks credential values and secret-looking tokens in agent output.
+#[derive(Debug)]
+pub(crate) struct Redactor {
+    secrets: RwLock<Vec<String>>,
+    patterns: Vec<Regex>,
+}
+
+impl Redactor {
+    pub(crate) fn new(env_vars: &[String], patterns: &[Regex]) -> Self {
+        Self::with_env(env_vars, patterns, |name| std::env::var(name).ok())
+    }
+
+    /// Like [`new`](Self::new), reading the `env_vars` values through `lookup`.
+    fn with_env(
+        env_vars: &[String],
+        patterns: &[Regex],
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Self {
+        let mut all_patterns: Vec<Regex> = BUILTIN_PATTERNS
+            .iter()
+            .map(|pattern| Regex::new(pattern).expect("valid builtin redaction pattern"))
+            .collect();
+        all_patterns.extend(patterns.iter().cloned());
+        let redactor = Self {
+            secrets: RwLock::new(Vec::new()),
+            patterns: all_patterns,
+        };
+        for name in env_vars {
+            if let Some(value) = lookup(name) {
+                redactor.add_secret(&value);
+            }
+        }
+        redactor
+    }
+
+    pub(crate) fn add_secret(&self, value: &str) {
+        let value = value.trim();
+        if value.len() < MIN_SECRET_LEN {
+            return;
+        }
+        let mut secrets = self.secrets.write().unwrap();
+        if secrets.iter().any(|secret| secret == value) {
+            return;
+        }
+        secrets.push(value.to_string());
+        // Longest first so a secret containing another is masked whole.
+        secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    }
+
+    pub(crate) fn add_credentials(&self, credentials: &ExtractedCredentials) {
+        let providers = credentials
+            .anthropic
+            .iter()
+            .chain(credentials.openai.iter())
+            .chain(credentials.other.values());
+        for provider in providers {
+            self.add_secret(&provider.api_key);
+        }
+    }
+
+    pub(crate) fn redact_str<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut result = Cow::Borrowed(text);
+        for secret in self.secrets.read().unwrap().iter() {
+            if result.contains(secret.as_str()) {
+                result = Cow::Owned(result.replace(secret.as_str(), MASK));
+            }
+        }
+        for pattern in &self.patterns {
+            if pattern.is_match(&result) {
+                result = Cow::Owned(pattern.replace_all(&result, MASK).into_owned());
+            }
+        }
+        result
+    }
+
+    /// Redacts every string in `value`, returning whether anything changed.
+    pub(crate) fn redact_value(&self, value: &mut Value) -> bool {
+        match value {
+            Value::String(text) => match self.redact_str(text) {
+                Cow::Owned(redacted) => {
+                    *text = redacted;
+                    true
+                }
+                Cow::Borrowed(_) => false,
+            },
+            Value::Array(items) => items
+                .iter_mut()
+                .fold(false, |changed, item| self.redact_value(item) | changed),
+            Value::Object(map) => map
+                .values_mut()
+                .fold(false, |changed, item| self.redact_value(item) | changed),
+            _ => false,
+        }
+    }
+
+    /// Masks secrets in an event's data and raw payload before it is stored or broadcast.
+    pub(crate) fn redact_conversion(&self, conversion: &mut EventConversion) {
+        if let Some(raw) = conversion.raw.as_mut() {
+            self.redact_value(raw);
+        }
+        // The data enum is untagged, so round-trip each variant through its own type.
+        let redacted = match &mut conversion.data {
+            UniversalEventData::Turn(data) => self.redact_typed(data),
+            UniversalEventData::SessionStarted(data) => self.redact_typed(data),
+            UniversalEventData::SessionEnded(data) => self.redact_typed(data),
+            UniversalEventData::Item(data) => self.redact_typed(data),
+            UniversalEventData::ItemDelta(data) => self.redact_typed(data),
+            UniversalEventData::Error(data) => self.redact_typed(data),
+            UniversalEventData::Permission(data) => self.redact_typed(data),
+            UniversalEventData::Question(data) => self.redact_typed(data),
+            UniversalEventData::AgentUnparsed(data) => self.redact_typed(data),
+        };
+        if !redacted {
+            // Masking broke the data's shape, e.g. a pattern matched an enum tag.
+            // Withhold the data rather than pass it on unmasked.
+            let raw = conversion.raw.take();
+            let native_session_id = conversion.native_session_id.take();
+            *conversion = agent_unparsed(
+                "redaction",
+                "event data withheld: it could not be redacted",
+                Value::Null,
+            )
+            .with_raw(raw)
+            .with_native_session(native_session_id);
+        }
+    }
+
+    /// Masks secrets in `data` through its JSON form. Returns false when the masked
+    /// value no longer deserializes, leaving `data` untouched.
+    fn redact_typed<T: Serialize + DeserializeOwned>(&self, data: &mut T) -> bool {
+        let Ok(mut value) = serde_json::to_value(&*data) else {
+            return false;
+        };
+        if !self.redact_value(&mut value) {
+            return true;
+        }
+        match serde_json::from_value(value) {
+            Ok(redacted) => {
+                *data = redacted;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Copies `reader` to `writer` line by line, masking secrets.
+    pub(crate) fn copy_lines(&self, reader: impl Read, mut writer: impl Write) {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let text = String::from_utf8_lossy(&line);
+            if writer.write_all(self.redact_str(&text).as_bytes()).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sandbox_agent_agent_management::credentials::{AuthType, ProviderCredentials};
+    use sandbox_agent_universal_agent_schema::{
+        item_from_text, ItemEventData, ItemRole, UniversalEventType,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn redacts_credentials_env_values_and_patterns() {
+        let redactor = Redactor::with_env(
+            &["SANDBOX_AGENT_TEST_REDACT".to_string(), "UNSET".to_string()],
+            &[Regex::new(r"tok_[0-9]{6}").unwrap()],
+            |name| (name == "SANDBOX_AGENT_TEST_REDACT").then(|| "hunter2-hunter2".to_string()),
+        );
+        redactor.add_credentials(&ExtractedCredentials {
+            anthropic: Some(ProviderCredentials {
+                api_key: "oauth-abcdefgh".to_string(),
+                source: "environment".to_string(),
+                auth_type: AuthType::Oauth,
+                provider: "anthropic".to_string(),
+            }),
+            openai: None,
+            other: Default::default(),
+        });
+        redactor.add_secret("short");
+
+        let mut value = json!({
+            "output": "key=oauth-abcdefgh pw=hunter2-hunter2 id=tok_123456",
+            "nested": ["sk-abcdefghijklmnopqrstuvwxyz", "short", 42],
+        });
+        assert!(redactor.redact_value(&mut value));
+        assert_eq!(
+            value,
+            json!({
+                "output": "key=[REDACTED] pw=[REDACTED] id=[REDACTED]",
+                "nested": ["[REDACTED]", "short", 42],
+            })
+        );
+        assert!(!redactor.redact_value(&mut json!({ "clean": "nothing here" })));
+
+        let mut out = Vec::new();
+        redactor.copy_lines("a hunter2-hunter2\nb\n".as_bytes(), &mut out);
+        assert_eq!(String::from_utf8(out).unwrap(), "a [REDACTED]\nb\n");
+    }
+
+    #[test]
+    fn withholds_event_data_that_cannot_be_redacted() {
+        // "message" is also the item's kind tag, so masking it breaks deserialization.
+        let redactor = Redactor::with_env(&[], &[Regex::new("message").unwrap()], |_| None);
+        let mut conversion = EventConversion::new(
+            UniversalEventType::ItemCompleted,
+            UniversalEventData::Item(ItemEventData {
+                item: item_from_text(ItemRole::Assistant, "secret message".to_string()),
+            }),
+        )
+        .with_native_session(Some("native-1".to_string()))
+        .with_raw(Some(json!({ "text": "secret message" })));
+
+        redactor.redact_conversion(&mut conversion);
+        assert_eq!(conversion.event_type, UniversalEventType::AgentUnparsed);
+        let UniversalEventData::AgentUnparsed(data) = &conversion.data else {
+            panic!("expected agent.unparsed, got {:?}", conversion.data);
+        };
+        assert_eq!(data.location, "redaction");
+        assert_eq!(conversion.raw, Some(json!({ "text": "secret [REDACTED]" })));
+        assert_eq!(conversion.native_session_id.as_deref(), Some("native-1"));
+    }
+}
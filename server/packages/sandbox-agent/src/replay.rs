@@ -0,0 +1,151 @@
+//! Recorded native agent output, and a backend that replays it through the same
+//! converters a live session uses so converter regressions can be caught offline.
+//!
+//! A fixture is NDJSON: a header line naming the agent, then one line per native line
+//! in the format of `GET /v1/sessions/{id}/debug-log`:
+//!
+//! ```text
+//! {"agent":"opencode","nativeSessionId":"ses_1"}
+//! {"direction":"event","line":"{\"type\":\"session.idle\",...}"}
+//! ```
+
+use std::path::Path;
+
+use sandbox_agent_agent_management::agents::AgentId;
+use sandbox_agent_error::SandboxError;
+use sandbox_agent_universal_agent_schema::UniversalEvent;
+use serde::{Deserialize, Serialize};
+
+use crate::debug_log::DebugDirection;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FixtureHeader {
+    agent: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    native_session_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct FixtureLine {
+    direction: DebugDirection,
+    line: String,
+}
+
+/// Native lines recorded from one agent session.
+#[derive(Debug, Clone)]
+pub struct ReplayFixture {
+    pub(crate) agent: AgentId,
+    pub(crate) native_session_id: Option<String>,
+    pub(crate) lines: Vec<(DebugDirection, String)>,
+}
+
+impl ReplayFixture {
+    /// Builds a fixture from a session's debug log.
+    pub fn from_debug_log(
+        agent: AgentId,
+        native_session_id: Option<String>,
+        debug_log: &str,
+    ) -> Result<Self, SandboxError> {
+        Ok(Self {
+            agent,
+            native_session_id,
+            lines: parse_lines(debug_log.lines(), 1)?,
+        })
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SandboxError> {
+        let mut lines = text.lines();
+        let header = lines.next().unwrap_or_default();
+        let header: FixtureHeader =
+            serde_json::from_str(header).map_err(|err| SandboxError::InvalidRequest {
+                message: format!("invalid fixture header: {err}"),
+            })?;
+        let agent = AgentId::parse(&header.agent).ok_or_else(|| SandboxError::InvalidRequest {
+            message: format!("unknown fixture agent: {}", header.agent),
+        })?;
+        Ok(Self {
+            agent,
+            native_session_id: header.native_session_id,
+            lines: parse_lines(lines, 2)?,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SandboxError> {
+        let text = std::fs::read_to_string(path).map_err(|err| SandboxError::InvalidRequest {
+            message: format!("failed to read fixture {}: {err}", path.display()),
+        })?;
+        Self::parse(&text)
+    }
+
+    pub fn agent(&self) -> AgentId {
+        self.agent
+    }
+
+    pub fn to_ndjson(&self) -> String {
+        let header = FixtureHeader {
+            agent: self.agent.as_str().to_string(),
+            native_session_id: self.native_session_id.clone(),
+        };
+        let mut text = serde_json::to_string(&header).unwrap_or_default();
+        text.push('\n');
+        for (direction, line) in &self.lines {
+            let line = FixtureLine {
+                direction: *direction,
+                line: line.clone(),
+            };
+            text.push_str(&serde_json::to_string(&line).unwrap_or_default());
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn parse_lines<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    first_line_number: usize,
+) -> Result<Vec<(DebugDirection, String)>, SandboxError> {
+    lines
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let entry: FixtureLine =
+                serde_json::from_str(line).map_err(|err| SandboxError::InvalidRequest {
+                    message: format!("invalid fixture line {}: {err}", index + first_line_number),
+                })?;
+            Ok((entry.direction, entry.line))
+        })
+        .collect()
+}
+
+/// Feeds the agent output in `fixture` through a throwaway session and returns the
+/// events it produced. Lines the daemon sent to the agent are only used for routing;
+/// event `time` is the only field that differs between runs.
+pub async fn replay(fixture: &ReplayFixture) -> Result<Vec<UniversalEvent>, SandboxError> {
+    crate::router::replay_fixture(fixture).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_debug_logs_and_round_trips() {
+        let debug_log = concat!(
+            r#"{"seq":0,"time":"2026-01-02T10:00:00Z","direction":"stdin","line":"hi"}"#,
+            "\n",
+            r#"{"seq":1,"time":"2026-01-02T10:00:01Z","direction":"stdout","line":"{\"type\":\"done\"}"}"#,
+            "\n",
+        );
+        let fixture = ReplayFixture::from_debug_log(AgentId::Amp, None, debug_log).unwrap();
+        assert_eq!(fixture.lines.len(), 2);
+        assert_eq!(fixture.lines[1].0, DebugDirection::Stdout);
+
+        let parsed = ReplayFixture::parse(&fixture.to_ndjson()).unwrap();
+        assert_eq!(parsed.agent(), AgentId::Amp);
+        assert_eq!(parsed.lines, fixture.lines);
+
+        let err = ReplayFixture::parse("{\"agent\":\"amp\"}\nnot json\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+    }
+}
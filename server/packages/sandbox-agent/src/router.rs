@@ -10,7 +10,7 @@ use std::sync::{Arc, Weak};
 use std::time::{Duration, Instant};
 
 use axum::body::Bytes;
-use axum::extract::{Path, Query, State};
+use axum::extract::{Extension, MatchedPath, Path, Query, State};
 use axum::http::{header, HeaderMap, HeaderValue, Request, StatusCode};
 use axum::middleware::Next;
 use axum::response::sse::Event;
@@ -22,14 +22,14 @@ use base64::Engine;
 use futures::{stream, StreamExt};
 use reqwest::Client;
 use sandbox_agent_error::{AgentError, ErrorType, ProblemDetails, SandboxError};
+use sandbox_agent_universal_agent_schema::convert_acp::AcpStream;
 use sandbox_agent_universal_agent_schema::{
-    codex as codex_schema, convert_amp, convert_claude, convert_codex, convert_opencode,
-    turn_ended_event, turn_started_event, AgentUnparsedData, ContentPart, ErrorData,
-    EventConversion, EventSource, FileAction, ItemDeltaData, ItemEventData, ItemKind, ItemRole,
-    ItemStatus, PermissionEventData, PermissionStatus, QuestionEventData, QuestionStatus,
-    ReasoningVisibility, SessionEndReason, SessionEndedData, SessionStartedData, StderrOutput,
-    TerminatedBy, TurnEventData, TurnPhase, UniversalEvent, UniversalEventData, UniversalEventType,
-    UniversalItem,
+    codex as codex_schema, convert_acp, convert_codex, convert_opencode, turn_ended_event,
+    turn_started_event, AgentUnparsedData, ContentPart, ErrorData, EventConversion, EventSource,
+    FileAction, ItemDeltaData, ItemEventData, ItemKind, ItemRole, ItemStatus, PermissionEventData,
+    PermissionStatus, QuestionEventData, QuestionStatus, ReasoningVisibility, SessionEndReason,
+    SessionEndedData, SessionStartedData, StderrOutput, TerminatedBy, TurnEventData, TurnPhase,
+    UniversalEvent, UniversalEventData, UniversalEventType, UniversalItem,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -38,22 +38,46 @@ use tar::Archive;
 use tokio::sync::futures::OwnedNotified;
 use tokio::sync::{broadcast, mpsc, oneshot, Mutex, Notify};
 use tokio::time::sleep;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 use tokio_stream::wrappers::BroadcastStream;
 use toml_edit::{value, Array, DocumentMut, Item, Table};
 use tower_http::trace::TraceLayer;
 use tracing::Span;
 use utoipa::{Modify, OpenApi, ToSchema};
 
-use crate::agent_server_logs::AgentServerLogs;
+use crate::agent_adapter::{
+    adapter, agent_registry, agent_unparsed, PermissionReplyContext, QuestionReplyContext,
+    ServerKind, SessionHomeRef,
+};
+pub use crate::agent_adapter::{
+    AgentCapabilities, AgentModeInfo, AgentModelInfo, AgentModelsResponse, McpCommand,
+    McpOAuthConfig, McpOAuthConfigOrDisabled, McpRemoteTransport, McpServerConfig, PermissionReply,
+};
+use crate::agent_server_logs::{self, AgentServerLogs};
+use crate::debug_log::{DebugDirection, DebugLog};
+use crate::diagnostics::{disk_space, recent_errors};
+use crate::egress_proxy::{provider_hosts, EgressEvent, EgressLease, EgressProxy, HostPattern};
+use crate::install_jobs::InstallJobs;
+use crate::logs::{LogFiles, DEFAULT_TAIL_LINES, MAX_TAIL_LINES};
+use crate::metrics::metrics;
+use crate::mock_scenarios::{MockScenarios, MockStep};
 use crate::opencode_compat::{build_opencode_router, OpenCodeAppState};
+use crate::rate_limit::{Caller, Cap, RateLimitConfig, RateLimiter, RateLimits};
+use crate::redaction::Redactor;
+use crate::replay::ReplayFixture;
+use crate::server_logs;
+use crate::session_home::SessionHome;
+use crate::session_tokens::SessionTokenSigner;
 use crate::ui;
 use sandbox_agent_agent_management::agents::{
-    AgentError as ManagerError, AgentId, AgentManager, InstallOptions, SpawnOptions, StreamingSpawn,
+    is_newer_version, AgentError as ManagerError, AgentId, AgentManager, InstallOptions,
+    InstallResult, SpawnOptions, StreamingSpawn,
 };
 use sandbox_agent_agent_management::credentials::{
     extract_all_credentials, AuthType, CredentialExtractionOptions, ExtractedCredentials,
     ProviderCredentials,
 };
+use sandbox_agent_agent_management::limits::{LimitExceeded, ResourceGuard, ResourceLimits};
 
 const MOCK_EVENT_DELAY_MS: u64 = 200;
 static USER_MESSAGE_COUNTER: AtomicU64 = AtomicU64::new(1);
@@ -62,7 +86,7 @@ const ANTHROPIC_VERSION: &str = "2023-06-01";
 const CODEX_MODEL_LIST_TIMEOUT_SECS: u64 = 10;
 const SKILL_ROOTS: [&str; 3] = [".agents/skills", ".claude/skills", ".opencode/skill"];
 
-fn claude_fallback_models() -> AgentModelsResponse {
+pub(crate) fn claude_fallback_models() -> AgentModelsResponse {
     // Claude Code accepts model aliases: default, sonnet, opus, haiku
     // These work for both API key and OAuth users
     AgentModelsResponse {
@@ -119,12 +143,34 @@ impl BrandingMode {
     }
 }
 
+/// Daemon-wide session settings.
+#[derive(Debug, Clone, Default)]
+pub struct SessionConfig {
+    /// Give every session its own HOME/XDG directories unless the request opts out.
+    pub isolate_home: bool,
+    /// Root for per-session state. Defaults to the platform data directory.
+    pub state_dir: Option<PathBuf>,
+    /// Route every session through the egress proxy unless it sets its own policy.
+    pub restrict_egress: bool,
+    /// Host patterns allowed for every restricted session.
+    pub egress_allow: Vec<String>,
+    /// Environment variables whose values are masked in events and agent logs.
+    pub redact_env: Vec<String>,
+    /// Extra patterns masked in events and agent logs.
+    pub redact_patterns: Vec<regex::Regex>,
+    /// Request rates and session/turn caps, server-wide and per token.
+    pub rate_limits: RateLimitConfig,
+    /// Scripted mock agent replies, checked before the built-in mock commands.
+    pub mock_scenarios: MockScenarios,
+}
+
 #[derive(Debug)]
 pub struct AppState {
     auth: AuthConfig,
     agent_manager: Arc<AgentManager>,
     session_manager: Arc<SessionManager>,
     pub(crate) branding: BrandingMode,
+    started_at: Instant,
 }
 
 impl AppState {
@@ -136,9 +182,18 @@ impl AppState {
         auth: AuthConfig,
         agent_manager: AgentManager,
         branding: BrandingMode,
+    ) -> Self {
+        Self::with_session_config(auth, agent_manager, branding, SessionConfig::default())
+    }
+
+    pub fn with_session_config(
+        auth: AuthConfig,
+        agent_manager: AgentManager,
+        branding: BrandingMode,
+        session_config: SessionConfig,
     ) -> Self {
         let agent_manager = Arc::new(agent_manager);
-        let session_manager = Arc::new(SessionManager::new(agent_manager.clone()));
+        let session_manager = Arc::new(SessionManager::new(agent_manager.clone(), session_config));
         session_manager
             .server_manager
             .set_owner(Arc::downgrade(&session_manager));
@@ -147,6 +202,7 @@ impl AppState {
             agent_manager,
             session_manager,
             branding,
+            started_at: Instant::now(),
         }
     }
 
@@ -154,23 +210,180 @@ impl AppState {
         self.session_manager.clone()
     }
 
-    pub(crate) async fn ensure_opencode_server(&self) -> Result<String, SandboxError> {
-        self.session_manager.ensure_opencode_server().await
+    pub(crate) async fn ensure_opencode_server(
+        &self,
+        agent: AgentId,
+    ) -> Result<String, SandboxError> {
+        self.session_manager.ensure_opencode_server(agent).await
     }
 }
 
+const SESSION_TOKEN_DEFAULT_TTL_SECS: u64 = 15 * 60;
+const SESSION_TOKEN_MAX_TTL_SECS: u64 = 24 * 60 * 60;
+
 #[derive(Debug, Clone)]
 pub struct AuthConfig {
+    /// Shared token with every scope, kept for `--token`.
     pub token: Option<String>,
+    pub tokens: Vec<ScopedToken>,
+    session_tokens: SessionTokenSigner,
 }
 
 impl AuthConfig {
     pub fn disabled() -> Self {
-        Self { token: None }
+        Self {
+            token: None,
+            tokens: Vec::new(),
+            session_tokens: SessionTokenSigner::random(),
+        }
     }
 
     pub fn with_token(token: String) -> Self {
-        Self { token: Some(token) }
+        Self {
+            token: Some(token),
+            ..Self::disabled()
+        }
+    }
+
+    /// Signs session tokens with a fixed secret so they survive restarts and can be
+    /// verified by every replica sharing the secret.
+    pub fn with_session_token_secret(mut self, secret: &[u8]) -> Self {
+        self.session_tokens = SessionTokenSigner::new(secret);
+        self
+    }
+
+    pub fn with_tokens(mut self, tokens: Vec<ScopedToken>) -> Self {
+        self.tokens.extend(tokens);
+        self
+    }
+
+    /// Adds the tokens listed in a JSON file of the form `{ "tokens": [...] }`.
+    pub fn with_tokens_file(self, path: &StdPath) -> Result<Self, std::io::Error> {
+        #[derive(Deserialize)]
+        struct TokensFile {
+            tokens: Vec<ScopedToken>,
+        }
+        let text = fs::read_to_string(path)?;
+        let file: TokensFile = serde_json::from_str(&text)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        Ok(self.with_tokens(file.tokens))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.token.is_some() || !self.tokens.is_empty()
+    }
+
+    fn resolve(&self, provided: &str) -> Result<ScopedToken, SandboxError> {
+        if self.token.as_deref() == Some(provided) {
+            return Ok(ScopedToken::admin(provided.to_string()));
+        }
+        if let Some(token) = self.tokens.iter().find(|token| token.token == provided) {
+            return Ok(token.clone());
+        }
+        let claims = self.session_tokens.verify(provided)?;
+        Ok(ScopedToken {
+            name: None,
+            token: provided.to_string(),
+            scopes: claims.scopes,
+            sessions: Some(vec![claims.sub]),
+            limits: None,
+        })
+    }
+}
+
+/// Permission granted to an API token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, JsonSchema)]
+pub enum TokenScope {
+    /// List sessions and agents, read events.
+    #[serde(rename = "read")]
+    Read,
+    /// Send messages and terminate sessions.
+    #[serde(rename = "messages:send")]
+    MessagesSend,
+    /// Reply to permission requests and questions.
+    #[serde(rename = "permissions:reply")]
+    PermissionsReply,
+    #[serde(rename = "fs:read")]
+    FsRead,
+    #[serde(rename = "fs:write")]
+    FsWrite,
+    /// Everything, including session creation, agent installs and the OpenCode API.
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl TokenScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::MessagesSend => "messages:send",
+            TokenScope::PermissionsReply => "permissions:reply",
+            TokenScope::FsRead => "fs:read",
+            TokenScope::FsWrite => "fs:write",
+            TokenScope::Admin => "admin",
+        }
+    }
+
+    fn implies(self, required: TokenScope) -> bool {
+        self == required
+            || self == TokenScope::Admin
+            || (self == TokenScope::FsWrite && required == TokenScope::FsRead)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScopedToken {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub token: String,
+    pub scopes: Vec<TokenScope>,
+    /// Session ids this token is limited to; `None` allows every session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sessions: Option<Vec<String>>,
+    /// Overrides the server's per-token rate limits for this token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<RateLimits>,
+}
+
+impl ScopedToken {
+    pub fn admin(token: String) -> Self {
+        Self {
+            name: None,
+            token,
+            scopes: vec![TokenScope::Admin],
+            sessions: None,
+            limits: None,
+        }
+    }
+
+    /// Identity used for rate limits and session ownership. Named tokens go by name and
+    /// session tokens by session, so re-minted tokens keep the same identity.
+    fn caller_key(&self) -> String {
+        if let Some(name) = &self.name {
+            return format!("name:{name}");
+        }
+        match &self.sessions {
+            Some(sessions) => format!("sessions:{}", sessions.join(",")),
+            None => format!("token:{}", self.token),
+        }
+    }
+
+    fn check(&self, scope: TokenScope, session_id: Option<&str>) -> Result<(), SandboxError> {
+        if !self.scopes.iter().any(|granted| granted.implies(scope)) {
+            return Err(SandboxError::PermissionDenied {
+                message: Some(format!("token is missing the {} scope", scope.as_str())),
+            });
+        }
+        if let Some(sessions) = &self.sessions {
+            let allowed = session_id.is_some_and(|id| sessions.iter().any(|session| session == id));
+            if !allowed {
+                return Err(SandboxError::PermissionDenied {
+                    message: Some("token is not valid for this session".to_string()),
+                });
+            }
+        }
+        Ok(())
     }
 }
 
@@ -181,10 +394,19 @@ pub fn build_router(state: AppState) -> Router {
 pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>) {
     let mut v1_router = Router::new()
         .route("/health", get(get_health))
+        .route("/health/ready", get(get_readiness))
+        .route("/diagnostics", get(get_diagnostics))
         .route("/agents", get(list_agents))
+        .route("/agents/:agent", delete(uninstall_agent))
         .route("/agents/:agent/install", post(install_agent))
+        .route("/agents/:agent/updates", get(get_agent_updates))
         .route("/agents/:agent/modes", get(get_agent_modes))
         .route("/agents/:agent/models", get(get_agent_models))
+        .route("/agents/:agent/logs", get(get_agent_logs))
+        .route("/install-jobs", get(list_install_jobs))
+        .route("/install-jobs/:job_id", get(get_install_job))
+        .route("/install-jobs/:job_id/events", get(get_install_job_events))
+        .route("/logs/server", get(get_server_logs))
         .route("/sessions", get(list_sessions))
         .route("/sessions/:session_id", post(create_session))
         .route("/sessions/:session_id/messages", post(post_message))
@@ -193,8 +415,10 @@ pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>)
             post(post_message_stream),
         )
         .route("/sessions/:session_id/terminate", post(terminate_session))
+        .route("/sessions/:session_id/tokens", post(create_session_token))
         .route("/sessions/:session_id/events", get(get_events))
         .route("/sessions/:session_id/events/sse", get(get_events_sse))
+        .route("/sessions/:session_id/debug-log", get(get_debug_log))
         .route(
             "/sessions/:session_id/questions/:question_id/reply",
             post(reply_question),
@@ -216,24 +440,55 @@ pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>)
         .route("/fs/upload-batch", post(fs_upload_batch))
         .with_state(shared.clone());
 
-    if shared.auth.token.is_some() {
+    let rate_limited = shared.session_manager.rate_limiter.is_enabled();
+    if rate_limited {
+        v1_router = v1_router.layer(axum::middleware::from_fn_with_state(
+            shared.clone(),
+            enforce_rate_limits,
+        ));
+    }
+    if shared.auth.is_enabled() {
         v1_router = v1_router.layer(axum::middleware::from_fn_with_state(
             shared.clone(),
             require_token,
         ));
     }
+    v1_router = v1_router.layer(axum::middleware::from_fn(track_http_metrics));
 
     let opencode_state = OpenCodeAppState::new(shared.clone());
     let mut opencode_router = build_opencode_router(opencode_state.clone());
     let mut opencode_root_router = build_opencode_router(opencode_state);
-    if shared.auth.token.is_some() {
+    if rate_limited {
         opencode_router = opencode_router.layer(axum::middleware::from_fn_with_state(
             shared.clone(),
-            require_token,
+            enforce_rate_limits,
         ));
         opencode_root_router = opencode_root_router.layer(axum::middleware::from_fn_with_state(
             shared.clone(),
-            require_token,
+            enforce_rate_limits,
+        ));
+    }
+    if shared.auth.is_enabled() {
+        opencode_router = opencode_router.layer(axum::middleware::from_fn_with_state(
+            shared.clone(),
+            require_admin_token,
+        ));
+        opencode_root_router = opencode_root_router.layer(axum::middleware::from_fn_with_state(
+            shared.clone(),
+            require_admin_token,
+        ));
+    }
+    opencode_router = opencode_router.layer(axum::middleware::from_fn(track_http_metrics));
+    opencode_root_router =
+        opencode_root_router.layer(axum::middleware::from_fn(track_http_metrics));
+
+    let mut metrics_router = Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(shared.clone());
+    if shared.auth.is_enabled() {
+        metrics_router = metrics_router.layer(axum::middleware::from_fn_with_state(
+            shared.clone(),
+            require_read_token,
         ));
     }
 
@@ -242,6 +497,7 @@ pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>)
         .nest("/v1", v1_router)
         .nest("/opencode", opencode_router)
         .merge(opencode_root_router)
+        .merge(metrics_router)
         .fallback(not_found);
 
     router = router.merge(ui::router());
@@ -268,14 +524,18 @@ pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>)
                     tracing::info_span!(
                         "http.request",
                         method = %req.method(),
-                        uri = %req.uri(),
-                        headers = ?headers
+                        uri = %loggable_uri(req.uri()),
+                        headers = ?headers,
+                        otel.kind = "server",
+                        http.response.status_code = tracing::field::Empty,
                     )
                 } else {
                     tracing::info_span!(
                         "http.request",
                         method = %req.method(),
-                        uri = %req.uri()
+                        uri = %loggable_uri(req.uri()),
+                        otel.kind = "server",
+                        http.response.status_code = tracing::field::Empty,
                     )
                 }
             })
@@ -283,6 +543,10 @@ pub fn build_router_with_state(shared: Arc<AppState>) -> (Router, Arc<AppState>)
                 tracing::info!(parent: span, "request");
             })
             .on_response(|res: &Response<_>, latency: Duration, span: &Span| {
+                span.record(
+                    "http.response.status_code",
+                    i64::from(res.status().as_u16()),
+                );
                 tracing::info!(
                     parent: span,
                     status = %res.status(),
@@ -303,17 +567,28 @@ pub async fn shutdown_servers(state: &Arc<AppState>) {
 #[openapi(
     paths(
         get_health,
+        get_readiness,
+        get_diagnostics,
         install_agent,
+        uninstall_agent,
+        get_agent_updates,
+        list_install_jobs,
+        get_install_job,
+        get_install_job_events,
         get_agent_modes,
         get_agent_models,
+        get_agent_logs,
+        get_server_logs,
         list_agents,
         list_sessions,
         create_session,
         post_message,
         post_message_stream,
         terminate_session,
+        create_session_token,
         get_events,
         get_events_sse,
+        get_debug_log,
         reply_question,
         reject_question,
         reply_permission,
@@ -329,6 +604,13 @@ pub async fn shutdown_servers(state: &Arc<AppState>) {
     components(
         schemas(
             AgentInstallRequest,
+            AgentUninstallQuery,
+            AgentUpdatesResponse,
+            InstallJob,
+            InstallJobStatus,
+            InstallJobProgress,
+            InstallJobPhase,
+            InstallJobListResponse,
             AgentModeInfo,
             AgentModesResponse,
             AgentModelInfo,
@@ -336,20 +618,33 @@ pub async fn shutdown_servers(state: &Arc<AppState>) {
             AgentCapabilities,
             AgentInfo,
             AgentListResponse,
+            LogsQuery,
+            LogsResponse,
             ServerStatus,
             ServerStatusInfo,
             SessionInfo,
             SessionListResponse,
             HealthResponse,
+            ReadinessCheck,
+            ReadinessResponse,
+            DiagnosticsResponse,
+            CredentialStatus,
+            DiskSpaceInfo,
+            RecentError,
             CreateSessionRequest,
             SkillsConfig,
             SkillSource,
+            SessionResourceLimits,
+            SessionEgressPolicy,
             McpCommand,
             McpRemoteTransport,
             McpOAuthConfig,
             McpOAuthConfigOrDisabled,
             McpServerConfig,
             CreateSessionResponse,
+            TokenScope,
+            CreateSessionTokenRequest,
+            CreateSessionTokenResponse,
             FsPathQuery,
             FsEntriesQuery,
             FsSessionQuery,
@@ -433,7 +728,24 @@ impl IntoResponse for ApiError {
         };
         let status =
             StatusCode::from_u16(problem.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-        (status, Json(problem)).into_response()
+        if status.is_server_error() {
+            recent_errors().record(
+                "http",
+                None,
+                None,
+                problem.detail.as_deref().unwrap_or(&problem.title),
+            );
+        }
+        let mut response = (status, Json(problem)).into_response();
+        if let ApiError::Sandbox(SandboxError::RateLimited {
+            retry_after_secs, ..
+        }) = &self
+        {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(*retry_after_secs));
+        }
+        response
     }
 }
 
@@ -445,6 +757,8 @@ struct SessionState {
     permission_mode: String,
     model: Option<String>,
     variant: Option<String>,
+    /// Installed version to run; `None` runs the unversioned install.
+    agent_version: Option<String>,
     working_dir: PathBuf,
     native_session_id: Option<String>,
     ended: bool,
@@ -465,10 +779,9 @@ struct SessionState {
     broadcaster: broadcast::Sender<UniversalEvent>,
     opencode_stream_started: bool,
     codex_sender: Option<mpsc::UnboundedSender<String>>,
-    claude_sender: Option<mpsc::UnboundedSender<String>>,
+    /// Stdin of the running per-turn process, for agents that read replies there.
+    stdin_sender: Option<mpsc::UnboundedSender<String>>,
     session_started_emitted: bool,
-    last_claude_message_id: Option<String>,
-    claude_message_counter: u64,
     pending_assistant_native_ids: VecDeque<String>,
     pending_assistant_counter: u64,
     created_at: i64,
@@ -477,12 +790,32 @@ struct SessionState {
     title: Option<String>,
     mcp: Option<BTreeMap<String, McpServerConfig>>,
     skills: Option<SkillsConfig>,
+    limits: ResourceLimits,
+    home: Option<SessionHome>,
+    egress: Option<EgressLease>,
+    /// Key of the token that created the session, for per-token session caps.
+    owner: Option<String>,
+    /// A message was sent and the turn has not ended yet.
+    turn_active: bool,
+    /// Key of the token that started the turn in progress.
+    turn_owner: Option<String>,
+    turn_started_at: Option<Instant>,
+    /// Trace spans, exported when OTLP is configured.
+    span: Span,
+    turn_span: Option<Span>,
+    /// Open tool call spans by call id, closed by the matching tool result.
+    tool_spans: HashMap<String, Span>,
+    /// Latest input/output token counts the agent reported during the turn.
+    turn_usage: Option<(u64, u64)>,
+    /// Native agent I/O, captured when the session was created with `debugLog`.
+    debug_log: Option<Arc<DebugLog>>,
 }
 
 #[derive(Debug, Clone)]
 struct PendingPermission {
     action: String,
     metadata: Option<Value>,
+    requested_at: Instant,
 }
 
 #[derive(Debug, Clone)]
@@ -510,6 +843,18 @@ impl SessionState {
             .duration_since(std::time::UNIX_EPOCH)
             .map(|d| d.as_millis() as i64)
             .unwrap_or(0);
+        let span = tracing::info_span!(
+            parent: None,
+            "session",
+            session.id = %session_id,
+            agent = agent.as_str(),
+            gen_ai.request.model = tracing::field::Empty,
+            session.end_reason = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+        );
+        if let Some(model) = &request.model {
+            span.record("gen_ai.request.model", model.as_str());
+        }
 
         Ok(Self {
             session_id,
@@ -518,6 +863,7 @@ impl SessionState {
             permission_mode,
             model: request.model.clone(),
             variant: request.variant.clone(),
+            agent_version: request.agent_version.clone(),
             working_dir,
             native_session_id: None,
             ended: false,
@@ -538,10 +884,8 @@ impl SessionState {
             broadcaster,
             opencode_stream_started: false,
             codex_sender: None,
-            claude_sender: None,
+            stdin_sender: None,
             session_started_emitted: false,
-            last_claude_message_id: None,
-            claude_message_counter: 0,
             pending_assistant_native_ids: VecDeque::new(),
             pending_assistant_counter: 0,
             created_at: now,
@@ -550,6 +894,22 @@ impl SessionState {
             title: request.title.clone(),
             mcp: request.mcp.clone(),
             skills: request.skills.clone(),
+            limits: request
+                .limits
+                .as_ref()
+                .map(ResourceLimits::from)
+                .unwrap_or_default(),
+            home: None,
+            egress: None,
+            owner: None,
+            turn_active: false,
+            turn_owner: None,
+            turn_started_at: None,
+            span,
+            turn_span: None,
+            tool_spans: HashMap::new(),
+            turn_usage: None,
+            debug_log: None,
         })
     }
 
@@ -611,13 +971,13 @@ impl SessionState {
         self.codex_sender.clone()
     }
 
-    fn set_claude_sender(&mut self, sender: Option<mpsc::UnboundedSender<String>>) {
-        self.claude_sender = sender;
+    fn set_stdin_sender(&mut self, sender: Option<mpsc::UnboundedSender<String>>) {
+        self.stdin_sender = sender;
     }
 
     #[allow(dead_code)]
-    fn claude_sender(&self) -> Option<mpsc::UnboundedSender<String>> {
-        self.claude_sender.clone()
+    fn stdin_sender(&self) -> Option<mpsc::UnboundedSender<String>> {
+        self.stdin_sender.clone()
     }
 
     fn normalize_conversion(&mut self, mut conversion: EventConversion) -> Vec<EventConversion> {
@@ -773,6 +1133,22 @@ impl SessionState {
 
         self.update_pending(&event);
         self.update_item_tracking(&event);
+        self.record_error(&event);
+        if matches!(
+            event.event_type,
+            UniversalEventType::TurnEnded | UniversalEventType::SessionEnded
+        ) {
+            if let Some(started) = self.turn_started_at.take() {
+                metrics()
+                    .turn_duration
+                    .with_label_values(&[self.agent.as_str()])
+                    .observe(started.elapsed().as_secs_f64());
+            }
+            self.turn_active = false;
+            self.turn_owner = None;
+            self.end_turn_span(false);
+        }
+        self.trace_event(&event);
 
         // Suppress question-tool permissions (AskUserQuestion/ExitPlanMode) from frontends.
         // The permission is still stored in pending_permissions (via update_pending above)
@@ -797,6 +1173,12 @@ impl SessionState {
             }
         }
 
+        if let Ok(Value::String(event_type)) = serde_json::to_value(&event.event_type) {
+            metrics()
+                .events
+                .with_label_values(&[self.agent.as_str(), event_type.as_str()])
+                .inc();
+        }
         self.events.push(event.clone());
         let _ = self.broadcaster.send(event.clone());
         if self.native_session_id.is_none() {
@@ -830,13 +1212,19 @@ impl SessionState {
                         PendingPermission {
                             action: data.action.clone(),
                             metadata: data.metadata.clone(),
+                            requested_at: Instant::now(),
                         },
                     );
                 }
             }
             UniversalEventType::PermissionResolved => {
                 if let UniversalEventData::Permission(data) = &event.data {
-                    self.pending_permissions.remove(&data.permission_id);
+                    if let Some(pending) = self.pending_permissions.remove(&data.permission_id) {
+                        metrics()
+                            .permission_wait
+                            .with_label_values(&[self.agent.as_str()])
+                            .observe(pending.requested_at.elapsed().as_secs_f64());
+                    }
                 }
             }
             _ => {}
@@ -904,6 +1292,20 @@ impl SessionState {
         reason: SessionEndReason,
         terminated_by: TerminatedBy,
     ) {
+        if self.ends_for_good(&reason) {
+            if let Ok(Value::String(reason)) = serde_json::to_value(&reason) {
+                metrics()
+                    .sessions_ended
+                    .with_label_values(&[self.agent.as_str(), reason.as_str()])
+                    .inc();
+                self.span.record("session.end_reason", reason.as_str());
+            }
+            if matches!(reason, SessionEndReason::Error) {
+                self.span.record("otel.status_code", "error");
+            }
+            self.end_turn_span(false);
+            self.span = Span::none();
+        }
         self.ended = true;
         self.ended_exit_code = exit_code;
         self.ended_message = Some(message);
@@ -911,6 +1313,117 @@ impl SessionState {
         self.terminated_by = Some(terminated_by);
     }
 
+    /// Keeps agent errors and failed session ends for `GET /v1/diagnostics`.
+    fn record_error(&self, event: &UniversalEvent) {
+        let message = match &event.data {
+            UniversalEventData::Error(data) => data.message.clone(),
+            UniversalEventData::SessionEnded(data)
+                if matches!(data.reason, SessionEndReason::Error) =>
+            {
+                data.message
+                    .clone()
+                    .unwrap_or_else(|| "session ended with an error".to_string())
+            }
+            _ => return,
+        };
+        recent_errors().record(
+            "agent",
+            Some(self.agent.as_str()),
+            Some(&self.session_id),
+            &message,
+        );
+    }
+
+    fn start_turn_span(&mut self) {
+        let span = tracing::info_span!(
+            parent: &self.span,
+            "turn",
+            session.id = %self.session_id,
+            agent = self.agent.as_str(),
+            gen_ai.request.model = tracing::field::Empty,
+            gen_ai.usage.input_tokens = tracing::field::Empty,
+            gen_ai.usage.output_tokens = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+        );
+        if let Some(model) = &self.model {
+            span.record("gen_ai.request.model", model.as_str());
+        }
+        self.turn_span = Some(span);
+        self.turn_usage = None;
+    }
+
+    /// Closes the turn span and any tool spans still open under it.
+    fn end_turn_span(&mut self, failed: bool) {
+        self.tool_spans.clear();
+        let Some(span) = self.turn_span.take() else {
+            return;
+        };
+        // Recorded as i64: OpenTelemetry has no unsigned integer attributes.
+        if let Some((input, output)) = self.turn_usage.take() {
+            span.record("gen_ai.usage.input_tokens", input as i64);
+            span.record("gen_ai.usage.output_tokens", output as i64);
+        }
+        if failed {
+            span.record("otel.status_code", "error");
+        }
+    }
+
+    /// Opens a tool span when a tool call item appears and closes it on the matching
+    /// tool result, and picks up token usage from raw agent payloads during a turn.
+    fn trace_event(&mut self, event: &UniversalEvent) {
+        if self.turn_span.is_some() {
+            if let Some(usage) = event.raw.as_ref().and_then(raw_token_usage) {
+                self.turn_usage = Some(usage);
+            }
+        }
+        if !matches!(
+            event.event_type,
+            UniversalEventType::ItemStarted | UniversalEventType::ItemCompleted
+        ) {
+            return;
+        }
+        let UniversalEventData::Item(data) = &event.data else {
+            return;
+        };
+        let item = &data.item;
+        for part in &item.content {
+            match part {
+                ContentPart::ToolCall { name, call_id, .. } if item.kind == ItemKind::ToolCall => {
+                    if self.tool_spans.contains_key(call_id) {
+                        continue;
+                    }
+                    let parent = self.turn_span.as_ref().unwrap_or(&self.span);
+                    let span = tracing::info_span!(
+                        parent: parent,
+                        "tool_call",
+                        session.id = %self.session_id,
+                        agent = self.agent.as_str(),
+                        tool.name = name.as_str(),
+                        tool.call_id = call_id.as_str(),
+                        otel.status_code = tracing::field::Empty,
+                    );
+                    self.tool_spans.insert(call_id.clone(), span);
+                }
+                ContentPart::ToolResult { call_id, .. }
+                    if event.event_type == UniversalEventType::ItemCompleted =>
+                {
+                    if let Some(span) = self.tool_spans.remove(call_id) {
+                        if matches!(item.status, ItemStatus::Failed) {
+                            span.record("otel.status_code", "error");
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Resumable agents complete after every turn and are reopened by the next message,
+    /// so only errors and terminations end them.
+    fn ends_for_good(&self, reason: &SessionEndReason) -> bool {
+        !matches!(reason, SessionEndReason::Completed) || !agent_supports_resume(self.agent)
+    }
+
     fn ended_error(&self) -> Option<SandboxError> {
         self.ended_error_for_messages(false)
     }
@@ -983,7 +1496,7 @@ impl SessionState {
 #[derive(Debug)]
 enum ManagedServerKind {
     Http { base_url: String },
-    Stdio { server: Arc<CodexServer> },
+    Stdio { server: Arc<StdioServer> },
 }
 
 #[derive(Debug)]
@@ -996,6 +1509,7 @@ struct ManagedServer {
     last_error: Option<String>,
     shutdown_requested: bool,
     instance_id: u64,
+    limits: Option<ResourceGuard>,
 }
 
 #[derive(Debug)]
@@ -1007,6 +1521,7 @@ struct AgentServerManager {
     http_client: Client,
     log_base_dir: PathBuf,
     auto_restart: bool,
+    redactor: Arc<Redactor>,
     owner: std::sync::Mutex<Option<Weak<SessionManager>>>,
     #[cfg(feature = "test-utils")]
     restart_notifier: Mutex<Option<mpsc::UnboundedSender<AgentId>>>,
@@ -1019,6 +1534,11 @@ pub(crate) struct SessionManager {
     server_manager: Arc<AgentServerManager>,
     http_client: Client,
     model_catalog: Mutex<ModelCatalogState>,
+    session_config: SessionConfig,
+    egress_proxy: tokio::sync::OnceCell<Arc<EgressProxy>>,
+    redactor: Arc<Redactor>,
+    rate_limiter: RateLimiter,
+    install_jobs: InstallJobs,
 }
 
 #[derive(Debug, Default)]
@@ -1027,14 +1547,14 @@ struct ModelCatalogState {
     in_flight: HashMap<AgentId, Arc<Notify>>,
 }
 
-/// Shared Codex app-server process that handles multiple sessions via JSON-RPC.
-/// Similar to OpenCode's server model - a single long-running process that multiplexes
-/// multiple thread (session) conversations.
-struct CodexServer {
+/// Shared JSON-RPC server process over stdio (Codex app-server or an ACP agent) that
+/// handles multiple sessions. Similar to OpenCode's server model - a single long-running
+/// process that multiplexes multiple thread (session) conversations.
+struct StdioServer {
     /// Sender for writing to the process stdin
     stdin_sender: mpsc::UnboundedSender<String>,
     /// Pending JSON-RPC requests awaiting responses, keyed by request ID
-    pending_requests: std::sync::Mutex<HashMap<i64, oneshot::Sender<CodexRequestResult>>>,
+    pending_requests: std::sync::Mutex<HashMap<i64, oneshot::Sender<StdioRequestResult>>>,
     /// Optional mapping from request ID to session ID for routing request-scoped errors
     request_sessions: std::sync::Mutex<HashMap<i64, String>>,
     /// Next request ID for JSON-RPC
@@ -1045,17 +1565,19 @@ struct CodexServer {
     initialize_lock: Mutex<()>,
     /// Mapping from thread_id to session_id for routing notifications
     thread_sessions: std::sync::Mutex<HashMap<String, String>>,
+    /// Conversion state of ACP sessions, keyed by session_id
+    acp_streams: std::sync::Mutex<HashMap<String, AcpStream>>,
 }
 
-impl std::fmt::Debug for CodexServer {
+impl std::fmt::Debug for StdioServer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("CodexServer")
+        f.debug_struct("StdioServer")
             .field("next_id", &self.next_id.load(Ordering::SeqCst))
             .finish()
     }
 }
 
-impl CodexServer {
+impl StdioServer {
     fn new(stdin_sender: mpsc::UnboundedSender<String>) -> Self {
         Self {
             stdin_sender,
@@ -1065,6 +1587,7 @@ impl CodexServer {
             initialized: std::sync::Mutex::new(false),
             initialize_lock: Mutex::new(()),
             thread_sessions: std::sync::Mutex::new(HashMap::new()),
+            acp_streams: std::sync::Mutex::new(HashMap::new()),
         }
     }
 
@@ -1076,7 +1599,7 @@ impl CodexServer {
         &self,
         id: i64,
         request: &impl Serialize,
-    ) -> Option<oneshot::Receiver<CodexRequestResult>> {
+    ) -> Option<oneshot::Receiver<StdioRequestResult>> {
         self.send_request_with_session(id, request, None)
     }
 
@@ -1085,7 +1608,7 @@ impl CodexServer {
         id: i64,
         request: &impl Serialize,
         session_id: Option<String>,
-    ) -> Option<oneshot::Receiver<CodexRequestResult>> {
+    ) -> Option<oneshot::Receiver<StdioRequestResult>> {
         let (tx, rx) = oneshot::channel();
         {
             let mut pending = self.pending_requests.lock().unwrap();
@@ -1113,7 +1636,7 @@ impl CodexServer {
         self.stdin_sender.send(line).is_ok()
     }
 
-    fn complete_request(&self, id: i64, result: CodexRequestResult) {
+    fn complete_request(&self, id: i64, result: StdioRequestResult) {
         let tx = {
             let mut pending = self.pending_requests.lock().unwrap();
             pending.remove(&id)
@@ -1156,11 +1679,18 @@ impl CodexServer {
     fn clear_threads(&self) {
         let mut sessions = self.thread_sessions.lock().unwrap();
         sessions.clear();
+        let mut streams = self.acp_streams.lock().unwrap();
+        streams.clear();
+    }
+
+    fn acp_stream<T>(&self, session_id: &str, f: impl FnOnce(&mut AcpStream) -> T) -> T {
+        let mut streams = self.acp_streams.lock().unwrap();
+        f(streams.entry(session_id.to_string()).or_default())
     }
 }
 
 #[derive(Debug, Clone)]
-enum CodexRequestResult {
+enum StdioRequestResult {
     Response(Value),
     Error(codex_schema::JsonrpcErrorError),
 }
@@ -1187,6 +1717,21 @@ pub(crate) struct PendingQuestionInfo {
 }
 
 impl ManagedServer {
+    fn stop(&mut self) {
+        self.shutdown_requested = true;
+        self.status = ServerStatus::Stopped;
+        self.start_time = None;
+        if let Ok(mut guard) = self.child.lock() {
+            if let Some(child) = guard.as_mut() {
+                let _ = child.kill();
+            }
+        }
+        if let ManagedServerKind::Stdio { server } = &self.kind {
+            server.clear_pending();
+            server.clear_threads();
+        }
+    }
+
     fn base_url(&self) -> Option<String> {
         match &self.kind {
             ManagedServerKind::Http { base_url } => Some(base_url.clone()),
@@ -1214,6 +1759,7 @@ impl AgentServerManager {
         http_client: Client,
         log_base_dir: PathBuf,
         auto_restart: bool,
+        redactor: Arc<Redactor>,
     ) -> Self {
         Self {
             agent_manager,
@@ -1223,6 +1769,7 @@ impl AgentServerManager {
             http_client,
             log_base_dir,
             auto_restart,
+            redactor,
             owner: std::sync::Mutex::new(None),
             #[cfg(feature = "test-utils")]
             restart_notifier: Mutex::new(None),
@@ -1320,7 +1867,7 @@ impl AgentServerManager {
             }
         }
 
-        let (base_url, child) = self.spawn_http_server(agent).await?;
+        let (base_url, child, limits) = self.spawn_http_server(agent).await?;
         let restart_count = {
             let servers = self.servers.lock().await;
             servers
@@ -1357,9 +1904,16 @@ impl AgentServerManager {
                     last_error: None,
                     shutdown_requested: false,
                     instance_id: restart_count,
+                    limits,
                 },
             );
         }
+        if restart_count > 0 {
+            metrics()
+                .agent_server_restarts
+                .with_label_values(&[agent.as_str()])
+                .inc();
+        }
 
         if let Err(err) = self.wait_for_http_server(&base_url).await {
             if let Ok(mut guard) = child.lock() {
@@ -1376,10 +1930,23 @@ impl AgentServerManager {
         Ok(base_url)
     }
 
+    /// Returns the agent's stdio server if it is running, without starting one.
+    async fn running_stdio_server(&self, agent: AgentId) -> Option<Arc<StdioServer>> {
+        let servers = self.servers.lock().await;
+        match servers.get(&agent) {
+            Some(ManagedServer {
+                kind: ManagedServerKind::Stdio { server },
+                status: ServerStatus::Running,
+                ..
+            }) => Some(server.clone()),
+            _ => None,
+        }
+    }
+
     async fn ensure_stdio_server(
         self: &Arc<Self>,
         agent: AgentId,
-    ) -> Result<(Arc<CodexServer>, Option<mpsc::UnboundedReceiver<String>>), SandboxError> {
+    ) -> Result<(Arc<StdioServer>, Option<mpsc::UnboundedReceiver<String>>), SandboxError> {
         {
             let servers = self.servers.lock().await;
             if let Some(server) = servers.get(&agent) {
@@ -1391,7 +1958,7 @@ impl AgentServerManager {
             }
         }
 
-        let (server, stdout_rx, child) = self.spawn_stdio_server(agent).await?;
+        let (server, stdout_rx, child, limits) = self.spawn_stdio_server(agent).await?;
         let restart_count = {
             let servers = self.servers.lock().await;
             servers
@@ -1427,9 +1994,16 @@ impl AgentServerManager {
                     last_error: None,
                     shutdown_requested: false,
                     instance_id: restart_count,
+                    limits,
                 },
             );
         }
+        if restart_count > 0 {
+            metrics()
+                .agent_server_restarts
+                .with_label_values(&[agent.as_str()])
+                .inc();
+        }
 
         self.spawn_monitor_task(agent, restart_count, child);
 
@@ -1439,18 +2013,14 @@ impl AgentServerManager {
     async fn shutdown(&self) {
         let mut servers = self.servers.lock().await;
         for server in servers.values_mut() {
-            server.shutdown_requested = true;
-            server.status = ServerStatus::Stopped;
-            server.start_time = None;
-            if let Ok(mut guard) = server.child.lock() {
-                if let Some(child) = guard.as_mut() {
-                    let _ = child.kill();
-                }
-            }
-            if let ManagedServerKind::Stdio { server } = &server.kind {
-                server.clear_pending();
-                server.clear_threads();
-            }
+            server.stop();
+        }
+    }
+
+    /// Stops the agent's server, if it has one, without restarting it.
+    async fn stop_server(&self, agent: AgentId) {
+        if let Some(server) = self.servers.lock().await.get_mut(&agent) {
+            server.stop();
         }
     }
 
@@ -1475,27 +2045,40 @@ impl AgentServerManager {
     async fn spawn_http_server(
         self: &Arc<Self>,
         agent: AgentId,
-    ) -> Result<(String, Arc<std::sync::Mutex<Option<std::process::Child>>>), SandboxError> {
+    ) -> Result<
+        (
+            String,
+            Arc<std::sync::Mutex<Option<std::process::Child>>>,
+            Option<ResourceGuard>,
+        ),
+        SandboxError,
+    > {
         let manager = self.agent_manager.clone();
         let log_dir = self.log_base_dir.clone();
-        let (base_url, child) = tokio::task::spawn_blocking(
-            move || -> Result<(String, std::process::Child), SandboxError> {
+        let redactor = self.redactor.clone();
+        let (base_url, child, limits) = tokio::task::spawn_blocking(
+            move || -> Result<(String, std::process::Child, Option<ResourceGuard>), SandboxError> {
                 let path = manager
                     .resolve_binary(agent)
                     .map_err(|err| map_spawn_error(agent, err))?;
                 let port = find_available_port()?;
                 let mut command = std::process::Command::new(path);
-                let stderr = AgentServerLogs::new(log_dir, agent.as_str()).open()?;
+                let log = AgentServerLogs::new(log_dir, agent.as_str()).open()?;
                 command
                     .arg("serve")
                     .arg("--port")
                     .arg(port.to_string())
                     .stdout(Stdio::null())
-                    .stderr(stderr);
-                let child = command.spawn().map_err(|err| SandboxError::StreamError {
+                    .stderr(Stdio::piped());
+                let limits =
+                    manager.prepare_limits(agent, &mut command, &ResourceLimits::default());
+                let mut child = command.spawn().map_err(|err| SandboxError::StreamError {
                     message: err.to_string(),
                 })?;
-                Ok((format!("http://127.0.0.1:{port}"), child))
+                if let Some(stderr) = child.stderr.take() {
+                    agent_server_logs::copy_output(log, stderr, redactor);
+                }
+                Ok((format!("http://127.0.0.1:{port}"), child, limits))
             },
         )
         .await
@@ -1503,7 +2086,11 @@ impl AgentServerManager {
             message: err.to_string(),
         })??;
 
-        Ok((base_url, Arc::new(std::sync::Mutex::new(Some(child)))))
+        Ok((
+            base_url,
+            Arc::new(std::sync::Mutex::new(Some(child))),
+            limits,
+        ))
     }
 
     async fn spawn_stdio_server(
@@ -1511,45 +2098,50 @@ impl AgentServerManager {
         agent: AgentId,
     ) -> Result<
         (
-            Arc<CodexServer>,
+            Arc<StdioServer>,
             mpsc::UnboundedReceiver<String>,
             Arc<std::sync::Mutex<Option<std::process::Child>>>,
+            Option<ResourceGuard>,
         ),
         SandboxError,
     > {
         let manager = self.agent_manager.clone();
         let log_dir = self.log_base_dir.clone();
+        let redactor = self.redactor.clone();
         let (stdin_tx, stdin_rx) = mpsc::unbounded_channel::<String>();
         let (stdout_tx, stdout_rx) = mpsc::unbounded_channel::<String>();
 
-        let child =
-            tokio::task::spawn_blocking(move || -> Result<std::process::Child, SandboxError> {
-                let path = manager
-                    .resolve_binary(agent)
+        let (child, limits) = tokio::task::spawn_blocking(
+            move || -> Result<(std::process::Child, Option<ResourceGuard>), SandboxError> {
+                let mut command = manager
+                    .server_command(agent)
                     .map_err(|err| map_spawn_error(agent, err))?;
-                let mut command = std::process::Command::new(path);
-                let stderr = AgentServerLogs::new(log_dir, agent.as_str()).open()?;
+                let log = AgentServerLogs::new(log_dir, agent.as_str()).open()?;
                 command
-                    .arg("app-server")
                     .stdin(Stdio::piped())
                     .stdout(Stdio::piped())
-                    .stderr(stderr);
+                    .stderr(Stdio::piped());
+                let limits =
+                    manager.prepare_limits(agent, &mut command, &ResourceLimits::default());
 
                 let mut child = command.spawn().map_err(|err| SandboxError::StreamError {
                     message: err.to_string(),
                 })?;
+                if let Some(stderr) = child.stderr.take() {
+                    agent_server_logs::copy_output(log, stderr, redactor);
+                }
 
                 let stdin = child
                     .stdin
                     .take()
                     .ok_or_else(|| SandboxError::StreamError {
-                        message: "codex stdin unavailable".to_string(),
+                        message: format!("{} stdin unavailable", agent.as_str()),
                     })?;
                 let stdout = child
                     .stdout
                     .take()
                     .ok_or_else(|| SandboxError::StreamError {
-                        message: "codex stdout unavailable".to_string(),
+                        message: format!("{} stdout unavailable", agent.as_str()),
                     })?;
 
                 let stdin_rx_mut = std::sync::Mutex::new(stdin_rx);
@@ -1576,19 +2168,21 @@ impl AgentServerManager {
                     }
                 });
 
-                Ok(child)
-            })
-            .await
-            .map_err(|err| SandboxError::StreamError {
-                message: err.to_string(),
-            })??;
+                Ok((child, limits))
+            },
+        )
+        .await
+        .map_err(|err| SandboxError::StreamError {
+            message: err.to_string(),
+        })??;
 
-        let server = Arc::new(CodexServer::new(stdin_tx));
+        let server = Arc::new(StdioServer::new(stdin_tx));
 
         Ok((
             server,
             stdout_rx,
             Arc::new(std::sync::Mutex::new(Some(child))),
+            limits,
         ))
     }
 
@@ -1637,6 +2231,7 @@ impl AgentServerManager {
         let message = format!("agent server exited with status {:?}", status);
         let mut codex_server = None;
         let mut shutdown_requested = false;
+        let mut limits = None;
         {
             let mut servers = self.servers.lock().await;
             if let Some(server) = servers.get_mut(&agent) {
@@ -1656,6 +2251,7 @@ impl AgentServerManager {
                 if let Ok(mut guard) = server.child.lock() {
                     *guard = None;
                 }
+                limits = server.limits.take();
                 if let ManagedServerKind::Stdio { server } = &server.kind {
                     codex_server = Some(server.clone());
                 }
@@ -1667,11 +2263,22 @@ impl AgentServerManager {
             server.clear_threads();
         }
 
+        let limit_hits = limits
+            .as_ref()
+            .map(ResourceGuard::exceeded)
+            .unwrap_or_default();
+        if let Some(limits) = limits {
+            let _ = tokio::task::spawn_blocking(move || drop(limits)).await;
+        }
+
         if shutdown_requested {
             self.clear_mappings(agent).await;
             return;
         }
 
+        if !limit_hits.is_empty() {
+            self.notify_sessions_of_limit_hits(agent, &limit_hits).await;
+        }
         self.notify_sessions_of_error(agent, &message, exit_code)
             .await;
 
@@ -1694,11 +2301,11 @@ impl AgentServerManager {
         agent: AgentId,
     ) -> Result<(), SandboxError> {
         sleep(Duration::from_millis(500)).await;
-        match agent {
-            AgentId::Opencode => {
+        match adapter(agent).server_kind() {
+            ServerKind::OpencodeHttp => {
                 let _ = self.ensure_http_server(agent).await?;
             }
-            AgentId::Codex => {
+            ServerKind::CodexAppServer => {
                 let (server, receiver) = self.ensure_stdio_server(agent).await?;
                 if let Some(stdout_rx) = receiver {
                     let owner = self.owner.lock().expect("owner lock").clone();
@@ -1707,18 +2314,42 @@ impl AgentServerManager {
                         let server_clone = server.clone();
                         tokio::spawn(async move {
                             owner_clone
-                                .handle_codex_server_output(server_clone, stdout_rx)
+                                .handle_codex_server_output(agent, server_clone, stdout_rx)
                                 .await;
                         });
                         let _ = owner.codex_server_initialize(&server).await;
                     }
                 }
             }
-            _ => {}
+            ServerKind::Acp => {
+                let owner = self.owner.lock().expect("owner lock").clone();
+                if let Some(owner) = owner.as_ref().and_then(|weak| weak.upgrade()) {
+                    let _ = owner.ensure_acp_server(agent).await;
+                }
+            }
+            ServerKind::PerTurn => {}
         }
         Ok(())
     }
 
+    async fn notify_sessions_of_limit_hits(&self, agent: AgentId, hits: &[LimitExceeded]) {
+        let session_ids = {
+            let sessions = self.sessions.lock().await;
+            sessions
+                .get(&agent)
+                .cloned()
+                .unwrap_or_default()
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+        let owner = { self.owner.lock().expect("owner lock").clone() };
+        if let Some(owner) = owner.and_then(|weak| weak.upgrade()) {
+            for session_id in session_ids {
+                owner.record_limit_hits(&session_id, hits).await;
+            }
+        }
+    }
+
     async fn notify_sessions_of_error(
         &self,
         agent: AgentId,
@@ -1764,6 +2395,7 @@ impl AgentServerManager {
     }
 
     async fn update_server_error(&self, agent: AgentId, message: String) {
+        recent_errors().record("agent_server", Some(agent.as_str()), None, &message);
         let mut servers = self.servers.lock().await;
         if let Some(server) = servers.get_mut(&agent) {
             server.status = ServerStatus::Error;
@@ -1774,13 +2406,19 @@ impl AgentServerManager {
 }
 
 impl SessionManager {
-    fn new(agent_manager: Arc<AgentManager>) -> Self {
+    fn new(agent_manager: Arc<AgentManager>, session_config: SessionConfig) -> Self {
         let log_base_dir = default_log_dir();
+        let redactor = Arc::new(Redactor::new(
+            &session_config.redact_env,
+            &session_config.redact_patterns,
+        ));
+        redactor.add_credentials(&extract_all_credentials(&CredentialExtractionOptions::new()));
         let server_manager = Arc::new(AgentServerManager::new(
             agent_manager.clone(),
             Client::new(),
             log_base_dir,
             true,
+            redactor.clone(),
         ));
         Self {
             agent_manager,
@@ -1788,9 +2426,48 @@ impl SessionManager {
             server_manager,
             http_client: Client::new(),
             model_catalog: Mutex::new(ModelCatalogState::default()),
+            rate_limiter: RateLimiter::new(session_config.rate_limits.clone()),
+            session_config,
+            egress_proxy: tokio::sync::OnceCell::new(),
+            redactor,
+            install_jobs: InstallJobs::default(),
         }
     }
 
+    /// Installs `agent` as a job, behind any other install of it.
+    fn start_install(
+        &self,
+        agent: AgentId,
+        options: InstallOptions,
+    ) -> (
+        InstallJob,
+        tokio::task::JoinHandle<Result<InstallResult, SandboxError>>,
+    ) {
+        let manager = self.agent_manager.clone();
+        self.install_jobs
+            .start(agent, options.version.clone(), move |progress| {
+                let options = InstallOptions {
+                    progress: Some(progress),
+                    ..options
+                };
+                adapter(agent)
+                    .install(&manager, options)
+                    .map_err(|err| map_install_error(agent, err))
+            })
+    }
+
+    /// Sessions that have not ended and run `agent`, or only `version` of it.
+    async fn sessions_using(&self, agent: AgentId, version: Option<&str>) -> Vec<String> {
+        self.sessions
+            .lock()
+            .await
+            .iter()
+            .filter(|session| session.agent == agent && !session.ended)
+            .filter(|session| version.is_none() || session.agent_version.as_deref() == version)
+            .map(|session| session.session_id.clone())
+            .collect()
+    }
+
     fn session_ref<'a>(sessions: &'a [SessionState], session_id: &str) -> Option<&'a SessionState> {
         sessions
             .iter()
@@ -1842,10 +2519,46 @@ impl SessionManager {
     pub(crate) async fn create_session(
         self: &Arc<Self>,
         session_id: String,
-        request: CreateSessionRequest,
+        mut request: CreateSessionRequest,
+        caller: Option<&Caller>,
     ) -> Result<CreateSessionResponse, SandboxError> {
         let agent_id = parse_agent_id(&request.agent)?;
+        let pinned = self.agent_manager.pinned_version(agent_id);
+        // `latest` selects the unversioned install.
+        let differs_from_pinned = request
+            .agent_version
+            .as_deref()
+            .is_some_and(|version| (version != "latest").then_some(version) != pinned);
+        if agent_uses_shared_server(agent_id) && differs_from_pinned {
+            return Err(SandboxError::InvalidRequest {
+                message: format!(
+                    "{agent_id} sessions share one server; pin its version in the lockfile instead"
+                ),
+            });
+        }
+        // A shared server would carry every session's traffic, so it cannot be
+        // held to one session's allowlist; refuse rather than run unrestricted.
+        if agent_uses_shared_server(agent_id)
+            && (request.egress.is_some() || self.session_config.restrict_egress)
         {
+            return Err(SandboxError::InvalidRequest {
+                message: format!(
+                    "egress restrictions are not supported for {agent_id}: sessions share one server process"
+                ),
+            });
+        }
+        // Shared servers are spawned once with the daemon's limits for the agent.
+        if agent_uses_shared_server(agent_id) && request.limits.is_some() {
+            return Err(SandboxError::InvalidRequest {
+                message: format!(
+                    "limits are not supported for {agent_id}: sessions share one server process"
+                ),
+            });
+        }
+        if request.agent_version.is_none() {
+            request.agent_version = pinned.map(str::to_string);
+        }
+        let _slot = {
             let sessions = self.sessions.lock().await;
             if sessions
                 .iter()
@@ -1853,49 +2566,260 @@ impl SessionManager {
             {
                 return Err(SandboxError::SessionAlreadyExists { session_id });
             }
-        }
+            let active = sessions.iter().filter(|session| !session.ended);
+            let for_caller = caller.map_or(0, |caller| {
+                active
+                    .clone()
+                    .filter(|session| session.owner.as_ref() == Some(&caller.key))
+                    .count()
+            });
+            self.rate_limiter
+                .reserve_session(caller, active.count(), for_caller)?
+        };
 
-        if agent_id != AgentId::Mock {
-            let manager = self.agent_manager.clone();
-            let agent_version = request.agent_version.clone();
-            let agent_name = request.agent.clone();
-            let install_result = tokio::task::spawn_blocking(move || {
-                manager.install(
-                    agent_id,
-                    InstallOptions {
-                        reinstall: false,
-                        version: agent_version,
-                    },
-                )
-            })
-            .await
-            .map_err(|err| SandboxError::InstallFailed {
-                agent: agent_name,
+        let installed = |manager: &AgentManager| {
+            agent_id == AgentId::Mock
+                || manager
+                    .resolve_binary_version(agent_id, request.agent_version.as_deref())
+                    .is_ok()
+        };
+        // Held until the session is registered, so an uninstall either finds it or
+        // finishes before it starts.
+        let installs = self.install_jobs.shared(agent_id).await;
+        let _installs = if installed(&self.agent_manager) {
+            installs
+        } else {
+            drop(installs);
+            let (_, install) = self.start_install(
+                agent_id,
+                InstallOptions {
+                    reinstall: false,
+                    version: request.agent_version.clone(),
+                    sha256: None,
+                    from: None,
+                    mirror: None,
+                    progress: None,
+                },
+            );
+            install.await.map_err(|err| SandboxError::InstallFailed {
+                agent: request.agent.clone(),
                 stderr: Some(err.to_string()),
+            })??;
+            let installs = self.install_jobs.shared(agent_id).await;
+            if !installed(&self.agent_manager) {
+                return Err(SandboxError::AgentNotInstalled {
+                    agent: request.agent.clone(),
+                });
+            }
+            installs
+        };
+
+        let home = self
+            .create_session_home(agent_id, &session_id, request.isolate_home)
+            .await?;
+        let egress = match self
+            .register_session_egress(agent_id, &session_id, request.egress.as_ref())
+            .await
+        {
+            Ok(egress) => egress,
+            Err(err) => {
+                self.release_session_resources(home, None).await;
+                return Err(err);
+            }
+        };
+        let result = self
+            .create_session_prepared(
+                session_id.clone(),
+                agent_id,
+                request,
+                home.clone(),
+                egress.clone(),
+            )
+            .await;
+        match &result {
+            Ok(_) => {
+                metrics()
+                    .sessions_created
+                    .with_label_values(&[agent_id.as_str()])
+                    .inc();
+                if let Some(caller) = caller {
+                    let mut sessions = self.sessions.lock().await;
+                    if let Some(session) = Self::session_mut(&mut sessions, &session_id) {
+                        session.owner = Some(caller.key.clone());
+                    }
+                }
+            }
+            Err(_) => self.release_session_resources(home, egress).await,
+        }
+        result
+    }
+
+    async fn release_session_resources(
+        &self,
+        home: Option<SessionHome>,
+        egress: Option<EgressLease>,
+    ) {
+        if let Some(egress) = egress {
+            if let Some(proxy) = self.egress_proxy.get() {
+                proxy.unregister(&egress);
+            }
+        }
+        if let Some(home) = home {
+            remove_session_home(home).await;
+        }
+    }
+
+    /// Registers the session with the egress proxy when it has a policy or the server
+    /// restricts egress by default. Provider hosts for the agent are allowed unless disabled.
+    async fn register_session_egress(
+        self: &Arc<Self>,
+        agent: AgentId,
+        session_id: &str,
+        policy: Option<&SessionEgressPolicy>,
+    ) -> Result<Option<EgressLease>, SandboxError> {
+        if policy.is_none() && !self.session_config.restrict_egress {
+            return Ok(None);
+        }
+        let mut patterns = self.session_config.egress_allow.clone();
+        if policy.and_then(|policy| policy.allow_provider_hosts) != Some(false) {
+            patterns.extend(provider_hosts(agent).iter().map(|host| host.to_string()));
+        }
+        if let Some(policy) = policy {
+            patterns.extend(policy.allow.iter().cloned());
+        }
+        let allow = patterns
+            .iter()
+            .map(|pattern| HostPattern::parse(pattern))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|message| SandboxError::InvalidRequest { message })?;
+        let proxy = self.egress_proxy().await?;
+        let lease = proxy
+            .register(session_id, allow)
+            .map_err(|err| SandboxError::StreamError {
+                message: format!("failed to register egress policy: {err}"),
             })?;
-            install_result.map_err(|err| map_install_error(agent_id, err))?;
+        Ok(Some(lease))
+    }
+
+    /// Starts the egress proxy on first use and records its connection log on sessions.
+    async fn egress_proxy(self: &Arc<Self>) -> Result<Arc<EgressProxy>, SandboxError> {
+        let manager = Arc::downgrade(self);
+        self.egress_proxy
+            .get_or_try_init(|| async move {
+                let (proxy, mut events) =
+                    EgressProxy::start()
+                        .await
+                        .map_err(|err| SandboxError::StreamError {
+                            message: format!("failed to start egress proxy: {err}"),
+                        })?;
+                tokio::spawn(async move {
+                    while let Some(event) = events.recv().await {
+                        let Some(manager) = manager.upgrade() else {
+                            break;
+                        };
+                        let session_id = event.session_id.clone();
+                        let _ = manager
+                            .record_conversions(&session_id, vec![egress_status_event(event)])
+                            .await;
+                    }
+                });
+                Ok(proxy)
+            })
+            .await
+            .cloned()
+    }
+
+    /// Creates and seeds a private HOME when isolation is requested or enabled by default.
+    /// Agents with a shared server (Codex, OpenCode, ACP) cannot be isolated.
+    async fn create_session_home(
+        &self,
+        agent: AgentId,
+        session_id: &str,
+        requested: Option<bool>,
+    ) -> Result<Option<SessionHome>, SandboxError> {
+        let shared_server = agent_uses_shared_server(agent);
+        let isolate = requested.unwrap_or(self.session_config.isolate_home && !shared_server);
+        if !isolate {
+            return Ok(None);
+        }
+        if shared_server {
+            return Err(SandboxError::InvalidRequest {
+                message: format!(
+                    "isolateHome is not supported for {}: sessions share one server process",
+                    agent.as_str()
+                ),
+            });
         }
+        let state_dir = self
+            .session_config
+            .state_dir
+            .clone()
+            .unwrap_or_else(default_state_dir);
+        let id = session_id.to_string();
+        let home = tokio::task::spawn_blocking(move || {
+            let home = SessionHome::create(&state_dir, &id)?;
+            if let Some(source_home) = dirs::home_dir() {
+                if let Err(err) = home.seed_credentials(&source_home) {
+                    let _ = home.remove();
+                    return Err(err);
+                }
+            }
+            Ok(home)
+        })
+        .await
+        .map_err(|err| SandboxError::StreamError {
+            message: err.to_string(),
+        })?
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::InvalidInput => SandboxError::InvalidRequest {
+                message: err.to_string(),
+            },
+            std::io::ErrorKind::AlreadyExists => SandboxError::SessionAlreadyExists {
+                session_id: session_id.to_string(),
+            },
+            _ => SandboxError::StreamError {
+                message: format!("failed to create session home: {err}"),
+            },
+        })?;
+        Ok(Some(home))
+    }
 
+    async fn create_session_prepared(
+        self: &Arc<Self>,
+        session_id: String,
+        agent_id: AgentId,
+        request: CreateSessionRequest,
+        home: Option<SessionHome>,
+        egress: Option<EgressLease>,
+    ) -> Result<CreateSessionResponse, SandboxError> {
         let skill_dirs = if let Some(skills) = &request.skills {
             let sources = skills.sources.clone();
+            let link_root = home
+                .as_ref()
+                .map(|home| home.path().to_path_buf())
+                .or_else(|| request.directory.as_ref().map(PathBuf::from));
             Some(
-                tokio::task::spawn_blocking(move || install_skill_sources(&sources))
-                    .await
-                    .map_err(|err| SandboxError::StreamError {
-                        message: err.to_string(),
-                    })??,
+                tokio::task::spawn_blocking(move || match link_root {
+                    Some(link_root) => install_skill_sources_into(&sources, &link_root),
+                    None => install_skill_sources(&sources),
+                })
+                .await
+                .map_err(|err| SandboxError::StreamError {
+                    message: err.to_string(),
+                })??,
             )
         } else {
             None
         };
 
         if let Some(mcp) = &request.mcp {
-            self.apply_mcp_config(agent_id, mcp).await?;
+            self.apply_mcp_config(agent_id, mcp, home.as_ref()).await?;
         }
 
-        if agent_id == AgentId::Opencode {
+        let server_kind = adapter(agent_id).server_kind();
+        if server_kind == ServerKind::OpencodeHttp {
             if let Some(skill_dirs) = skill_dirs.as_ref() {
-                self.apply_opencode_skills(skill_dirs).await?;
+                self.apply_opencode_skills(agent_id, skill_dirs).await?;
             }
         }
 
@@ -1911,12 +2835,17 @@ impl SessionManager {
         };
 
         let mut session = SessionState::new(session_id.clone(), agent_id, &request)?;
-        if agent_id == AgentId::Opencode {
-            let opencode_session_id = self.create_opencode_session().await?;
+        session.home = home;
+        session.egress = egress;
+        if request.debug_log.unwrap_or(false) {
+            session.debug_log = Some(Arc::new(DebugLog::new(self.redactor.clone())));
+        }
+        if server_kind == ServerKind::OpencodeHttp {
+            let opencode_session_id = self.create_opencode_session(agent_id).await?;
             session.native_session_id = Some(opencode_session_id);
         }
-        if agent_id == AgentId::Codex {
-            // Create a thread in the shared Codex app-server
+        if matches!(server_kind, ServerKind::CodexAppServer | ServerKind::Acp) {
+            // Create a thread in the shared Codex app-server, or a session in the ACP server
             let snapshot = SessionSnapshot {
                 session_id: session_id.clone(),
                 agent: agent_id,
@@ -1924,10 +2853,24 @@ impl SessionManager {
                 permission_mode: session.permission_mode.clone(),
                 model: session.model.clone(),
                 variant: session.variant.clone(),
+                agent_version: session.agent_version.clone(),
                 native_session_id: None,
+                limits: session.limits,
+                home: None,
+                egress: None,
+                debug_log: session.debug_log.clone(),
             };
-            let thread_id = self.create_codex_thread(&session_id, &snapshot).await?;
-            session.native_session_id = Some(thread_id);
+            if server_kind == ServerKind::CodexAppServer {
+                let thread_id = self.create_codex_thread(&session_id, &snapshot).await?;
+                session.native_session_id = Some(thread_id);
+            } else {
+                let (acp_session_id, stdin_sender) = self
+                    .create_acp_session(&session_id, &snapshot, request.mcp.as_ref())
+                    .await?;
+                session.native_session_id = Some(acp_session_id);
+                // Permission replies are JSON-RPC responses written to the shared server.
+                session.set_stdin_sender(Some(stdin_sender));
+            }
         }
         if agent_id == AgentId::Mock {
             session.native_session_id = Some(format!("mock-{session_id}"));
@@ -1962,23 +2905,35 @@ impl SessionManager {
         }
 
         let native_session_id = session.native_session_id.clone();
+        let limits = session.limits;
         let mut sessions = self.sessions.lock().await;
         sessions.push(session);
         drop(sessions);
-        if agent_id == AgentId::Opencode || agent_id == AgentId::Codex {
+        if agent_uses_shared_server(agent_id) {
             self.server_manager
                 .register_session(agent_id, &session_id, native_session_id.as_deref())
                 .await;
         }
 
-        if agent_id == AgentId::Opencode {
+        if server_kind == ServerKind::OpencodeHttp {
             self.ensure_opencode_stream(session_id).await?;
         }
 
+        let limits_fallback = if agent_id == AgentId::Mock {
+            None
+        } else {
+            let manager = self.agent_manager.clone();
+            tokio::task::spawn_blocking(move || manager.limits_fallback(agent_id, &limits))
+                .await
+                .ok()
+                .flatten()
+        };
+
         Ok(CreateSessionResponse {
             healthy: true,
             error: None,
             native_session_id,
+            limits_fallback,
         })
     }
 
@@ -1986,50 +2941,46 @@ impl SessionManager {
         self: &Arc<Self>,
         agent_id: AgentId,
         mcp: &BTreeMap<String, McpServerConfig>,
+        home: Option<&SessionHome>,
     ) -> Result<(), SandboxError> {
         if mcp.is_empty() {
             return Ok(());
         }
-        match agent_id {
-            AgentId::Claude => {
-                let mcp = mcp.clone();
-                tokio::task::spawn_blocking(move || write_claude_mcp_config(&mcp))
-                    .await
-                    .map_err(|err| SandboxError::StreamError {
-                        message: err.to_string(),
-                    })??;
-                Ok(())
-            }
-            AgentId::Codex => {
-                let mcp = mcp.clone();
-                tokio::task::spawn_blocking(move || write_codex_mcp_config(&mcp))
-                    .await
-                    .map_err(|err| SandboxError::StreamError {
-                        message: err.to_string(),
-                    })??;
-                let server = self.ensure_codex_server().await?;
-                self.reload_codex_mcp(&server).await
-            }
-            AgentId::Opencode => self.apply_opencode_mcp(mcp).await,
-            AgentId::Amp => {
+        match adapter(agent_id).server_kind() {
+            ServerKind::OpencodeHttp => self.apply_opencode_mcp(agent_id, mcp).await,
+            kind => {
                 let agent_manager = self.agent_manager.clone();
                 let mcp = mcp.clone();
-                tokio::task::spawn_blocking(move || apply_amp_mcp_config(&agent_manager, &mcp))
-                    .await
-                    .map_err(|err| SandboxError::StreamError {
-                        message: err.to_string(),
-                    })??;
+                let home = home.map(|home| (home.path().to_path_buf(), home.env()));
+                tokio::task::spawn_blocking(move || {
+                    let home = home.as_ref().map(|(path, env)| SessionHomeRef {
+                        path,
+                        env: env.clone(),
+                    });
+                    adapter(agent_id).write_mcp_config(&agent_manager, &mcp, home.as_ref())
+                })
+                .await
+                .map_err(|err| SandboxError::StreamError {
+                    message: err.to_string(),
+                })??;
+                if kind == ServerKind::CodexAppServer {
+                    let server = self.ensure_codex_server(agent_id).await?;
+                    self.reload_codex_mcp(&server).await?;
+                }
                 Ok(())
             }
-            AgentId::Mock => Ok(()),
         }
     }
 
-    async fn apply_opencode_skills(&self, skill_dirs: &[PathBuf]) -> Result<(), SandboxError> {
+    async fn apply_opencode_skills(
+        &self,
+        agent: AgentId,
+        skill_dirs: &[PathBuf],
+    ) -> Result<(), SandboxError> {
         if skill_dirs.is_empty() {
             return Ok(());
         }
-        let base_url = self.ensure_opencode_server().await?;
+        let base_url = self.ensure_opencode_server(agent).await?;
         let url = format!("{base_url}/config");
         let response = self.http_client.get(&url).send().await;
         let mut existing_paths = Vec::<String>::new();
@@ -2093,6 +3044,7 @@ impl SessionManager {
 
     async fn clear_codex_session_model_if_unavailable(
         &self,
+        agent: AgentId,
         session_id: &str,
         model_id: &str,
     ) -> bool {
@@ -2100,7 +3052,7 @@ impl SessionManager {
         let Some(session) = SessionManager::session_mut(&mut sessions, session_id) else {
             return false;
         };
-        if session.agent == AgentId::Codex && session.model.as_deref() == Some(model_id) {
+        if session.agent == agent && session.model.as_deref() == Some(model_id) {
             session.model = None;
             session.updated_at = std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -2111,15 +3063,15 @@ impl SessionManager {
         false
     }
 
-    async fn invalidate_codex_model_cache(&self) {
+    async fn invalidate_codex_model_cache(&self, agent: AgentId) {
         let mut catalog = self.model_catalog.lock().await;
-        catalog.models.remove(&AgentId::Codex);
+        catalog.models.remove(&agent);
     }
 
-    async fn codex_native_session_id(&self, session_id: &str) -> Option<String> {
+    async fn codex_native_session_id(&self, agent: AgentId, session_id: &str) -> Option<String> {
         let sessions = self.sessions.lock().await;
         let session = SessionManager::session_ref(&sessions, session_id)?;
-        if session.agent != AgentId::Codex {
+        if session.agent != agent {
             return None;
         }
         session.native_session_id.clone()
@@ -2127,6 +3079,7 @@ impl SessionManager {
 
     async fn handle_codex_model_unavailable(
         &self,
+        agent: AgentId,
         session_id: &str,
         model_id: &str,
         native_session_id: Option<String>,
@@ -2135,16 +3088,16 @@ impl SessionManager {
             model_id = %model_id,
             "codex model rejected at runtime; clearing session model and refreshing model cache"
         );
-        self.invalidate_codex_model_cache().await;
+        self.invalidate_codex_model_cache(agent).await;
         if !self
-            .clear_codex_session_model_if_unavailable(session_id, model_id)
+            .clear_codex_session_model_if_unavailable(agent, session_id, model_id)
             .await
         {
             return;
         }
         let native_session_id = match native_session_id {
             Some(native_session_id) => Some(native_session_id),
-            None => self.codex_native_session_id(session_id).await,
+            None => self.codex_native_session_id(agent, session_id).await,
         };
         let _ = self
             .record_conversions(
@@ -2158,7 +3111,7 @@ impl SessionManager {
     }
 
     pub(crate) async fn delete_session(&self, session_id: &str) -> Result<(), SandboxError> {
-        let (agent, native_session_id) = {
+        let (agent, native_session_id, home, egress) = {
             let mut sessions = self.sessions.lock().await;
             let Some(index) = sessions
                 .iter()
@@ -2169,24 +3122,31 @@ impl SessionManager {
                 });
             };
             let session = sessions.remove(index);
-            (session.agent, session.native_session_id)
+            (
+                session.agent,
+                session.native_session_id,
+                session.home,
+                session.egress,
+            )
         };
 
-        if agent == AgentId::Opencode || agent == AgentId::Codex {
+        if agent_uses_shared_server(agent) {
             self.server_manager
                 .unregister_session(agent, session_id, native_session_id.as_deref())
                 .await;
         }
+        adapter(agent).session_removed(session_id);
+        self.release_session_resources(home, egress).await;
 
         Ok(())
     }
 
     async fn agent_modes(&self, agent: AgentId) -> Result<Vec<AgentModeInfo>, SandboxError> {
-        if agent != AgentId::Opencode {
+        if adapter(agent).server_kind() != ServerKind::OpencodeHttp {
             return Ok(agent_modes_for(agent));
         }
 
-        match self.fetch_opencode_modes().await {
+        match self.fetch_opencode_modes(agent).await {
             Ok(mut modes) => {
                 ensure_custom_mode(&mut modes);
                 if modes.is_empty() {
@@ -2231,7 +3191,7 @@ impl SessionManager {
                     let mut catalog = self.model_catalog.lock().await;
                     catalog.in_flight.remove(&agent);
                     if let Ok(response_value) = &response {
-                        if should_cache_agent_models(agent, response_value) {
+                        if adapter(agent).cache_models(response_value) {
                             catalog.models.insert(agent, response_value.clone());
                         }
                     }
@@ -2246,21 +3206,29 @@ impl SessionManager {
         self: &Arc<Self>,
         agent: AgentId,
     ) -> Result<AgentModelsResponse, SandboxError> {
-        match agent {
-            AgentId::Claude => match self.fetch_claude_models().await {
-                Ok(response) if !response.models.is_empty() => Ok(response),
-                _ => Ok(claude_fallback_models()),
-            },
-            AgentId::Codex => self.fetch_codex_models().await,
-            AgentId::Opencode => match self.fetch_opencode_models().await {
+        let adapter = adapter(agent);
+        match adapter.server_kind() {
+            ServerKind::CodexAppServer => self.fetch_codex_models(agent).await,
+            ServerKind::OpencodeHttp => match self.fetch_opencode_models(agent).await {
                 Ok(models) => Ok(models),
                 Err(_) => Ok(AgentModelsResponse {
                     models: Vec::new(),
                     default_model: None,
                 }),
             },
-            AgentId::Amp => Ok(amp_models_response()),
-            AgentId::Mock => Ok(mock_models_response()),
+            _ => {
+                let fetch = match self.extract_credentials().await {
+                    Ok(credentials) => adapter.fetch_models(self.http_client.clone(), credentials),
+                    Err(_) => None,
+                };
+                let fetched = match fetch {
+                    Some(fetch) => fetch.await.ok(),
+                    None => None,
+                };
+                Ok(fetched
+                    .filter(|response| !response.models.is_empty())
+                    .unwrap_or_else(|| adapter.models()))
+            }
         }
     }
 
@@ -2269,14 +3237,81 @@ impl SessionManager {
         session_id: String,
         message: String,
         attachments: Vec<MessageAttachment>,
+        caller: Option<&Caller>,
     ) -> Result<(), SandboxError> {
         // Use allow_ended=true and do explicit check to allow resumable agents
         let session_snapshot = self.session_snapshot_for_message(&session_id).await?;
-        let prompt_with_attachments = format_message_with_attachments(&message, &attachments);
-        let prompt = if session_snapshot.agent == AgentId::Opencode {
+        self.begin_turn(&session_id, caller).await?;
+        let result = self
+            .send_message_inner(session_id.clone(), session_snapshot, message, attachments)
+            .await;
+        if result.is_err() {
+            self.end_turn(&session_id).await;
+        }
+        result
+    }
+
+    /// Marks a turn as in progress, enforcing the active turn caps. A message sent while
+    /// the session's turn is still running does not count as another turn.
+    async fn begin_turn(
+        &self,
+        session_id: &str,
+        caller: Option<&Caller>,
+    ) -> Result<(), SandboxError> {
+        let mut sessions = self.sessions.lock().await;
+        let others = sessions
+            .iter()
+            .filter(|session| session.turn_active && session.session_id != session_id);
+        let for_caller = caller.map_or(0, |caller| {
+            others
+                .clone()
+                .filter(|session| session.turn_owner.as_ref() == Some(&caller.key))
+                .count()
+        });
+        let already_active =
+            Self::session_ref(&sessions, session_id).is_some_and(|session| session.turn_active);
+        if !already_active {
+            self.rate_limiter
+                .check_cap(Cap::ActiveTurns, caller, others.count(), for_caller)?;
+        }
+        if let Some(session) = Self::session_mut(&mut sessions, session_id) {
+            session.turn_active = true;
+            if !already_active {
+                session.turn_owner = caller.map(|caller| caller.key.clone());
+                session.turn_started_at = Some(Instant::now());
+                session.start_turn_span();
+                metrics()
+                    .turns
+                    .with_label_values(&[session.agent.as_str()])
+                    .inc();
+            }
+        }
+        Ok(())
+    }
+
+    async fn end_turn(&self, session_id: &str) {
+        let mut sessions = self.sessions.lock().await;
+        if let Some(session) = Self::session_mut(&mut sessions, session_id) {
+            session.turn_active = false;
+            session.turn_owner = None;
+            session.turn_started_at = None;
+            session.end_turn_span(true);
+        }
+    }
+
+    async fn send_message_inner(
+        self: &Arc<Self>,
+        session_id: String,
+        session_snapshot: SessionSnapshot,
+        message: String,
+        attachments: Vec<MessageAttachment>,
+    ) -> Result<(), SandboxError> {
+        let server_kind = adapter(session_snapshot.agent).server_kind();
+        // OpenCode takes attachments as message parts rather than in the prompt text.
+        let prompt = if server_kind == ServerKind::OpencodeHttp {
             message.clone()
         } else {
-            prompt_with_attachments
+            format_message_with_attachments(&message, &attachments)
         };
         if !agent_emits_turn_started(session_snapshot.agent) {
             let _ = self
@@ -2290,12 +3325,13 @@ impl SessionManager {
             self.send_mock_message(session_id, prompt).await?;
             return Ok(());
         }
-        if matches!(session_snapshot.agent, AgentId::Claude | AgentId::Amp) {
+        // The shared Codex and OpenCode servers echo the user's message themselves.
+        if matches!(server_kind, ServerKind::PerTurn | ServerKind::Acp) {
             let _ = self
                 .record_conversions(&session_id, user_message_conversions(&prompt))
                 .await;
         }
-        if session_snapshot.agent == AgentId::Opencode {
+        if server_kind == ServerKind::OpencodeHttp {
             self.ensure_opencode_stream(session_id.clone()).await?;
             self.send_opencode_prompt(&session_snapshot, &prompt, &attachments)
                 .await?;
@@ -2306,7 +3342,7 @@ impl SessionManager {
             }
             return Ok(());
         }
-        if session_snapshot.agent == AgentId::Codex {
+        if server_kind == ServerKind::CodexAppServer {
             // Use the shared Codex app-server
             self.send_codex_turn(&session_snapshot, &prompt).await?;
             if !agent_supports_item_started(session_snapshot.agent) {
@@ -2316,29 +3352,23 @@ impl SessionManager {
             }
             return Ok(());
         }
+        if server_kind == ServerKind::Acp {
+            self.send_acp_prompt(&session_snapshot, &prompt).await?;
+            return Ok(());
+        }
 
         // Reopen the session if it was ended (for resumable agents)
         self.reopen_session_if_ended(&session_id).await;
 
         let manager = self.agent_manager.clone();
-        let initial_input = if session_snapshot.agent == AgentId::Claude {
-            Some(claude_user_message_line(&session_snapshot, &prompt))
-        } else {
-            None
-        };
-        let credentials = tokio::task::spawn_blocking(move || {
-            let options = CredentialExtractionOptions::new();
-            extract_all_credentials(&options)
-        })
-        .await
-        .map_err(|err| SandboxError::StreamError {
-            message: err.to_string(),
-        })?;
+        let initial_input = adapter(session_snapshot.agent)
+            .initial_input(&prompt, &input_session_id(&session_snapshot));
+        let credentials = self.extract_credentials().await?;
 
         let spawn_options = build_spawn_options(&session_snapshot, prompt.clone(), credentials);
         let agent_id = session_snapshot.agent;
         let spawn_result =
-            tokio::task::spawn_blocking(move || manager.spawn_streaming(agent_id, spawn_options))
+            tokio::task::spawn_blocking(move || adapter(agent_id).spawn(&manager, spawn_options))
                 .await
                 .map_err(|err| SandboxError::StreamError {
                     message: err.to_string(),
@@ -2361,6 +3391,121 @@ impl SessionManager {
         Ok(())
     }
 
+    /// Runs a recorded fixture through the handlers live sessions use for agent output.
+    async fn replay(
+        self: &Arc<Self>,
+        fixture: &ReplayFixture,
+    ) -> Result<Vec<UniversalEvent>, SandboxError> {
+        let session_id = "replay";
+        let request: CreateSessionRequest =
+            serde_json::from_value(json!({ "agent": fixture.agent.as_str() })).map_err(|err| {
+                SandboxError::InvalidRequest {
+                    message: err.to_string(),
+                }
+            })?;
+        let mut session = SessionState::new(session_id.to_string(), fixture.agent, &request)?;
+        session.native_session_id = fixture.native_session_id.clone();
+        self.sessions.lock().await.push(session);
+
+        match fixture.agent {
+            AgentId::Mock => {
+                return Err(SandboxError::InvalidRequest {
+                    message: "mock sessions have no native output to replay".to_string(),
+                });
+            }
+            agent if adapter(agent).server_kind() == ServerKind::CodexAppServer => {
+                self.replay_codex(session_id, fixture).await
+            }
+            agent if adapter(agent).server_kind() == ServerKind::OpencodeHttp => {
+                let native_session_id = fixture.native_session_id.as_deref().ok_or_else(|| {
+                    SandboxError::InvalidRequest {
+                        message: "OpenCode fixtures need a nativeSessionId".to_string(),
+                    }
+                })?;
+                for (direction, line) in &fixture.lines {
+                    if *direction == DebugDirection::Event {
+                        self.handle_opencode_event(session_id, native_session_id, line, None)
+                            .await;
+                    }
+                }
+            }
+            agent => {
+                for (direction, line) in &fixture.lines {
+                    if matches!(direction, DebugDirection::Stdout | DebugDirection::Stderr) {
+                        self.handle_process_line(agent, session_id, line).await;
+                    }
+                }
+            }
+        }
+
+        let sessions = self.sessions.lock().await;
+        Ok(Self::session_ref(&sessions, session_id)
+            .map(|session| session.events.clone())
+            .unwrap_or_default())
+    }
+
+    /// Replays Codex app-server output, routing every thread and request in the
+    /// fixture to the replay session as the shared server would.
+    async fn replay_codex(self: &Arc<Self>, session_id: &str, fixture: &ReplayFixture) {
+        let (stdin_tx, _stdin_rx) = mpsc::unbounded_channel();
+        let server = Arc::new(StdioServer::new(stdin_tx));
+        let (stdout_tx, stdout_rx) = mpsc::unbounded_channel();
+        for (direction, line) in &fixture.lines {
+            let Ok(value) = serde_json::from_str::<Value>(line) else {
+                if *direction == DebugDirection::Stdout {
+                    let _ = stdout_tx.send(line.clone());
+                }
+                continue;
+            };
+            match direction {
+                DebugDirection::Stdin if value.get("method").is_some() => {
+                    if let Some(id) = value.get("id").and_then(Value::as_i64) {
+                        let mut sessions = server.request_sessions.lock().unwrap();
+                        sessions.insert(id, session_id.to_string());
+                    }
+                }
+                DebugDirection::Stdout => {
+                    let thread_id = serde_json::from_value(value.clone())
+                        .ok()
+                        .and_then(|notification| {
+                            codex_thread_id_from_server_notification(&notification)
+                        })
+                        .or_else(|| {
+                            serde_json::from_value(value)
+                                .ok()
+                                .and_then(|request| codex_thread_id_from_server_request(&request))
+                        });
+                    if let Some(thread_id) = thread_id {
+                        server.register_thread(thread_id, session_id.to_string());
+                    }
+                    let _ = stdout_tx.send(line.clone());
+                }
+                _ => {}
+            }
+        }
+        drop(stdout_tx);
+        Arc::clone(self)
+            .handle_codex_server_output(fixture.agent, server, stdout_rx)
+            .await;
+    }
+
+    /// Converts one output line of a per-turn agent process into session events.
+    async fn handle_process_line(&self, agent: AgentId, session_id: &str, line: &str) {
+        // Closing stdin after the turn's final line lets the process exit.
+        if let Ok(value) = serde_json::from_str::<Value>(line) {
+            if adapter(agent).ends_turn(&value) {
+                let mut sessions = self.sessions.lock().await;
+                if let Some(session) = Self::session_mut(&mut sessions, session_id) {
+                    session.set_stdin_sender(None);
+                }
+            }
+        }
+        let conversions = parse_agent_line(agent, line, session_id);
+        if !conversions.is_empty() {
+            let _ = self.record_conversions(session_id, conversions).await;
+        }
+    }
+
     async fn emit_synthetic_assistant_start(&self, session_id: &str) -> Result<(), SandboxError> {
         let conversion = {
             let mut sessions = self.sessions.lock().await;
@@ -2377,6 +3522,18 @@ impl SessionManager {
         Ok(())
     }
 
+    async fn session_debug_log(&self, session_id: &str) -> Option<Arc<DebugLog>> {
+        let sessions = self.sessions.lock().await;
+        Self::session_ref(&sessions, session_id)?.debug_log.clone()
+    }
+
+    /// Appends a native line to the session's debug log, if it keeps one.
+    async fn record_debug_line(&self, session_id: &str, direction: DebugDirection, line: &str) {
+        if let Some(log) = self.session_debug_log(session_id).await {
+            log.record(direction, line);
+        }
+    }
+
     /// Reopens a session that was ended by an agent process completing.
     /// This allows resumable agents (Claude, Amp, OpenCode) to continue conversations.
     async fn reopen_session_if_ended(&self, session_id: &str) {
@@ -2423,15 +3580,40 @@ impl SessionManager {
         session.record_conversions(vec![ended]);
         let agent = session.agent;
         let native_session_id = session.native_session_id.clone();
+        let home = session.home.take();
+        let egress = session.egress.take();
         drop(sessions);
-        if agent == AgentId::Opencode || agent == AgentId::Codex {
+        if let Some(acp_session_id) = native_session_id
+            .as_deref()
+            .filter(|_| adapter(agent).server_kind() == ServerKind::Acp)
+        {
+            self.cancel_acp_session(agent, acp_session_id).await;
+        }
+        if agent_uses_shared_server(agent) {
             self.server_manager
                 .unregister_session(agent, &session_id, native_session_id.as_deref())
                 .await;
         }
+        self.release_session_resources(home, egress).await;
         Ok(())
     }
 
+    async fn debug_log(&self, session_id: &str) -> Result<String, SandboxError> {
+        let log = {
+            let sessions = self.sessions.lock().await;
+            let session = Self::session_ref(&sessions, session_id).ok_or_else(|| {
+                SandboxError::SessionNotFound {
+                    session_id: session_id.to_string(),
+                }
+            })?;
+            session.debug_log.clone()
+        };
+        let log = log.ok_or_else(|| SandboxError::InvalidRequest {
+            message: "session was created without debugLog".to_string(),
+        })?;
+        Ok(log.to_ndjson())
+    }
+
     async fn events(
         &self,
         session_id: &str,
@@ -2474,6 +3656,11 @@ impl SessionManager {
         Ok(EventsResponse { events, has_more })
     }
 
+    pub(crate) async fn active_session_count(&self) -> usize {
+        let sessions = self.sessions.lock().await;
+        sessions.iter().filter(|session| !session.ended).count()
+    }
+
     pub(crate) async fn list_sessions(&self) -> Vec<SessionInfo> {
         let sessions = self.sessions.lock().await;
         sessions
@@ -2496,6 +3683,7 @@ impl SessionManager {
             permission_mode: state.permission_mode.clone(),
             model: state.model.clone(),
             variant: state.variant.clone(),
+            agent_version: state.agent_version.clone(),
             native_session_id: state.native_session_id.clone(),
             ended: state.ended,
             event_count: state.events.len() as u64,
@@ -2598,7 +3786,7 @@ impl SessionManager {
         question_id: &str,
         answers: Vec<Vec<String>>,
     ) -> Result<(), SandboxError> {
-        let (agent, native_session_id, pending_question, claude_sender, linked_permission) = {
+        let (agent, native_session_id, pending_question, stdin_sender, linked_permission) = {
             let mut sessions = self.sessions.lock().await;
             let session = Self::session_mut(&mut sessions, session_id).ok_or_else(|| {
                 SandboxError::SessionNotFound {
@@ -2614,85 +3802,42 @@ impl SessionManager {
             if let Some(err) = session.ended_error() {
                 return Err(err);
             }
-            // For Claude, check if there's a linked AskUserQuestion/ExitPlanMode permission
-            let linked_perm = if session.agent == AgentId::Claude {
-                session.take_question_tool_permission()
-            } else {
-                None
-            };
+            // Questions asked through an AskUserQuestion/ExitPlanMode permission (Claude)
+            let linked_perm = session.take_question_tool_permission();
             (
                 session.agent,
                 session.native_session_id.clone(),
                 pending,
-                session.claude_sender(),
+                session.stdin_sender(),
                 linked_perm,
             )
         };
 
         let response = answers.first().and_then(|inner| inner.first()).cloned();
 
-        if agent == AgentId::Opencode {
+        if adapter(agent).server_kind() == ServerKind::OpencodeHttp {
             let agent_session_id =
                 native_session_id
                     .clone()
                     .ok_or_else(|| SandboxError::InvalidRequest {
                         message: "missing OpenCode session id".to_string(),
                     })?;
-            self.opencode_question_reply(&agent_session_id, question_id, answers.clone())
+            self.opencode_question_reply(agent, &agent_session_id, question_id, answers.clone())
                 .await?;
-        } else if agent == AgentId::Claude {
-            let sender = claude_sender.ok_or_else(|| SandboxError::InvalidRequest {
-                message: "Claude session is not active".to_string(),
-            })?;
-            if let Some((perm_id, perm)) = &linked_permission {
-                // Use the permission control response to deliver the answer.
-                // Build updatedInput from the original input with the answers map added.
-                let original_input = perm
-                    .metadata
+        } else {
+            let native_sid = native_session_id.as_deref().unwrap_or(session_id);
+            let line = adapter(agent).question_reply_line(&QuestionReplyContext {
+                native_session_id: native_sid,
+                question_id,
+                answers: Some(&answers),
+                linked_permission_id: linked_permission.as_ref().map(|(id, _)| id.as_str()),
+                linked_permission_metadata: linked_permission
                     .as_ref()
-                    .and_then(|m| m.get("input"))
-                    .cloned()
-                    .unwrap_or(Value::Null);
-                let mut updated = match original_input {
-                    Value::Object(map) => map,
-                    _ => serde_json::Map::new(),
-                };
-                // Build answers map: { "0": "selected option", "1": "another option", ... }
-                let answers_map: serde_json::Map<String, Value> = answers
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(i, inner)| {
-                        inner
-                            .first()
-                            .map(|v| (i.to_string(), Value::String(v.clone())))
-                    })
-                    .collect();
-                updated.insert("answers".to_string(), Value::Object(answers_map));
-
-                let mut response_map = serde_json::Map::new();
-                response_map.insert("updatedInput".to_string(), Value::Object(updated));
-                let line =
-                    claude_control_response_line(perm_id, "allow", Value::Object(response_map));
-                sender
-                    .send(line)
-                    .map_err(|_| SandboxError::InvalidRequest {
-                        message: "Claude session is not active".to_string(),
-                    })?;
-            } else {
-                // No linked permission — fall back to tool_result
-                let native_sid = native_session_id
-                    .clone()
-                    .unwrap_or_else(|| session_id.to_string());
-                let response_text = response.clone().unwrap_or_default();
-                let line = claude_tool_result_line(&native_sid, question_id, &response_text, false);
-                sender
-                    .send(line)
-                    .map_err(|_| SandboxError::InvalidRequest {
-                        message: "Claude session is not active".to_string(),
-                    })?;
+                    .and_then(|(_, perm)| perm.metadata.as_ref()),
+            });
+            if let Some(line) = line {
+                send_stdin_line(agent, stdin_sender, line)?;
             }
-        } else {
-            // TODO: Forward question replies to subprocess agents.
         }
 
         // Emit QuestionResolved
@@ -2738,7 +3883,7 @@ impl SessionManager {
         session_id: &str,
         question_id: &str,
     ) -> Result<(), SandboxError> {
-        let (agent, native_session_id, pending_question, claude_sender, linked_permission) = {
+        let (agent, native_session_id, pending_question, stdin_sender, linked_permission) = {
             let mut sessions = self.sessions.lock().await;
             let session = Self::session_mut(&mut sessions, session_id).ok_or_else(|| {
                 SandboxError::SessionNotFound {
@@ -2754,66 +3899,40 @@ impl SessionManager {
             if let Some(err) = session.ended_error() {
                 return Err(err);
             }
-            let linked_perm = if session.agent == AgentId::Claude {
-                session.take_question_tool_permission()
-            } else {
-                None
-            };
+            let linked_perm = session.take_question_tool_permission();
             (
                 session.agent,
                 session.native_session_id.clone(),
                 pending,
-                session.claude_sender(),
+                session.stdin_sender(),
                 linked_perm,
             )
         };
 
-        if agent == AgentId::Opencode {
+        if adapter(agent).server_kind() == ServerKind::OpencodeHttp {
             let agent_session_id =
                 native_session_id
                     .clone()
                     .ok_or_else(|| SandboxError::InvalidRequest {
                         message: "missing OpenCode session id".to_string(),
                     })?;
-            self.opencode_question_reject(&agent_session_id, question_id)
+            self.opencode_question_reject(agent, &agent_session_id, question_id)
                 .await?;
-        } else if agent == AgentId::Claude {
-            let sender = claude_sender.ok_or_else(|| SandboxError::InvalidRequest {
-                message: "Claude session is not active".to_string(),
-            })?;
-            if let Some((perm_id, _)) = &linked_permission {
-                // Deny via the permission control response
-                let mut response_map = serde_json::Map::new();
-                response_map.insert(
-                    "message".to_string(),
-                    Value::String("Permission denied.".to_string()),
-                );
-                let line =
-                    claude_control_response_line(perm_id, "deny", Value::Object(response_map));
-                sender
-                    .send(line)
-                    .map_err(|_| SandboxError::InvalidRequest {
-                        message: "Claude session is not active".to_string(),
-                    })?;
-            } else {
-                let native_sid = native_session_id
-                    .clone()
-                    .unwrap_or_else(|| session_id.to_string());
-                let line = claude_tool_result_line(
-                    &native_sid,
-                    question_id,
-                    "User rejected the question.",
-                    true,
-                );
-                sender
-                    .send(line)
-                    .map_err(|_| SandboxError::InvalidRequest {
-                        message: "Claude session is not active".to_string(),
-                    })?;
-            }
         } else {
-            // TODO: Forward question rejections to subprocess agents.
-        }
+            let native_sid = native_session_id.as_deref().unwrap_or(session_id);
+            let line = adapter(agent).question_reply_line(&QuestionReplyContext {
+                native_session_id: native_sid,
+                question_id,
+                answers: None,
+                linked_permission_id: linked_permission.as_ref().map(|(id, _)| id.as_str()),
+                linked_permission_metadata: linked_permission
+                    .as_ref()
+                    .and_then(|(_, perm)| perm.metadata.as_ref()),
+            });
+            if let Some(line) = line {
+                send_stdin_line(agent, stdin_sender, line)?;
+            }
+        }
 
         // Emit QuestionResolved
         if let Some(pending) = pending_question {
@@ -2860,7 +3979,7 @@ impl SessionManager {
         reply: PermissionReply,
     ) -> Result<(), SandboxError> {
         let reply_for_status = reply.clone();
-        let (agent, native_session_id, pending_permission, claude_sender) = {
+        let (agent, native_session_id, pending_permission, stdin_sender) = {
             let mut sessions = self.sessions.lock().await;
             let session = Self::session_mut(&mut sessions, session_id).ok_or_else(|| {
                 SandboxError::SessionNotFound {
@@ -2886,77 +4005,24 @@ impl SessionManager {
                 session.agent,
                 session.native_session_id.clone(),
                 pending,
-                session.claude_sender(),
+                session.stdin_sender(),
             )
         };
 
-        if agent == AgentId::Codex {
-            // Use the shared Codex server to send the permission reply
-            let server = self.ensure_codex_server().await?;
-            let pending =
-                pending_permission
-                    .clone()
-                    .ok_or_else(|| SandboxError::InvalidRequest {
-                        message: "missing codex permission metadata".to_string(),
-                    })?;
-            let line = codex_permission_response_line(permission_id, &pending, reply.clone())?;
-            server
-                .stdin_sender
-                .send(line)
-                .map_err(|_| SandboxError::InvalidRequest {
-                    message: "codex server not active".to_string(),
-                })?;
-        } else if agent == AgentId::Opencode {
-            let agent_session_id =
-                native_session_id
-                    .clone()
-                    .ok_or_else(|| SandboxError::InvalidRequest {
-                        message: "missing OpenCode session id".to_string(),
-                    })?;
-            self.opencode_permission_reply(&agent_session_id, permission_id, reply.clone())
-                .await?;
-        } else if agent == AgentId::Claude {
-            let sender = claude_sender.ok_or_else(|| SandboxError::InvalidRequest {
-                message: "Claude session is not active".to_string(),
-            })?;
-            let metadata = pending_permission
-                .as_ref()
-                .and_then(|pending| pending.metadata.as_ref())
-                .and_then(Value::as_object);
-            let updated_input = metadata
-                .and_then(|map| map.get("input"))
-                .cloned()
-                .unwrap_or(Value::Null);
-
-            let mut response_map = serde_json::Map::new();
-            match reply {
-                PermissionReply::Reject => {
-                    response_map.insert(
-                        "message".to_string(),
-                        Value::String("Permission denied.".to_string()),
-                    );
-                }
-                PermissionReply::Once | PermissionReply::Always => {
-                    if !updated_input.is_null() {
-                        response_map.insert("updatedInput".to_string(), updated_input);
-                    }
-                }
-            }
-            let response_value = Value::Object(response_map);
-
-            let behavior = match reply {
-                PermissionReply::Reject => "deny",
-                PermissionReply::Once | PermissionReply::Always => "allow",
-            };
-
-            let line = claude_control_response_line(permission_id, behavior, response_value);
-            sender
-                .send(line)
-                .map_err(|_| SandboxError::InvalidRequest {
-                    message: "Claude session is not active".to_string(),
-                })?;
-        } else {
-            // TODO: Forward permission replies to subprocess agents.
+        if let Some(pending) = pending_permission.as_ref() {
+            self.send_permission_reply(
+                session_id,
+                agent,
+                native_session_id.as_deref(),
+                stdin_sender,
+                &PermissionReplyContext {
+                    permission_id,
+                    action: &pending.action,
+                    metadata: pending.metadata.as_ref(),
+                    reply,
+                },
+            )
+            .await?;
         }
 
         if let Some(pending) = pending_permission {
@@ -3006,7 +4072,7 @@ impl SessionManager {
         session_id: String,
         message: String,
     ) -> Result<(), SandboxError> {
-        let prefix = {
+        let (prefix, debug_log) = {
             let mut sessions = self.sessions.lock().await;
             let session = Self::session_mut(&mut sessions, &session_id).ok_or_else(|| {
                 SandboxError::SessionNotFound {
@@ -3017,19 +4083,38 @@ impl SessionManager {
                 return Err(err);
             }
             session.mock_sequence = session.mock_sequence.saturating_add(1);
-            format!("mock_{}", session.mock_sequence)
+            (
+                format!("mock_{}", session.mock_sequence),
+                session.debug_log.clone(),
+            )
         };
+        if let Some(log) = &debug_log {
+            log.record(DebugDirection::Stdin, &message);
+        }
 
-        let mut conversions = Vec::new();
+        let mut outputs = Vec::new();
         let trimmed = message.trim();
         if !trimmed.is_empty() {
-            conversions.extend(mock_user_message(&prefix, trimmed));
+            outputs.extend(
+                mock_user_message(&prefix, trimmed)
+                    .into_iter()
+                    .map(MockOutput::Event),
+            );
+        }
+        match self.session_config.mock_scenarios.find(trimmed) {
+            Some(scenario) => outputs.extend(mock_scenario_outputs(&prefix, &scenario.steps)),
+            None => outputs.extend(
+                mock_command_conversions(&prefix, trimmed)
+                    .into_iter()
+                    .map(MockOutput::Event),
+            ),
         }
-        conversions.extend(mock_command_conversions(&prefix, trimmed));
 
         let manager = Arc::clone(self);
         tokio::spawn(async move {
-            manager.emit_mock_events(session_id, conversions).await;
+            manager
+                .emit_mock_events(session_id, outputs, debug_log)
+                .await;
         });
 
         Ok(())
@@ -3038,9 +4123,21 @@ impl SessionManager {
     async fn emit_mock_events(
         self: Arc<Self>,
         session_id: String,
-        conversions: Vec<EventConversion>,
+        outputs: Vec<MockOutput>,
+        debug_log: Option<Arc<DebugLog>>,
     ) {
-        for conversion in conversions {
+        // The mock agent has no wire format; its native output is the event data itself.
+        for output in outputs {
+            let conversion = match output {
+                MockOutput::Event(conversion) => conversion,
+                MockOutput::Pause(duration) => {
+                    sleep(duration).await;
+                    continue;
+                }
+            };
+            if let Some(log) = &debug_log {
+                log.record_json(DebugDirection::Stdout, &conversion.data);
+            }
             if self
                 .record_conversions(&session_id, vec![conversion])
                 .await
@@ -3065,29 +4162,32 @@ impl SessionManager {
             stdout,
             stderr,
             codex_options,
+            limits,
         } = spawn;
         let (tx, mut rx) = mpsc::unbounded_channel::<String>();
-        let mut codex_state = codex_options
-            .filter(|_| agent == AgentId::Codex)
-            .map(CodexAppServerState::new);
+        // Spawns that carry Codex options speak the app-server protocol on stdio.
+        let mut codex_state = codex_options.map(CodexAppServerState::new);
         let mut codex_sender: Option<mpsc::UnboundedSender<String>> = None;
         let mut terminate_early = false;
+        let debug_log = self.session_debug_log(&session_id).await;
 
         if let Some(stdout) = stdout {
             let tx_stdout = tx.clone();
+            let debug = debug_log.clone().map(|log| (log, DebugDirection::Stdout));
             tokio::task::spawn_blocking(move || {
-                read_lines(stdout, tx_stdout);
+                read_lines(stdout, tx_stdout, debug);
             });
         }
         if let Some(stderr) = stderr {
             let tx_stderr = tx.clone();
+            let debug = debug_log.clone().map(|log| (log, DebugDirection::Stderr));
             tokio::task::spawn_blocking(move || {
-                read_lines(stderr, tx_stderr);
+                read_lines(stderr, tx_stderr, debug);
             });
         }
         drop(tx);
 
-        if agent == AgentId::Codex {
+        if codex_state.is_some() {
             if let Some(stdin) = stdin {
                 let (writer_tx, writer_rx) = mpsc::unbounded_channel::<String>();
                 codex_sender = Some(writer_tx.clone());
@@ -3097,75 +4197,57 @@ impl SessionManager {
                         session.set_codex_sender(Some(writer_tx));
                     }
                 }
+                let debug_log = debug_log.clone();
                 tokio::task::spawn_blocking(move || {
-                    write_lines(stdin, writer_rx);
+                    write_lines(stdin, writer_rx, debug_log);
                 });
             }
             if let (Some(state), Some(sender)) = (codex_state.as_mut(), codex_sender.as_ref()) {
                 state.start(sender);
             }
-        } else if agent == AgentId::Claude {
-            if let Some(stdin) = stdin {
-                let (writer_tx, writer_rx) = mpsc::unbounded_channel::<String>();
-                {
-                    let mut sessions = self.sessions.lock().await;
-                    if let Some(session) = Self::session_mut(&mut sessions, &session_id) {
-                        session.set_claude_sender(Some(writer_tx.clone()));
-                    }
-                }
-                if let Some(initial) = initial_input {
-                    let _ = writer_tx.send(initial);
+        } else if let Some(stdin) = stdin {
+            let (writer_tx, writer_rx) = mpsc::unbounded_channel::<String>();
+            {
+                let mut sessions = self.sessions.lock().await;
+                if let Some(session) = Self::session_mut(&mut sessions, &session_id) {
+                    session.set_stdin_sender(Some(writer_tx.clone()));
                 }
-                tokio::task::spawn_blocking(move || {
-                    write_lines(stdin, writer_rx);
-                });
             }
+            if let Some(initial) = initial_input {
+                let _ = writer_tx.send(initial);
+            }
+            let debug_log = debug_log.clone();
+            tokio::task::spawn_blocking(move || {
+                write_lines(stdin, writer_rx, debug_log);
+            });
         }
 
         while let Some(line) = rx.recv().await {
-            if agent == AgentId::Codex {
-                if let Some(state) = codex_state.as_mut() {
-                    let outcome = state.handle_line(&line);
-                    if !outcome.conversions.is_empty() {
-                        let _ = self
-                            .record_conversions(&session_id, outcome.conversions)
-                            .await;
-                    }
-                    if outcome.should_terminate {
-                        terminate_early = true;
-                        break;
-                    }
-                }
-            } else if agent == AgentId::Claude {
-                if let Ok(value) = serde_json::from_str::<Value>(&line) {
-                    if value.get("type").and_then(Value::as_str) == Some("result") {
-                        let mut sessions = self.sessions.lock().await;
-                        if let Some(session) = Self::session_mut(&mut sessions, &session_id) {
-                            session.set_claude_sender(None);
-                        }
-                    }
+            if let Some(state) = codex_state.as_mut() {
+                let outcome = state.handle_line(&line);
+                if !outcome.conversions.is_empty() {
+                    let _ = self
+                        .record_conversions(&session_id, outcome.conversions)
+                        .await;
                 }
-                let conversions = self.parse_claude_line(&line, &session_id).await;
-                if !conversions.is_empty() {
-                    let _ = self.record_conversions(&session_id, conversions).await;
+                if outcome.should_terminate {
+                    terminate_early = true;
+                    break;
                 }
             } else {
-                let conversions = parse_agent_line(agent, &line, &session_id);
-                if !conversions.is_empty() {
-                    let _ = self.record_conversions(&session_id, conversions).await;
-                }
+                self.handle_process_line(agent, &session_id, &line).await;
             }
         }
 
-        if agent == AgentId::Codex {
+        if codex_state.is_some() {
             let mut sessions = self.sessions.lock().await;
             if let Some(session) = Self::session_mut(&mut sessions, &session_id) {
                 session.set_codex_sender(None);
             }
-        } else if agent == AgentId::Claude {
+        } else {
             let mut sessions = self.sessions.lock().await;
             if let Some(session) = Self::session_mut(&mut sessions, &session_id) {
-                session.set_claude_sender(None);
+                session.set_stdin_sender(None);
             }
         }
 
@@ -3173,6 +4255,14 @@ impl SessionManager {
             let _ = child.kill();
         }
         let status = tokio::task::spawn_blocking(move || child.wait()).await;
+        adapter(agent).process_exited(&session_id);
+        if let Some(limits) = limits {
+            let hits = limits.exceeded();
+            if !hits.is_empty() {
+                self.record_limit_hits(&session_id, &hits).await;
+            }
+            let _ = tokio::task::spawn_blocking(move || drop(limits)).await;
+        }
         match status {
             Ok(Ok(status)) if status.success() => {
                 if !agent_supports_resume(agent) {
@@ -3257,11 +4347,60 @@ impl SessionManager {
         }
     }
 
+    /// Delivers a permission reply where the agent's server kind reads it: the shared
+    /// Codex server's stdin, OpenCode's HTTP API or the turn's stdin.
+    async fn send_permission_reply(
+        &self,
+        session_id: &str,
+        agent: AgentId,
+        native_session_id: Option<&str>,
+        stdin_sender: Option<mpsc::UnboundedSender<String>>,
+        reply: &PermissionReplyContext<'_>,
+    ) -> Result<(), SandboxError> {
+        let adapter = adapter(agent);
+        if adapter.server_kind() == ServerKind::OpencodeHttp {
+            let agent_session_id =
+                native_session_id.ok_or_else(|| SandboxError::InvalidRequest {
+                    message: "missing OpenCode session id".to_string(),
+                })?;
+            return self
+                .opencode_permission_reply(
+                    agent,
+                    agent_session_id,
+                    reply.permission_id,
+                    reply.reply.clone(),
+                )
+                .await;
+        }
+        let line = adapter.permission_reply_line(reply);
+        if adapter.server_kind() != ServerKind::CodexAppServer {
+            return match line {
+                Some(line) => send_stdin_line(agent, stdin_sender, line),
+                None => Ok(()),
+            };
+        }
+        let line = line.ok_or_else(|| SandboxError::InvalidRequest {
+            message: "unsupported codex permission request".to_string(),
+        })?;
+        let (server, _) = self.server_manager.ensure_stdio_server(agent).await?;
+        self.record_debug_line(session_id, DebugDirection::Stdin, &line)
+            .await;
+        server
+            .stdin_sender
+            .send(line)
+            .map_err(|_| SandboxError::InvalidRequest {
+                message: "codex server not active".to_string(),
+            })
+    }
+
     async fn record_conversions(
         &self,
         session_id: &str,
-        conversions: Vec<EventConversion>,
+        mut conversions: Vec<EventConversion>,
     ) -> Result<Vec<UniversalEvent>, SandboxError> {
+        for conversion in &mut conversions {
+            self.redactor.redact_conversion(conversion);
+        }
         let (events, auto_approvals) = {
             let mut sessions = self.sessions.lock().await;
             let session = Self::session_mut(&mut sessions, session_id).ok_or_else(|| {
@@ -3270,17 +4409,16 @@ impl SessionManager {
                 }
             })?;
             let mut accept_edits_permission_ids = Vec::new();
-            if session.agent == AgentId::Codex && session.permission_mode == "acceptEdits" {
-                for conversion in &conversions {
-                    if conversion.event_type != UniversalEventType::PermissionRequested {
-                        continue;
-                    }
-                    let UniversalEventData::Permission(data) = &conversion.data else {
-                        continue;
-                    };
-                    if is_file_change_action(&data.action) {
-                        accept_edits_permission_ids.push(data.permission_id.clone());
-                    }
+            let adapter = adapter(session.agent);
+            for conversion in &conversions {
+                if conversion.event_type != UniversalEventType::PermissionRequested {
+                    continue;
+                }
+                let UniversalEventData::Permission(data) = &conversion.data else {
+                    continue;
+                };
+                if adapter.auto_approves(&session.permission_mode, &data.action) {
+                    accept_edits_permission_ids.push(data.permission_id.clone());
                 }
             }
             let events = session.record_conversions(conversions);
@@ -3301,7 +4439,7 @@ impl SessionManager {
                     auto_approvals.push((
                         session.agent,
                         session.native_session_id.clone(),
-                        session.claude_sender(),
+                        session.stdin_sender(),
                         data.permission_id.clone(),
                         pending,
                         PermissionReply::Always,
@@ -3317,7 +4455,7 @@ impl SessionManager {
                     auto_approvals.push((
                         session.agent,
                         session.native_session_id.clone(),
-                        session.claude_sender(),
+                        session.stdin_sender(),
                         permission_id.clone(),
                         pending,
                         PermissionReply::Always,
@@ -3328,88 +4466,24 @@ impl SessionManager {
             (events, auto_approvals)
         };
 
-        for (agent, native_session_id, claude_sender, permission_id, pending, reply) in
+        for (agent, native_session_id, stdin_sender, permission_id, pending, reply) in
             auto_approvals
         {
             let reply_for_status = reply.clone();
-            let reply_result = match agent {
-                AgentId::Codex => {
-                    let (server, _) = self
-                        .server_manager
-                        .ensure_stdio_server(AgentId::Codex)
-                        .await?;
-                    let line =
-                        codex_permission_response_line(&permission_id, &pending, reply.clone())?;
-                    server
-                        .stdin_sender
-                        .send(line)
-                        .map_err(|_| SandboxError::InvalidRequest {
-                            message: "codex server not active".to_string(),
-                        })
-                }
-                AgentId::Opencode => {
-                    let agent_session_id =
-                        native_session_id
-                            .clone()
-                            .ok_or_else(|| SandboxError::InvalidRequest {
-                                message: "missing OpenCode session id".to_string(),
-                            });
-                    match agent_session_id {
-                        Ok(agent_session_id) => {
-                            self.opencode_permission_reply(
-                                &agent_session_id,
-                                &permission_id,
-                                reply.clone(),
-                            )
-                            .await
-                        }
-                        Err(err) => Err(err),
-                    }
-                }
-                AgentId::Claude => {
-                    let sender = claude_sender.ok_or_else(|| SandboxError::InvalidRequest {
-                        message: "Claude session is not active".to_string(),
-                    });
-                    match sender {
-                        Ok(sender) => {
-                            let metadata = pending.metadata.as_ref().and_then(Value::as_object);
-                            let updated_input = metadata
-                                .and_then(|map| map.get("input"))
-                                .cloned()
-                                .unwrap_or(Value::Null);
-                            let mut response_map = serde_json::Map::new();
-                            match reply.clone() {
-                                PermissionReply::Reject => {
-                                    response_map.insert(
-                                        "message".to_string(),
-                                        Value::String("Permission denied.".to_string()),
-                                    );
-                                }
-                                PermissionReply::Once | PermissionReply::Always => {
-                                    if !updated_input.is_null() {
-                                        response_map
-                                            .insert("updatedInput".to_string(), updated_input);
-                                    }
-                                }
-                            }
-                            let behavior = match reply.clone() {
-                                PermissionReply::Reject => "deny",
-                                PermissionReply::Once | PermissionReply::Always => "allow",
-                            };
-                            let line = claude_control_response_line(
-                                &permission_id,
-                                behavior,
-                                Value::Object(response_map),
-                            );
-                            sender.send(line).map_err(|_| SandboxError::InvalidRequest {
-                                message: "Claude session is not active".to_string(),
-                            })
-                        }
-                        Err(err) => Err(err),
-                    }
-                }
-                _ => Ok(()),
-            };
+            let reply_result = self
+                .send_permission_reply(
+                    session_id,
+                    agent,
+                    native_session_id.as_deref(),
+                    stdin_sender,
+                    &PermissionReplyContext {
+                        permission_id: &permission_id,
+                        action: &pending.action,
+                        metadata: pending.metadata.as_ref(),
+                        reply,
+                    },
+                )
+                .await;
 
             if let Err(err) = reply_result {
                 tracing::warn!(
@@ -3451,87 +4525,6 @@ impl SessionManager {
         Ok(events)
     }
 
-    async fn parse_claude_line(&self, line: &str, session_id: &str) -> Vec<EventConversion> {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            return Vec::new();
-        }
-        let mut value: Value = match serde_json::from_str(trimmed) {
-            Ok(value) => value,
-            Err(err) => {
-                return vec![agent_unparsed(
-                    "claude",
-                    &err.to_string(),
-                    Value::String(trimmed.to_string()),
-                )];
-            }
-        };
-        let event_type = value.get("type").and_then(Value::as_str).unwrap_or("");
-        let native_session_id = value
-            .get("session_id")
-            .and_then(Value::as_str)
-            .or_else(|| value.get("sessionId").and_then(Value::as_str))
-            .map(|id| id.to_string());
-        if event_type == "assistant" || event_type == "result" || native_session_id.is_some() {
-            let mut sessions = self.sessions.lock().await;
-            if let Some(session) = Self::session_mut(&mut sessions, session_id) {
-                if let Some(native_session_id) = native_session_id.as_ref() {
-                    if session.native_session_id.is_none() {
-                        session.native_session_id = Some(native_session_id.clone());
-                    }
-                }
-                if event_type == "assistant" {
-                    let id = value
-                        .get("message")
-                        .and_then(|message| message.get("id"))
-                        .and_then(Value::as_str)
-                        .map(|id| id.to_string())
-                        .unwrap_or_else(|| {
-                            session.claude_message_counter += 1;
-                            let generated = format!(
-                                "{}_message_{}",
-                                session.session_id, session.claude_message_counter
-                            );
-                            if let Some(message) =
-                                value.get_mut("message").and_then(Value::as_object_mut)
-                            {
-                                message.insert("id".to_string(), Value::String(generated.clone()));
-                            } else if let Some(map) = value.as_object_mut() {
-                                map.insert(
-                                    "message".to_string(),
-                                    serde_json::json!({
-                                        "id": generated
-                                    }),
-                                );
-                            }
-                            generated
-                        });
-                    session.last_claude_message_id = Some(id);
-                } else if event_type == "result" {
-                    let has_message_id =
-                        value.get("message_id").is_some() || value.get("messageId").is_some();
-                    if !has_message_id {
-                        let id = session.last_claude_message_id.take().unwrap_or_else(|| {
-                            session.claude_message_counter += 1;
-                            format!(
-                                "{}_message_{}",
-                                session.session_id, session.claude_message_counter
-                            )
-                        });
-                        if let Some(map) = value.as_object_mut() {
-                            map.insert("message_id".to_string(), Value::String(id));
-                        }
-                    } else {
-                        session.last_claude_message_id = None;
-                    }
-                }
-            }
-        }
-
-        convert_claude::event_to_universal_with_session(&value, session_id.to_string())
-            .unwrap_or_else(|err| vec![agent_unparsed("claude", &err, value)])
-    }
-
     async fn record_error(
         &self,
         session_id: &str,
@@ -3550,6 +4543,23 @@ impl SessionManager {
         let _ = self.record_conversions(session_id, vec![conversion]).await;
     }
 
+    /// Reports resource limits the agent process ran into as `error` events.
+    async fn record_limit_hits(&self, session_id: &str, hits: &[LimitExceeded]) {
+        for hit in hits {
+            self.record_error(
+                session_id,
+                hit.to_string(),
+                Some("resource_limit".to_string()),
+                Some(json!({
+                    "limit": hit.kind.as_str(),
+                    "value": hit.limit,
+                    "events": hit.events,
+                })),
+            )
+            .await;
+        }
+    }
+
     async fn mark_session_ended(
         &self,
         session_id: &str,
@@ -3596,7 +4606,7 @@ impl SessionManager {
         self: &Arc<Self>,
         session_id: String,
     ) -> Result<(), SandboxError> {
-        let native_session_id =
+        let (agent, native_session_id) =
             {
                 let mut sessions = self.sessions.lock().await;
                 let session = Self::session_mut(&mut sessions, &session_id).ok_or_else(|| {
@@ -3613,13 +4623,13 @@ impl SessionManager {
                     }
                 })?;
                 session.opencode_stream_started = true;
-                native_session_id
+                (session.agent, native_session_id)
             };
 
         let manager = Arc::clone(self);
         tokio::spawn(async move {
             manager
-                .stream_opencode_events(session_id, native_session_id)
+                .stream_opencode_events(agent, session_id, native_session_id)
                 .await;
         });
 
@@ -3628,10 +4638,11 @@ impl SessionManager {
 
     async fn stream_opencode_events(
         self: Arc<Self>,
+        agent: AgentId,
         session_id: String,
         native_session_id: String,
     ) {
-        let base_url = match self.ensure_opencode_server().await {
+        let base_url = match self.ensure_opencode_server(agent).await {
             Ok(base_url) => base_url,
             Err(err) => {
                 self.record_error(
@@ -3641,7 +4652,7 @@ impl SessionManager {
                     None,
                 )
                 .await;
-                let logs = self.read_agent_stderr(AgentId::Opencode);
+                let logs = self.read_agent_stderr(agent);
                 self.mark_session_ended(
                     &session_id,
                     None,
@@ -3666,7 +4677,7 @@ impl SessionManager {
                     None,
                 )
                 .await;
-                let logs = self.read_agent_stderr(AgentId::Opencode);
+                let logs = self.read_agent_stderr(agent);
                 self.mark_session_ended(
                     &session_id,
                     None,
@@ -3690,7 +4701,7 @@ impl SessionManager {
                 None,
             )
             .await;
-            let logs = self.read_agent_stderr(AgentId::Opencode);
+            let logs = self.read_agent_stderr(agent);
             self.mark_session_ended(
                 &session_id,
                 None,
@@ -3703,6 +4714,7 @@ impl SessionManager {
             return;
         }
 
+        let debug_log = self.session_debug_log(&session_id).await;
         let mut accumulator = SseAccumulator::new();
         let mut stream = response.bytes_stream();
         while let Some(chunk) = stream.next().await {
@@ -3716,7 +4728,7 @@ impl SessionManager {
                         None,
                     )
                     .await;
-                    let logs = self.read_agent_stderr(AgentId::Opencode);
+                    let logs = self.read_agent_stderr(agent);
                     self.mark_session_ended(
                         &session_id,
                         None,
@@ -3731,54 +4743,76 @@ impl SessionManager {
             };
             let text = String::from_utf8_lossy(&chunk);
             for event_payload in accumulator.push(&text) {
-                let value: Value = match serde_json::from_str(&event_payload) {
-                    Ok(value) => value,
-                    Err(err) => {
-                        let conversion = agent_unparsed(
-                            "opencode",
-                            &err.to_string(),
-                            Value::String(event_payload.clone()),
-                        );
-                        let _ = self.record_conversions(&session_id, vec![conversion]).await;
-                        continue;
-                    }
-                };
-                if !opencode_event_matches_session(&value, &native_session_id) {
-                    continue;
+                self.handle_opencode_event(
+                    &session_id,
+                    &native_session_id,
+                    &event_payload,
+                    debug_log.as_deref(),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Converts one event from the shared OpenCode SSE stream, skipping events that
+    /// belong to other sessions.
+    async fn handle_opencode_event(
+        &self,
+        session_id: &str,
+        native_session_id: &str,
+        event_payload: &str,
+        debug_log: Option<&DebugLog>,
+    ) {
+        let value: Value = match serde_json::from_str(event_payload) {
+            Ok(value) => value,
+            Err(err) => {
+                if let Some(log) = debug_log {
+                    log.record(DebugDirection::Event, event_payload);
                 }
-                let conversions = match serde_json::from_value(value.clone()) {
-                    Ok(event) => match convert_opencode::event_to_universal(&event) {
-                        Ok(conversions) => conversions,
-                        Err(err) => vec![agent_unparsed("opencode", &err, value.clone())],
-                    },
-                    Err(err) => vec![agent_unparsed("opencode", &err.to_string(), value.clone())],
-                };
-                let _ = self.record_conversions(&session_id, conversions).await;
+                let conversion = agent_unparsed(
+                    "opencode",
+                    &err.to_string(),
+                    Value::String(event_payload.to_string()),
+                );
+                let _ = self.record_conversions(session_id, vec![conversion]).await;
+                return;
             }
+        };
+        if !opencode_event_matches_session(&value, native_session_id) {
+            return;
         }
+        if let Some(log) = debug_log {
+            log.record(DebugDirection::Event, event_payload);
+        }
+        let conversions = match convert_opencode::parse_event(value.clone()) {
+            Ok(event) => match convert_opencode::event_to_universal(&event) {
+                Ok(conversions) => conversions,
+                Err(err) => vec![agent_unparsed("opencode", &err, value.clone())],
+            },
+            Err(err) => vec![agent_unparsed("opencode", &err, value.clone())],
+        };
+        let _ = self.record_conversions(session_id, conversions).await;
     }
 
-    async fn ensure_opencode_server(&self) -> Result<String, SandboxError> {
-        self.server_manager
-            .ensure_http_server(AgentId::Opencode)
-            .await
+    async fn ensure_opencode_server(&self, agent: AgentId) -> Result<String, SandboxError> {
+        self.server_manager.ensure_http_server(agent).await
     }
 
     /// Ensures a shared Codex app-server process is running.
     /// Spawns the process if not already running, sets up stdin/stdout tasks,
     /// and performs the initialize handshake if needed.
-    async fn ensure_codex_server(self: &Arc<Self>) -> Result<Arc<CodexServer>, SandboxError> {
-        let (server, receiver) = self
-            .server_manager
-            .ensure_stdio_server(AgentId::Codex)
-            .await?;
+    async fn ensure_codex_server(
+        self: &Arc<Self>,
+        agent: AgentId,
+    ) -> Result<Arc<StdioServer>, SandboxError> {
+        let (server, receiver) = self.server_manager.ensure_stdio_server(agent).await?;
 
         if let Some(stdout_rx) = receiver {
             let server_for_task = server.clone();
             let self_for_task = Arc::clone(self);
             tokio::spawn(async move {
                 self_for_task
-                    .handle_codex_server_output(server_for_task, stdout_rx)
+                    .handle_codex_server_output(agent, server_for_task, stdout_rx)
                     .await;
             });
         }
@@ -3791,7 +4825,8 @@ impl SessionManager {
     /// Handles output from the Codex app-server, routing responses and notifications.
     async fn handle_codex_server_output(
         self: Arc<Self>,
-        server: Arc<CodexServer>,
+        agent: AgentId,
+        server: Arc<StdioServer>,
         mut stdout_rx: mpsc::UnboundedReceiver<String>,
     ) {
         while let Some(line) = stdout_rx.recv().await {
@@ -3815,8 +4850,11 @@ impl SessionManager {
                 codex_schema::JsonrpcMessage::Response(response) => {
                     // Route response to waiting request
                     if let Some(id) = codex_request_id_to_i64(&response.id) {
-                        server.take_request_session(id);
-                        server.complete_request(id, CodexRequestResult::Response(response.result));
+                        if let Some(session_id) = server.take_request_session(id) {
+                            self.record_debug_line(&session_id, DebugDirection::Stdout, trimmed)
+                                .await;
+                        }
+                        server.complete_request(id, StdioRequestResult::Response(response.result));
                     }
                 }
                 codex_schema::JsonrpcMessage::Notification(_) => {
@@ -3828,6 +4866,12 @@ impl SessionManager {
                             codex_thread_id_from_server_notification(&notification)
                         {
                             if let Some(session_id) = server.session_for_thread(&thread_id) {
+                                self.record_debug_line(
+                                    &session_id,
+                                    DebugDirection::Stdout,
+                                    trimmed,
+                                )
+                                .await;
                                 if let codex_schema::ServerNotification::Error(params) =
                                     &notification
                                 {
@@ -3835,6 +4879,7 @@ impl SessionManager {
                                         codex_unavailable_model_from_message(&params.error.message)
                                     {
                                         self.handle_codex_model_unavailable(
+                                            agent,
                                             &session_id,
                                             &model_id,
                                             Some(thread_id.clone()),
@@ -3861,6 +4906,12 @@ impl SessionManager {
                     {
                         if let Some(thread_id) = codex_thread_id_from_server_request(&request) {
                             if let Some(session_id) = server.session_for_thread(&thread_id) {
+                                self.record_debug_line(
+                                    &session_id,
+                                    DebugDirection::Stdout,
+                                    trimmed,
+                                )
+                                .await;
                                 match codex_request_to_universal(&request) {
                                     Ok(mut conversions) => {
                                         for conversion in &mut conversions {
@@ -3885,13 +4936,20 @@ impl SessionManager {
                 codex_schema::JsonrpcMessage::Error(error) => {
                     if let Some(id) = codex_request_id_to_i64(&error.id) {
                         let session_id = server.take_request_session(id);
-                        server.complete_request(id, CodexRequestResult::Error(error.error.clone()));
+                        server.complete_request(id, StdioRequestResult::Error(error.error.clone()));
                         if let Some(session_id) = session_id {
+                            self.record_debug_line(&session_id, DebugDirection::Stdout, trimmed)
+                                .await;
                             if let Some(model_id) =
                                 codex_unavailable_model_from_rpc_error(&error.error)
                             {
-                                self.handle_codex_model_unavailable(&session_id, &model_id, None)
-                                    .await;
+                                self.handle_codex_model_unavailable(
+                                    agent,
+                                    &session_id,
+                                    &model_id,
+                                    None,
+                                )
+                                .await;
                             }
                             let _ = self
                                 .record_conversions(
@@ -3911,7 +4969,7 @@ impl SessionManager {
     }
 
     /// Performs the initialize/initialized handshake with the Codex server.
-    async fn codex_server_initialize(&self, server: &CodexServer) -> Result<(), SandboxError> {
+    async fn codex_server_initialize(&self, server: &StdioServer) -> Result<(), SandboxError> {
         let _initialize_guard = server.initialize_lock.lock().await;
         if server.is_initialized() {
             return Ok(());
@@ -3938,7 +4996,7 @@ impl SessionManager {
         // Wait for initialize response with timeout
         let result = tokio::time::timeout(Duration::from_secs(30), rx).await;
         match result {
-            Ok(Ok(CodexRequestResult::Response(_))) => {
+            Ok(Ok(StdioRequestResult::Response(_))) => {
                 // Send initialized notification
                 let notification = codex_schema::JsonrpcNotification {
                     method: "initialized".to_string(),
@@ -3948,7 +5006,7 @@ impl SessionManager {
                 server.set_initialized();
                 Ok(())
             }
-            Ok(Ok(CodexRequestResult::Error(error))) => Err(codex_request_error_to_sandbox(
+            Ok(Ok(StdioRequestResult::Error(error))) => Err(codex_request_error_to_sandbox(
                 "initialize request failed",
                 &error,
             )),
@@ -3961,7 +5019,7 @@ impl SessionManager {
         }
     }
 
-    async fn reload_codex_mcp(&self, server: &CodexServer) -> Result<(), SandboxError> {
+    async fn reload_codex_mcp(&self, server: &StdioServer) -> Result<(), SandboxError> {
         let id = server.next_request_id();
         let request = codex_schema::ClientRequest::ConfigMcpServerReload {
             id: codex_schema::RequestId::from(id),
@@ -3990,7 +5048,7 @@ impl SessionManager {
         session_id: &str,
         session: &SessionSnapshot,
     ) -> Result<String, SandboxError> {
-        let server = self.ensure_codex_server().await?;
+        let server = self.ensure_codex_server(session.agent).await?;
 
         let id = server.next_request_id();
         let mut params = codex_schema::ThreadStartParams::default();
@@ -4002,6 +5060,9 @@ impl SessionManager {
             id: codex_schema::RequestId::from(id),
             params,
         };
+        if let Some(log) = &session.debug_log {
+            log.record_json(DebugDirection::Stdin, &request);
+        }
 
         let rx = server
             .send_request(id, &request)
@@ -4012,7 +5073,7 @@ impl SessionManager {
         // Wait for thread/start response
         let result = tokio::time::timeout(Duration::from_secs(30), rx).await;
         match result {
-            Ok(Ok(CodexRequestResult::Response(response))) => {
+            Ok(Ok(StdioRequestResult::Response(response))) => {
                 // Extract thread_id from response
                 let thread_id = response
                     .get("thread")
@@ -4029,7 +5090,7 @@ impl SessionManager {
 
                 Ok(thread_id)
             }
-            Ok(Ok(CodexRequestResult::Error(error))) => Err(codex_request_error_to_sandbox(
+            Ok(Ok(StdioRequestResult::Error(error))) => Err(codex_request_error_to_sandbox(
                 "thread/start request failed",
                 &error,
             )),
@@ -4048,7 +5109,7 @@ impl SessionManager {
         session: &SessionSnapshot,
         prompt: &str,
     ) -> Result<(), SandboxError> {
-        let server = self.ensure_codex_server().await?;
+        let server = self.ensure_codex_server(session.agent).await?;
 
         let thread_id =
             session
@@ -4081,6 +5142,9 @@ impl SessionManager {
             params,
         };
 
+        if let Some(log) = &session.debug_log {
+            log.record_json(DebugDirection::Stdin, &request);
+        }
         // Send but don't wait for response - notifications will stream back
         server
             .send_request_with_session(id, &request, Some(session.session_id.clone()))
@@ -4091,149 +5155,371 @@ impl SessionManager {
         Ok(())
     }
 
-    async fn fetch_opencode_modes(&self) -> Result<Vec<AgentModeInfo>, SandboxError> {
-        let base_url = self.ensure_opencode_server().await?;
-        let endpoints = [
-            format!("{base_url}/app/agents"),
-            format!("{base_url}/agents"),
-        ];
-        for url in endpoints {
-            let response = self.http_client.get(&url).send().await;
-            let response = match response {
-                Ok(response) => response,
-                Err(_) => continue,
-            };
-            if !response.status().is_success() {
-                continue;
-            }
-            let value: Value = response
-                .json()
-                .await
-                .map_err(|err| SandboxError::StreamError {
-                    message: err.to_string(),
-                })?;
-            let modes = parse_opencode_modes(&value);
-            if !modes.is_empty() {
-                return Ok(modes);
-            }
+    /// Ensures the shared ACP server for the agent is running and initialized.
+    async fn ensure_acp_server(
+        self: &Arc<Self>,
+        agent: AgentId,
+    ) -> Result<Arc<StdioServer>, SandboxError> {
+        let (server, receiver) = self.server_manager.ensure_stdio_server(agent).await?;
+
+        if let Some(stdout_rx) = receiver {
+            let server_for_task = server.clone();
+            let self_for_task = Arc::clone(self);
+            tokio::spawn(async move {
+                self_for_task
+                    .handle_acp_server_output(agent, server_for_task, stdout_rx)
+                    .await;
+            });
         }
-        Err(SandboxError::StreamError {
-            message: "OpenCode agent modes unavailable".to_string(),
-        })
+
+        self.acp_server_initialize(&server).await?;
+
+        Ok(server)
     }
 
-    async fn fetch_claude_models(&self) -> Result<AgentModelsResponse, SandboxError> {
-        let started = Instant::now();
-        let credentials = self.extract_credentials().await?;
-        let Some(cred) = credentials.anthropic else {
-            tracing::info!(
-                elapsed_ms = started.elapsed().as_millis() as u64,
-                "claude model fetch skipped (no anthropic credentials)"
-            );
-            return Ok(AgentModelsResponse {
-                models: Vec::new(),
-                default_model: None,
-            });
-        };
+    /// Handles output from an ACP server: responses, `session/update` notifications
+    /// and requests from the agent.
+    async fn handle_acp_server_output(
+        self: Arc<Self>,
+        agent: AgentId,
+        server: Arc<StdioServer>,
+        mut stdout_rx: mpsc::UnboundedReceiver<String>,
+    ) {
+        while let Some(line) = stdout_rx.recv().await {
+            let trimmed = line.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            let Ok(value) = serde_json::from_str::<Value>(trimmed) else {
+                continue;
+            };
+            let method = value.get("method").and_then(Value::as_str);
+            let params = value.get("params").cloned().unwrap_or(Value::Null);
+            let session_id = params
+                .get("sessionId")
+                .and_then(Value::as_str)
+                .and_then(|acp_session_id| server.session_for_thread(acp_session_id));
 
-        let headers = build_anthropic_headers(&cred)?;
-        let response = self
-            .http_client
-            .get(ANTHROPIC_MODELS_URL)
-            .headers(headers)
-            .send()
-            .await
-            .map_err(|err| SandboxError::StreamError {
-                message: err.to_string(),
-            })?;
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            if matches!(cred.auth_type, AuthType::Oauth) {
-                tracing::warn!(
-                    status = %status,
-                    elapsed_ms = started.elapsed().as_millis() as u64,
-                    "Anthropic model list rejected OAuth credentials; using Claude OAuth fallback models"
-                );
-                return Ok(claude_fallback_models());
+            match (method, value.get("id")) {
+                (None, Some(id)) => {
+                    let Some(id) = id.as_i64() else {
+                        continue;
+                    };
+                    if let Some(session_id) = server.take_request_session(id) {
+                        self.record_debug_line(&session_id, DebugDirection::Stdout, trimmed)
+                            .await;
+                    }
+                    let result = match value.get("error") {
+                        Some(error) => StdioRequestResult::Error(acp_rpc_error(error)),
+                        None => StdioRequestResult::Response(
+                            value.get("result").cloned().unwrap_or(Value::Null),
+                        ),
+                    };
+                    server.complete_request(id, result);
+                }
+                (Some("session/update"), None) => {
+                    let Some(session_id) = session_id else {
+                        continue;
+                    };
+                    self.record_debug_line(&session_id, DebugDirection::Stdout, trimmed)
+                        .await;
+                    let acp_session_id = params
+                        .get("sessionId")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default();
+                    let update = params.get("update").cloned().unwrap_or(Value::Null);
+                    let conversions = server
+                        .acp_stream(&session_id, |stream| {
+                            stream.update_to_universal(acp_session_id, &update)
+                        })
+                        .unwrap_or_else(|err| {
+                            vec![agent_unparsed(agent.as_str(), &err, value.clone())]
+                        });
+                    let _ = self.record_conversions(&session_id, conversions).await;
+                }
+                (Some("session/request_permission"), Some(id)) => {
+                    let Some(session_id) = session_id else {
+                        continue;
+                    };
+                    self.record_debug_line(&session_id, DebugDirection::Stdout, trimmed)
+                        .await;
+                    let conversions =
+                        match convert_acp::permission_request_to_universal(id, &params) {
+                            Ok(mut conversions) => {
+                                for conversion in &mut conversions {
+                                    conversion.raw = Some(value.clone());
+                                }
+                                conversions
+                            }
+                            Err(err) => vec![agent_unparsed(agent.as_str(), &err, value.clone())],
+                        };
+                    let _ = self.record_conversions(&session_id, conversions).await;
+                }
+                (Some(method), Some(id)) => {
+                    // The client advertises no fs or terminal capabilities.
+                    let response = json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": format!("method not found: {method}") },
+                    });
+                    server.send_notification(&response);
+                }
+                _ => {}
             }
-            return Err(SandboxError::StreamError {
-                message: format!("Anthropic models request failed {status}: {body}"),
-            });
         }
+    }
 
-        let value: Value = response
-            .json()
-            .await
-            .map_err(|err| SandboxError::StreamError {
-                message: err.to_string(),
+    /// Performs the ACP `initialize` handshake.
+    async fn acp_server_initialize(&self, server: &StdioServer) -> Result<(), SandboxError> {
+        let _initialize_guard = server.initialize_lock.lock().await;
+        if server.is_initialized() {
+            return Ok(());
+        }
+
+        let id = server.next_request_id();
+        let request = acp_request(
+            id,
+            "initialize",
+            json!({
+                "protocolVersion": 1,
+                "clientCapabilities": {
+                    "fs": { "readTextFile": false, "writeTextFile": false },
+                    "terminal": false,
+                },
+            }),
+        );
+        let rx = server
+            .send_request(id, &request)
+            .ok_or_else(|| SandboxError::StreamError {
+                message: "failed to send initialize request".to_string(),
             })?;
-        let data = value
-            .get("data")
-            .and_then(Value::as_array)
-            .cloned()
-            .unwrap_or_default();
 
-        let mut models = Vec::new();
-        let mut default_model: Option<String> = None;
-        let mut default_created: Option<String> = None;
-        for item in data {
-            let Some(id) = item.get("id").and_then(Value::as_str) else {
-                continue;
-            };
-            let name = item
-                .get("display_name")
-                .and_then(Value::as_str)
-                .map(|value| value.to_string());
-            let created = item
-                .get("created_at")
-                .and_then(Value::as_str)
-                .map(|value| value.to_string());
-            if let Some(created) = created.as_ref() {
-                let should_update = match default_created.as_deref() {
-                    Some(current) => created.as_str() > current,
-                    None => true,
-                };
-                if should_update {
-                    default_created = Some(created.clone());
-                    default_model = Some(id.to_string());
-                }
+        match tokio::time::timeout(Duration::from_secs(30), rx).await {
+            Ok(Ok(StdioRequestResult::Response(_))) => {
+                server.set_initialized();
+                Ok(())
             }
-            models.push(AgentModelInfo {
-                id: id.to_string(),
-                name,
-                variants: None,
-                default_variant: None,
-            });
+            Ok(Ok(StdioRequestResult::Error(error))) => Err(codex_request_error_to_sandbox(
+                "initialize request failed",
+                &error,
+            )),
+            Ok(Err(_)) => Err(SandboxError::StreamError {
+                message: "initialize request cancelled".to_string(),
+            }),
+            Err(_) => Err(SandboxError::StreamError {
+                message: "initialize request timed out".to_string(),
+            }),
         }
-        models.sort_by(|a, b| a.id.cmp(&b.id));
-        if default_model.is_none() {
-            default_model = models.first().map(|model| model.id.clone());
+    }
+
+    /// Creates an ACP session with `session/new` and selects the requested mode.
+    /// Returns the agent's session id and the server's stdin, which takes permission replies.
+    async fn create_acp_session(
+        self: &Arc<Self>,
+        session_id: &str,
+        session: &SessionSnapshot,
+        mcp: Option<&BTreeMap<String, McpServerConfig>>,
+    ) -> Result<(String, mpsc::UnboundedSender<String>), SandboxError> {
+        let server = self.ensure_acp_server(session.agent).await?;
+        let cwd = std::env::current_dir().map_err(|err| SandboxError::StreamError {
+            message: err.to_string(),
+        })?;
+        let mcp_servers = match mcp {
+            Some(mcp) => acp_mcp_servers(mcp)?,
+            None => Vec::new(),
+        };
+
+        let id = server.next_request_id();
+        let request = acp_request(
+            id,
+            "session/new",
+            json!({ "cwd": cwd, "mcpServers": mcp_servers }),
+        );
+        if let Some(log) = &session.debug_log {
+            log.record_json(DebugDirection::Stdin, &request);
         }
+        let rx = server
+            .send_request(id, &request)
+            .ok_or_else(|| SandboxError::StreamError {
+                message: "failed to send session/new request".to_string(),
+            })?;
+        let acp_session_id = match tokio::time::timeout(Duration::from_secs(30), rx).await {
+            Ok(Ok(StdioRequestResult::Response(response))) => response
+                .get("sessionId")
+                .and_then(Value::as_str)
+                .ok_or_else(|| SandboxError::StreamError {
+                    message: "session/new response missing sessionId".to_string(),
+                })?
+                .to_string(),
+            Ok(Ok(StdioRequestResult::Error(error))) => {
+                return Err(codex_request_error_to_sandbox(
+                    "session/new request failed",
+                    &error,
+                ))
+            }
+            Ok(Err(_)) => {
+                return Err(SandboxError::StreamError {
+                    message: "session/new request cancelled".to_string(),
+                })
+            }
+            Err(_) => {
+                return Err(SandboxError::StreamError {
+                    message: "session/new request timed out".to_string(),
+                })
+            }
+        };
+        server.register_thread(acp_session_id.clone(), session_id.to_string());
 
-        if models.is_empty() && matches!(cred.auth_type, AuthType::Oauth) {
-            tracing::warn!(
-                elapsed_ms = started.elapsed().as_millis() as u64,
-                "Anthropic model list was empty for OAuth credentials; using Claude OAuth fallback models"
+        if session.agent_mode != "build" {
+            let id = server.next_request_id();
+            let request = acp_request(
+                id,
+                "session/set_mode",
+                json!({ "sessionId": acp_session_id, "modeId": session.agent_mode }),
             );
-            return Ok(claude_fallback_models());
+            let rx =
+                server
+                    .send_request(id, &request)
+                    .ok_or_else(|| SandboxError::StreamError {
+                        message: "failed to send session/set_mode request".to_string(),
+                    })?;
+            match tokio::time::timeout(Duration::from_secs(30), rx).await {
+                Ok(Ok(StdioRequestResult::Response(_))) => {}
+                Ok(Ok(StdioRequestResult::Error(error))) => {
+                    return Err(SandboxError::InvalidRequest {
+                        message: format!(
+                            "agent rejected mode {}: {}",
+                            session.agent_mode, error.message
+                        ),
+                    })
+                }
+                _ => {
+                    return Err(SandboxError::StreamError {
+                        message: "session/set_mode request failed".to_string(),
+                    })
+                }
+            }
         }
 
-        tracing::info!(
-            elapsed_ms = started.elapsed().as_millis() as u64,
-            model_count = models.len(),
-            has_default = default_model.is_some(),
-            "claude model fetch completed"
+        Ok((acp_session_id, server.stdin_sender.clone()))
+    }
+
+    /// Sends a `session/prompt` request. The turn ends when the request returns.
+    async fn send_acp_prompt(
+        self: &Arc<Self>,
+        session: &SessionSnapshot,
+        prompt: &str,
+    ) -> Result<(), SandboxError> {
+        let server = self.ensure_acp_server(session.agent).await?;
+        let acp_session_id =
+            session
+                .native_session_id
+                .clone()
+                .ok_or_else(|| SandboxError::InvalidRequest {
+                    message: "missing ACP session id".to_string(),
+                })?;
+
+        let id = server.next_request_id();
+        let request = acp_request(
+            id,
+            "session/prompt",
+            json!({
+                "sessionId": acp_session_id,
+                "prompt": [{ "type": "text", "text": prompt }],
+            }),
         );
-        Ok(AgentModelsResponse {
-            models,
-            default_model,
+        if let Some(log) = &session.debug_log {
+            log.record_json(DebugDirection::Stdin, &request);
+        }
+        let rx = server
+            .send_request_with_session(id, &request, Some(session.session_id.clone()))
+            .ok_or_else(|| SandboxError::StreamError {
+                message: "failed to send session/prompt request".to_string(),
+            })?;
+
+        let manager = Arc::clone(self);
+        let session_id = session.session_id.clone();
+        tokio::spawn(async move {
+            let mut conversions = Vec::new();
+            let stop_reason = match rx.await {
+                Ok(StdioRequestResult::Response(response)) => response
+                    .get("stopReason")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                Ok(StdioRequestResult::Error(error)) => {
+                    conversions.push(EventConversion::new(
+                        UniversalEventType::Error,
+                        UniversalEventData::Error(ErrorData {
+                            message: error.message.clone(),
+                            code: Some("jsonrpc.error".to_string()),
+                            details: serde_json::to_value(&error).ok(),
+                        }),
+                    ));
+                    None
+                }
+                // The server exited; the process monitor reports it.
+                Err(_) => return,
+            };
+            conversions.extend(server.acp_stream(&session_id, |stream| {
+                stream.turn_ended(&acp_session_id, stop_reason.as_deref())
+            }));
+            let _ = manager.record_conversions(&session_id, conversions).await;
+        });
+
+        Ok(())
+    }
+
+    /// Sends `session/cancel` for a session whose ACP server is still running.
+    async fn cancel_acp_session(&self, agent: AgentId, acp_session_id: &str) {
+        if let Some(server) = self.server_manager.running_stdio_server(agent).await {
+            server.send_notification(&json!({
+                "jsonrpc": "2.0",
+                "method": "session/cancel",
+                "params": { "sessionId": acp_session_id },
+            }));
+        }
+    }
+
+    async fn fetch_opencode_modes(
+        &self,
+        agent: AgentId,
+    ) -> Result<Vec<AgentModeInfo>, SandboxError> {
+        let base_url = self.ensure_opencode_server(agent).await?;
+        let endpoints = [
+            format!("{base_url}/app/agents"),
+            format!("{base_url}/agents"),
+        ];
+        for url in endpoints {
+            let response = self.http_client.get(&url).send().await;
+            let response = match response {
+                Ok(response) => response,
+                Err(_) => continue,
+            };
+            if !response.status().is_success() {
+                continue;
+            }
+            let value: Value = response
+                .json()
+                .await
+                .map_err(|err| SandboxError::StreamError {
+                    message: err.to_string(),
+                })?;
+            let modes = parse_opencode_modes(&value);
+            if !modes.is_empty() {
+                return Ok(modes);
+            }
+        }
+        Err(SandboxError::StreamError {
+            message: "OpenCode agent modes unavailable".to_string(),
         })
     }
 
-    async fn fetch_codex_models(self: &Arc<Self>) -> Result<AgentModelsResponse, SandboxError> {
+    async fn fetch_codex_models(
+        self: &Arc<Self>,
+        agent: AgentId,
+    ) -> Result<AgentModelsResponse, SandboxError> {
         let started = Instant::now();
-        let server = self.ensure_codex_server().await?;
+        let server = self.ensure_codex_server(agent).await?;
         tracing::info!(
             elapsed_ms = started.elapsed().as_millis() as u64,
             "codex model fetch server ready"
@@ -4266,8 +5552,8 @@ impl SessionManager {
             let result =
                 tokio::time::timeout(Duration::from_secs(CODEX_MODEL_LIST_TIMEOUT_SECS), rx).await;
             let value = match result {
-                Ok(Ok(CodexRequestResult::Response(value))) => value,
-                Ok(Ok(CodexRequestResult::Error(error))) => {
+                Ok(Ok(StdioRequestResult::Response(value))) => value,
+                Ok(Ok(StdioRequestResult::Error(error))) => {
                     tracing::warn!(
                         elapsed_ms = started.elapsed().as_millis() as u64,
                         page = pages + 1,
@@ -4377,9 +5663,12 @@ impl SessionManager {
         })
     }
 
-    async fn fetch_opencode_models(&self) -> Result<AgentModelsResponse, SandboxError> {
+    async fn fetch_opencode_models(
+        &self,
+        agent: AgentId,
+    ) -> Result<AgentModelsResponse, SandboxError> {
         let started = Instant::now();
-        let base_url = self.ensure_opencode_server().await?;
+        let base_url = self.ensure_opencode_server(agent).await?;
         let endpoints = [
             format!("{base_url}/config/providers"),
             format!("{base_url}/provider"),
@@ -4443,19 +5732,22 @@ impl SessionManager {
         })
     }
 
+    /// Extracts credentials and registers them for redaction.
     async fn extract_credentials(&self) -> Result<ExtractedCredentials, SandboxError> {
-        tokio::task::spawn_blocking(move || {
+        let credentials = tokio::task::spawn_blocking(move || {
             let options = CredentialExtractionOptions::new();
             extract_all_credentials(&options)
         })
         .await
         .map_err(|err| SandboxError::StreamError {
             message: err.to_string(),
-        })
+        })?;
+        self.redactor.add_credentials(&credentials);
+        Ok(credentials)
     }
 
-    async fn create_opencode_session(&self) -> Result<String, SandboxError> {
-        let base_url = self.ensure_opencode_server().await?;
+    async fn create_opencode_session(&self, agent: AgentId) -> Result<String, SandboxError> {
+        let base_url = self.ensure_opencode_server(agent).await?;
         let url = format!("{base_url}/session");
         for _ in 0..10 {
             let response = self.http_client.post(&url).json(&json!({})).send().await;
@@ -4496,12 +5788,13 @@ impl SessionManager {
 
     async fn apply_opencode_mcp(
         &self,
+        agent: AgentId,
         mcp: &BTreeMap<String, McpServerConfig>,
     ) -> Result<(), SandboxError> {
         if mcp.is_empty() {
             return Ok(());
         }
-        let base_url = self.ensure_opencode_server().await?;
+        let base_url = self.ensure_opencode_server(agent).await?;
         let url = format!("{base_url}/mcp");
         let mut existing = HashSet::new();
         if let Ok(response) = self.http_client.get(&url).send().await {
@@ -4541,7 +5834,7 @@ impl SessionManager {
         prompt: &str,
         attachments: &[MessageAttachment],
     ) -> Result<(), SandboxError> {
-        let base_url = self.ensure_opencode_server().await?;
+        let base_url = self.ensure_opencode_server(session.agent).await?;
         let session_id =
             session
                 .native_session_id
@@ -4571,6 +5864,9 @@ impl SessionManager {
         if let Some(variant) = session.variant.as_deref() {
             body["variant"] = json!(variant);
         }
+        if let Some(log) = &session.debug_log {
+            log.record_json(DebugDirection::Request, &body);
+        }
 
         let response = self
             .http_client
@@ -4594,11 +5890,12 @@ impl SessionManager {
 
     async fn opencode_question_reply(
         &self,
+        agent: AgentId,
         _session_id: &str,
         request_id: &str,
         answers: Vec<Vec<String>>,
     ) -> Result<(), SandboxError> {
-        let base_url = self.ensure_opencode_server().await?;
+        let base_url = self.ensure_opencode_server(agent).await?;
         let url = format!("{base_url}/question/reply");
         let response = self
             .http_client
@@ -4624,10 +5921,11 @@ impl SessionManager {
 
     async fn opencode_question_reject(
         &self,
+        agent: AgentId,
         _session_id: &str,
         request_id: &str,
     ) -> Result<(), SandboxError> {
-        let base_url = self.ensure_opencode_server().await?;
+        let base_url = self.ensure_opencode_server(agent).await?;
         let url = format!("{base_url}/question/reject");
         let response = self
             .http_client
@@ -4650,11 +5948,12 @@ impl SessionManager {
 
     async fn opencode_permission_reply(
         &self,
+        agent: AgentId,
         _session_id: &str,
         request_id: &str,
         reply: PermissionReply,
     ) -> Result<(), SandboxError> {
-        let base_url = self.ensure_opencode_server().await?;
+        let base_url = self.ensure_opencode_server(agent).await?;
         let url = format!("{base_url}/permission/reply");
         let response = self
             .http_client
@@ -4681,28 +5980,200 @@ impl SessionManager {
 
 async fn require_token(
     State(state): State<Arc<AppState>>,
-    req: Request<axum::body::Body>,
+    mut req: Request<axum::body::Body>,
     next: Next,
 ) -> Result<Response, ApiError> {
     let path = req.uri().path();
-    if path == "/v1/health" || path == "/health" {
+    if is_health_path(path) {
         return Ok(next.run(req).await);
     }
 
-    let expected = match &state.auth.token {
-        Some(token) => token.as_str(),
-        None => return Ok(next.run(req).await),
-    };
+    let path = path.strip_prefix("/v1").unwrap_or(path);
+    let (scope, session_id) = required_scope(req.method(), path, req.uri().query());
+    let provided =
+        extract_token(req.headers()).or_else(|| sse_query_token(req.method(), path, req.uri()));
+    let authorized = authorize(&state, provided, scope, session_id.as_deref())?;
+    req.extensions_mut().insert(authorized.caller);
+    if authorized.session_bound {
+        req.extensions_mut().insert(SessionBound);
+    }
+    Ok(next.run(req).await)
+}
 
-    let provided = extract_token(req.headers());
-    if provided.as_deref() == Some(expected) {
-        Ok(next.run(req).await)
-    } else {
-        Err(SandboxError::TokenInvalid {
-            message: Some("missing or invalid token".to_string()),
+/// OpenCode-compatible routes can drive any session, so they need an admin token.
+async fn require_admin_token(
+    State(state): State<Arc<AppState>>,
+    mut req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let authorized = authorize(
+        &state,
+        extract_token(req.headers()),
+        TokenScope::Admin,
+        None,
+    )?;
+    req.extensions_mut().insert(authorized.caller);
+    Ok(next.run(req).await)
+}
+
+/// `/metrics` is scraped by monitoring, which only needs read access.
+async fn require_read_token(
+    State(state): State<Arc<AppState>>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    authorize(&state, extract_token(req.headers()), TokenScope::Read, None)?;
+    Ok(next.run(req).await)
+}
+
+/// Records latency per matched route. Unmatched paths share one label so arbitrary
+/// URLs cannot grow the metric's label set.
+async fn track_http_metrics(req: Request<axum::body::Body>, next: Next) -> Response {
+    let method = req.method().clone();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let started = Instant::now();
+    let response = next.run(req).await;
+    metrics()
+        .http_requests
+        .with_label_values(&[method.as_str(), &route, response.status().as_str()])
+        .observe(started.elapsed().as_secs_f64());
+    response
+}
+
+/// Marks requests made with a token bound to sessions. Filesystem routes then stay
+/// inside the session's working directory.
+#[derive(Debug, Clone, Copy)]
+struct SessionBound;
+
+struct Authorized {
+    /// Identity used for rate limits.
+    caller: Caller,
+    session_bound: bool,
+}
+
+/// Resolves and checks the request's token.
+fn authorize(
+    state: &AppState,
+    provided: Option<String>,
+    scope: TokenScope,
+    session_id: Option<&str>,
+) -> Result<Authorized, SandboxError> {
+    let provided = provided.ok_or_else(|| SandboxError::TokenInvalid {
+        message: Some("missing or invalid token".to_string()),
+    })?;
+    let token = state.auth.resolve(&provided)?;
+    token.check(scope, session_id)?;
+    Ok(Authorized {
+        caller: state
+            .session_manager
+            .rate_limiter
+            .caller(&token.caller_key(), token.limits.as_ref()),
+        session_bound: token.sessions.is_some(),
+    })
+}
+
+/// Applies per-token and server-wide request rates. Runs inside the auth middleware so
+/// the caller is known; without auth only the global rate applies.
+async fn enforce_rate_limits(
+    State(state): State<Arc<AppState>>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if !is_health_path(req.uri().path()) {
+        state
+            .session_manager
+            .rate_limiter
+            .check_request(req.extensions().get::<Caller>())?;
+    }
+    Ok(next.run(req).await)
+}
+
+/// Health and readiness probes skip auth and rate limits so orchestrators can poll them.
+fn is_health_path(path: &str) -> bool {
+    let path = path.strip_prefix("/v1").unwrap_or(path);
+    path == "/health" || path == "/health/ready"
+}
+
+/// Scope and session a `/v1` request needs, keyed by method and path.
+/// Filesystem routes are tied to a session through their `sessionId` query parameter.
+fn required_scope(
+    method: &axum::http::Method,
+    path: &str,
+    query: Option<&str>,
+) -> (TokenScope, Option<String>) {
+    let query_session = || {
+        query?
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == "sessionId" || *key == "session_id")
+            .map(|(_, value)| value.to_string())
+    };
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let is_get = method == axum::http::Method::GET;
+    match segments.as_slice() {
+        ["agents"] | ["agents", _, "modes" | "models" | "updates"] if is_get => {
+            (TokenScope::Read, None)
+        }
+        ["install-jobs", ..] if is_get => (TokenScope::Read, None),
+        ["diagnostics"] if is_get => (TokenScope::Read, None),
+        ["sessions"] if is_get => (TokenScope::Read, None),
+        ["sessions", session_id, rest @ ..] => {
+            let scope = match rest {
+                ["events"] | ["events", "sse"] if is_get => TokenScope::Read,
+                ["messages"] | ["messages", "stream"] | ["terminate"] => TokenScope::MessagesSend,
+                ["questions", _, "reply" | "reject"] | ["permissions", _, "reply"] => {
+                    TokenScope::PermissionsReply
+                }
+                _ => TokenScope::Admin,
+            };
+            (scope, Some(session_id.to_string()))
         }
-        .into())
+        ["fs", ..] if is_get => (TokenScope::FsRead, query_session()),
+        ["fs", ..] => (TokenScope::FsWrite, query_session()),
+        _ => (TokenScope::Admin, None),
+    }
+}
+
+/// `?token=` on GET SSE routes, which browsers open with `EventSource` and so
+/// cannot send an `Authorization` header.
+fn sse_query_token(
+    method: &axum::http::Method,
+    path: &str,
+    uri: &axum::http::Uri,
+) -> Option<String> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let is_sse = matches!(
+        segments.as_slice(),
+        ["sessions", _, "events", "sse"] | ["install-jobs", _, "events"]
+    );
+    if method != axum::http::Method::GET || !is_sse {
+        return None;
     }
+    let Query(params) = Query::<HashMap<String, String>>::try_from_uri(uri).ok()?;
+    params
+        .get("token")
+        .filter(|token| !token.is_empty())
+        .cloned()
+}
+
+/// The request URI for logs, with any `token` query parameter masked.
+fn loggable_uri(uri: &axum::http::Uri) -> String {
+    let Some(query) = uri.query() else {
+        return uri.to_string();
+    };
+    let masked = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some(("token", _)) => "token=<redacted>",
+            _ => pair,
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{}?{masked}", uri.path())
 }
 
 fn extract_token(headers: &HeaderMap) -> Option<String> {
@@ -4750,65 +6221,103 @@ fn extract_token(headers: &HeaderMap) -> Option<String> {
 pub struct AgentInstallRequest {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub reinstall: Option<bool>,
+    /// Version to install next to the others. Defaults to the pinned version, then the latest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Expected SHA-256 of the downloaded release file, checked instead of the published one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Return the install job right away (202) instead of waiting for it to finish.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct AgentModeInfo {
-    pub id: String,
-    pub name: String,
-    pub description: String,
+pub struct AgentUninstallQuery {
+    /// Remove only this side-by-side version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentUpdatesResponse {
+    /// Version the binary sessions run by default reports; unset when it is not installed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed_version: Option<String>,
+    /// Newest published version, as `version` on install takes it.
+    pub latest_version: String,
+    /// Whether `latestVersion` is newer than the installed one.
+    pub update_available: bool,
 }
 
+/// An agent install. Installs of one agent run one at a time, in the order requested.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct AgentModelInfo {
+pub struct InstallJob {
     pub id: String,
+    pub agent: String,
+    /// Version requested; unset installs the pinned or latest version.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub name: Option<String>,
+    pub version: Option<String>,
+    pub status: InstallJobStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<InstallJobProgress>,
+    /// Version reported by the installed binary.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub variants: Option<Vec<String>>,
+    pub installed_version: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub default_variant: Option<String>,
+    pub error: Option<ProblemDetails>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallJobStatus {
+    /// Waiting for another install of the same agent.
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl InstallJobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct AgentModelsResponse {
-    pub models: Vec<AgentModelInfo>,
+pub struct InstallJobProgress {
+    pub phase: InstallJobPhase,
+    /// File being downloaded.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub default_model: Option<String>,
+    pub url: Option<String>,
+    pub downloaded_bytes: u64,
+    /// Size of the download, when the server reports one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum InstallJobPhase {
+    Downloading,
+    /// The download was verified and is being unpacked into place.
+    Extracting,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct AgentModesResponse {
-    pub modes: Vec<AgentModeInfo>,
+pub struct InstallJobListResponse {
+    pub jobs: Vec<InstallJob>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct AgentCapabilities {
-    // TODO: add agent-agnostic tests that cover every capability flag here.
-    pub plan_mode: bool,
-    pub permissions: bool,
-    pub questions: bool,
-    pub tool_calls: bool,
-    pub tool_results: bool,
-    pub text_messages: bool,
-    pub images: bool,
-    pub file_attachments: bool,
-    pub session_lifecycle: bool,
-    pub error_events: bool,
-    pub reasoning: bool,
-    pub status: bool,
-    pub command_execution: bool,
-    pub file_changes: bool,
-    pub mcp_tools: bool,
-    pub streaming_deltas: bool,
-    pub item_started: bool,
-    /// Whether this agent uses a shared long-running server process (vs per-turn subprocess)
-    pub shared_process: bool,
+pub struct AgentModesResponse {
+    pub modes: Vec<AgentModeInfo>,
 }
 
 /// Status of a shared server process for an agent
@@ -4847,6 +6356,12 @@ pub struct AgentInfo {
     pub version: Option<String>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
+    /// Version pinned in the server's lockfile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pinned_version: Option<String>,
+    /// Versions installed side by side, selectable with `agentVersion`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub installed_versions: Vec<String>,
     pub capabilities: AgentCapabilities,
     /// Status of the shared server process (only present for agents with shared_process=true)
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -4868,6 +6383,8 @@ pub struct SessionInfo {
     pub permission_mode: String,
     pub model: Option<String>,
     pub variant: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_version: Option<String>,
     pub native_session_id: Option<String>,
     pub ended: bool,
     pub event_count: u64,
@@ -4892,6 +6409,75 @@ pub struct HealthResponse {
     pub status: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadinessCheck {
+    /// `disk`, `agentServers` or `sessionCapacity`
+    pub name: String,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadinessResponse {
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialStatus {
+    pub provider: String,
+    pub available: bool,
+    /// Where the credential was found, such as `environment` or a config file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// `api_key` or `oauth`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiskSpaceInfo {
+    /// `install` or `logs`
+    pub name: String,
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub available_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentError {
+    pub time: String,
+    /// `agent`, `agent_server` or `http`
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsResponse {
+    pub version: String,
+    pub uptime_ms: u64,
+    pub active_sessions: usize,
+    pub agents: Vec<AgentInfo>,
+    pub credentials: Vec<CredentialStatus>,
+    pub disks: Vec<DiskSpaceInfo>,
+    /// Newest first
+    pub recent_errors: Vec<RecentError>,
+    pub readiness: ReadinessResponse,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct FsPathQuery {
@@ -5005,117 +6591,77 @@ pub struct SkillsConfig {
     pub sources: Vec<SkillSource>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+// Layered over the daemon's per-agent limits. Rejected for agents with a shared
+// server (OpenCode, Codex, ACP), which only use the daemon limits.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct SkillSource {
-    #[serde(rename = "type")]
-    pub source_type: String,
-    pub source: String,
+pub struct SessionResourceLimits {
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub skills: Option<Vec<String>>,
-    #[serde(default, skip_serializing_if = "Option::is_none", rename = "ref")]
-    pub git_ref: Option<String>,
+    pub memory_bytes: Option<u64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub subpath: Option<String>,
+    pub cpu_weight: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_pids: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_open_files: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
-#[serde(untagged)]
-pub enum McpCommand {
-    Command(String),
-    CommandWithArgs(Vec<String>),
+impl From<&SessionResourceLimits> for ResourceLimits {
+    fn from(limits: &SessionResourceLimits) -> Self {
+        ResourceLimits {
+            memory_bytes: limits.memory_bytes,
+            cpu_weight: limits.cpu_weight,
+            max_pids: limits.max_pids,
+            max_open_files: limits.max_open_files,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
-#[serde(rename_all = "lowercase")]
-pub enum McpRemoteTransport {
-    Http,
-    Sse,
+// Enforced by the daemon's egress proxy through HTTP(S)_PROXY. Shared agent
+// servers (OpenCode, Codex) cannot be restricted per session.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionEgressPolicy {
+    /// Host patterns the session may reach: `example.com`, `*.example.com`, `example.com:8443` or `*`.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Also allow the agent's model provider endpoints. Defaults to true.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allow_provider_hosts: Option<bool>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema, JsonSchema)]
 #[serde(rename_all = "camelCase")]
-pub struct McpOAuthConfig {
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub client_id: Option<String>,
+pub struct CreateSessionTokenRequest {
+    /// Scopes granted to the token. Defaults to `read`, `messages:send` and `permissions:reply`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub client_secret: Option<String>,
+    pub scopes: Option<Vec<TokenScope>>,
+    /// Token lifetime in seconds. Defaults to 900, at most 86400.
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub scope: Option<String>,
+    pub ttl_seconds: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
-#[serde(untagged)]
-pub enum McpOAuthConfigOrDisabled {
-    Config(McpOAuthConfig),
-    Disabled(bool),
+#[serde(rename_all = "camelCase")]
+pub struct CreateSessionTokenResponse {
+    pub token: String,
+    pub scopes: Vec<TokenScope>,
+    /// Expiry as Unix milliseconds.
+    pub expires_at: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
-#[serde(rename_all = "camelCase", tag = "type")]
-pub enum McpServerConfig {
-    #[serde(rename = "local", alias = "stdio")]
-    Local {
-        command: McpCommand,
-        #[serde(default)]
-        args: Vec<String>,
-        #[serde(
-            default,
-            skip_serializing_if = "Option::is_none",
-            alias = "environment"
-        )]
-        env: Option<BTreeMap<String, String>>,
-        #[serde(default, skip_serializing_if = "Option::is_none")]
-        enabled: Option<bool>,
-        #[serde(
-            default,
-            skip_serializing_if = "Option::is_none",
-            rename = "timeoutMs",
-            alias = "timeout"
-        )]
-        #[schema(rename = "timeoutMs")]
-        timeout_ms: Option<u64>,
-        #[serde(default, skip_serializing_if = "Option::is_none")]
-        cwd: Option<String>,
-    },
-    #[serde(rename = "remote", alias = "http")]
-    Remote {
-        url: String,
-        #[serde(default, skip_serializing_if = "Option::is_none")]
-        headers: Option<BTreeMap<String, String>>,
-        #[serde(
-            default,
-            skip_serializing_if = "Option::is_none",
-            rename = "bearerTokenEnvVar",
-            alias = "bearerTokenEnvVar",
-            alias = "bearer_token_env_var"
-        )]
-        #[schema(rename = "bearerTokenEnvVar")]
-        bearer_token_env_var: Option<String>,
-        #[serde(
-            default,
-            skip_serializing_if = "Option::is_none",
-            rename = "envHeaders",
-            alias = "envHttpHeaders",
-            alias = "env_http_headers"
-        )]
-        #[schema(rename = "envHeaders")]
-        env_headers: Option<BTreeMap<String, String>>,
-        #[serde(default, skip_serializing_if = "Option::is_none")]
-        oauth: Option<McpOAuthConfigOrDisabled>,
-        #[serde(default, skip_serializing_if = "Option::is_none")]
-        enabled: Option<bool>,
-        #[serde(
-            default,
-            skip_serializing_if = "Option::is_none",
-            rename = "timeoutMs",
-            alias = "timeout"
-        )]
-        #[schema(rename = "timeoutMs")]
-        timeout_ms: Option<u64>,
-        #[serde(default, skip_serializing_if = "Option::is_none")]
-        transport: Option<McpRemoteTransport>,
-    },
+#[serde(rename_all = "camelCase")]
+pub struct SkillSource {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skills: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "ref")]
+    pub git_ref: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subpath: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
@@ -5139,6 +6685,16 @@ pub struct CreateSessionRequest {
     pub mcp: Option<BTreeMap<String, McpServerConfig>>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub skills: Option<SkillsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits: Option<SessionResourceLimits>,
+    /// Give the session its own HOME/XDG directories. Defaults to the server setting.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub isolate_home: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub egress: Option<SessionEgressPolicy>,
+    /// Capture every native line exchanged with the agent for `GET /v1/sessions/{id}/debug-log`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug_log: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
@@ -5149,6 +6705,10 @@ pub struct CreateSessionResponse {
     pub error: Option<AgentError>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub native_session_id: Option<String>,
+    /// Set when the agent's resource limits cannot use a cgroup and fall back to
+    /// rlimits, which cap each process or user rather than the process tree.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limits_fallback: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
@@ -5169,6 +6729,29 @@ pub struct MessageAttachment {
     pub filename: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsQuery {
+    /// Number of most recent lines to return (default 200, max 10000)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tail: Option<usize>,
+    /// Only lines written at or after this RFC 3339 time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// Stream the tail and then new lines as SSE `log` events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LogsResponse {
+    /// Oldest first
+    pub lines: Vec<String>,
+    /// Whether older matching lines were left out by `tail`
+    pub truncated: bool,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EventsQuery {
@@ -5214,69 +6797,230 @@ pub struct PermissionReplyRequest {
     pub reply: PermissionReply,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, JsonSchema)]
-#[serde(rename_all = "lowercase")]
-pub enum PermissionReply {
-    Once,
-    Always,
-    Reject,
-}
+#[utoipa::path(
+    post,
+    path = "/v1/agents/{agent}/install",
+    request_body = AgentInstallRequest,
+    responses(
+        (status = 204, description = "Agent installed"),
+        (status = 202, description = "Install job started, with `background`", body = InstallJob),
+        (status = 400, description = "Invalid request", body = ProblemDetails),
+        (status = 404, description = "Agent not found", body = ProblemDetails),
+        (status = 500, description = "Installation failed", body = ProblemDetails)
+    ),
+    params(("agent" = String, Path, description = "Agent id")),
+    tag = "agents"
+)]
+/// Install Agent
+///
+/// Installs or updates a coding agent (e.g. claude, codex, opencode, amp). Waits for
+/// the install unless `background` is set, in which case the job is returned to follow.
+async fn install_agent(
+    State(state): State<Arc<AppState>>,
+    Path(agent): Path<String>,
+    Json(request): Json<AgentInstallRequest>,
+) -> Result<Response, ApiError> {
+    let agent_id = parse_agent_id(&agent)?;
+    let (job, install) = state.session_manager.start_install(
+        agent_id,
+        InstallOptions {
+            reinstall: request.reinstall.unwrap_or(false),
+            version: request.version,
+            sha256: request.sha256,
+            from: None,
+            mirror: None,
+            progress: None,
+        },
+    );
+    if request.background.unwrap_or(false) {
+        return Ok((StatusCode::ACCEPTED, Json(job)).into_response());
+    }
 
-impl std::str::FromStr for PermissionReply {
-    type Err = String;
+    install.await.map_err(|err| SandboxError::InstallFailed {
+        agent: agent.clone(),
+        stderr: Some(err.to_string()),
+    })??;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
 
-    fn from_str(value: &str) -> Result<Self, Self::Err> {
-        match value.to_ascii_lowercase().as_str() {
-            "once" => Ok(Self::Once),
-            "always" => Ok(Self::Always),
-            "reject" => Ok(Self::Reject),
-            _ => Err(format!("invalid permission reply: {value}")),
-        }
+#[utoipa::path(
+    delete,
+    path = "/v1/agents/{agent}",
+    params(
+        ("agent" = String, Path, description = "Agent id"),
+        ("version" = Option<String>, Query, description = "Remove only this side-by-side version")
+    ),
+    responses(
+        (status = 204, description = "Agent uninstalled"),
+        (status = 400, description = "Invalid request", body = ProblemDetails),
+        (status = 404, description = "Agent or version not installed", body = ProblemDetails),
+        (status = 409, description = "Sessions are using the agent", body = ProblemDetails)
+    ),
+    tag = "agents"
+)]
+/// Uninstall Agent
+///
+/// Removes the agent's installs, or one version of it. Refused while sessions that
+/// have not ended use what would be removed; a shared server with no sessions left is
+/// stopped first. Binaries found on `PATH` are kept.
+async fn uninstall_agent(
+    State(state): State<Arc<AppState>>,
+    Path(agent): Path<String>,
+    Query(query): Query<AgentUninstallQuery>,
+) -> Result<StatusCode, ApiError> {
+    let agent_id = parse_agent_id(&agent)?;
+    let session_manager = &state.session_manager;
+    // Waits for installs and session starts of the agent and keeps new ones from
+    // starting meanwhile.
+    let _installs = session_manager.install_jobs.exclusive(agent_id).await;
+    let session_ids = session_manager
+        .sessions_using(agent_id, query.version.as_deref())
+        .await;
+    if !session_ids.is_empty() {
+        return Err(SandboxError::AgentInUse { agent, session_ids }.into());
+    }
+    if agent_uses_shared_server(agent_id) {
+        session_manager.server_manager.stop_server(agent_id).await;
     }
+
+    let manager = state.agent_manager.clone();
+    tokio::task::spawn_blocking(move || manager.uninstall(agent_id, query.version.as_deref()))
+        .await
+        .map_err(|err| SandboxError::StreamError {
+            message: err.to_string(),
+        })?
+        .map_err(|err| map_install_error(agent_id, err))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/agents/{agent}/updates",
+    params(("agent" = String, Path, description = "Agent id")),
+    responses(
+        (status = 200, description = "Installed and latest published versions", body = AgentUpdatesResponse),
+        (status = 400, description = "Agent has no published releases", body = ProblemDetails),
+        (status = 502, description = "Release host could not be reached", body = ProblemDetails)
+    ),
+    tag = "agents"
+)]
+/// Check Agent Updates
+///
+/// Compares the installed version with the newest one the agent's release host
+/// publishes, through the configured mirror if there is one.
+async fn get_agent_updates(
+    State(state): State<Arc<AppState>>,
+    Path(agent): Path<String>,
+) -> Result<Json<AgentUpdatesResponse>, ApiError> {
+    let agent_id = parse_agent_id(&agent)?;
+    let manager = state.agent_manager.clone();
+    let response = tokio::task::spawn_blocking(move || {
+        let latest_version = manager.latest_version(agent_id).map_err(|err| match err {
+            ManagerError::UnsupportedAgent { agent } => SandboxError::UnsupportedAgent { agent },
+            err => SandboxError::StreamError {
+                message: format!("checking {agent_id} updates: {err}"),
+            },
+        })?;
+        let installed_version = manager.version(agent_id).ok().flatten();
+        let update_available = installed_version
+            .as_deref()
+            .is_some_and(|installed| is_newer_version(&latest_version, installed));
+        Ok::<_, SandboxError>(AgentUpdatesResponse {
+            installed_version,
+            latest_version,
+            update_available,
+        })
+    })
+    .await
+    .map_err(|err| SandboxError::StreamError {
+        message: err.to_string(),
+    })??;
+    Ok(Json(response))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/install-jobs",
+    responses((status = 200, description = "Recent install jobs, oldest first", body = InstallJobListResponse)),
+    tag = "agents"
+)]
+/// List Install Jobs
+///
+/// Returns running and queued installs and the most recent finished ones.
+async fn list_install_jobs(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<InstallJobListResponse>, ApiError> {
+    Ok(Json(InstallJobListResponse {
+        jobs: state.session_manager.install_jobs.list(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/install-jobs/{job_id}",
+    responses(
+        (status = 200, description = "Install job", body = InstallJob),
+        (status = 400, description = "Unknown job", body = ProblemDetails)
+    ),
+    params(("job_id" = String, Path, description = "Install job id")),
+    tag = "agents"
+)]
+/// Get Install Job
+async fn get_install_job(
+    State(state): State<Arc<AppState>>,
+    Path(job_id): Path<String>,
+) -> Result<Json<InstallJob>, ApiError> {
+    let job = install_job(&state, &job_id)?;
+    let job = job.borrow().clone();
+    Ok(Json(job))
 }
 
 #[utoipa::path(
-    post,
-    path = "/v1/agents/{agent}/install",
-    request_body = AgentInstallRequest,
+    get,
+    path = "/v1/install-jobs/{job_id}/events",
     responses(
-        (status = 204, description = "Agent installed"),
-        (status = 400, description = "Invalid request", body = ProblemDetails),
-        (status = 404, description = "Agent not found", body = ProblemDetails),
-        (status = 500, description = "Installation failed", body = ProblemDetails)
+        (status = 200, description = "SSE stream of `job` events, ending when the job finishes"),
+        (status = 400, description = "Unknown job", body = ProblemDetails)
     ),
-    params(("agent" = String, Path, description = "Agent id")),
+    params(("job_id" = String, Path, description = "Install job id")),
     tag = "agents"
 )]
-/// Install Agent
+/// Follow Install Job (SSE)
 ///
-/// Installs or updates a coding agent (e.g. claude, codex, opencode, amp).
-async fn install_agent(
+/// Sends the job as a `job` event now and whenever its status or progress changes.
+/// The stream ends after the event for the finished job.
+async fn get_install_job_events(
     State(state): State<Arc<AppState>>,
-    Path(agent): Path<String>,
-    Json(request): Json<AgentInstallRequest>,
-) -> Result<StatusCode, ApiError> {
-    let agent_id = parse_agent_id(&agent)?;
-    let reinstall = request.reinstall.unwrap_or(false);
-    let manager = state.agent_manager.clone();
-
-    let result = tokio::task::spawn_blocking(move || {
-        manager.install(
-            agent_id,
-            InstallOptions {
-                reinstall,
-                version: None,
-            },
-        )
-    })
-    .await
-    .map_err(|err| SandboxError::InstallFailed {
-        agent: agent.clone(),
-        stderr: Some(err.to_string()),
-    })?;
+    Path(job_id): Path<String>,
+) -> Result<Sse<impl futures::Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let receiver = install_job(&state, &job_id)?;
+    let events = stream::unfold((Some(receiver), true), |(receiver, first)| async move {
+        let mut receiver = receiver?;
+        if !first {
+            receiver.changed().await.ok()?;
+        }
+        let job = receiver.borrow_and_update().clone();
+        let event = Event::default()
+            .event("job")
+            .json_data(&job)
+            .unwrap_or_else(|_| Event::default().event("job").data("{}"));
+        let receiver = (!job.status.is_finished()).then_some(receiver);
+        Some((Ok::<Event, Infallible>(event), (receiver, false)))
+    });
+    Ok(Sse::new(events).keep_alive(axum::response::sse::KeepAlive::default()))
+}
 
-    result.map_err(|err| map_install_error(agent_id, err))?;
-    Ok(StatusCode::NO_CONTENT)
+fn install_job(
+    state: &AppState,
+    job_id: &str,
+) -> Result<tokio::sync::watch::Receiver<InstallJob>, SandboxError> {
+    state
+        .session_manager
+        .install_jobs
+        .subscribe(job_id)
+        .ok_or_else(|| SandboxError::InvalidRequest {
+            message: format!("install job not found: {job_id}"),
+        })
 }
 
 #[utoipa::path(
@@ -5341,6 +7085,15 @@ async fn not_found() -> (StatusCode, String) {
     )
 }
 
+/// Prometheus metrics in the text exposition format.
+async fn get_metrics() -> Response {
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        metrics().render(),
+    )
+        .into_response()
+}
+
 #[utoipa::path(
     get,
     path = "/v1/health",
@@ -5356,6 +7109,185 @@ async fn get_health() -> Json<HealthResponse> {
     })
 }
 
+/// Free space below which the readiness probe fails, since installs and logs would.
+const MIN_FREE_DISK_BYTES: u64 = 100 * 1024 * 1024;
+
+#[utoipa::path(
+    get,
+    path = "/v1/health/ready",
+    responses(
+        (status = 200, description = "Server is ready for traffic", body = ReadinessResponse),
+        (status = 503, description = "Server is not ready", body = ReadinessResponse)
+    ),
+    tag = "meta"
+)]
+/// Readiness Check
+///
+/// Reports whether the server can take new sessions: enough free disk in the install
+/// and log directories, no shared agent server in an error state, and room under the
+/// session cap. Responds 503 when any check fails.
+async fn get_readiness(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let readiness = readiness(&state, &disk_info(&state).await).await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(readiness))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/diagnostics",
+    responses((status = 200, description = "Server diagnostics", body = DiagnosticsResponse)),
+    tag = "meta"
+)]
+/// Diagnostics
+///
+/// Returns installed agents and versions, credential availability per provider, shared
+/// server status, disk space, active sessions, recent errors and readiness in one call.
+async fn get_diagnostics(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<DiagnosticsResponse>, ApiError> {
+    let agents = agent_infos(&state).await?;
+    let credentials = tokio::task::spawn_blocking(credential_statuses)
+        .await
+        .map_err(|err| SandboxError::StreamError {
+            message: err.to_string(),
+        })?;
+    let disks = disk_info(&state).await;
+    let readiness = readiness(&state, &disks).await;
+    let active_sessions = state.session_manager.active_session_count().await;
+    let recent_errors = recent_errors()
+        .snapshot()
+        .into_iter()
+        .map(|record| RecentError {
+            time: record.time,
+            source: record.source.to_string(),
+            agent: record.agent,
+            session_id: record.session_id,
+            message: record.message,
+        })
+        .collect();
+
+    Ok(Json(DiagnosticsResponse {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_ms: state.started_at.elapsed().as_millis() as u64,
+        active_sessions,
+        agents,
+        credentials,
+        disks,
+        recent_errors,
+        readiness,
+    }))
+}
+
+async fn disk_info(state: &AppState) -> Vec<DiskSpaceInfo> {
+    let dirs = vec![
+        ("install", state.agent_manager.install_dir().to_path_buf()),
+        (
+            "logs",
+            state.session_manager.server_manager.log_base_dir.clone(),
+        ),
+    ];
+    tokio::task::spawn_blocking(move || {
+        dirs.into_iter()
+            .map(|(name, path)| {
+                let space = disk_space(&path);
+                DiskSpaceInfo {
+                    name: name.to_string(),
+                    path: path.to_string_lossy().to_string(),
+                    available_bytes: space.map(|(available, _)| available),
+                    total_bytes: space.map(|(_, total)| total),
+                }
+            })
+            .collect()
+    })
+    .await
+    .unwrap_or_default()
+}
+
+async fn readiness(state: &AppState, disks: &[DiskSpaceInfo]) -> ReadinessResponse {
+    let low_disks: Vec<String> = disks
+        .iter()
+        .filter(|disk| {
+            disk.available_bytes
+                .is_some_and(|available| available < MIN_FREE_DISK_BYTES)
+        })
+        .map(|disk| {
+            format!(
+                "{} directory has {} MiB free",
+                disk.name,
+                disk.available_bytes.unwrap_or(0) / (1024 * 1024)
+            )
+        })
+        .collect();
+
+    let statuses = state.session_manager.server_manager.status_snapshot().await;
+    let mut failed_servers: Vec<String> = statuses
+        .iter()
+        .filter(|(_, status)| matches!(status.status, ServerStatus::Error))
+        .map(|(agent, status)| match &status.last_error {
+            Some(error) => format!("{} server: {error}", agent.as_str()),
+            None => format!("{} server is in an error state", agent.as_str()),
+        })
+        .collect();
+    failed_servers.sort();
+
+    let active = state.session_manager.active_session_count().await;
+    let capacity = state
+        .session_manager
+        .rate_limiter
+        .check_cap(Cap::Sessions, None, active, 0)
+        .err()
+        .map(|err| err.to_string());
+
+    let checks = vec![
+        readiness_check("disk", low_disks),
+        readiness_check("agentServers", failed_servers),
+        readiness_check("sessionCapacity", capacity.into_iter().collect()),
+    ];
+    ReadinessResponse {
+        ready: checks.iter().all(|check| check.ok),
+        checks,
+    }
+}
+
+fn readiness_check(name: &str, problems: Vec<String>) -> ReadinessCheck {
+    ReadinessCheck {
+        name: name.to_string(),
+        ok: problems.is_empty(),
+        message: (!problems.is_empty()).then(|| problems.join("; ")),
+    }
+}
+
+fn credential_statuses() -> Vec<CredentialStatus> {
+    let credentials = extract_all_credentials(&CredentialExtractionOptions::new());
+    let status = |provider: &str, found: Option<&ProviderCredentials>| CredentialStatus {
+        provider: provider.to_string(),
+        available: found.is_some(),
+        source: found.map(|credentials| credentials.source.clone()),
+        auth_type: found.map(|credentials| match credentials.auth_type {
+            AuthType::ApiKey => "api_key".to_string(),
+            AuthType::Oauth => "oauth".to_string(),
+        }),
+    };
+    let mut statuses = vec![
+        status("anthropic", credentials.anthropic.as_ref()),
+        status("openai", credentials.openai.as_ref()),
+    ];
+    let mut other: Vec<_> = credentials.other.iter().collect();
+    other.sort_by(|a, b| a.0.cmp(b.0));
+    statuses.extend(
+        other
+            .into_iter()
+            .map(|(provider, found)| status(provider, Some(found))),
+    );
+    statuses
+}
+
 #[utoipa::path(
     get,
     path = "/v1/agents",
@@ -5368,64 +7300,175 @@ async fn get_health() -> Json<HealthResponse> {
 async fn list_agents(
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<AgentListResponse>, ApiError> {
+    let agents = agent_infos(&state).await?;
+    Ok(Json(AgentListResponse { agents }))
+}
+
+async fn agent_infos(state: &AppState) -> Result<Vec<AgentInfo>, SandboxError> {
     let manager = state.agent_manager.clone();
     let server_statuses = state.session_manager.server_manager.status_snapshot().await;
 
-    let agents =
-        tokio::task::spawn_blocking(move || {
-            let credentials = extract_all_credentials(&CredentialExtractionOptions::new());
-            let has_anthropic = credentials.anthropic.is_some();
-            let has_openai = credentials.openai.is_some();
+    let agents = tokio::task::spawn_blocking(move || {
+        let credentials = extract_all_credentials(&CredentialExtractionOptions::new());
+
+        all_agents()
+            .into_iter()
+            .map(|agent_id| {
+                let installed = manager.is_installed(agent_id);
+                let version = manager.version(agent_id).ok().flatten();
+                let path = manager.resolve_binary(agent_id).ok();
+                let capabilities = agent_capabilities_for(agent_id);
+
+                let credentials_available = adapter(agent_id).credentials_available(&credentials);
+
+                // Add server_status for agents with shared processes
+                let server_status = if capabilities.shared_process {
+                    Some(
+                        server_statuses
+                            .get(&agent_id)
+                            .cloned()
+                            .unwrap_or(ServerStatusInfo {
+                                status: ServerStatus::Stopped,
+                                base_url: None,
+                                uptime_ms: None,
+                                restart_count: 0,
+                                last_error: None,
+                            }),
+                    )
+                } else {
+                    None
+                };
 
-            all_agents()
-                .into_iter()
-                .map(|agent_id| {
-                    let installed = manager.is_installed(agent_id);
-                    let version = manager.version(agent_id).ok().flatten();
-                    let path = manager.resolve_binary(agent_id).ok();
-                    let capabilities = agent_capabilities_for(agent_id);
-
-                    let credentials_available = match agent_id {
-                        AgentId::Claude | AgentId::Amp => has_anthropic,
-                        AgentId::Codex => has_openai,
-                        AgentId::Opencode => has_anthropic || has_openai,
-                        AgentId::Mock => true,
-                    };
+                AgentInfo {
+                    id: agent_id.as_str().to_string(),
+                    installed,
+                    credentials_available,
+                    version,
+                    path: path.map(|path| path.to_string_lossy().to_string()),
+                    pinned_version: manager.pinned_version(agent_id).map(str::to_string),
+                    installed_versions: manager.installed_versions(agent_id),
+                    capabilities,
+                    server_status,
+                }
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|err| SandboxError::StreamError {
+        message: err.to_string(),
+    })?;
 
-                    // Add server_status for agents with shared processes
-                    let server_status =
-                        if capabilities.shared_process {
-                            Some(server_statuses.get(&agent_id).cloned().unwrap_or(
-                                ServerStatusInfo {
-                                    status: ServerStatus::Stopped,
-                                    base_url: None,
-                                    uptime_ms: None,
-                                    restart_count: 0,
-                                    last_error: None,
-                                },
-                            ))
-                        } else {
-                            None
-                        };
+    Ok(agents)
+}
 
-                    AgentInfo {
-                        id: agent_id.as_str().to_string(),
-                        installed,
-                        credentials_available,
-                        version,
-                        path: path.map(|path| path.to_string_lossy().to_string()),
-                        capabilities,
-                        server_status,
-                    }
+#[utoipa::path(
+    get,
+    path = "/v1/agents/{agent}/logs",
+    params(
+        ("agent" = String, Path, description = "Agent id"),
+        ("tail" = Option<usize>, Query, description = "Number of most recent lines (default 200, max 10000)"),
+        ("since" = Option<String>, Query, description = "Only lines written at or after this RFC 3339 time"),
+        ("follow" = Option<bool>, Query, description = "Stream as SSE and keep sending new lines")
+    ),
+    responses(
+        (status = 200, description = "Agent server log lines, or an SSE stream when following", body = LogsResponse),
+        (status = 400, description = "Invalid request", body = ProblemDetails)
+    ),
+    tag = "agents"
+)]
+/// Get Agent Logs
+///
+/// Returns the stderr log of an agent's shared server process (OpenCode, Codex),
+/// with secrets masked. With `follow=true` the response is an SSE stream of `log` events.
+async fn get_agent_logs(
+    State(state): State<Arc<AppState>>,
+    Path(agent): Path<String>,
+    Query(query): Query<LogsQuery>,
+) -> Result<Response, ApiError> {
+    let agent = parse_agent_id(&agent)?;
+    let files = AgentServerLogs::new(
+        state.session_manager.server_manager.log_base_dir.clone(),
+        agent.as_str(),
+    )
+    .log_files();
+    logs_response(files, query).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/logs/server",
+    params(
+        ("tail" = Option<usize>, Query, description = "Number of most recent lines (default 200, max 10000)"),
+        ("since" = Option<String>, Query, description = "Only lines written at or after this RFC 3339 time"),
+        ("follow" = Option<bool>, Query, description = "Stream as SSE and keep sending new lines")
+    ),
+    responses(
+        (status = 200, description = "Server log lines, or an SSE stream when following", body = LogsResponse),
+        (status = 400, description = "Invalid request", body = ProblemDetails)
+    ),
+    tag = "meta"
+)]
+/// Get Server Logs
+///
+/// Returns the server's own rotated logs. With `follow=true` the response is an SSE
+/// stream of `log` events.
+async fn get_server_logs(Query(query): Query<LogsQuery>) -> Result<Response, ApiError> {
+    logs_response(server_logs::log_files(), query).await
+}
+
+/// How often a follow stream checks the log for new lines.
+const LOG_FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+async fn logs_response(files: LogFiles, query: LogsQuery) -> Result<Response, ApiError> {
+    let tail = query.tail.unwrap_or(DEFAULT_TAIL_LINES).min(MAX_TAIL_LINES);
+    let since = query
+        .since
+        .as_deref()
+        .map(|since| {
+            time::OffsetDateTime::parse(since, &time::format_description::well_known::Rfc3339)
+                .map_err(|err| SandboxError::InvalidRequest {
+                    message: format!("invalid since time: {err}"),
                 })
-                .collect::<Vec<_>>()
         })
-        .await
-        .map_err(|err| SandboxError::StreamError {
-            message: err.to_string(),
-        })?;
+        .transpose()?;
+    let follow = query.follow.unwrap_or(false);
 
-    Ok(Json(AgentListResponse { agents }))
+    let (lines, truncated, follower) = tokio::task::spawn_blocking(move || {
+        let follower = follow.then(|| files.follow());
+        let (lines, truncated) = files.read(tail, since, follower.as_ref());
+        (lines, truncated, follower)
+    })
+    .await
+    .map_err(|err| SandboxError::StreamError {
+        message: err.to_string(),
+    })?;
+
+    let Some(follower) = follower else {
+        return Ok(Json(LogsResponse { lines, truncated }).into_response());
+    };
+    let initial = stream::iter(lines);
+    let live = stream::unfold(follower, |mut follower| async move {
+        loop {
+            tokio::time::sleep(LOG_FOLLOW_INTERVAL).await;
+            let (returned, lines) = tokio::task::spawn_blocking(move || {
+                let lines = follower.poll();
+                (follower, lines)
+            })
+            .await
+            .ok()?;
+            follower = returned;
+            if !lines.is_empty() {
+                return Some((stream::iter(lines), follower));
+            }
+        }
+    })
+    .flatten();
+    let events = initial
+        .chain(live)
+        .map(|line| Ok::<Event, Infallible>(Event::default().event("log").data(line)));
+    Ok(Sse::new(events)
+        .keep_alive(axum::response::sse::KeepAlive::default())
+        .into_response())
 }
 
 #[utoipa::path(
@@ -5462,11 +7505,12 @@ async fn list_sessions(
 async fn create_session(
     State(state): State<Arc<AppState>>,
     Path(session_id): Path<String>,
+    caller: Option<Extension<Caller>>,
     Json(request): Json<CreateSessionRequest>,
 ) -> Result<Json<CreateSessionResponse>, ApiError> {
     let response = state
         .session_manager
-        .create_session(session_id, request)
+        .create_session(session_id, request, caller.as_deref())
         .await?;
     Ok(Json(response))
 }
@@ -5488,11 +7532,17 @@ async fn create_session(
 async fn post_message(
     State(state): State<Arc<AppState>>,
     Path(session_id): Path<String>,
+    caller: Option<Extension<Caller>>,
     Json(request): Json<MessageRequest>,
 ) -> Result<StatusCode, ApiError> {
     state
         .session_manager
-        .send_message(session_id, request.message, request.attachments)
+        .send_message(
+            session_id,
+            request.message,
+            request.attachments,
+            caller.as_deref(),
+        )
         .await?;
     Ok(StatusCode::NO_CONTENT)
 }
@@ -5518,6 +7568,7 @@ async fn post_message_stream(
     State(state): State<Arc<AppState>>,
     Path(session_id): Path<String>,
     Query(query): Query<TurnStreamQuery>,
+    caller: Option<Extension<Caller>>,
     Json(request): Json<MessageRequest>,
 ) -> Result<Sse<impl futures::Stream<Item = Result<Event, Infallible>>>, ApiError> {
     let include_raw = query.include_raw.unwrap_or(false);
@@ -5527,7 +7578,12 @@ async fn post_message_stream(
         .await?;
     state
         .session_manager
-        .send_message(session_id, request.message, request.attachments)
+        .send_message(
+            session_id,
+            request.message,
+            request.attachments,
+            caller.as_deref(),
+        )
         .await?;
     let stream = stream_turn_events(subscription, snapshot.agent, include_raw);
     Ok(Sse::new(stream))
@@ -5554,6 +7610,68 @@ async fn terminate_session(
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[utoipa::path(
+    post,
+    path = "/v1/sessions/{session_id}/tokens",
+    request_body = CreateSessionTokenRequest,
+    params(("session_id" = String, Path, description = "Session id")),
+    responses(
+        (status = 200, description = "Session token created", body = CreateSessionTokenResponse),
+        (status = 400, description = "Invalid scopes or lifetime", body = ProblemDetails),
+        (status = 404, description = "Session not found", body = ProblemDetails)
+    ),
+    tag = "sessions"
+)]
+/// Create Session Token
+///
+/// Mints a short-lived signed token limited to one session, for handing to browser clients.
+async fn create_session_token(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+    Json(request): Json<CreateSessionTokenRequest>,
+) -> Result<Json<CreateSessionTokenResponse>, ApiError> {
+    let scopes = request.scopes.unwrap_or_else(|| {
+        vec![
+            TokenScope::Read,
+            TokenScope::MessagesSend,
+            TokenScope::PermissionsReply,
+        ]
+    });
+    if scopes.is_empty() || scopes.contains(&TokenScope::Admin) {
+        return Err(SandboxError::InvalidRequest {
+            message: "session tokens need at least one scope and cannot carry admin".to_string(),
+        }
+        .into());
+    }
+    let ttl_seconds = request
+        .ttl_seconds
+        .unwrap_or(SESSION_TOKEN_DEFAULT_TTL_SECS);
+    if ttl_seconds == 0 || ttl_seconds > SESSION_TOKEN_MAX_TTL_SECS {
+        return Err(SandboxError::InvalidRequest {
+            message: format!("ttlSeconds must be between 1 and {SESSION_TOKEN_MAX_TTL_SECS}"),
+        }
+        .into());
+    }
+    if state
+        .session_manager
+        .get_session_info(&session_id)
+        .await
+        .is_none()
+    {
+        return Err(SandboxError::SessionNotFound { session_id }.into());
+    }
+    let (token, claims) =
+        state
+            .auth
+            .session_tokens
+            .mint(&session_id, scopes, Duration::from_secs(ttl_seconds));
+    Ok(Json(CreateSessionTokenResponse {
+        token,
+        scopes: claims.scopes,
+        expires_at: (claims.exp as i64).saturating_mul(1000),
+    }))
+}
+
 #[utoipa::path(
     get,
     path = "/v1/sessions/{session_id}/events",
@@ -5632,7 +7750,13 @@ async fn get_events_sse(
                     }
                     Some(Ok::<Event, Infallible>(to_sse_event(event)))
                 }
-                Err(_) => None,
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                    metrics()
+                        .broadcast_lagged
+                        .with_label_values(&["sse"])
+                        .inc_by(skipped);
+                    None
+                }
             }
         }
     });
@@ -5641,6 +7765,29 @@ async fn get_events_sse(
     Ok(Sse::new(stream))
 }
 
+#[utoipa::path(
+    get,
+    path = "/v1/sessions/{session_id}/debug-log",
+    params(("session_id" = String, Path, description = "Session id")),
+    responses(
+        (status = 200, description = "Captured native agent I/O as NDJSON", body = String, content_type = "application/x-ndjson"),
+        (status = 400, description = "Session was created without debugLog", body = ProblemDetails),
+        (status = 404, description = "Session not found", body = ProblemDetails)
+    ),
+    tag = "sessions"
+)]
+/// Get Debug Log
+///
+/// Returns every native line exchanged with the agent since the session was created
+/// with `debugLog: true`, one JSON object per line.
+async fn get_debug_log(
+    State(state): State<Arc<AppState>>,
+    Path(session_id): Path<String>,
+) -> Result<Response, ApiError> {
+    let body = state.session_manager.debug_log(&session_id).await?;
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response())
+}
+
 #[utoipa::path(
     post,
     path = "/v1/sessions/{session_id}/questions/{question_id}/reply",
@@ -5741,10 +7888,12 @@ async fn reply_permission(
 /// Lists files and directories at the given path.
 async fn fs_entries(
     State(state): State<Arc<AppState>>,
+    bound: Option<Extension<SessionBound>>,
     Query(query): Query<FsEntriesQuery>,
 ) -> Result<Json<Vec<FsEntry>>, ApiError> {
     let path = query.path.unwrap_or_else(|| ".".to_string());
-    let target = resolve_fs_path(&state, query.session_id.as_deref(), &path).await?;
+    let target =
+        resolve_fs_path(&state, query.session_id.as_deref(), &path, bound.is_some()).await?;
     let metadata = fs::metadata(&target).map_err(|err| map_fs_error(&target, err))?;
     if !metadata.is_dir() {
         return Err(SandboxError::InvalidRequest {
@@ -5797,9 +7946,16 @@ async fn fs_entries(
 /// Reads the raw bytes of a file.
 async fn fs_read_file(
     State(state): State<Arc<AppState>>,
+    bound: Option<Extension<SessionBound>>,
     Query(query): Query<FsPathQuery>,
 ) -> Result<Response, ApiError> {
-    let target = resolve_fs_path(&state, query.session_id.as_deref(), &query.path).await?;
+    let target = resolve_fs_path(
+        &state,
+        query.session_id.as_deref(),
+        &query.path,
+        bound.is_some(),
+    )
+    .await?;
     let metadata = fs::metadata(&target).map_err(|err| map_fs_error(&target, err))?;
     if !metadata.is_file() {
         return Err(SandboxError::InvalidRequest {
@@ -5831,10 +7987,17 @@ async fn fs_read_file(
 /// Writes raw bytes to a file, creating it if it doesn't exist.
 async fn fs_write_file(
     State(state): State<Arc<AppState>>,
+    bound: Option<Extension<SessionBound>>,
     Query(query): Query<FsPathQuery>,
     body: Bytes,
 ) -> Result<Json<FsWriteResponse>, ApiError> {
-    let target = resolve_fs_path(&state, query.session_id.as_deref(), &query.path).await?;
+    let target = resolve_fs_path(
+        &state,
+        query.session_id.as_deref(),
+        &query.path,
+        bound.is_some(),
+    )
+    .await?;
     if let Some(parent) = target.parent() {
         fs::create_dir_all(parent).map_err(|err| map_fs_error(parent, err))?;
     }
@@ -5861,9 +8024,16 @@ async fn fs_write_file(
 /// Deletes a file or directory.
 async fn fs_delete_entry(
     State(state): State<Arc<AppState>>,
+    bound: Option<Extension<SessionBound>>,
     Query(query): Query<FsDeleteQuery>,
 ) -> Result<Json<FsActionResponse>, ApiError> {
-    let target = resolve_fs_path(&state, query.session_id.as_deref(), &query.path).await?;
+    let target = resolve_fs_path(
+        &state,
+        query.session_id.as_deref(),
+        &query.path,
+        bound.is_some(),
+    )
+    .await?;
     let metadata = fs::metadata(&target).map_err(|err| map_fs_error(&target, err))?;
     if metadata.is_dir() {
         if query.recursive.unwrap_or(false) {
@@ -5894,9 +8064,16 @@ async fn fs_delete_entry(
 /// Creates a directory, including any missing parent directories.
 async fn fs_mkdir(
     State(state): State<Arc<AppState>>,
+    bound: Option<Extension<SessionBound>>,
     Query(query): Query<FsPathQuery>,
 ) -> Result<Json<FsActionResponse>, ApiError> {
-    let target = resolve_fs_path(&state, query.session_id.as_deref(), &query.path).await?;
+    let target = resolve_fs_path(
+        &state,
+        query.session_id.as_deref(),
+        &query.path,
+        bound.is_some(),
+    )
+    .await?;
     fs::create_dir_all(&target).map_err(|err| map_fs_error(&target, err))?;
     Ok(Json(FsActionResponse {
         path: target.to_string_lossy().to_string(),
@@ -5916,12 +8093,13 @@ async fn fs_mkdir(
 /// Moves or renames a file or directory.
 async fn fs_move(
     State(state): State<Arc<AppState>>,
+    bound: Option<Extension<SessionBound>>,
     Query(query): Query<FsSessionQuery>,
     Json(request): Json<FsMoveRequest>,
 ) -> Result<Json<FsMoveResponse>, ApiError> {
     let session_id = query.session_id.as_deref();
-    let from = resolve_fs_path(&state, session_id, &request.from).await?;
-    let to = resolve_fs_path(&state, session_id, &request.to).await?;
+    let from = resolve_fs_path(&state, session_id, &request.from, bound.is_some()).await?;
+    let to = resolve_fs_path(&state, session_id, &request.to, bound.is_some()).await?;
     if to.exists() {
         if request.overwrite.unwrap_or(false) {
             let metadata = fs::metadata(&to).map_err(|err| map_fs_error(&to, err))?;
@@ -5962,9 +8140,16 @@ async fn fs_move(
 /// Returns metadata (size, timestamps, type) for a path.
 async fn fs_stat(
     State(state): State<Arc<AppState>>,
+    bound: Option<Extension<SessionBound>>,
     Query(query): Query<FsPathQuery>,
 ) -> Result<Json<FsStat>, ApiError> {
-    let target = resolve_fs_path(&state, query.session_id.as_deref(), &query.path).await?;
+    let target = resolve_fs_path(
+        &state,
+        query.session_id.as_deref(),
+        &query.path,
+        bound.is_some(),
+    )
+    .await?;
     let metadata = fs::metadata(&target).map_err(|err| map_fs_error(&target, err))?;
     let entry_type = if metadata.is_dir() {
         FsEntryType::Directory
@@ -6000,6 +8185,7 @@ async fn fs_stat(
 /// Uploads a tar.gz archive and extracts it to the destination directory.
 async fn fs_upload_batch(
     State(state): State<Arc<AppState>>,
+    bound: Option<Extension<SessionBound>>,
     headers: HeaderMap,
     Query(query): Query<FsUploadBatchQuery>,
     body: Bytes,
@@ -6015,7 +8201,7 @@ async fn fs_upload_batch(
         .into());
     }
     let path = query.path.unwrap_or_else(|| ".".to_string());
-    let base = resolve_fs_path(&state, query.session_id.as_deref(), &path).await?;
+    let base = resolve_fs_path(&state, query.session_id.as_deref(), &path, bound.is_some()).await?;
     fs::create_dir_all(&base).map_err(|err| map_fs_error(&base, err))?;
 
     let mut archive = Archive::new(Cursor::new(body));
@@ -6034,6 +8220,17 @@ async fn fs_upload_batch(
         if clean_path.as_os_str().is_empty() {
             continue;
         }
+        // Links could point later entries outside the session's working directory.
+        let entry_type = entry.header().entry_type();
+        if bound.is_some() && (entry_type.is_symlink() || entry_type.is_hard_link()) {
+            return Err(SandboxError::PermissionDenied {
+                message: Some(format!(
+                    "links are not allowed with session tokens: {}",
+                    entry_path.display()
+                )),
+            }
+            .into());
+        }
         let dest = base.join(&clean_path);
         if !dest.starts_with(&base) {
             return Err(SandboxError::InvalidRequest {
@@ -6062,239 +8259,43 @@ async fn fs_upload_batch(
     }))
 }
 
-fn all_agents() -> [AgentId; 5] {
-    [
-        AgentId::Claude,
-        AgentId::Codex,
-        AgentId::Opencode,
-        AgentId::Amp,
-        AgentId::Mock,
-    ]
+fn all_agents() -> Vec<AgentId> {
+    agent_registry().agents()
 }
 
 /// Returns true if the agent supports resuming a session after its process exits.
 /// These agents can use --resume/--continue to continue a conversation.
-fn agent_supports_resume(agent: AgentId) -> bool {
-    matches!(
-        agent,
-        AgentId::Claude | AgentId::Amp | AgentId::Opencode | AgentId::Codex
-    )
-}
-
-fn agent_supports_item_started(agent: AgentId) -> bool {
-    agent_capabilities_for(agent).item_started
-}
-
-fn agent_emits_turn_started(agent: AgentId) -> bool {
-    matches!(agent, AgentId::Codex | AgentId::Opencode)
-}
-
-fn agent_capabilities_for(agent: AgentId) -> AgentCapabilities {
-    match agent {
-        // Claude CLI supports tool calls/results and permission prompts via the SDK control protocol,
-        // but we still emit synthetic item.started events.
-        AgentId::Claude => AgentCapabilities {
-            plan_mode: false,
-            permissions: true,
-            questions: true,
-            tool_calls: true,
-            tool_results: true,
-            text_messages: true,
-            images: false,
-            file_attachments: false,
-            session_lifecycle: false,
-            error_events: false,
-            reasoning: false,
-            status: false,
-            command_execution: false,
-            file_changes: false,
-            mcp_tools: true,
-            streaming_deltas: true,
-            item_started: false,
-            shared_process: false, // per-turn subprocess with --resume
-        },
-        AgentId::Codex => AgentCapabilities {
-            plan_mode: true,
-            permissions: true,
-            questions: false,
-            tool_calls: true,
-            tool_results: true,
-            text_messages: true,
-            images: true,
-            file_attachments: true,
-            session_lifecycle: true,
-            error_events: true,
-            reasoning: true,
-            status: true,
-            command_execution: true,
-            file_changes: true,
-            mcp_tools: true,
-            streaming_deltas: true,
-            item_started: true,
-            shared_process: true, // shared app-server via JSON-RPC
-        },
-        AgentId::Opencode => AgentCapabilities {
-            plan_mode: false,
-            permissions: false,
-            questions: false,
-            tool_calls: true,
-            tool_results: true,
-            text_messages: true,
-            images: true,
-            file_attachments: true,
-            session_lifecycle: true,
-            error_events: true,
-            reasoning: false,
-            status: false,
-            command_execution: false,
-            file_changes: false,
-            mcp_tools: true,
-            streaming_deltas: true,
-            item_started: true,
-            shared_process: true, // shared HTTP server
-        },
-        AgentId::Amp => AgentCapabilities {
-            plan_mode: false,
-            permissions: false,
-            questions: false,
-            tool_calls: true,
-            tool_results: true,
-            text_messages: true,
-            images: false,
-            file_attachments: false,
-            session_lifecycle: false,
-            error_events: true,
-            reasoning: false,
-            status: false,
-            command_execution: false,
-            file_changes: false,
-            mcp_tools: true,
-            streaming_deltas: false,
-            item_started: false,
-            shared_process: false, // per-turn subprocess with --continue
-        },
-        AgentId::Mock => AgentCapabilities {
-            plan_mode: true,
-            permissions: true,
-            questions: true,
-            tool_calls: true,
-            tool_results: true,
-            text_messages: true,
-            images: true,
-            file_attachments: true,
-            session_lifecycle: true,
-            error_events: true,
-            reasoning: true,
-            status: true,
-            command_execution: true,
-            file_changes: true,
-            mcp_tools: true,
-            streaming_deltas: true,
-            item_started: true,
-            shared_process: false, // in-memory mock (no subprocess)
-        },
-    }
-}
-
-fn parse_agent_id(agent: &str) -> Result<AgentId, SandboxError> {
-    AgentId::parse(agent).ok_or_else(|| SandboxError::UnsupportedAgent {
-        agent: agent.to_string(),
-    })
-}
-
-fn agent_modes_for(agent: AgentId) -> Vec<AgentModeInfo> {
-    match agent {
-        AgentId::Opencode => vec![
-            AgentModeInfo {
-                id: "build".to_string(),
-                name: "Build".to_string(),
-                description: "Default build mode".to_string(),
-            },
-            AgentModeInfo {
-                id: "plan".to_string(),
-                name: "Plan".to_string(),
-                description: "Planning mode".to_string(),
-            },
-            AgentModeInfo {
-                id: "custom".to_string(),
-                name: "Custom".to_string(),
-                description: "Any user-defined OpenCode agent name".to_string(),
-            },
-        ],
-        AgentId::Codex => vec![
-            AgentModeInfo {
-                id: "build".to_string(),
-                name: "Build".to_string(),
-                description: "Default build mode".to_string(),
-            },
-            AgentModeInfo {
-                id: "plan".to_string(),
-                name: "Plan".to_string(),
-                description: "Planning mode via prompt prefix".to_string(),
-            },
-        ],
-        AgentId::Claude => vec![
-            AgentModeInfo {
-                id: "build".to_string(),
-                name: "Build".to_string(),
-                description: "Default build mode".to_string(),
-            },
-            AgentModeInfo {
-                id: "plan".to_string(),
-                name: "Plan".to_string(),
-                description: "Plan mode (prompt-only)".to_string(),
-            },
-        ],
-        AgentId::Amp => vec![AgentModeInfo {
-            id: "build".to_string(),
-            name: "Build".to_string(),
-            description: "Default build mode".to_string(),
-        }],
-        AgentId::Mock => vec![
-            AgentModeInfo {
-                id: "build".to_string(),
-                name: "Build".to_string(),
-                description: "Mock agent for UI testing".to_string(),
-            },
-            AgentModeInfo {
-                id: "plan".to_string(),
-                name: "Plan".to_string(),
-                description: "Plan-only mock mode".to_string(),
-            },
-        ],
-    }
+fn agent_supports_resume(agent: AgentId) -> bool {
+    adapter(agent).supports_resume()
 }
 
-fn amp_models_response() -> AgentModelsResponse {
-    let models = vec![AgentModelInfo {
-        id: "amp-default".to_string(),
-        name: Some("Amp Default".to_string()),
-        variants: None,
-        default_variant: None,
-    }];
-    AgentModelsResponse {
-        models,
-        default_model: Some("amp-default".to_string()),
-    }
+fn agent_supports_item_started(agent: AgentId) -> bool {
+    agent_capabilities_for(agent).item_started
 }
 
-fn mock_models_response() -> AgentModelsResponse {
-    AgentModelsResponse {
-        models: vec![AgentModelInfo {
-            id: "mock".to_string(),
-            name: Some("Mock".to_string()),
-            variants: None,
-            default_variant: None,
-        }],
-        default_model: Some("mock".to_string()),
-    }
+fn agent_emits_turn_started(agent: AgentId) -> bool {
+    adapter(agent).emits_turn_started()
 }
 
-fn should_cache_agent_models(agent: AgentId, response: &AgentModelsResponse) -> bool {
-    if agent == AgentId::Opencode && response.models.is_empty() {
-        return false;
-    }
-    true
+/// Returns true if all sessions of the agent talk to one long-running server process.
+fn agent_uses_shared_server(agent: AgentId) -> bool {
+    agent_capabilities_for(agent).shared_process
+}
+
+fn agent_capabilities_for(agent: AgentId) -> AgentCapabilities {
+    adapter(agent).capabilities()
+}
+
+fn parse_agent_id(agent: &str) -> Result<AgentId, SandboxError> {
+    AgentId::parse(agent)
+        .filter(|agent| agent_registry().get(*agent).is_some())
+        .ok_or_else(|| SandboxError::UnsupportedAgent {
+            agent: agent.to_string(),
+        })
+}
+
+fn agent_modes_for(agent: AgentId) -> Vec<AgentModeInfo> {
+    adapter(agent).modes()
 }
 
 fn parse_opencode_models(value: &Value) -> Option<AgentModelsResponse> {
@@ -6361,53 +8362,13 @@ fn parse_opencode_models(value: &Value) -> Option<AgentModelsResponse> {
 
 fn normalize_agent_mode(agent: AgentId, agent_mode: Option<&str>) -> Result<String, SandboxError> {
     let mode = agent_mode.unwrap_or("build");
-    match agent {
-        AgentId::Opencode => Ok(mode.to_string()),
-        AgentId::Codex => match mode {
-            "build" | "plan" => Ok(mode.to_string()),
-            value => Err(SandboxError::ModeNotSupported {
-                agent: agent.as_str().to_string(),
-                mode: value.to_string(),
-            }
-            .into()),
-        },
-        AgentId::Claude => match mode {
-            "build" | "plan" => Ok(mode.to_string()),
-            value => Err(SandboxError::ModeNotSupported {
-                agent: agent.as_str().to_string(),
-                mode: value.to_string(),
-            }
-            .into()),
-        },
-        AgentId::Amp => match mode {
-            "build" => Ok("build".to_string()),
-            value => Err(SandboxError::ModeNotSupported {
-                agent: agent.as_str().to_string(),
-                mode: value.to_string(),
-            }
-            .into()),
-        },
-        AgentId::Mock => match mode {
-            "build" | "plan" => Ok(mode.to_string()),
-            value => Err(SandboxError::ModeNotSupported {
-                agent: agent.as_str().to_string(),
-                mode: value.to_string(),
-            }
-            .into()),
-        },
-    }
-}
-
-/// Check if the current process is running as root (uid 0)
-fn is_running_as_root() -> bool {
-    #[cfg(unix)]
-    {
-        unsafe { libc::getuid() == 0 }
-    }
-    #[cfg(not(unix))]
-    {
-        false
+    if !adapter(agent).accepts_agent_mode(mode) {
+        return Err(SandboxError::ModeNotSupported {
+            agent: agent.as_str().to_string(),
+            mode: mode.to_string(),
+        });
     }
+    Ok(mode.to_string())
 }
 
 fn normalize_permission_mode(
@@ -6423,34 +8384,12 @@ fn normalize_permission_mode(
             .into())
         }
     };
-    if agent != AgentId::Claude && mode == "acceptEdits" && agent != AgentId::Codex {
-        // acceptEdits is Claude-only unless explicitly handled; treat it as a no-op for other agents.
+    if mode == "acceptEdits" && !adapter(agent).permission_modes().contains(&mode) {
+        // Agents that do not handle acceptEdits treat it as a no-op.
         mode = "default";
     }
-    if agent == AgentId::Claude {
-        // Claude refuses --dangerously-skip-permissions when running as root,
-        // which is common in container environments (Docker, Daytona, E2B).
-        // Return an error if user explicitly requests bypass while running as root.
-        if mode == "bypass" && is_running_as_root() {
-            return Err(SandboxError::InvalidRequest {
-                message: "permission mode 'bypass' is not supported when running as root (Claude refuses --dangerously-skip-permissions with root privileges)".to_string(),
-            }
-            .into());
-        }
-        // Pass through bypass/acceptEdits/plan if explicitly requested, otherwise use default
-        if mode == "bypass" || mode == "acceptEdits" || mode == "plan" {
-            return Ok(mode.to_string());
-        }
-        return Ok("default".to_string());
-    }
-    let supported = match agent {
-        AgentId::Claude => false,
-        AgentId::Codex => matches!(mode, "default" | "plan" | "bypass" | "acceptEdits"),
-        AgentId::Amp => matches!(mode, "default" | "bypass"),
-        AgentId::Opencode => matches!(mode, "default"),
-        AgentId::Mock => matches!(mode, "default" | "plan" | "bypass"),
-    };
-    if !supported {
+    adapter(agent).check_permission_mode(mode)?;
+    if !adapter(agent).permission_modes().contains(&mode) {
         return Err(SandboxError::ModeNotSupported {
             agent: agent.as_str().to_string(),
             mode: mode.to_string(),
@@ -6476,10 +8415,22 @@ fn map_install_error(agent: AgentId, err: ManagerError) -> SandboxError {
         ManagerError::BinaryNotFound { .. } => SandboxError::AgentNotInstalled {
             agent: agent.as_str().to_string(),
         },
+        ManagerError::InvalidAgentName { name, reason } => SandboxError::InvalidRequest {
+            message: format!("invalid agent name {name:?}: {reason}"),
+        },
         ManagerError::ResumeUnsupported { agent } => SandboxError::InvalidRequest {
             message: format!("resume unsupported for {agent}"),
         },
+        ManagerError::InvalidVersion { .. } => SandboxError::InvalidRequest {
+            message: err.to_string(),
+        },
+        ManagerError::VersionNotInstalled { .. } => SandboxError::AgentNotInstalled {
+            agent: agent.as_str().to_string(),
+        },
         ManagerError::UnsupportedPlatform { .. }
+        | ManagerError::ChecksumMismatch { .. }
+        | ManagerError::RuntimeNotFound { .. }
+        | ManagerError::DependenciesFailed { .. }
         | ManagerError::DownloadFailed { .. }
         | ManagerError::Http(_)
         | ManagerError::UrlParse(_)
@@ -6493,9 +8444,11 @@ fn map_install_error(agent: AgentId, err: ManagerError) -> SandboxError {
 
 fn map_spawn_error(agent: AgentId, err: ManagerError) -> SandboxError {
     match err {
-        ManagerError::BinaryNotFound { .. } => SandboxError::AgentNotInstalled {
-            agent: agent.as_str().to_string(),
-        },
+        ManagerError::BinaryNotFound { .. } | ManagerError::VersionNotInstalled { .. } => {
+            SandboxError::AgentNotInstalled {
+                agent: agent.as_str().to_string(),
+            }
+        }
         ManagerError::ResumeUnsupported { agent } => SandboxError::InvalidRequest {
             message: format!("resume unsupported for {agent}"),
         },
@@ -6515,39 +8468,26 @@ fn build_spawn_options(
     let mut options = SpawnOptions::new(prompt);
     options.model = session.model.clone();
     options.variant = session.variant.clone();
+    options.version = session.agent_version.clone();
     options.agent_mode = Some(session.agent_mode.clone());
     options.permission_mode = Some(session.permission_mode.clone());
+    options.limits = session.limits;
+    if let Some(home) = &session.home {
+        options.env.extend(home.env());
+    }
+    if let Some(egress) = &session.egress {
+        options.env.extend(egress.env());
+    }
     options.session_id = session.native_session_id.clone().or_else(|| {
-        if session.agent == AgentId::Opencode {
+        if adapter(session.agent).server_kind() == ServerKind::OpencodeHttp {
             Some(session.session_id.clone())
         } else {
             None
         }
     });
     if let Some(anthropic) = credentials.anthropic {
-        let should_inject_claude_env = !(session.agent == AgentId::Claude
-            && anthropic.source == "claude-code"
-            && anthropic.provider == "anthropic");
-        if should_inject_claude_env {
-            if session.agent == AgentId::Claude && anthropic.auth_type == AuthType::Oauth {
-                options
-                    .env
-                    .entry("CLAUDE_CODE_OAUTH_TOKEN".to_string())
-                    .or_insert(anthropic.api_key.clone());
-                options
-                    .env
-                    .entry("ANTHROPIC_AUTH_TOKEN".to_string())
-                    .or_insert(anthropic.api_key);
-            } else {
-                options
-                    .env
-                    .entry("ANTHROPIC_API_KEY".to_string())
-                    .or_insert(anthropic.api_key.clone());
-                options
-                    .env
-                    .entry("CLAUDE_API_KEY".to_string())
-                    .or_insert(anthropic.api_key);
-            }
+        for (name, value) in adapter(session.agent).anthropic_env(&anthropic) {
+            options.env.entry(name.to_string()).or_insert(value);
         }
     }
     if let Some(openai) = credentials.openai {
@@ -6560,6 +8500,15 @@ fn build_spawn_options(
             .entry("CODEX_API_KEY".to_string())
             .or_insert(openai.api_key);
     }
+    // OAuth logins are read by the Gemini CLI from ~/.gemini itself.
+    if let Some(google) = credentials.other.get("google") {
+        if google.auth_type == AuthType::ApiKey {
+            options
+                .env
+                .entry("GEMINI_API_KEY".to_string())
+                .or_insert(google.api_key.clone());
+        }
+    }
     options
 }
 
@@ -6578,7 +8527,12 @@ mod tests {
             permission_mode: "default".to_string(),
             model: None,
             variant: None,
+            agent_version: None,
             native_session_id: None,
+            limits: ResourceLimits::default(),
+            home: None,
+            egress: None,
+            debug_log: None,
         }
     }
 
@@ -6608,6 +8562,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn sse_routes_take_query_tokens_and_logs_mask_them() {
+        let uri: axum::http::Uri = "/v1/sessions/s1/events/sse?offset=2&token=abc"
+            .parse()
+            .unwrap();
+        let get = axum::http::Method::GET;
+        assert_eq!(
+            sse_query_token(&get, "/sessions/s1/events/sse", &uri).as_deref(),
+            Some("abc")
+        );
+        assert_eq!(sse_query_token(&get, "/sessions/s1/events", &uri), None);
+        assert_eq!(
+            sse_query_token(&axum::http::Method::POST, "/sessions/s1/events/sse", &uri),
+            None
+        );
+        assert_eq!(
+            loggable_uri(&uri),
+            "/v1/sessions/s1/events/sse?offset=2&token=<redacted>"
+        );
+    }
+
     #[test]
     fn build_spawn_options_skips_claude_env_for_claude_code_source() {
         let options = build_spawn_options(
@@ -6638,6 +8613,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn build_spawn_options_injects_gemini_api_key_only() {
+        let mut credentials = ExtractedCredentials::default();
+        credentials.other.insert(
+            "google".to_string(),
+            ProviderCredentials {
+                api_key: "gemini-key".to_string(),
+                source: "environment".to_string(),
+                auth_type: AuthType::ApiKey,
+                provider: "google".to_string(),
+            },
+        );
+        let options = build_spawn_options(
+            &test_snapshot(AgentId::Gemini),
+            "hello".to_string(),
+            credentials.clone(),
+        );
+        assert_eq!(
+            options.env.get("GEMINI_API_KEY").map(String::as_str),
+            Some("gemini-key")
+        );
+
+        credentials.other.get_mut("google").unwrap().auth_type = AuthType::Oauth;
+        let options = build_spawn_options(
+            &test_snapshot(AgentId::Gemini),
+            "hello".to_string(),
+            credentials,
+        );
+        assert!(!options.env.contains_key("GEMINI_API_KEY"));
+    }
+
     #[test]
     fn build_spawn_options_uses_oauth_env_for_claude_oauth_credentials() {
         let options = build_spawn_options(
@@ -6905,6 +8911,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn session_home_isolates_skills_mcp_and_env() {
+        let _lock = CWD_LOCK.lock().unwrap();
+        let state = tempfile::tempdir().unwrap();
+        let skills = tempfile::tempdir().unwrap();
+        make_skill_dir(skills.path(), "alpha");
+
+        let home = SessionHome::create(state.path(), "sess-1").unwrap();
+
+        let sources = vec![SkillSource {
+            source_type: "local".to_string(),
+            source: skills.path().join("alpha").to_string_lossy().to_string(),
+            skills: None,
+            git_ref: None,
+            subpath: None,
+        }];
+        install_skill_sources_into(&sources, home.path()).unwrap();
+        assert!(home.path().join(".claude/skills/alpha/SKILL.md").exists());
+
+        let mut mcp = BTreeMap::new();
+        mcp.insert(
+            "docs".to_string(),
+            McpServerConfig::Remote {
+                url: "https://example.com/mcp".to_string(),
+                headers: None,
+                bearer_token_env_var: None,
+                env_headers: None,
+                oauth: None,
+                enabled: None,
+                timeout_ms: None,
+                transport: None,
+            },
+        );
+        let claude_config = home.path().join(".claude.json");
+        write_claude_mcp_config(&claude_config, &mcp).unwrap();
+        let written: Value =
+            serde_json::from_str(&fs::read_to_string(&claude_config).unwrap()).unwrap();
+        assert!(written["mcpServers"]["docs"].is_object());
+
+        let mut snapshot = test_snapshot(AgentId::Claude);
+        snapshot.home = Some(home.clone());
+        let options = build_spawn_options(
+            &snapshot,
+            "hello".to_string(),
+            ExtractedCredentials::default(),
+        );
+        assert_eq!(
+            options.env.get("HOME").map(PathBuf::from).as_deref(),
+            Some(home.path())
+        );
+    }
+
     #[test]
     fn install_skill_sources_local_single() {
         let _lock = CWD_LOCK.lock().unwrap();
@@ -7193,9 +9251,41 @@ mod tests {
             "prefix dir should be stripped"
         );
     }
+
+    #[test]
+    fn raw_token_usage_reads_each_agent_shape() {
+        let claude = json!({"type": "result", "usage": {"input_tokens": 12, "output_tokens": 3}});
+        assert_eq!(raw_token_usage(&claude), Some((12, 3)));
+
+        let codex = json!({
+            "method": "thread/tokenUsage/updated",
+            "params": {"tokenUsage": {"last": {"inputTokens": 40, "outputTokens": 9}}}
+        });
+        assert_eq!(raw_token_usage(&codex), Some((40, 9)));
+
+        let opencode = json!({
+            "type": "message.updated",
+            "properties": {"info": {"tokens": {"input": 7, "output": 2}}}
+        });
+        assert_eq!(raw_token_usage(&opencode), Some((7, 2)));
+
+        assert_eq!(raw_token_usage(&json!({"type": "assistant"})), None);
+    }
 }
 
 fn install_skill_sources(sources: &[SkillSource]) -> Result<Vec<PathBuf>, SandboxError> {
+    let cwd = std::env::current_dir().map_err(|err| SandboxError::StreamError {
+        message: err.to_string(),
+    })?;
+    install_skill_sources_into(sources, &cwd)
+}
+
+/// Installs skills and links them under `link_root` (the project or an isolated session home).
+/// Relative local sources still resolve against the daemon's working directory.
+fn install_skill_sources_into(
+    sources: &[SkillSource],
+    link_root: &StdPath,
+) -> Result<Vec<PathBuf>, SandboxError> {
     let cwd = std::env::current_dir().map_err(|err| SandboxError::StreamError {
         message: err.to_string(),
     })?;
@@ -7248,7 +9338,7 @@ fn install_skill_sources(sources: &[SkillSource]) -> Result<Vec<PathBuf>, Sandbo
                 .to_string_lossy()
                 .to_string();
             for root in SKILL_ROOTS {
-                let dest = cwd.join(root).join(&skill_name);
+                let dest = link_root.join(root).join(&skill_name);
                 ensure_skill_link(&canonical, &dest)?;
             }
         }
@@ -7580,24 +9670,44 @@ fn copy_dir_recursive(src: &StdPath, dest: &StdPath) -> std::io::Result<()> {
     Ok(())
 }
 
-fn write_claude_mcp_config(mcp: &BTreeMap<String, McpServerConfig>) -> Result<(), SandboxError> {
-    let cwd = std::env::current_dir().map_err(|err| SandboxError::StreamError {
-        message: err.to_string(),
-    })?;
-    let path = cwd.join(".mcp.json");
+pub(crate) fn write_claude_mcp_config(
+    path: &StdPath,
+    mcp: &BTreeMap<String, McpServerConfig>,
+) -> Result<(), SandboxError> {
+    write_mcp_servers_json(path, mcp, claude_mcp_entry)
+}
+
+pub(crate) fn write_gemini_mcp_config(
+    path: &StdPath,
+    mcp: &BTreeMap<String, McpServerConfig>,
+) -> Result<(), SandboxError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| SandboxError::StreamError {
+            message: err.to_string(),
+        })?;
+    }
+    write_mcp_servers_json(path, mcp, gemini_mcp_entry)
+}
+
+/// Merges `mcp` into the `mcpServers` object of a JSON settings file.
+fn write_mcp_servers_json(
+    path: &StdPath,
+    mcp: &BTreeMap<String, McpServerConfig>,
+    entry: fn(&McpServerConfig) -> Result<Value, SandboxError>,
+) -> Result<(), SandboxError> {
     let mut root = if path.exists() {
-        let text = fs::read_to_string(&path).map_err(|err| SandboxError::StreamError {
+        let text = fs::read_to_string(path).map_err(|err| SandboxError::StreamError {
             message: err.to_string(),
         })?;
         serde_json::from_str::<Value>(&text).map_err(|err| SandboxError::InvalidRequest {
-            message: format!("invalid .mcp.json: {err}"),
+            message: format!("invalid {}: {err}", path.display()),
         })?
     } else {
         Value::Object(Map::new())
     };
     let Some(object) = root.as_object_mut() else {
         return Err(SandboxError::InvalidRequest {
-            message: "invalid .mcp.json: expected object".to_string(),
+            message: format!("invalid {}: expected object", path.display()),
         });
     };
     let servers = object
@@ -7605,14 +9715,14 @@ fn write_claude_mcp_config(mcp: &BTreeMap<String, McpServerConfig>) -> Result<()
         .or_insert_with(|| Value::Object(Map::new()));
     let Some(server_map) = servers.as_object_mut() else {
         return Err(SandboxError::InvalidRequest {
-            message: "invalid .mcp.json: mcpServers must be an object".to_string(),
+            message: format!("invalid {}: mcpServers must be an object", path.display()),
         });
     };
     for (name, config) in mcp {
-        server_map.insert(name.clone(), claude_mcp_entry(config)?);
+        server_map.insert(name.clone(), entry(config)?);
     }
     fs::write(
-        &path,
+        path,
         serde_json::to_string_pretty(&root).map_err(|err| SandboxError::StreamError {
             message: err.to_string(),
         })?,
@@ -7623,7 +9733,9 @@ fn write_claude_mcp_config(mcp: &BTreeMap<String, McpServerConfig>) -> Result<()
     Ok(())
 }
 
-fn write_codex_mcp_config(mcp: &BTreeMap<String, McpServerConfig>) -> Result<(), SandboxError> {
+pub(crate) fn write_codex_mcp_config(
+    mcp: &BTreeMap<String, McpServerConfig>,
+) -> Result<(), SandboxError> {
     let cwd = std::env::current_dir().map_err(|err| SandboxError::StreamError {
         message: err.to_string(),
     })?;
@@ -7662,9 +9774,10 @@ fn write_codex_mcp_config(mcp: &BTreeMap<String, McpServerConfig>) -> Result<(),
     Ok(())
 }
 
-fn apply_amp_mcp_config(
+pub(crate) fn apply_amp_mcp_config(
     agent_manager: &AgentManager,
     mcp: &BTreeMap<String, McpServerConfig>,
+    home_env: Option<&[(String, String)]>,
 ) -> Result<(), SandboxError> {
     let path = agent_manager.resolve_binary(AgentId::Amp).map_err(|_| {
         SandboxError::AgentNotInstalled {
@@ -7677,6 +9790,9 @@ fn apply_amp_mcp_config(
     for (name, config) in mcp {
         let mut cmd = Command::new(&path);
         cmd.current_dir(&cwd);
+        if let Some(home_env) = home_env {
+            cmd.envs(home_env.iter().cloned());
+        }
         cmd.arg("mcp").arg("add").arg(name);
         match config {
             McpServerConfig::Local { command, args, .. } => {
@@ -7839,6 +9955,130 @@ fn claude_mcp_entry(config: &McpServerConfig) -> Result<Value, SandboxError> {
     }
 }
 
+fn gemini_mcp_entry(config: &McpServerConfig) -> Result<Value, SandboxError> {
+    match config {
+        McpServerConfig::Local {
+            command,
+            args,
+            env,
+            timeout_ms,
+            cwd,
+            ..
+        } => {
+            let (cmd_name, cmd_args) = mcp_command_parts(command, args)?;
+            let mut map = Map::new();
+            map.insert("command".to_string(), Value::String(cmd_name));
+            if !cmd_args.is_empty() {
+                map.insert(
+                    "args".to_string(),
+                    Value::Array(cmd_args.into_iter().map(Value::String).collect()),
+                );
+            }
+            if let Some(env) = env {
+                map.insert("env".to_string(), json!(env));
+            }
+            if let Some(cwd) = cwd {
+                map.insert("cwd".to_string(), Value::String(cwd.clone()));
+            }
+            if let Some(timeout_ms) = timeout_ms {
+                map.insert("timeout".to_string(), json!(timeout_ms));
+            }
+            Ok(Value::Object(map))
+        }
+        McpServerConfig::Remote {
+            url,
+            headers,
+            bearer_token_env_var,
+            env_headers,
+            timeout_ms,
+            transport,
+            ..
+        } => {
+            let mut map = Map::new();
+            // Gemini takes SSE servers as `url` and streamable HTTP servers as `httpUrl`.
+            let key = match transport.clone().unwrap_or(McpRemoteTransport::Http) {
+                McpRemoteTransport::Http => "httpUrl",
+                McpRemoteTransport::Sse => "url",
+            };
+            map.insert(key.to_string(), Value::String(url.clone()));
+            let merged = merged_headers(
+                headers.as_ref(),
+                bearer_token_env_var.as_ref(),
+                env_headers.as_ref(),
+            );
+            if !merged.is_empty() {
+                map.insert("headers".to_string(), json!(merged));
+            }
+            if let Some(timeout_ms) = timeout_ms {
+                map.insert("timeout".to_string(), json!(timeout_ms));
+            }
+            Ok(Value::Object(map))
+        }
+    }
+}
+
+/// Converts MCP servers into the `mcpServers` list of an ACP `session/new` request.
+/// ACP has no `enabled` flag, so disabled servers are left out.
+fn acp_mcp_servers(mcp: &BTreeMap<String, McpServerConfig>) -> Result<Vec<Value>, SandboxError> {
+    let name_value = |map: &BTreeMap<String, String>| {
+        map.iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect::<Vec<_>>()
+    };
+    let mut servers = Vec::new();
+    for (name, config) in mcp {
+        match config {
+            McpServerConfig::Local {
+                command,
+                args,
+                env,
+                enabled,
+                ..
+            } => {
+                if *enabled == Some(false) {
+                    continue;
+                }
+                let (cmd_name, cmd_args) = mcp_command_parts(command, args)?;
+                servers.push(json!({
+                    "name": name,
+                    "command": cmd_name,
+                    "args": cmd_args,
+                    "env": name_value(&env.clone().unwrap_or_default()),
+                }));
+            }
+            McpServerConfig::Remote {
+                url,
+                headers,
+                bearer_token_env_var,
+                env_headers,
+                enabled,
+                transport,
+                ..
+            } => {
+                if *enabled == Some(false) {
+                    continue;
+                }
+                let kind = match transport.clone().unwrap_or(McpRemoteTransport::Http) {
+                    McpRemoteTransport::Http => "http",
+                    McpRemoteTransport::Sse => "sse",
+                };
+                let merged = merged_headers(
+                    headers.as_ref(),
+                    bearer_token_env_var.as_ref(),
+                    env_headers.as_ref(),
+                );
+                servers.push(json!({
+                    "type": kind,
+                    "name": name,
+                    "url": url,
+                    "headers": name_value(&merged),
+                }));
+            }
+        }
+    }
+    Ok(servers)
+}
+
 fn codex_mcp_table(config: &McpServerConfig) -> Result<Table, SandboxError> {
     let mut table = Table::new();
     match config {
@@ -7956,21 +10196,75 @@ fn merged_headers(
                 .or_insert_with(|| format!("${value}"));
         }
     }
-    merged
+    merged
+}
+
+/// Resolves a filesystem route's path. Relative paths are taken from the session's
+/// working directory, or the home directory without a session. With a session-bound
+/// token (`confined`), the result must stay inside the session's working directory.
+async fn resolve_fs_path(
+    state: &Arc<AppState>,
+    session_id: Option<&str>,
+    raw_path: &str,
+    confined: bool,
+) -> Result<PathBuf, SandboxError> {
+    let path = PathBuf::from(raw_path);
+    if !confined {
+        if path.is_absolute() {
+            return Ok(path);
+        }
+        let root = resolve_fs_root(state, session_id).await?;
+        let relative = sanitize_relative_path(&path)?;
+        return Ok(root.join(relative));
+    }
+
+    let session_id = session_id.ok_or_else(|| SandboxError::PermissionDenied {
+        message: Some("session tokens need a sessionId for filesystem routes".to_string()),
+    })?;
+    let root = state
+        .session_manager
+        .session_working_dir(session_id)
+        .await?;
+    let root = root
+        .canonicalize()
+        .map_err(|err| map_fs_error(&root, err))?;
+    let target = if path.is_absolute() {
+        path
+    } else {
+        root.join(sanitize_relative_path(&path)?)
+    };
+    let resolved = canonicalize_existing(&target)?;
+    if !resolved.starts_with(&root) {
+        return Err(SandboxError::PermissionDenied {
+            message: Some(format!(
+                "path is outside the session's working directory: {raw_path}"
+            )),
+        });
+    }
+    Ok(resolved)
 }
 
-async fn resolve_fs_path(
-    state: &Arc<AppState>,
-    session_id: Option<&str>,
-    raw_path: &str,
-) -> Result<PathBuf, SandboxError> {
-    let path = PathBuf::from(raw_path);
-    if path.is_absolute() {
-        return Ok(path);
+/// Canonicalizes the longest existing ancestor of `path` and appends the rest, so
+/// symlinks and `..` are resolved before a containment check.
+fn canonicalize_existing(path: &StdPath) -> Result<PathBuf, SandboxError> {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return Ok(missing
+                .iter()
+                .rev()
+                .fold(canonical, |resolved, name| resolved.join(name)));
+        }
+        // A trailing `..` below a missing directory cannot be resolved.
+        let (Some(name), Some(parent)) = (existing.file_name(), existing.parent()) else {
+            return Err(SandboxError::InvalidRequest {
+                message: format!("invalid path: {}", path.display()),
+            });
+        };
+        missing.push(name);
+        existing = parent;
     }
-    let root = resolve_fs_root(state, session_id).await?;
-    let relative = sanitize_relative_path(&path)?;
-    Ok(root.join(relative))
 }
 
 async fn resolve_fs_root(
@@ -8059,15 +10353,15 @@ fn opencode_file_part_input(attachment: &MessageAttachment) -> Value {
     Value::Object(map)
 }
 
-fn claude_input_session_id(session: &SessionSnapshot) -> String {
+/// The agent's session id, or ours when it has not reported one.
+fn input_session_id(session: &SessionSnapshot) -> String {
     session
         .native_session_id
         .clone()
         .unwrap_or_else(|| session.session_id.clone())
 }
 
-fn claude_user_message_line(session: &SessionSnapshot, message: &str) -> String {
-    let session_id = claude_input_session_id(session);
+pub(crate) fn claude_user_message_line(session_id: &str, message: &str) -> String {
     serde_json::json!({
         "type": "user",
         "message": {
@@ -8080,57 +10374,17 @@ fn claude_user_message_line(session: &SessionSnapshot, message: &str) -> String
     .to_string()
 }
 
-fn claude_tool_result_line(
-    session_id: &str,
-    tool_use_id: &str,
-    content: &str,
-    is_error: bool,
-) -> String {
-    serde_json::json!({
-        "type": "user",
-        "message": {
-            "role": "user",
-            "content": [{
-                "type": "tool_result",
-                "tool_use_id": tool_use_id,
-                "content": content,
-                "is_error": is_error,
-            }],
-        },
-        "parent_tool_use_id": null,
-        "session_id": session_id,
-    })
-    .to_string()
-}
-
-fn claude_control_response_line(request_id: &str, behavior: &str, response: Value) -> String {
-    let mut response_obj = serde_json::Map::new();
-    response_obj.insert("behavior".to_string(), Value::String(behavior.to_string()));
-    if let Some(message) = response.get("message") {
-        response_obj.insert("message".to_string(), message.clone());
-    }
-    if let Some(updated_input) = response.get("updatedInput") {
-        response_obj.insert("updatedInput".to_string(), updated_input.clone());
-    }
-    if let Some(updated_permissions) = response.get("updatedPermissions") {
-        response_obj.insert(
-            "updatedPermissions".to_string(),
-            updated_permissions.clone(),
-        );
-    }
-    if let Some(interrupt) = response.get("interrupt") {
-        response_obj.insert("interrupt".to_string(), interrupt.clone());
-    }
-
-    serde_json::json!({
-        "type": "control_response",
-        "response": {
-            "subtype": "success",
-            "request_id": request_id,
-            "response": Value::Object(response_obj),
-        }
-    })
-    .to_string()
+/// Writes a reply line to the stdin of the agent's running turn.
+fn send_stdin_line(
+    agent: AgentId,
+    sender: Option<mpsc::UnboundedSender<String>>,
+    line: String,
+) -> Result<(), SandboxError> {
+    sender
+        .and_then(|sender| sender.send(line).ok())
+        .ok_or_else(|| SandboxError::InvalidRequest {
+            message: format!("{} session is not active", agent.as_str()),
+        })
 }
 
 /// Returns true if the given action name corresponds to a question tool
@@ -8149,7 +10403,7 @@ pub(crate) fn is_question_tool_action(action: &str) -> bool {
     )
 }
 
-fn is_file_change_action(action: &str) -> bool {
+pub(crate) fn is_file_change_action(action: &str) -> bool {
     matches!(action, "fileChange" | "file_change" | "file-change")
         || action.eq_ignore_ascii_case("filechange")
 }
@@ -8208,7 +10462,11 @@ fn permission_action_category(action: &str) -> Option<String> {
     }
 }
 
-fn read_lines<R: std::io::Read>(reader: R, sender: mpsc::UnboundedSender<String>) {
+fn read_lines<R: std::io::Read>(
+    reader: R,
+    sender: mpsc::UnboundedSender<String>,
+    debug: Option<(Arc<DebugLog>, DebugDirection)>,
+) {
     let mut reader = BufReader::new(reader);
     let mut line = String::new();
     loop {
@@ -8217,6 +10475,9 @@ fn read_lines<R: std::io::Read>(reader: R, sender: mpsc::UnboundedSender<String>
             Ok(0) => break,
             Ok(_) => {
                 let trimmed = line.trim_end_matches(&['\r', '\n'][..]).to_string();
+                if let Some((log, direction)) = &debug {
+                    log.record(*direction, &trimmed);
+                }
                 if sender.send(trimmed).is_err() {
                     break;
                 }
@@ -8226,8 +10487,15 @@ fn read_lines<R: std::io::Read>(reader: R, sender: mpsc::UnboundedSender<String>
     }
 }
 
-fn write_lines(mut stdin: std::process::ChildStdin, mut receiver: mpsc::UnboundedReceiver<String>) {
+fn write_lines(
+    mut stdin: std::process::ChildStdin,
+    mut receiver: mpsc::UnboundedReceiver<String>,
+    debug_log: Option<Arc<DebugLog>>,
+) {
     while let Some(line) = receiver.blocking_recv() {
+        if let Some(log) = &debug_log {
+            log.record(DebugDirection::Stdin, &line);
+        }
         if writeln!(stdin, "{line}").is_err() {
             break;
         }
@@ -8736,6 +11004,18 @@ fn codex_rpc_error_to_universal(error: &codex_schema::JsonrpcError) -> EventConv
     EventConversion::new(UniversalEventType::Error, UniversalEventData::Error(data))
 }
 
+fn acp_request(id: i64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn acp_rpc_error(error: &Value) -> codex_schema::JsonrpcErrorError {
+    serde_json::from_value(error.clone()).unwrap_or_else(|_| codex_schema::JsonrpcErrorError {
+        code: -32603,
+        data: None,
+        message: error.to_string(),
+    })
+}
+
 fn codex_request_error_to_sandbox(
     context: &str,
     error: &codex_schema::JsonrpcErrorError,
@@ -8745,6 +11025,32 @@ fn codex_request_error_to_sandbox(
     }
 }
 
+fn egress_status_event(event: EgressEvent) -> EventConversion {
+    let label = if event.allowed {
+        "egress.allowed"
+    } else {
+        "egress.denied"
+    };
+    EventConversion::new(
+        UniversalEventType::ItemCompleted,
+        UniversalEventData::Item(ItemEventData {
+            item: UniversalItem {
+                item_id: String::new(),
+                native_item_id: None,
+                parent_id: None,
+                kind: ItemKind::Status,
+                role: Some(ItemRole::System),
+                content: vec![ContentPart::Status {
+                    label: label.to_string(),
+                    detail: Some(format!("{} {}", event.method, event.target)),
+                }],
+                status: ItemStatus::Completed,
+            },
+        }),
+    )
+    .synthetic()
+}
+
 fn codex_model_unavailable_status_event(
     native_session_id: Option<String>,
     model_id: &str,
@@ -8841,12 +11147,12 @@ fn is_likely_model_id(candidate: &str) -> bool {
     candidate.contains('-')
 }
 
-fn codex_permission_response_line(
+pub(crate) fn codex_permission_response_line(
     permission_id: &str,
-    pending: &PendingPermission,
+    metadata: Option<&Value>,
     reply: PermissionReply,
 ) -> Result<String, SandboxError> {
-    let metadata = pending.metadata.clone().unwrap_or(Value::Null);
+    let metadata = metadata.cloned().unwrap_or(Value::Null);
     let request_id = codex_request_id_from_metadata(&metadata)
         .or_else(|| codex_request_id_from_string(permission_id))
         .ok_or_else(|| SandboxError::InvalidRequest {
@@ -8936,46 +11242,7 @@ fn codex_file_change_decision_for_reply(
 }
 
 fn parse_agent_line(agent: AgentId, line: &str, session_id: &str) -> Vec<EventConversion> {
-    let trimmed = line.trim();
-    if trimmed.is_empty() {
-        return Vec::new();
-    }
-    let value: Value = match serde_json::from_str(trimmed) {
-        Ok(value) => value,
-        Err(err) => {
-            return vec![agent_unparsed(
-                agent.as_str(),
-                &err.to_string(),
-                Value::String(trimmed.to_string()),
-            )];
-        }
-    };
-    match agent {
-        AgentId::Claude => {
-            convert_claude::event_to_universal_with_session(&value, session_id.to_string())
-                .unwrap_or_else(|err| vec![agent_unparsed("claude", &err, value)])
-        }
-        AgentId::Codex => match serde_json::from_value(value.clone()) {
-            Ok(notification) => convert_codex::notification_to_universal(&notification)
-                .unwrap_or_else(|err| vec![agent_unparsed("codex", &err, value)]),
-            Err(err) => vec![agent_unparsed("codex", &err.to_string(), value)],
-        },
-        AgentId::Opencode => match serde_json::from_value(value.clone()) {
-            Ok(event) => convert_opencode::event_to_universal(&event)
-                .unwrap_or_else(|err| vec![agent_unparsed("opencode", &err, value)]),
-            Err(err) => vec![agent_unparsed("opencode", &err.to_string(), value)],
-        },
-        AgentId::Amp => match serde_json::from_value(value.clone()) {
-            Ok(event) => convert_amp::event_to_universal(&event)
-                .unwrap_or_else(|err| vec![agent_unparsed("amp", &err, value)]),
-            Err(err) => vec![agent_unparsed("amp", &err.to_string(), value)],
-        },
-        AgentId::Mock => vec![agent_unparsed(
-            "mock",
-            "mock agent does not parse streaming output",
-            value,
-        )],
-    }
+    adapter(agent).parse_line(line, session_id)
 }
 
 fn opencode_event_matches_session(value: &Value, session_id: &str) -> bool {
@@ -9001,6 +11268,9 @@ fn extract_opencode_session_id(value: &Value) -> Option<String> {
     if let Some(id) = extract_nested_string(value, &["properties", "part", "sessionID"]) {
         return Some(id);
     }
+    if let Some(id) = extract_nested_string(value, &["properties", "info", "sessionID"]) {
+        return Some(id);
+    }
     if let Some(id) = extract_nested_string(value, &["session", "id"]) {
         return Some(id);
     }
@@ -9022,6 +11292,21 @@ fn extract_nested_string(value: &Value, path: &[&str]) -> Option<String> {
     current.as_str().map(|s| s.to_string())
 }
 
+/// Replays a fixture in a session manager of its own, so nothing touches live sessions.
+pub(crate) async fn replay_fixture(
+    fixture: &ReplayFixture,
+) -> Result<Vec<UniversalEvent>, SandboxError> {
+    let agent_manager = AgentManager::new(std::env::temp_dir().join("sandbox-agent-replay"))
+        .map_err(|err| SandboxError::StreamError {
+            message: err.to_string(),
+        })?;
+    let manager = Arc::new(SessionManager::new(
+        Arc::new(agent_manager),
+        SessionConfig::default(),
+    ));
+    manager.replay(fixture).await
+}
+
 #[cfg(feature = "test-utils")]
 pub mod test_utils {
     use super::*;
@@ -9038,7 +11323,8 @@ pub mod test_utils {
             let temp_dir = TempDir::new().expect("temp dir");
             let agent_manager =
                 Arc::new(AgentManager::new(temp_dir.path()).expect("agent manager"));
-            let session_manager = Arc::new(SessionManager::new(agent_manager));
+            let session_manager =
+                Arc::new(SessionManager::new(agent_manager, SessionConfig::default()));
             session_manager
                 .server_manager
                 .set_owner_async(Arc::downgrade(&session_manager))
@@ -9115,6 +11401,10 @@ pub mod test_utils {
                 title: None,
                 mcp: None,
                 skills: None,
+                limits: None,
+                isolate_home: None,
+                egress: None,
+                debug_log: None,
             };
             let mut session =
                 SessionState::new(session_id.to_string(), agent, &request).expect("session");
@@ -9129,7 +11419,7 @@ pub mod test_utils {
             instance_id: u64,
         ) -> Arc<std::sync::Mutex<Option<std::process::Child>>> {
             let (stdin_tx, _stdin_rx) = mpsc::unbounded_channel::<String>();
-            let server = Arc::new(CodexServer::new(stdin_tx));
+            let server = Arc::new(StdioServer::new(stdin_tx));
             let child = Arc::new(std::sync::Mutex::new(child));
             self.session_manager
                 .server_manager
@@ -9147,6 +11437,7 @@ pub mod test_utils {
                         last_error: None,
                         shutdown_requested: false,
                         instance_id,
+                        limits: None,
                     },
                 );
             child
@@ -9171,6 +11462,7 @@ pub mod test_utils {
                         last_error: None,
                         shutdown_requested: false,
                         instance_id,
+                        limits: None,
                     },
                 );
         }
@@ -9191,6 +11483,10 @@ pub mod test_utils {
             self.session_manager.server_manager.shutdown().await;
         }
 
+        pub async fn stop_server(&self, agent: AgentId) {
+            self.session_manager.server_manager.stop_server(agent).await;
+        }
+
         pub async fn server_status(&self, agent: AgentId) -> Option<ServerStatus> {
             let servers = self.session_manager.server_manager.servers.lock().await;
             servers.get(&agent).map(|server| server.status.clone())
@@ -9258,15 +11554,21 @@ pub mod test_utils {
     }
 }
 
-fn default_log_dir() -> PathBuf {
+fn default_state_dir() -> PathBuf {
     dirs::data_dir()
-        .map(|dir| dir.join("sandbox-agent").join("logs").join("servers"))
-        .unwrap_or_else(|| {
-            PathBuf::from(".")
-                .join(".sandbox-agent")
-                .join("logs")
-                .join("servers")
-        })
+        .map(|dir| dir.join("sandbox-agent"))
+        .unwrap_or_else(|| PathBuf::from(".").join(".sandbox-agent"))
+}
+
+fn default_log_dir() -> PathBuf {
+    default_state_dir().join("logs").join("servers")
+}
+
+async fn remove_session_home(home: SessionHome) {
+    let result = tokio::task::spawn_blocking(move || home.remove()).await;
+    if let Ok(Err(err)) = result {
+        tracing::warn!(error = %err, "failed to remove session home");
+    }
 }
 
 fn find_available_port() -> Result<u16, SandboxError> {
@@ -9415,14 +11717,211 @@ fn should_append_turn_ended(events: &[EventConversion]) -> bool {
     let Some(last) = events.last() else {
         return false;
     };
-    !matches!(
-        last.event_type,
+    !leaves_turn_open(&last.event_type)
+}
+
+/// The mock turn stays open after these: the session ended, or a reply is pending.
+fn leaves_turn_open(event_type: &UniversalEventType) -> bool {
+    matches!(
+        event_type,
         UniversalEventType::SessionEnded
             | UniversalEventType::PermissionRequested
             | UniversalEventType::QuestionRequested
     )
 }
 
+/// What the mock agent does next.
+enum MockOutput {
+    Event(EventConversion),
+    Pause(Duration),
+}
+
+fn mock_scenario_outputs(prefix: &str, steps: &[MockStep]) -> Vec<MockOutput> {
+    let mut outputs = Vec::new();
+    for (index, step) in steps.iter().enumerate() {
+        let native_item_id = format!("{prefix}_step{index}");
+        let events = match step {
+            MockStep::Message { text, deltas } => {
+                let text = if text.is_empty() {
+                    deltas.concat()
+                } else {
+                    text.clone()
+                };
+                if deltas.is_empty() {
+                    mock_assistant_message(native_item_id, text)
+                } else {
+                    mock_streamed_message(native_item_id, deltas, text)
+                }
+            }
+            MockStep::ToolCall {
+                name,
+                arguments,
+                output,
+                failed,
+            } => {
+                mock_scenario_tool_call(native_item_id, name, arguments, output.as_deref(), *failed)
+            }
+            MockStep::Permission { action, metadata } => vec![EventConversion::new(
+                UniversalEventType::PermissionRequested,
+                UniversalEventData::Permission(PermissionEventData {
+                    permission_id: native_item_id,
+                    action: action.clone(),
+                    status: PermissionStatus::Requested,
+                    metadata: metadata.clone(),
+                }),
+            )],
+            MockStep::Question { prompt, options } => vec![EventConversion::new(
+                UniversalEventType::QuestionRequested,
+                UniversalEventData::Question(QuestionEventData {
+                    question_id: native_item_id,
+                    prompt: prompt.clone(),
+                    options: options.clone(),
+                    response: None,
+                    status: QuestionStatus::Requested,
+                }),
+            )],
+            MockStep::Delay { ms } => {
+                outputs.push(MockOutput::Pause(Duration::from_millis(*ms)));
+                continue;
+            }
+            MockStep::Error { message, code } => vec![EventConversion::new(
+                UniversalEventType::Error,
+                UniversalEventData::Error(ErrorData {
+                    message: message.clone(),
+                    code: code.clone(),
+                    details: None,
+                }),
+            )],
+            MockStep::Crash { message, exit_code } => vec![EventConversion::new(
+                UniversalEventType::SessionEnded,
+                UniversalEventData::SessionEnded(SessionEndedData {
+                    reason: SessionEndReason::Error,
+                    terminated_by: TerminatedBy::Agent,
+                    message: message.clone(),
+                    exit_code: *exit_code,
+                    stderr: None,
+                }),
+            )],
+        };
+        outputs.extend(events.into_iter().map(MockOutput::Event));
+    }
+    let last_event = outputs.iter().rev().find_map(|output| match output {
+        MockOutput::Event(event) => Some(&event.event_type),
+        MockOutput::Pause(_) => None,
+    });
+    if !last_event.is_some_and(leaves_turn_open) {
+        outputs.push(MockOutput::Event(turn_ended_event(None, None).synthetic()));
+    }
+    outputs
+}
+
+fn mock_streamed_message(
+    native_item_id: String,
+    deltas: &[String],
+    text: String,
+) -> Vec<EventConversion> {
+    let mut events = vec![mock_item_event(
+        UniversalEventType::ItemStarted,
+        mock_item(
+            native_item_id.clone(),
+            ItemKind::Message,
+            ItemRole::Assistant,
+            ItemStatus::InProgress,
+            Vec::new(),
+        ),
+    )];
+    events.extend(
+        deltas
+            .iter()
+            .map(|delta| mock_delta(native_item_id.clone(), delta)),
+    );
+    events.push(mock_item_event(
+        UniversalEventType::ItemCompleted,
+        mock_item(
+            native_item_id,
+            ItemKind::Message,
+            ItemRole::Assistant,
+            ItemStatus::Completed,
+            vec![ContentPart::Text { text }],
+        ),
+    ));
+    events
+}
+
+fn mock_scenario_tool_call(
+    native_item_id: String,
+    name: &str,
+    arguments: &Value,
+    output: Option<&str>,
+    failed: bool,
+) -> Vec<EventConversion> {
+    let call_id = format!("{native_item_id}_call");
+    let arguments = match arguments {
+        Value::Null => "{}".to_string(),
+        Value::String(arguments) => arguments.clone(),
+        other => other.to_string(),
+    };
+    let call_part = ContentPart::ToolCall {
+        name: name.to_string(),
+        arguments,
+        call_id: call_id.clone(),
+    };
+    let mut events = vec![
+        mock_item_event(
+            UniversalEventType::ItemStarted,
+            mock_item(
+                native_item_id.clone(),
+                ItemKind::ToolCall,
+                ItemRole::Assistant,
+                ItemStatus::InProgress,
+                vec![call_part.clone()],
+            ),
+        ),
+        mock_item_event(
+            UniversalEventType::ItemCompleted,
+            mock_item(
+                native_item_id.clone(),
+                ItemKind::ToolCall,
+                ItemRole::Assistant,
+                ItemStatus::Completed,
+                vec![call_part],
+            ),
+        ),
+    ];
+    if let Some(output) = output {
+        let result_native = format!("{native_item_id}_result");
+        let result_parts = vec![ContentPart::ToolResult {
+            call_id,
+            output: output.to_string(),
+        }];
+        events.push(mock_item_event(
+            UniversalEventType::ItemStarted,
+            mock_item(
+                result_native.clone(),
+                ItemKind::ToolResult,
+                ItemRole::Tool,
+                ItemStatus::InProgress,
+                result_parts.clone(),
+            ),
+        ));
+        events.push(mock_item_event(
+            UniversalEventType::ItemCompleted,
+            mock_item(
+                result_native,
+                ItemKind::ToolResult,
+                ItemRole::Tool,
+                if failed {
+                    ItemStatus::Failed
+                } else {
+                    ItemStatus::Completed
+                },
+                result_parts,
+            ),
+        ));
+    }
+    events
+}
+
 fn mock_command_events(prefix: &str, trimmed: &str) -> Vec<EventConversion> {
     if trimmed.eq_ignore_ascii_case(MOCK_OK_PROMPT) {
         return mock_assistant_message(format!("{prefix}_ok"), "OK".to_string());
@@ -10190,19 +12689,6 @@ fn mock_delta(native_item_id: String, delta: &str) -> EventConversion {
     )
 }
 
-fn agent_unparsed(location: &str, error: &str, raw: Value) -> EventConversion {
-    EventConversion::new(
-        UniversalEventType::AgentUnparsed,
-        UniversalEventData::AgentUnparsed(AgentUnparsedData {
-            error: error.to_string(),
-            location: location.to_string(),
-            raw_hash: None,
-        }),
-    )
-    .synthetic()
-    .with_raw(Some(raw))
-}
-
 fn now_rfc3339() -> String {
     time::OffsetDateTime::now_utc()
         .format(&time::format_description::well_known::Rfc3339)
@@ -10240,7 +12726,13 @@ fn stream_turn_events(
             loop {
                 match state.receiver.recv().await {
                     Ok(event) => break event,
-                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        metrics()
+                            .broadcast_lagged
+                            .with_label_values(&["turn_stream"])
+                            .inc_by(skipped);
+                        continue;
+                    }
                     Err(broadcast::error::RecvError::Closed) => return None,
                 }
             }
@@ -10258,6 +12750,24 @@ fn stream_turn_events(
     })
 }
 
+/// Input and output token counts from a raw agent payload: Claude `result` usage,
+/// Codex `thread/tokenUsage/updated` and OpenCode assistant message tokens.
+fn raw_token_usage(raw: &Value) -> Option<(u64, u64)> {
+    let counts = |value: &Value, input: &str, output: &str| {
+        Some((value.get(input)?.as_u64()?, value.get(output)?.as_u64()?))
+    };
+    raw.get("usage")
+        .and_then(|usage| counts(usage, "input_tokens", "output_tokens"))
+        .or_else(|| {
+            raw.pointer("/params/tokenUsage/last")
+                .and_then(|usage| counts(usage, "inputTokens", "outputTokens"))
+        })
+        .or_else(|| {
+            raw.pointer("/properties/info/tokens")
+                .and_then(|tokens| counts(tokens, "input", "output"))
+        })
+}
+
 fn is_turn_terminal(event: &UniversalEvent, _agent: AgentId) -> bool {
     match event.event_type {
         UniversalEventType::TurnEnded
@@ -10284,7 +12794,12 @@ struct SessionSnapshot {
     permission_mode: String,
     model: Option<String>,
     variant: Option<String>,
+    agent_version: Option<String>,
     native_session_id: Option<String>,
+    limits: ResourceLimits,
+    home: Option<SessionHome>,
+    egress: Option<EgressLease>,
+    debug_log: Option<Arc<DebugLog>>,
 }
 
 impl From<&SessionState> for SessionSnapshot {
@@ -10296,7 +12811,12 @@ impl From<&SessionState> for SessionSnapshot {
             permission_mode: session.permission_mode.clone(),
             model: session.model.clone(),
             variant: session.variant.clone(),
+            agent_version: session.agent_version.clone(),
             native_session_id: session.native_session_id.clone(),
+            limits: session.limits,
+            home: session.home.clone(),
+            egress: session.egress.clone(),
+            debug_log: session.debug_log.clone(),
         }
     }
 }
@@ -10308,6 +12828,107 @@ pub fn add_token_header(headers: &mut HeaderMap, token: &str) {
     }
 }
 
+/// Lists models from the Anthropic API, falling back to Claude Code's aliases for OAuth
+/// credentials the API does not accept.
+pub(crate) async fn fetch_anthropic_models(
+    client: reqwest::Client,
+    cred: ProviderCredentials,
+) -> Result<AgentModelsResponse, SandboxError> {
+    let started = Instant::now();
+    let headers = build_anthropic_headers(&cred)?;
+    let response = client
+        .get(ANTHROPIC_MODELS_URL)
+        .headers(headers)
+        .send()
+        .await
+        .map_err(|err| SandboxError::StreamError {
+            message: err.to_string(),
+        })?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if matches!(cred.auth_type, AuthType::Oauth) {
+            tracing::warn!(
+                status = %status,
+                elapsed_ms = started.elapsed().as_millis() as u64,
+                "Anthropic model list rejected OAuth credentials; using Claude OAuth fallback models"
+            );
+            return Ok(claude_fallback_models());
+        }
+        return Err(SandboxError::StreamError {
+            message: format!("Anthropic models request failed {status}: {body}"),
+        });
+    }
+
+    let value: Value = response
+        .json()
+        .await
+        .map_err(|err| SandboxError::StreamError {
+            message: err.to_string(),
+        })?;
+    let data = value
+        .get("data")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    let mut models = Vec::new();
+    let mut default_model: Option<String> = None;
+    let mut default_created: Option<String> = None;
+    for item in data {
+        let Some(id) = item.get("id").and_then(Value::as_str) else {
+            continue;
+        };
+        let name = item
+            .get("display_name")
+            .and_then(Value::as_str)
+            .map(|value| value.to_string());
+        let created = item
+            .get("created_at")
+            .and_then(Value::as_str)
+            .map(|value| value.to_string());
+        if let Some(created) = created.as_ref() {
+            let should_update = match default_created.as_deref() {
+                Some(current) => created.as_str() > current,
+                None => true,
+            };
+            if should_update {
+                default_created = Some(created.clone());
+                default_model = Some(id.to_string());
+            }
+        }
+        models.push(AgentModelInfo {
+            id: id.to_string(),
+            name,
+            variants: None,
+            default_variant: None,
+        });
+    }
+    models.sort_by(|a, b| a.id.cmp(&b.id));
+    if default_model.is_none() {
+        default_model = models.first().map(|model| model.id.clone());
+    }
+
+    if models.is_empty() && matches!(cred.auth_type, AuthType::Oauth) {
+        tracing::warn!(
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "Anthropic model list was empty for OAuth credentials; using Claude OAuth fallback models"
+        );
+        return Ok(claude_fallback_models());
+    }
+
+    tracing::info!(
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        model_count = models.len(),
+        has_default = default_model.is_some(),
+        "claude model fetch completed"
+    );
+    Ok(AgentModelsResponse {
+        models,
+        default_model,
+    })
+}
+
 fn build_anthropic_headers(
     credentials: &ProviderCredentials,
 ) -> Result<reqwest::header::HeaderMap, SandboxError> {
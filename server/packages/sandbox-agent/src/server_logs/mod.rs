@@ -7,3 +7,24 @@ mod windows;
 pub use unix::ServerLogs;
 #[cfg(windows)]
 pub use windows::ServerLogs;
+
+use std::path::PathBuf;
+
+use crate::logs::LogFiles;
+
+/// Daily log files are named `log-MM-DD-YY`.
+const LOG_FILE_PREFIX: &str = "log-";
+
+/// Directory the daemon's own logs rotate in (`SANDBOX_AGENT_LOG_DIR` overrides it).
+pub fn default_log_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("SANDBOX_AGENT_LOG_DIR") {
+        return PathBuf::from(dir);
+    }
+    dirs::data_dir()
+        .map(|dir| dir.join("sandbox-agent").join("logs"))
+        .unwrap_or_else(|| PathBuf::from(".").join(".sandbox-agent").join("logs"))
+}
+
+pub(crate) fn log_files() -> LogFiles {
+    LogFiles::new(default_log_dir(), LOG_FILE_PREFIX)
+}
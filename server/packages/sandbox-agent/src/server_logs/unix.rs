@@ -60,7 +60,11 @@ impl ServerLogs {
                 + Duration::days(1)),
         );
 
-        let file_name = format!("log-{}", self.last_rotation.format("%m-%d-%y"));
+        let file_name = format!(
+            "{}{}",
+            super::LOG_FILE_PREFIX,
+            self.last_rotation.format("%m-%d-%y")
+        );
         let path = self.path.join(file_name);
 
         let log_file = std::fs::OpenOptions::new()
@@ -0,0 +1,208 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Credential files copied from the daemon user's home into each isolated session home.
+/// Paths mirror the locations read by the credential extractors.
+const SEEDED_CREDENTIAL_FILES: [&str; 8] = [
+    ".claude.json",
+    ".claude/.credentials.json",
+    ".codex/auth.json",
+    ".local/share/opencode/auth.json",
+    ".amp/config.json",
+    ".local/share/amp/secrets.json",
+    ".gemini/oauth_creds.json",
+    ".gemini/settings.json",
+];
+
+/// Private HOME and XDG base directories for a single session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SessionHome {
+    root: PathBuf,
+}
+
+impl SessionHome {
+    /// Creates `<state_dir>/sessions/<session_id>/home` along with its XDG subdirectories.
+    /// Fails with `AlreadyExists` rather than reusing another session's directory.
+    pub(crate) fn create(state_dir: &Path, session_id: &str) -> io::Result<Self> {
+        if !is_safe_dir_name(session_id) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("session id {session_id:?} cannot be used as a directory name"),
+            ));
+        }
+        let sessions_dir = state_dir.join("sessions");
+        fs::create_dir_all(&sessions_dir)?;
+        let session_dir = sessions_dir.join(session_id);
+        fs::create_dir(&session_dir).map_err(|err| match err.kind() {
+            io::ErrorKind::AlreadyExists => io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("session directory {} already exists", session_dir.display()),
+            ),
+            _ => err,
+        })?;
+        let home = Self {
+            root: session_dir.join("home"),
+        };
+        for dir in [
+            home.config_dir(),
+            home.data_dir(),
+            home.cache_dir(),
+            home.state_dir(),
+        ] {
+            fs::create_dir_all(dir)?;
+        }
+        Ok(home)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.root
+    }
+
+    fn config_dir(&self) -> PathBuf {
+        self.root.join(".config")
+    }
+
+    fn data_dir(&self) -> PathBuf {
+        self.root.join(".local").join("share")
+    }
+
+    fn cache_dir(&self) -> PathBuf {
+        self.root.join(".cache")
+    }
+
+    fn state_dir(&self) -> PathBuf {
+        self.root.join(".local").join("state")
+    }
+
+    /// Copies existing credential files from `source_home` so agents stay signed in.
+    /// Files are copied rather than linked so token refreshes in one session never
+    /// leak into another.
+    pub(crate) fn seed_credentials(&self, source_home: &Path) -> io::Result<()> {
+        for relative in SEEDED_CREDENTIAL_FILES {
+            let source = source_home.join(relative);
+            if !source.is_file() {
+                continue;
+            }
+            let dest = self.root.join(relative);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&source, &dest)?;
+        }
+        Ok(())
+    }
+
+    /// Environment variables that point an agent process at this home.
+    pub(crate) fn env(&self) -> Vec<(String, String)> {
+        let path = |path: PathBuf| path.to_string_lossy().to_string();
+        vec![
+            ("HOME".to_string(), path(self.root.clone())),
+            ("USERPROFILE".to_string(), path(self.root.clone())),
+            ("XDG_CONFIG_HOME".to_string(), path(self.config_dir())),
+            ("XDG_DATA_HOME".to_string(), path(self.data_dir())),
+            ("XDG_CACHE_HOME".to_string(), path(self.cache_dir())),
+            ("XDG_STATE_HOME".to_string(), path(self.state_dir())),
+            ("CODEX_HOME".to_string(), path(self.root.join(".codex"))),
+        ]
+    }
+
+    /// Removes the session directory (the parent of the home directory).
+    pub(crate) fn remove(&self) -> io::Result<()> {
+        let session_dir = self.root.parent().unwrap_or(&self.root);
+        match fs::remove_dir_all(session_dir) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn is_safe_dir_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_a_home_once_per_session_id() {
+        let state = tempfile::tempdir().expect("state dir");
+        let home = SessionHome::create(state.path(), "sess-1").expect("create");
+        assert_eq!(home.path(), state.path().join("sessions/sess-1/home"));
+        for dir in [".config", ".local/share", ".cache", ".local/state"] {
+            assert!(home.path().join(dir).is_dir(), "{dir}");
+        }
+
+        fs::write(home.path().join("live"), b"in use").expect("write");
+        let err = SessionHome::create(state.path(), "sess-1").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert!(home.path().join("live").is_file());
+    }
+
+    #[test]
+    fn rejects_session_ids_that_are_not_plain_names() {
+        let state = tempfile::tempdir().expect("state dir");
+        for id in ["", ".", "..", "../escape", "a/b", "a\\b", "white space"] {
+            let err = SessionHome::create(state.path(), id).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{id:?}");
+        }
+        assert!(!state.path().join("sessions").exists());
+        assert!(SessionHome::create(state.path(), "v1.2_ok-id").is_ok());
+    }
+
+    #[test]
+    fn seeds_only_known_credential_files() {
+        let source = tempfile::tempdir().expect("source home");
+        fs::create_dir_all(source.path().join(".codex")).expect("mkdir");
+        fs::write(source.path().join(".codex/auth.json"), b"{\"token\":1}").expect("write");
+        fs::write(source.path().join(".claude.json"), b"{}").expect("write");
+        fs::write(source.path().join(".bash_history"), b"secret").expect("write");
+
+        let state = tempfile::tempdir().expect("state dir");
+        let home = SessionHome::create(state.path(), "sess").expect("create");
+        home.seed_credentials(source.path()).expect("seed");
+        assert_eq!(
+            fs::read(home.path().join(".codex/auth.json")).expect("codex auth"),
+            b"{\"token\":1}"
+        );
+        assert!(home.path().join(".claude.json").is_file());
+        assert!(!home.path().join(".bash_history").exists());
+        assert!(!home.path().join(".gemini").exists());
+
+        fs::write(home.path().join(".codex/auth.json"), b"refreshed").expect("write");
+        assert_eq!(
+            fs::read(source.path().join(".codex/auth.json")).expect("source auth"),
+            b"{\"token\":1}"
+        );
+    }
+
+    #[test]
+    fn env_points_at_the_home_and_remove_deletes_the_session_dir() {
+        let state = tempfile::tempdir().expect("state dir");
+        let home = SessionHome::create(state.path(), "sess").expect("create");
+        let env: std::collections::HashMap<_, _> = home.env().into_iter().collect();
+        let root = home.path().to_string_lossy().to_string();
+        assert_eq!(env["HOME"], root);
+        assert_eq!(env["USERPROFILE"], root);
+        for (key, dir) in [
+            ("XDG_CONFIG_HOME", home.config_dir()),
+            ("XDG_DATA_HOME", home.data_dir()),
+            ("XDG_CACHE_HOME", home.cache_dir()),
+            ("XDG_STATE_HOME", home.state_dir()),
+            ("CODEX_HOME", home.path().join(".codex")),
+        ] {
+            assert_eq!(env[key], dir.to_string_lossy(), "{key}");
+        }
+
+        home.remove().expect("remove");
+        assert!(!state.path().join("sessions/sess").exists());
+        assert!(state.path().join("sessions").is_dir());
+        home.remove().expect("remove twice");
+    }
+}
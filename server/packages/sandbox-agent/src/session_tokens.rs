@@ -0,0 +1,148 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::hmac;
+use sandbox_agent_error::SandboxError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::router::TokenScope;
+
+const HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+const ISSUER: &str = "sandbox-agent";
+
+/// Claims carried by a session token. `sub` is the session id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SessionTokenClaims {
+    pub(crate) iss: String,
+    pub(crate) sub: String,
+    pub(crate) scopes: Vec<TokenScope>,
+    pub(crate) iat: u64,
+    pub(crate) exp: u64,
+}
+
+/// Mints and verifies HMAC-SHA256 signed JWTs bound to a single session.
+#[derive(Debug, Clone)]
+pub(crate) struct SessionTokenSigner {
+    key: hmac::Key,
+}
+
+impl SessionTokenSigner {
+    pub(crate) fn new(secret: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        }
+    }
+
+    /// Signer with a per-process key; its tokens stop verifying after a restart.
+    pub(crate) fn random() -> Self {
+        let mut secret = [0u8; 32];
+        getrandom::getrandom(&mut secret).expect("system random source available");
+        Self::new(&secret)
+    }
+
+    pub(crate) fn mint(
+        &self,
+        session_id: &str,
+        scopes: Vec<TokenScope>,
+        ttl: Duration,
+    ) -> (String, SessionTokenClaims) {
+        let iat = unix_now();
+        let claims = SessionTokenClaims {
+            iss: ISSUER.to_string(),
+            sub: session_id.to_string(),
+            scopes,
+            iat,
+            exp: iat + ttl.as_secs(),
+        };
+        let payload = serde_json::to_vec(&claims).expect("serialize session token claims");
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(HEADER),
+            URL_SAFE_NO_PAD.encode(payload)
+        );
+        let signature = hmac::sign(&self.key, signing_input.as_bytes());
+        let token = format!(
+            "{signing_input}.{}",
+            URL_SAFE_NO_PAD.encode(signature.as_ref())
+        );
+        (token, claims)
+    }
+
+    pub(crate) fn verify(&self, token: &str) -> Result<SessionTokenClaims, SandboxError> {
+        let invalid = |message: &str| SandboxError::TokenInvalid {
+            message: Some(message.to_string()),
+        };
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("missing or invalid token"));
+        };
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| invalid("malformed session token"))?;
+        let signing_input = &token[..header.len() + 1 + payload.len()];
+        hmac::verify(&self.key, signing_input.as_bytes(), &signature)
+            .map_err(|_| invalid("session token signature mismatch"))?;
+
+        let header: Value = URL_SAFE_NO_PAD
+            .decode(header)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| invalid("malformed session token"))?;
+        if header.get("alg").and_then(Value::as_str) != Some("HS256") {
+            return Err(invalid("unsupported session token algorithm"));
+        }
+        let claims: SessionTokenClaims = URL_SAFE_NO_PAD
+            .decode(payload)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| invalid("malformed session token"))?;
+        if claims.iss != ISSUER {
+            return Err(invalid("session token issuer mismatch"));
+        }
+        if claims.exp <= unix_now() {
+            return Err(invalid("session token expired"));
+        }
+        Ok(claims)
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_tokens_round_trip_and_reject_tampering() {
+        let signer = SessionTokenSigner::new(b"test-secret");
+        let (token, claims) = signer.mint(
+            "sess-1",
+            vec![TokenScope::Read, TokenScope::MessagesSend],
+            Duration::from_secs(60),
+        );
+        assert_eq!(signer.verify(&token).expect("valid token"), claims);
+        assert_eq!(claims.sub, "sess-1");
+
+        let other = SessionTokenSigner::new(b"other-secret");
+        assert!(other.verify(&token).is_err());
+
+        let (expired, _) = signer.mint("sess-1", vec![TokenScope::Read], Duration::ZERO);
+        assert!(signer.verify(&expired).is_err());
+
+        // Swapping in a payload for another session breaks the signature.
+        let (forged, _) = other.mint("sess-2", vec![TokenScope::Read], Duration::from_secs(60));
+        let mut parts: Vec<&str> = token.split('.').collect();
+        parts[1] = forged.split('.').nth(1).unwrap();
+        assert!(signer.verify(&parts.join(".")).is_err());
+        assert!(signer.verify("not-a-jwt").is_err());
+    }
+}
@@ -0,0 +1,358 @@
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::Router;
+use hyper::server::conn::http1;
+use hyper_util::rt::TokioIo;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls;
+use tokio_rustls::TlsAcceptor;
+
+/// Endpoint prefix for the Unix domain socket listener, e.g. `unix:///run/sandbox-agent.sock`.
+pub(crate) const UNIX_SCHEME: &str = "unix://";
+
+/// Pause after a failed accept, such as `EMFILE`, so the loop does not spin while
+/// the error persists.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Returns the socket path when `endpoint` uses the `unix://` scheme.
+pub(crate) fn unix_socket_path(endpoint: &str) -> Option<PathBuf> {
+    endpoint
+        .strip_prefix(UNIX_SCHEME)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Loads a PEM certificate chain and private key into a rustls server config.
+pub(crate) fn load_tls_acceptor(cert_path: &Path, key_path: &Path) -> io::Result<TlsAcceptor> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let certs: Vec<rustls::Certificate> =
+        rustls_pemfile::certs(&mut BufReader::new(File::open(cert_path)?))?
+            .into_iter()
+            .map(rustls::Certificate)
+            .collect();
+    if certs.is_empty() {
+        return Err(invalid(format!(
+            "no certificates found in {}",
+            cert_path.display()
+        )));
+    }
+
+    let key = rustls_pemfile::read_all(&mut BufReader::new(File::open(key_path)?))?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(rustls::PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| invalid(format!("no private key found in {}", key_path.display())))?;
+
+    let mut config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|err| invalid(format!("invalid TLS certificate or key: {err}")))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Serves `router` over TLS until `shutdown` resolves, then drains open connections.
+pub(crate) async fn serve_tls(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    router: Router,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    tracing::warn!(error = %err, "failed to accept tls connection");
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        let acceptor = acceptor.clone();
+        let router = router.clone();
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            match acceptor.accept(stream).await {
+                Ok(stream) => serve_stream(stream, router, watcher).await,
+                Err(err) => tracing::debug!(peer = %peer, error = %err, "tls handshake failed"),
+            }
+        });
+    }
+    graceful.shutdown().await;
+    Ok(())
+}
+
+/// Creates the socket with owner-only permissions, replacing a stale socket file.
+/// The socket is bound inside a private directory and renamed into place, so it is
+/// never reachable with the umask's looser permissions.
+#[cfg(unix)]
+pub(crate) fn bind_unix_socket(path: &Path) -> io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    std::fs::create_dir_all(parent)?;
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a socket path", path.display()),
+        )
+    })?;
+    let mut suffix = [0u8; 8];
+    getrandom::getrandom(&mut suffix).map_err(|err| io::Error::other(err.to_string()))?;
+    let private_dir = parent.join(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        suffix
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<String>()
+    ));
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&private_dir)?;
+    let staged = private_dir.join(file_name);
+    let bound = tokio::net::UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_file(&staged);
+    let _ = std::fs::remove_dir(&private_dir);
+    bound
+}
+
+/// Serves `router` on a Unix socket until `shutdown` resolves, then removes the socket file.
+#[cfg(unix)]
+pub(crate) async fn serve_unix(
+    listener: tokio::net::UnixListener,
+    path: PathBuf,
+    router: Router,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    tracing::warn!(error = %err, "failed to accept unix socket connection");
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                }
+            },
+            _ = &mut shutdown => break,
+        };
+        tokio::spawn(serve_stream(stream, router.clone(), graceful.watcher()));
+    }
+    graceful.shutdown().await;
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
+
+async fn serve_stream<S>(stream: S, router: Router, watcher: hyper_util::server::graceful::Watcher)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let connection = http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), TowerToHyperService::new(router));
+    if let Err(err) = watcher.watch(connection).await {
+        tracing::debug!(error = %err, "connection closed with error");
+    }
+}
+
+/// Request body sent over a Unix socket: buffered, or streamed from a file.
+pub(crate) enum UnixRequestBody {
+    Bytes(axum::body::Bytes),
+    File(File),
+}
+
+impl From<axum::body::Bytes> for UnixRequestBody {
+    fn from(bytes: axum::body::Bytes) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+#[cfg(unix)]
+impl UnixRequestBody {
+    /// Files are read on a separate thread, a chunk at a time.
+    fn into_body(self) -> http_body_util::combinators::BoxBody<axum::body::Bytes, io::Error> {
+        use http_body_util::{BodyExt, Full, StreamBody};
+        use hyper::body::Frame;
+        use std::io::Read;
+
+        match self {
+            Self::Bytes(bytes) => Full::new(bytes).map_err(|never| match never {}).boxed(),
+            Self::File(mut file) => {
+                let (tx, rx) = tokio::sync::mpsc::channel(4);
+                std::thread::spawn(move || {
+                    let mut buf = vec![0u8; 64 * 1024];
+                    loop {
+                        let chunk = match file.read(&mut buf) {
+                            Ok(0) => break,
+                            Ok(read) => Ok(Frame::data(axum::body::Bytes::copy_from_slice(
+                                &buf[..read],
+                            ))),
+                            Err(err) => Err(err),
+                        };
+                        let failed = chunk.is_err();
+                        if tx.blocking_send(chunk).is_err() || failed {
+                            break;
+                        }
+                    }
+                });
+                StreamBody::new(tokio_stream::wrappers::ReceiverStream::new(rx)).boxed()
+            }
+        }
+    }
+}
+
+/// Sends one HTTP/1.1 request over a Unix socket and buffers the response body.
+#[cfg(unix)]
+pub(crate) fn send_unix_request(
+    socket: &Path,
+    request: axum::http::Request<UnixRequestBody>,
+    timeout: Option<Duration>,
+) -> io::Result<axum::http::Response<axum::body::Bytes>> {
+    use http_body_util::BodyExt;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let exchange = async {
+            let stream = tokio::net::UnixStream::connect(socket).await?;
+            let (mut sender, connection) =
+                hyper::client::conn::http1::handshake(TokioIo::new(stream))
+                    .await
+                    .map_err(io::Error::other)?;
+            tokio::spawn(connection);
+            let (parts, body) = request.into_parts();
+            let response = sender
+                .send_request(axum::http::Request::from_parts(parts, body.into_body()))
+                .await
+                .map_err(io::Error::other)?;
+            let (parts, body) = response.into_parts();
+            let body = body.collect().await.map_err(io::Error::other)?.to_bytes();
+            Ok(axum::http::Response::from_parts(parts, body))
+        };
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, exchange).await.map_err(|_| {
+                io::Error::new(io::ErrorKind::TimedOut, "unix socket request timed out")
+            })?,
+            None => exchange.await,
+        }
+    })
+}
+
+#[cfg(windows)]
+pub(crate) fn send_unix_request(
+    _socket: &Path,
+    _request: axum::http::Request<UnixRequestBody>,
+    _timeout: Option<std::time::Duration>,
+) -> io::Result<axum::http::Response<axum::body::Bytes>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "unix:// endpoints are not supported on Windows",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn unix_socket_serves_requests_and_cleans_up() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("run").join("sandbox-agent.sock");
+        let listener = bind_unix_socket(&path).expect("bind socket");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        let entries: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec!["sandbox-agent.sock"], "staging dir removed");
+
+        let router = Router::new()
+            .route("/v1/health", axum::routing::get(|| async { "ok" }))
+            .route(
+                "/v1/upload",
+                axum::routing::post(
+                    |body: axum::body::Bytes| async move { body.len().to_string() },
+                ),
+            );
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let server = tokio::spawn(serve_unix(listener, path.clone(), router, async {
+            let _ = stop_rx.await;
+        }));
+
+        let endpoint = format!("{UNIX_SCHEME}{}", path.display());
+        let socket = unix_socket_path(&endpoint).expect("unix endpoint");
+        let response = tokio::task::spawn_blocking(move || {
+            let request = axum::http::Request::get("/v1/health")
+                .header("host", "localhost")
+                .body(axum::body::Bytes::new().into())
+                .unwrap();
+            send_unix_request(&socket, request, Some(Duration::from_secs(5)))
+        })
+        .await
+        .unwrap()
+        .expect("unix request");
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        assert_eq!(response.body().as_ref(), b"ok");
+
+        let upload = dir.path().join("upload.bin");
+        std::fs::write(&upload, vec![7u8; 200 * 1024]).unwrap();
+        let socket = path.clone();
+        let response = tokio::task::spawn_blocking(move || {
+            let request = axum::http::Request::post("/v1/upload")
+                .header("host", "localhost")
+                .body(UnixRequestBody::File(File::open(upload).unwrap()))
+                .unwrap();
+            send_unix_request(&socket, request, Some(Duration::from_secs(5)))
+        })
+        .await
+        .unwrap()
+        .expect("streamed upload");
+        assert_eq!(response.body().as_ref(), b"204800");
+
+        let _ = stop_tx.send(());
+        server.await.unwrap().expect("server stops");
+        assert!(!path.exists(), "socket file removed on shutdown");
+        assert!(unix_socket_path("http://localhost:2468").is_none());
+    }
+}
@@ -54,6 +54,10 @@ fn test_agents_install_version_spawn() -> Result<(), Box<dyn std::error::Error>>
             InstallOptions {
                 reinstall: true,
                 version: None,
+                sha256: None,
+                from: None,
+                mirror: None,
+                progress: None,
             },
         )?;
         let version = manager.version(agent)?;
@@ -63,6 +63,1078 @@ async fn auth_snapshots() {
     });
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn scoped_tokens_enforce_scopes_and_sessions() {
+    let tokens: Vec<sandbox_agent::router::ScopedToken> = serde_json::from_value(json!([
+        { "name": "observer", "token": "read-token", "scopes": ["read"] },
+        {
+            "token": "user-token",
+            "scopes": ["read", "messages:send", "permissions:reply"],
+            "sessions": ["mine"]
+        }
+    ]))
+    .expect("parse scoped tokens");
+    let app = TestApp::new_with_auth(AuthConfig::disabled().with_tokens(tokens));
+
+    let cases = [
+        (Method::GET, "/v1/agents", "read-token", StatusCode::OK),
+        (Method::GET, "/v1/sessions", "read-token", StatusCode::OK),
+        (
+            Method::POST,
+            "/v1/agents/mock/install",
+            "read-token",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            Method::POST,
+            "/v1/sessions/mine/messages",
+            "read-token",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            Method::PUT,
+            "/v1/fs/file?path=x",
+            "read-token",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            Method::GET,
+            "/v1/sessions",
+            "user-token",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            Method::GET,
+            "/v1/sessions/other/events",
+            "user-token",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            Method::GET,
+            "/v1/sessions/mine/events",
+            "user-token",
+            StatusCode::NOT_FOUND,
+        ),
+        (
+            Method::POST,
+            "/v1/sessions/mine",
+            "user-token",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            Method::GET,
+            "/v1/agents",
+            "unknown-token",
+            StatusCode::UNAUTHORIZED,
+        ),
+    ];
+    for (method, uri, token, expected) in cases {
+        let (status, _) =
+            send_json_with_token(&app.app, method.clone(), uri, token, json!({})).await;
+        assert_eq!(status, expected, "{method} {uri} with {token}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn session_tokens_are_bound_to_one_session() {
+    let admin = "admin-token";
+    let app = TestApp::new_with_auth(AuthConfig::with_token(admin.to_string()));
+
+    for session_id in ["mine", "other"] {
+        let (status, _) = send_json_with_token(
+            &app.app,
+            Method::POST,
+            &format!("/v1/sessions/{session_id}"),
+            admin,
+            json!({ "agent": "mock", "permissionMode": "bypass" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "create session {session_id}");
+    }
+
+    let (status, payload) = send_json_with_token(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/mine/tokens",
+        admin,
+        json!({ "scopes": ["read"], "ttlSeconds": 60 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "mint session token: {payload}");
+    assert_eq!(payload["scopes"], json!(["read"]));
+    let token = payload["token"].as_str().expect("token string").to_string();
+
+    let cases = [
+        (Method::GET, "/v1/sessions/mine/events", StatusCode::OK),
+        (
+            Method::GET,
+            "/v1/sessions/other/events",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            Method::POST,
+            "/v1/sessions/mine/messages",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            Method::POST,
+            "/v1/sessions/mine/tokens",
+            StatusCode::FORBIDDEN,
+        ),
+        (Method::GET, "/v1/agents", StatusCode::FORBIDDEN),
+    ];
+    for (method, uri, expected) in cases {
+        let (status, _) =
+            send_json_with_token(&app.app, method.clone(), uri, &token, json!({})).await;
+        assert_eq!(status, expected, "{method} {uri} with session token");
+    }
+
+    // EventSource cannot set headers, so SSE routes also take `?token=`.
+    let query_token_status = |uri: String| {
+        let app = app.app.clone();
+        async move {
+            let request = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .body(Body::empty())
+                .expect("request");
+            app.oneshot(request).await.expect("response").status()
+        }
+    };
+    assert_eq!(
+        query_token_status(format!("/v1/sessions/mine/events/sse?token={token}")).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        query_token_status(format!("/v1/sessions/other/events/sse?token={token}")).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        query_token_status(format!("/v1/sessions/mine/events?token={token}")).await,
+        StatusCode::UNAUTHORIZED,
+        "query tokens are only read on SSE routes"
+    );
+
+    let mut tampered = token.clone();
+    tampered.push('x');
+    let (status, _) = send_json_with_token(
+        &app.app,
+        Method::GET,
+        "/v1/sessions/mine/events",
+        &tampered,
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "tampered session token");
+
+    let (status, _) = send_json_with_token(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/mine/tokens",
+        admin,
+        json!({ "scopes": ["admin"] }),
+    )
+    .await;
+    assert_eq!(
+        status,
+        StatusCode::BAD_REQUEST,
+        "admin scope is not mintable"
+    );
+
+    let (status, _) = send_json_with_token(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/missing/tokens",
+        admin,
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "unknown session");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn session_tokens_stay_inside_the_session_working_dir() {
+    let admin = "admin-token";
+    let app = TestApp::new_with_auth(AuthConfig::with_token(admin.to_string()));
+    let (status, _) = send_json_with_token(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/files",
+        admin,
+        json!({ "agent": "mock", "permissionMode": "bypass" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "create session");
+    let (status, payload) = send_json_with_token(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/files/tokens",
+        admin,
+        json!({ "scopes": ["fs:read", "fs:write"] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "mint session token: {payload}");
+    let token = payload["token"].as_str().expect("token string").to_string();
+
+    // Sessions run in the daemon's working directory, the crate root under test.
+    let inside = std::env::current_dir()
+        .expect("cwd")
+        .join("Cargo.toml")
+        .canonicalize()
+        .expect("canonical");
+    let outside = app._install_dir.path().join("outside.txt");
+    std::fs::write(&outside, "secret").expect("write outside");
+
+    let cases = [
+        (
+            Method::GET,
+            "/v1/fs/stat?sessionId=files&path=Cargo.toml".to_string(),
+            StatusCode::OK,
+        ),
+        (
+            Method::GET,
+            format!("/v1/fs/stat?sessionId=files&path={}", inside.display()),
+            StatusCode::OK,
+        ),
+        (
+            Method::GET,
+            format!("/v1/fs/file?sessionId=files&path={}", outside.display()),
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            Method::PUT,
+            format!("/v1/fs/file?sessionId=files&path={}", outside.display()),
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            Method::GET,
+            "/v1/fs/file?sessionId=files&path=../Cargo.toml".to_string(),
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            Method::GET,
+            "/v1/fs/stat?path=Cargo.toml".to_string(),
+            StatusCode::FORBIDDEN,
+        ),
+    ];
+    for (method, uri, expected) in cases {
+        let (status, payload) =
+            send_json_with_token(&app.app, method.clone(), &uri, &token, json!({})).await;
+        assert_eq!(status, expected, "{method} {uri}: {payload}");
+    }
+    assert_eq!(
+        std::fs::read_to_string(&outside).expect("read outside"),
+        "secret"
+    );
+
+    // The admin token keeps unrestricted filesystem access.
+    let (status, _) = send_json_with_token(
+        &app.app,
+        Method::GET,
+        &format!("/v1/fs/stat?path={}", outside.display()),
+        admin,
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn rate_limits_cap_requests_and_sessions() {
+    let tokens: Vec<sandbox_agent::router::ScopedToken> = serde_json::from_value(json!([
+        {
+            "token": "limited-token",
+            "scopes": ["admin"],
+            "limits": { "requestsPerSecond": 0.5, "burst": 3, "maxSessions": 1 }
+        },
+        { "token": "admin-token", "scopes": ["admin"] }
+    ]))
+    .expect("parse scoped tokens");
+    let rate_limits: sandbox_agent::rate_limit::RateLimitConfig =
+        serde_json::from_value(json!({ "global": { "maxSessions": 2 } }))
+            .expect("parse rate limits");
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    let manager = AgentManager::new(install_dir.path()).expect("create agent manager");
+    let state = AppState::with_session_config(
+        AuthConfig::disabled().with_tokens(tokens),
+        manager,
+        sandbox_agent::router::BrandingMode::SandboxAgent,
+        sandbox_agent::router::SessionConfig {
+            rate_limits,
+            ..Default::default()
+        },
+    );
+    let app = build_router(state);
+    let create = json!({ "agent": "mock", "permissionMode": "bypass" });
+
+    let (status, _) = send_json_with_token(
+        &app,
+        Method::POST,
+        "/v1/sessions/a",
+        "limited-token",
+        create.clone(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "first session for limited token");
+    let (status, headers, payload) = send_with_token(
+        &app,
+        Method::POST,
+        "/v1/sessions/b",
+        "limited-token",
+        create.clone(),
+    )
+    .await;
+    assert_eq!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS,
+        "per-token session cap"
+    );
+    assert_eq!(payload["type"], "urn:sandbox-agent:error:rate_limited");
+    assert!(headers.contains_key(header::RETRY_AFTER));
+
+    let (status, _) = send_json_with_token(
+        &app,
+        Method::GET,
+        "/v1/sessions",
+        "limited-token",
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "burst allows a third request");
+    let (status, headers, _) = send_with_token(
+        &app,
+        Method::GET,
+        "/v1/sessions",
+        "limited-token",
+        json!({}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "request rate");
+    let retry_after: u64 = headers[header::RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .expect("numeric Retry-After");
+    assert!(retry_after >= 1);
+
+    let (status, _) =
+        send_json_with_token(&app, Method::GET, "/v1/health", "limited-token", json!({})).await;
+    assert_eq!(status, StatusCode::OK, "health is not rate limited");
+
+    let (status, _) = send_json_with_token(
+        &app,
+        Method::POST,
+        "/v1/sessions/c",
+        "admin-token",
+        create.clone(),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "other tokens keep their own budget");
+    let (status, _) =
+        send_json_with_token(&app, Method::POST, "/v1/sessions/d", "admin-token", create).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS, "global session cap");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn session_tokens_for_one_session_share_a_rate_limit() {
+    let tokens: Vec<sandbox_agent::router::ScopedToken> = serde_json::from_value(json!([
+        { "token": "admin-token", "scopes": ["admin"], "limits": { "requestsPerSecond": 100 } }
+    ]))
+    .expect("parse scoped tokens");
+    let rate_limits: sandbox_agent::rate_limit::RateLimitConfig =
+        serde_json::from_value(json!({ "perToken": { "requestsPerSecond": 0.1, "burst": 2 } }))
+            .expect("parse rate limits");
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    let manager = AgentManager::new(install_dir.path()).expect("create agent manager");
+    let state = AppState::with_session_config(
+        AuthConfig::disabled().with_tokens(tokens),
+        manager,
+        sandbox_agent::router::BrandingMode::SandboxAgent,
+        sandbox_agent::router::SessionConfig {
+            rate_limits,
+            ..Default::default()
+        },
+    );
+    let app = build_router(state);
+    let (status, _) = send_json_with_token(
+        &app,
+        Method::POST,
+        "/v1/sessions/shared",
+        "admin-token",
+        json!({ "agent": "mock", "permissionMode": "bypass" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "create session");
+
+    let mut minted = Vec::new();
+    for ttl in [60, 120] {
+        let (status, payload) = send_json_with_token(
+            &app,
+            Method::POST,
+            "/v1/sessions/shared/tokens",
+            "admin-token",
+            json!({ "scopes": ["read"], "ttlSeconds": ttl }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "mint session token: {payload}");
+        minted.push(payload["token"].as_str().expect("token string").to_string());
+    }
+    assert_ne!(minted[0], minted[1]);
+
+    for token in [&minted[0], &minted[0]] {
+        let (status, _) = send_json_with_token(
+            &app,
+            Method::GET,
+            "/v1/sessions/shared/events",
+            token,
+            json!({}),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "within burst");
+    }
+    let (status, _) = send_json_with_token(
+        &app,
+        Method::GET,
+        "/v1/sessions/shared/events",
+        &minted[1],
+        json!({}),
+    )
+    .await;
+    assert_eq!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS,
+        "a second token for the same session draws from the same bucket"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn metrics_endpoint_reports_sessions_turns_and_routes() {
+    let token = "metrics-token";
+    let app = TestApp::new_with_auth(AuthConfig::with_token(token.to_string()));
+
+    let (status, _) = send_json_with_token(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/metrics-session",
+        token,
+        json!({ "agent": "mock", "permissionMode": "bypass" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "create session");
+    let (status, _) = send_json_with_token(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/metrics-session/messages",
+        token,
+        json!({ "message": PROMPT }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT, "send message");
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/metrics")
+        .body(Body::empty())
+        .expect("metrics request");
+    let (status, _headers, _body) = send_request(&app.app, request).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "metrics need a token");
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/metrics")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::empty())
+        .expect("metrics request");
+    let (status, headers, body) = send_request(&app.app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers[header::CONTENT_TYPE]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    let text = String::from_utf8(body.to_vec()).expect("utf8 metrics");
+    for expected in [
+        "sandbox_agent_sessions_created_total{agent=\"mock\"}",
+        "sandbox_agent_turns_total{agent=\"mock\"}",
+        "sandbox_agent_events_total{agent=\"mock\",type=\"session.started\"}",
+        "sandbox_agent_http_request_duration_seconds_count{method=\"POST\",route=\"/v1/sessions/:session_id\",status=\"200\"}",
+        "# TYPE sandbox_agent_sessions_created_total counter",
+    ] {
+        assert!(text.contains(expected), "missing {expected} in:\n{text}");
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn readiness_and_diagnostics_report_server_state() {
+    let token = "diagnostics-token";
+    let app = TestApp::new_with_auth(AuthConfig::with_token(token.to_string()));
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/health/ready")
+        .body(Body::empty())
+        .expect("readiness request");
+    let (status, _headers, body) = send_request(&app.app, request).await;
+    assert_eq!(status, StatusCode::OK, "readiness skips auth");
+    let readiness: Value = serde_json::from_slice(&body).expect("readiness json");
+    assert_eq!(readiness["ready"], json!(true), "{readiness}");
+    let checks: Vec<&str> = readiness["checks"]
+        .as_array()
+        .expect("checks")
+        .iter()
+        .filter_map(|check| check["name"].as_str())
+        .collect();
+    assert_eq!(checks, ["disk", "agentServers", "sessionCapacity"]);
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/diagnostics")
+        .body(Body::empty())
+        .expect("diagnostics request");
+    let (status, _headers, _body) = send_request(&app.app, request).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "diagnostics need a token");
+
+    let (status, _) = send_json_with_token(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/diagnostics-session",
+        token,
+        json!({ "agent": "mock", "permissionMode": "bypass" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "create session");
+
+    let (status, diagnostics) =
+        send_json_with_token(&app.app, Method::GET, "/v1/diagnostics", token, Value::Null).await;
+    assert_eq!(status, StatusCode::OK, "{diagnostics}");
+    assert_eq!(diagnostics["activeSessions"], json!(1));
+    assert_eq!(diagnostics["readiness"]["ready"], json!(true));
+    assert!(diagnostics["agents"]
+        .as_array()
+        .expect("agents")
+        .iter()
+        .any(|agent| agent["id"] == "mock" && agent["credentialsAvailable"] == json!(true)));
+    let providers: Vec<&str> = diagnostics["credentials"]
+        .as_array()
+        .expect("credentials")
+        .iter()
+        .filter_map(|credential| credential["provider"].as_str())
+        .collect();
+    assert!(
+        providers.starts_with(&["anthropic", "openai"]),
+        "{providers:?}"
+    );
+    let disks: Vec<&str> = diagnostics["disks"]
+        .as_array()
+        .expect("disks")
+        .iter()
+        .filter_map(|disk| disk["name"].as_str())
+        .collect();
+    assert_eq!(disks, ["install", "logs"]);
+    assert!(diagnostics["recentErrors"].is_array());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn log_endpoints_tail_filter_and_follow() {
+    let log_dir = tempfile::tempdir().expect("log dir");
+    std::fs::write(
+        log_dir.path().join("log-01-02-26"),
+        "ts=2026-01-02T10:00:00Z level=info message=early\n\
+         ts=2026-01-02T11:00:00Z level=info message=middle\n\
+         ts=2026-01-02T12:00:00Z level=warn message=late\n",
+    )
+    .expect("write log");
+    std::env::set_var("SANDBOX_AGENT_LOG_DIR", log_dir.path());
+    let app = TestApp::new();
+
+    let (status, payload) = send_json(&app.app, Method::GET, "/v1/logs/server?tail=1", None).await;
+    assert_eq!(status, StatusCode::OK, "{payload}");
+    assert_eq!(
+        payload,
+        json!({ "lines": ["ts=2026-01-02T12:00:00Z level=warn message=late"], "truncated": true })
+    );
+
+    let (status, payload) = send_json(
+        &app.app,
+        Method::GET,
+        "/v1/logs/server?since=2026-01-02T10:30:00Z",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{payload}");
+    assert_eq!(payload["lines"].as_array().map(Vec::len), Some(2));
+    assert_eq!(payload["truncated"], json!(false));
+
+    let (status, _) = send_json(
+        &app.app,
+        Method::GET,
+        "/v1/logs/server?since=yesterday",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, payload) = send_json(&app.app, Method::GET, "/v1/agents/codex/logs", None).await;
+    assert_eq!(status, StatusCode::OK, "{payload}");
+    assert!(payload["lines"].is_array());
+    let (status, _) = send_json(&app.app, Method::GET, "/v1/agents/nope/logs", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri("/v1/logs/server?follow=true&tail=2")
+        .body(Body::empty())
+        .expect("follow request");
+    let response = app
+        .app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("follow response");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/event-stream"
+    );
+    let mut body = response.into_body();
+    let mut text = String::new();
+    while !text.contains("message=late") {
+        let frame = tokio::time::timeout(Duration::from_secs(5), body.frame())
+            .await
+            .expect("log event")
+            .expect("frame")
+            .expect("frame ok");
+        if let Ok(data) = frame.into_data() {
+            text.push_str(&String::from_utf8_lossy(&data));
+        }
+    }
+    assert!(
+        text.contains("event: log\ndata: ts=2026-01-02T11:00:00Z"),
+        "{text}"
+    );
+    std::env::remove_var("SANDBOX_AGENT_LOG_DIR");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn debug_log_captures_native_io_when_enabled() {
+    let app = TestApp::new();
+    let (status, _) = send_json(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/debug-on",
+        Some(json!({ "agent": "mock", "debugLog": true })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    create_session(&app.app, AgentId::Mock, "debug-off", "bypass").await;
+    send_message(&app.app, "debug-on").await;
+
+    let request = || {
+        Request::builder()
+            .method(Method::GET)
+            .uri("/v1/sessions/debug-on/debug-log")
+            .body(Body::empty())
+            .expect("debug log request")
+    };
+    let start = Instant::now();
+    let (status, headers, body) = loop {
+        let (status, headers, body) = send_request(&app.app, request()).await;
+        if String::from_utf8_lossy(&body).contains("\"stdout\"")
+            || start.elapsed() > Duration::from_secs(10)
+        {
+            break (status, headers, body);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    };
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/x-ndjson");
+    let entries: Vec<Value> = String::from_utf8_lossy(&body)
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("ndjson entry"))
+        .collect();
+    assert_eq!(entries[0]["seq"], json!(0));
+    assert_eq!(entries[0]["direction"], json!("stdin"));
+    assert_eq!(entries[0]["line"], json!(PROMPT));
+    assert!(entries[0]["time"].is_string());
+    assert!(entries
+        .iter()
+        .any(|entry| entry["direction"] == json!("stdout")));
+
+    let (status, _) = send_json(
+        &app.app,
+        Method::GET,
+        "/v1/sessions/debug-off/debug-log",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send_json(
+        &app.app,
+        Method::GET,
+        "/v1/sessions/missing/debug-log",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn mock_scenarios_script_matching_prompts() {
+    let mock_scenarios = sandbox_agent::mock_scenarios::MockScenarios::parse(
+        &json!({
+            "scenarios": [{
+                "name": "crash-after-permission",
+                "pattern": "^deploy",
+                "steps": [
+                    { "type": "message", "deltas": ["Deploying", "..."] },
+                    { "type": "permission", "action": "command_execution" },
+                    { "type": "delay", "ms": 50 },
+                    { "type": "crash", "message": "agent killed", "exitCode": 137 }
+                ]
+            }]
+        })
+        .to_string(),
+        "test",
+    )
+    .expect("parse scenarios");
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    let manager = AgentManager::new(install_dir.path()).expect("create agent manager");
+    let state = AppState::with_session_config(
+        AuthConfig::disabled(),
+        manager,
+        sandbox_agent::router::BrandingMode::SandboxAgent,
+        sandbox_agent::router::SessionConfig {
+            mock_scenarios,
+            ..Default::default()
+        },
+    );
+    let app = build_router(state);
+    create_session(&app, AgentId::Mock, "scripted", "bypass").await;
+    let status = send_status(
+        &app,
+        Method::POST,
+        "/v1/sessions/scripted/messages",
+        Some(json!({ "message": "deploy to staging" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let start = Instant::now();
+    let events = loop {
+        let (_, payload) = send_json(&app, Method::GET, "/v1/sessions/scripted/events", None).await;
+        let events = payload["events"].as_array().cloned().unwrap_or_default();
+        if events.iter().any(|event| event["type"] == "session.ended")
+            || start.elapsed() > Duration::from_secs(10)
+        {
+            break events;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    };
+    let types: Vec<&str> = events
+        .iter()
+        .filter(|event| event["data"]["item"]["role"] != "user")
+        .filter_map(|event| event["type"].as_str())
+        .filter(|kind| *kind != "session.started" && *kind != "turn.started")
+        .collect();
+    assert_eq!(
+        types,
+        [
+            "item.started",
+            "item.delta",
+            "item.delta",
+            "item.completed",
+            "permission.requested",
+            "session.ended",
+        ]
+    );
+    let completed = events
+        .iter()
+        .find(|event| {
+            event["type"] == "item.completed" && event["data"]["item"]["role"] == "assistant"
+        })
+        .expect("assistant message");
+    assert_eq!(
+        completed["data"]["item"]["content"][0]["text"],
+        "Deploying..."
+    );
+    let ended = events.last().expect("session.ended");
+    assert_eq!(ended["data"]["reason"], "error");
+    assert_eq!(ended["data"]["exit_code"], 137);
+    assert_eq!(ended["data"]["message"], "agent killed");
+
+    create_session(&app, AgentId::Mock, "unscripted", "bypass").await;
+    send_message(&app, "unscripted").await;
+    let events = poll_events_until(&app, "unscripted", Duration::from_secs(10)).await;
+    assert!(
+        events.iter().any(is_assistant_message),
+        "built-in mock reply"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn restricted_egress_refuses_shared_server_agents() {
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    let manager = AgentManager::new(install_dir.path()).expect("create agent manager");
+    let state = AppState::with_session_config(
+        AuthConfig::disabled(),
+        manager,
+        sandbox_agent::router::BrandingMode::SandboxAgent,
+        sandbox_agent::router::SessionConfig {
+            restrict_egress: true,
+            ..Default::default()
+        },
+    );
+    let app = build_router(state);
+
+    for agent in ["codex", "opencode"] {
+        let (status, payload) = send_json(
+            &app,
+            Method::POST,
+            &format!("/v1/sessions/{agent}-restricted"),
+            Some(json!({ "agent": agent })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{agent}: {payload}");
+        assert!(
+            payload["detail"]
+                .as_str()
+                .is_some_and(|detail| detail.contains("egress")),
+            "{payload}"
+        );
+    }
+    let (status, payload) = send_json(
+        &app,
+        Method::POST,
+        "/v1/sessions/mock-restricted",
+        Some(json!({ "agent": "mock" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{payload}");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn session_limits_are_refused_for_shared_server_agents() {
+    let app = TestApp::new();
+
+    for agent in ["codex", "opencode"] {
+        let (status, payload) = send_json(
+            &app.app,
+            Method::POST,
+            &format!("/v1/sessions/{agent}-limits"),
+            Some(json!({ "agent": agent, "limits": { "maxPids": 64 } })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{agent}: {payload}");
+        assert!(
+            payload["detail"]
+                .as_str()
+                .is_some_and(|detail| detail.contains("limits")),
+            "{payload}"
+        );
+    }
+    let (status, payload) = send_json(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/mock-limits",
+        Some(json!({ "agent": "mock", "limits": { "maxPids": 64 } })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{payload}");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn uninstall_refuses_agents_in_use() {
+    let app = TestApp::new();
+    let binary = app._install_dir.path().join("amp");
+    std::fs::write(&binary, "#!/bin/sh\necho 1.0.0\n").expect("write amp");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755))
+            .expect("chmod amp");
+    }
+
+    let (status, _) = send_json(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/uses-amp",
+        Some(json!({ "agent": "amp" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "create amp session");
+    let (status, payload) = send_json(&app.app, Method::DELETE, "/v1/agents/amp", None).await;
+    assert_eq!(status, StatusCode::CONFLICT, "in use: {payload}");
+    assert_eq!(payload["details"]["sessionIds"], json!(["uses-amp"]));
+    assert!(binary.exists());
+
+    let status = send_status(
+        &app.app,
+        Method::POST,
+        "/v1/sessions/uses-amp/terminate",
+        None,
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let status = send_status(&app.app, Method::DELETE, "/v1/agents/amp", None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert!(!binary.exists());
+
+    let (status, payload) = send_json(&app.app, Method::DELETE, "/v1/agents/amp", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND, "{payload}");
+    let (status, payload) = send_json(&app.app, Method::DELETE, "/v1/agents/mock", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{payload}");
+    let (status, payload) = send_json(&app.app, Method::GET, "/v1/agents/mock/updates", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{payload}");
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn rlimit_fallback_is_reported_on_create() {
+    use sandbox_agent_agent_management::limits::ResourceLimitsConfig;
+    use std::os::unix::fs::PermissionsExt;
+
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    // Not a cgroup, so no per-session cgroup can be created under it.
+    let cgroup_root = tempfile::tempdir().expect("create cgroup root");
+    let manager = AgentManager::new(install_dir.path())
+        .expect("create agent manager")
+        .with_resource_limits(ResourceLimitsConfig {
+            cgroup_root: Some(cgroup_root.path().to_path_buf()),
+            ..ResourceLimitsConfig::default()
+        });
+    let binary = install_dir.path().join("amp");
+    std::fs::write(&binary, "#!/bin/sh\necho 1.0.0\n").expect("write amp");
+    std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).expect("chmod amp");
+    let app = build_router(AppState::new(AuthConfig::disabled(), manager));
+
+    let (status, payload) = send_json(
+        &app,
+        Method::POST,
+        "/v1/sessions/amp-no-cgroup",
+        Some(json!({ "agent": "amp", "limits": { "memoryBytes": 1073741824 } })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{payload}");
+    let fallback = payload["limitsFallback"]
+        .as_str()
+        .expect("fallback reported");
+    assert!(fallback.contains("cannot create a cgroup"), "{fallback}");
+
+    let (status, payload) = send_json(
+        &app,
+        Method::POST,
+        "/v1/sessions/amp-no-limits",
+        Some(json!({ "agent": "amp" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{payload}");
+    assert!(payload.get("limitsFallback").is_none(), "{payload}");
+}
+
+#[cfg(unix)]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn process_limit_hits_are_reported_as_error_events() {
+    use sandbox_agent_agent_management::limits::{
+        adopt_daemon_cgroup, ResourceGuard, ResourceLimits, ResourceLimitsConfig,
+    };
+    use std::os::unix::fs::PermissionsExt;
+
+    let limits = ResourceLimits {
+        max_pids: Some(4),
+        ..ResourceLimits::default()
+    };
+    let cgroup_root = adopt_daemon_cgroup();
+    if ResourceGuard::cgroup_fallback(&limits, cgroup_root.as_deref()).is_some() {
+        eprintln!("skipping: no cgroup v2 pids controller to report limit hits");
+        return;
+    }
+
+    let install_dir = tempfile::tempdir().expect("create temp install dir");
+    let manager = AgentManager::new(install_dir.path())
+        .expect("create agent manager")
+        .with_resource_limits(ResourceLimitsConfig {
+            cgroup_root,
+            ..ResourceLimitsConfig::default()
+        });
+    let app = build_router(AppState::new(AuthConfig::disabled(), manager));
+    let binary = install_dir.path().join("amp");
+    let script = r#"#!/bin/sh
+if [ "$1" = "--version" ]; then
+  echo 1.0.0
+  exit 0
+fi
+for i in 1 2 3 4 5 6 7 8; do
+  sleep 1 &
+done
+wait
+echo '{"type":"done"}'
+"#;
+    std::fs::write(&binary, script).expect("write amp");
+    std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).expect("chmod amp");
+
+    let (status, payload) = send_json(
+        &app,
+        Method::POST,
+        "/v1/sessions/amp-limits",
+        Some(json!({
+            "agent": "amp",
+            "permissionMode": "bypass",
+            "limits": { "maxPids": 4 },
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "create session: {payload}");
+    assert!(payload.get("limitsFallback").is_none(), "{payload}");
+    let status = send_status(
+        &app,
+        Method::POST,
+        "/v1/sessions/amp-limits/messages",
+        Some(json!({ "message": "fork" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let events = poll_events_until_match(&app, "amp-limits", Duration::from_secs(15), |events| {
+        events
+            .iter()
+            .any(|event| event["type"] == "error" && event["data"]["code"] == "resource_limit")
+    })
+    .await;
+    let error = events
+        .iter()
+        .find(|event| event["type"] == "error" && event["data"]["code"] == "resource_limit")
+        .expect("resource limit error event");
+    assert_eq!(error["data"]["details"]["limit"], "pids");
+    assert_eq!(error["data"]["details"]["value"], 4);
+}
+
+async fn send_json_with_token(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    token: &str,
+    body: Value,
+) -> (StatusCode, Value) {
+    let (status, _headers, payload) = send_with_token(app, method, uri, token, body).await;
+    (status, payload)
+}
+
+async fn send_with_token(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    token: &str,
+    body: Value,
+) -> (StatusCode, HeaderMap, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .expect("token request");
+    send_json_request(app, request).await
+}
+
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn cors_snapshots() {
     let cors = CorsLayer::new()
@@ -197,6 +1269,8 @@ async fn create_session_with_skill_sources() {
     std::fs::create_dir_all(&skill_path).expect("create skill subdir");
     std::fs::write(skill_path.join("SKILL.md"), "# Test Skill\nA test skill.")
         .expect("write SKILL.md");
+    // Skills are linked into the session directory, so keep it out of the package
+    let work_dir = tempfile::tempdir().expect("create work dir");
 
     // Create session with local skill source
     let (status, payload) = send_json(
@@ -205,6 +1279,7 @@ async fn create_session_with_skill_sources() {
         "/v1/sessions/skill-test-session",
         Some(json!({
             "agent": "mock",
+            "directory": work_dir.path().to_string_lossy(),
             "skills": {
                 "sources": [
                     {
@@ -228,6 +1303,10 @@ async fn create_session_with_skill_sources() {
             .unwrap_or(false),
         "session should be healthy"
     );
+    assert!(work_dir
+        .path()
+        .join(".claude/skills/my-test-skill/SKILL.md")
+        .exists());
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -242,6 +1321,7 @@ async fn create_session_with_skill_sources_filter() {
     std::fs::create_dir_all(&unwanted).expect("create unwanted dir");
     std::fs::write(wanted.join("SKILL.md"), "# Wanted").expect("write wanted SKILL.md");
     std::fs::write(unwanted.join("SKILL.md"), "# Unwanted").expect("write unwanted SKILL.md");
+    let work_dir = tempfile::tempdir().expect("create work dir");
 
     // Create session with filter
     let (status, payload) = send_json(
@@ -250,6 +1330,7 @@ async fn create_session_with_skill_sources_filter() {
         "/v1/sessions/skill-filter-session",
         Some(json!({
             "agent": "mock",
+            "directory": work_dir.path().to_string_lossy(),
             "skills": {
                 "sources": [
                     {
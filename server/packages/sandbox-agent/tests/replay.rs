@@ -0,0 +1,2 @@
+#[path = "replay/mod.rs"]
+mod replay;
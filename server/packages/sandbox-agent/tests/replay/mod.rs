@@ -0,0 +1,42 @@
+// Converter regression tests: recorded native agent output replayed offline.
+// Record new fixtures with `sandbox-agent api sessions record <SESSION_ID> --out <FILE>`.
+use std::path::Path;
+
+use sandbox_agent::replay::{replay, ReplayFixture};
+use serde_json::Value;
+
+async fn replay_events(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/replay/fixtures")
+        .join(format!("{name}.ndjson"));
+    let fixture = ReplayFixture::load(&path).expect("load fixture");
+    let events = replay(&fixture).await.expect("replay fixture");
+    let mut events = serde_json::to_value(events).expect("serialize events");
+    for event in events.as_array_mut().expect("events array") {
+        assert_ne!(event["type"], "agent.unparsed", "{event}");
+        let event = event.as_object_mut().expect("event object");
+        event.remove("time");
+        event.remove("raw");
+    }
+    events
+}
+
+#[tokio::test]
+async fn replays_claude_stream_json() {
+    insta::assert_yaml_snapshot!(replay_events("claude").await);
+}
+
+#[tokio::test]
+async fn replays_codex_app_server() {
+    insta::assert_yaml_snapshot!(replay_events("codex").await);
+}
+
+#[tokio::test]
+async fn replays_opencode_sse() {
+    insta::assert_yaml_snapshot!(replay_events("opencode").await);
+}
+
+#[tokio::test]
+async fn replays_amp_jsonl() {
+    insta::assert_yaml_snapshot!(replay_events("amp").await);
+}
@@ -73,6 +73,40 @@ async fn shutdown_marks_servers_stopped_and_kills_child() {
     assert!(exited);
 }
 
+#[tokio::test]
+async fn stop_server_stops_only_that_agent() {
+    let harness = TestHarness::new().await;
+    let codex = harness
+        .insert_stdio_server(AgentId::Codex, Some(spawn_sleep_process()), 0)
+        .await;
+    let opencode = harness
+        .insert_stdio_server(AgentId::Opencode, Some(spawn_sleep_process()), 0)
+        .await;
+
+    harness.stop_server(AgentId::Codex).await;
+    harness.stop_server(AgentId::Amp).await;
+
+    assert!(matches!(
+        harness.server_status(AgentId::Codex).await,
+        Some(sandbox_agent::router::ServerStatus::Stopped)
+    ));
+    assert!(matches!(
+        harness.server_status(AgentId::Opencode).await,
+        Some(sandbox_agent::router::ServerStatus::Running)
+    ));
+    wait_for_exit(&codex).await;
+    let exited = |child: &Arc<std::sync::Mutex<Option<std::process::Child>>>| {
+        let mut guard = child.lock().expect("child lock");
+        guard
+            .as_mut()
+            .and_then(|child| child.try_wait().ok().flatten())
+            .is_some()
+    };
+    assert!(exited(&codex));
+    assert!(!exited(&opencode));
+    harness.shutdown().await;
+}
+
 #[tokio::test]
 async fn handle_process_exit_marks_error_and_ends_sessions() {
     let harness = TestHarness::new().await;
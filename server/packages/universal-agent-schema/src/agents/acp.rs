@@ -0,0 +1,402 @@
+//! Agent Client Protocol (ACP) conversion. ACP has no published Rust types we can
+//! extract, so session updates are read from `serde_json::Value` by field name.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::Value;
+
+use crate::{
+    turn_ended_event, ContentPart, EventConversion, ItemDeltaData, ItemEventData, ItemKind,
+    ItemRole, ItemStatus, PermissionEventData, PermissionStatus, ReasoningVisibility,
+    UniversalEventData, UniversalEventType, UniversalItem,
+};
+
+static TEMP_ID: AtomicU64 = AtomicU64::new(1);
+
+fn next_temp_id(prefix: &str) -> String {
+    let id = TEMP_ID.fetch_add(1, Ordering::Relaxed);
+    format!("{prefix}_{id}")
+}
+
+/// Conversion state for one ACP session. Message and thought chunks carry no ids, so
+/// the open message is kept until a tool call or the end of the turn closes it.
+#[derive(Debug, Default)]
+pub struct AcpStream {
+    open_message: Option<OpenMessage>,
+    thought: String,
+}
+
+#[derive(Debug)]
+struct OpenMessage {
+    item_id: String,
+    text: String,
+}
+
+impl AcpStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Converts the `update` of a `session/update` notification.
+    pub fn update_to_universal(
+        &mut self,
+        session_id: &str,
+        update: &Value,
+    ) -> Result<Vec<EventConversion>, String> {
+        let kind = update
+            .get("sessionUpdate")
+            .and_then(Value::as_str)
+            .ok_or("session update missing sessionUpdate")?;
+        let mut events = Vec::new();
+        match kind {
+            // The daemon records the prompt itself.
+            "user_message_chunk" | "available_commands_update" | "current_mode_update" => {}
+            "agent_message_chunk" => {
+                events.extend(self.close_thought());
+                let text = content_text(update.get("content"));
+                let open = match self.open_message.as_mut() {
+                    Some(open) => open,
+                    None => {
+                        let item_id = next_temp_id("tmp_acp_message");
+                        events.push(EventConversion::new(
+                            UniversalEventType::ItemStarted,
+                            UniversalEventData::Item(ItemEventData {
+                                item: message_item(item_id.clone(), String::new(), false),
+                            }),
+                        ));
+                        self.open_message.insert(OpenMessage {
+                            item_id,
+                            text: String::new(),
+                        })
+                    }
+                };
+                if !text.is_empty() {
+                    open.text.push_str(&text);
+                    events.push(EventConversion::new(
+                        UniversalEventType::ItemDelta,
+                        UniversalEventData::ItemDelta(ItemDeltaData {
+                            item_id: open.item_id.clone(),
+                            native_item_id: None,
+                            delta: text,
+                        }),
+                    ));
+                }
+            }
+            "agent_thought_chunk" => {
+                events.extend(self.close_message());
+                self.thought.push_str(&content_text(update.get("content")));
+            }
+            "tool_call" => {
+                events.extend(self.close_open_items());
+                let call_id = string_field(update, "toolCallId")
+                    .unwrap_or_else(|| next_temp_id("tmp_acp_tool"));
+                let name = string_field(update, "title")
+                    .or_else(|| string_field(update, "kind"))
+                    .unwrap_or_else(|| "tool".to_string());
+                let arguments = update
+                    .get("rawInput")
+                    .map(|input| input.to_string())
+                    .unwrap_or_else(|| "{}".to_string());
+                events.push(item_completed(UniversalItem {
+                    item_id: next_temp_id("tmp_acp_tool_call"),
+                    native_item_id: Some(call_id.clone()),
+                    parent_id: None,
+                    kind: ItemKind::ToolCall,
+                    role: Some(ItemRole::Assistant),
+                    content: vec![ContentPart::ToolCall {
+                        name,
+                        arguments,
+                        call_id: call_id.clone(),
+                    }],
+                    status: ItemStatus::Completed,
+                }));
+                events.extend(tool_result(&call_id, update));
+            }
+            "tool_call_update" => {
+                events.extend(self.close_open_items());
+                let call_id = string_field(update, "toolCallId")
+                    .ok_or("tool_call_update missing toolCallId")?;
+                events.extend(tool_result(&call_id, update));
+            }
+            "plan" => {
+                events.extend(self.close_open_items());
+                let entries = update
+                    .get("entries")
+                    .and_then(Value::as_array)
+                    .map(|entries| {
+                        entries
+                            .iter()
+                            .filter_map(|entry| {
+                                let content = entry.get("content")?.as_str()?;
+                                let status = entry
+                                    .get("status")
+                                    .and_then(Value::as_str)
+                                    .unwrap_or("pending");
+                                Some(format!("[{status}] {content}"))
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    })
+                    .unwrap_or_default();
+                events.push(item_completed(UniversalItem {
+                    item_id: String::new(),
+                    native_item_id: None,
+                    parent_id: None,
+                    kind: ItemKind::Status,
+                    role: Some(ItemRole::Assistant),
+                    content: vec![ContentPart::Status {
+                        label: "plan".to_string(),
+                        detail: Some(entries),
+                    }],
+                    status: ItemStatus::Completed,
+                }));
+            }
+            other => return Err(format!("unsupported session update: {other}")),
+        }
+
+        for conversion in &mut events {
+            conversion.native_session_id = Some(session_id.to_string());
+            conversion.raw = Some(update.clone());
+        }
+        Ok(events)
+    }
+
+    /// Closes open items and ends the turn once `session/prompt` returns.
+    pub fn turn_ended(
+        &mut self,
+        session_id: &str,
+        stop_reason: Option<&str>,
+    ) -> Vec<EventConversion> {
+        let mut events = self.close_open_items();
+        let metadata = stop_reason.map(|reason| serde_json::json!({ "stopReason": reason }));
+        events.push(turn_ended_event(None, metadata).synthetic());
+        for conversion in &mut events {
+            conversion.native_session_id = Some(session_id.to_string());
+        }
+        events
+    }
+
+    fn close_open_items(&mut self) -> Vec<EventConversion> {
+        self.close_thought()
+            .into_iter()
+            .chain(self.close_message())
+            .collect()
+    }
+
+    fn close_message(&mut self) -> Option<EventConversion> {
+        let open = self.open_message.take()?;
+        Some(item_completed(message_item(open.item_id, open.text, true)))
+    }
+
+    fn close_thought(&mut self) -> Option<EventConversion> {
+        if self.thought.is_empty() {
+            return None;
+        }
+        let text = std::mem::take(&mut self.thought);
+        Some(item_completed(UniversalItem {
+            item_id: String::new(),
+            native_item_id: None,
+            parent_id: None,
+            kind: ItemKind::Message,
+            role: Some(ItemRole::Assistant),
+            content: vec![ContentPart::Reasoning {
+                text,
+                visibility: ReasoningVisibility::Public,
+            }],
+            status: ItemStatus::Completed,
+        }))
+    }
+}
+
+/// Converts a `session/request_permission` request. The JSON-RPC id and the offered
+/// options are kept in the metadata so the reply can pick an option.
+pub fn permission_request_to_universal(
+    request_id: &Value,
+    params: &Value,
+) -> Result<Vec<EventConversion>, String> {
+    let session_id =
+        string_field(params, "sessionId").ok_or("permission request missing sessionId")?;
+    let tool_call = params.get("toolCall").cloned().unwrap_or(Value::Null);
+    let action = string_field(&tool_call, "kind").unwrap_or_else(|| "tool".to_string());
+    let permission_id = match request_id {
+        Value::String(id) => id.clone(),
+        other => other.to_string(),
+    };
+    let metadata = serde_json::json!({
+        "acpRequestId": request_id,
+        "sessionId": session_id,
+        "toolCallId": tool_call.get("toolCallId"),
+        "title": tool_call.get("title"),
+        "rawInput": tool_call.get("rawInput"),
+        "options": params.get("options").cloned().unwrap_or(Value::Array(Vec::new())),
+    });
+    Ok(vec![EventConversion::new(
+        UniversalEventType::PermissionRequested,
+        UniversalEventData::Permission(PermissionEventData {
+            permission_id,
+            action,
+            status: PermissionStatus::Requested,
+            metadata: Some(metadata),
+        }),
+    )
+    .with_native_session(Some(session_id))])
+}
+
+/// Tool result for a `tool_call` or `tool_call_update` that reached a final status.
+fn tool_result(call_id: &str, update: &Value) -> Option<EventConversion> {
+    let status = update.get("status").and_then(Value::as_str)?;
+    let failed = match status {
+        "completed" => false,
+        "failed" => true,
+        _ => return None,
+    };
+    let mut output = update
+        .get("content")
+        .and_then(Value::as_array)
+        .map(|content| {
+            content
+                .iter()
+                .map(|entry| content_text(entry.get("content")))
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default();
+    if output.is_empty() {
+        output = match update.get("rawOutput") {
+            Some(Value::String(text)) => text.clone(),
+            Some(Value::Null) | None => String::new(),
+            Some(other) => other.to_string(),
+        };
+    }
+    Some(item_completed(UniversalItem {
+        item_id: next_temp_id("tmp_acp_tool_result"),
+        native_item_id: Some(call_id.to_string()),
+        parent_id: None,
+        kind: ItemKind::ToolResult,
+        role: Some(ItemRole::Tool),
+        content: vec![ContentPart::ToolResult {
+            call_id: call_id.to_string(),
+            output,
+        }],
+        status: if failed {
+            ItemStatus::Failed
+        } else {
+            ItemStatus::Completed
+        },
+    }))
+}
+
+/// Text of an ACP content block; other block types contribute nothing.
+fn content_text(content: Option<&Value>) -> String {
+    match content {
+        Some(block) if block.get("type").and_then(Value::as_str) == Some("text") => {
+            string_field(block, "text").unwrap_or_default()
+        }
+        _ => String::new(),
+    }
+}
+
+fn string_field(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+fn message_item(item_id: String, text: String, completed: bool) -> UniversalItem {
+    UniversalItem {
+        item_id,
+        native_item_id: None,
+        parent_id: None,
+        kind: ItemKind::Message,
+        role: Some(ItemRole::Assistant),
+        content: vec![ContentPart::Text { text }],
+        status: if completed {
+            ItemStatus::Completed
+        } else {
+            ItemStatus::InProgress
+        },
+    }
+}
+
+fn item_completed(item: UniversalItem) -> EventConversion {
+    EventConversion::new(
+        UniversalEventType::ItemCompleted,
+        UniversalEventData::Item(ItemEventData { item }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn groups_chunks_and_maps_tool_calls() {
+        let mut stream = AcpStream::new();
+        let first = stream
+            .update_to_universal(
+                "s1",
+                &json!({"sessionUpdate": "agent_message_chunk", "content": {"type": "text", "text": "Hi"}}),
+            )
+            .expect("chunk");
+        let types: Vec<_> = first.iter().map(|e| e.event_type.clone()).collect();
+        assert_eq!(
+            types,
+            [
+                UniversalEventType::ItemStarted,
+                UniversalEventType::ItemDelta
+            ]
+        );
+        assert_eq!(first[0].native_session_id.as_deref(), Some("s1"));
+
+        let tool = stream
+            .update_to_universal(
+                "s1",
+                &json!({"sessionUpdate": "tool_call", "toolCallId": "t1", "title": "Read file", "kind": "read", "status": "pending"}),
+            )
+            .expect("tool call");
+        // Closes the message, then records the call; no result while pending.
+        assert_eq!(tool.len(), 2);
+
+        let done = stream
+            .update_to_universal(
+                "s1",
+                &json!({"sessionUpdate": "tool_call_update", "toolCallId": "t1", "status": "failed",
+                        "content": [{"type": "content", "content": {"type": "text", "text": "denied"}}]}),
+            )
+            .expect("tool update");
+        let UniversalEventData::Item(result) = &done[0].data else {
+            panic!("expected tool result");
+        };
+        assert!(matches!(result.item.status, ItemStatus::Failed));
+        assert!(matches!(
+            &result.item.content[0],
+            ContentPart::ToolResult { output, .. } if output == "denied"
+        ));
+
+        let ended = stream.turn_ended("s1", Some("end_turn"));
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].event_type, UniversalEventType::TurnEnded);
+
+        assert!(stream
+            .update_to_universal("s1", &json!({"sessionUpdate": "mystery"}))
+            .is_err());
+    }
+
+    #[test]
+    fn permission_request_keeps_options() {
+        let events = permission_request_to_universal(
+            &json!(7),
+            &json!({"sessionId": "s1", "toolCall": {"toolCallId": "t1", "kind": "edit"},
+                    "options": [{"optionId": "ok", "name": "Allow", "kind": "allow_once"}]}),
+        )
+        .expect("permission");
+        let UniversalEventData::Permission(data) = &events[0].data else {
+            panic!("expected permission");
+        };
+        assert_eq!(data.permission_id, "7");
+        assert_eq!(data.action, "edit");
+        let metadata = data.metadata.as_ref().expect("metadata");
+        assert_eq!(metadata["acpRequestId"], json!(7));
+        assert_eq!(metadata["options"][0]["optionId"], "ok");
+    }
+}
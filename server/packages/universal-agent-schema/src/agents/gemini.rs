@@ -0,0 +1,304 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde_json::Value;
+
+use crate::gemini as schema;
+use crate::{
+    turn_ended_event, ContentPart, ErrorData, EventConversion, ItemDeltaData, ItemEventData,
+    ItemKind, ItemRole, ItemStatus, SessionEndReason, SessionEndedData, SessionStartedData,
+    TerminatedBy, UniversalEventData, UniversalEventType, UniversalItem,
+};
+
+static TEMP_ID: AtomicU64 = AtomicU64::new(1);
+
+fn next_temp_id(prefix: &str) -> String {
+    let id = TEMP_ID.fetch_add(1, Ordering::Relaxed);
+    format!("{prefix}_{id}")
+}
+
+/// Conversion state for one `gemini --output-format stream-json` process. Gemini
+/// streams assistant text as chunks with no message id, so the open message is kept
+/// here until a tool call or the result closes it.
+#[derive(Debug, Default)]
+pub struct GeminiStream {
+    open_message: Option<OpenMessage>,
+}
+
+#[derive(Debug)]
+struct OpenMessage {
+    item_id: String,
+    text: String,
+}
+
+impl GeminiStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn event_to_universal(
+        &mut self,
+        event: &schema::StreamJsonEvent,
+    ) -> Result<Vec<EventConversion>, String> {
+        let mut events = Vec::new();
+        match event.type_ {
+            schema::StreamJsonEventType::Init => {
+                events.push(
+                    EventConversion::new(
+                        UniversalEventType::SessionStarted,
+                        UniversalEventData::SessionStarted(SessionStartedData {
+                            metadata: Some(serde_json::json!({ "model": event.model })),
+                        }),
+                    )
+                    .with_native_session(event.session_id.clone()),
+                );
+            }
+            schema::StreamJsonEventType::Message => {
+                // The daemon records the prompt itself.
+                if event.role != Some(schema::StreamJsonEventRole::Assistant) {
+                    return Ok(Vec::new());
+                }
+                let text = event.content.clone().unwrap_or_default();
+                let open = match self.open_message.as_mut() {
+                    Some(open) => open,
+                    None => {
+                        let item_id = next_temp_id("tmp_gemini_message");
+                        events.push(EventConversion::new(
+                            UniversalEventType::ItemStarted,
+                            UniversalEventData::Item(ItemEventData {
+                                item: message_item(item_id.clone(), String::new(), false),
+                            }),
+                        ));
+                        self.open_message.insert(OpenMessage {
+                            item_id,
+                            text: String::new(),
+                        })
+                    }
+                };
+                open.text.push_str(&text);
+                if !text.is_empty() {
+                    events.push(EventConversion::new(
+                        UniversalEventType::ItemDelta,
+                        UniversalEventData::ItemDelta(ItemDeltaData {
+                            item_id: open.item_id.clone(),
+                            native_item_id: None,
+                            delta: text,
+                        }),
+                    ));
+                }
+                if event.delta != Some(true) {
+                    events.extend(self.close_message());
+                }
+            }
+            schema::StreamJsonEventType::ToolUse => {
+                events.extend(self.close_message());
+                let call_id = event
+                    .tool_id
+                    .clone()
+                    .unwrap_or_else(|| next_temp_id("tmp_gemini_tool"));
+                let arguments = serde_json::to_string(&Value::Object(event.parameters.clone()))
+                    .unwrap_or_else(|_| "{}".to_string());
+                let item = UniversalItem {
+                    item_id: next_temp_id("tmp_gemini_tool_call"),
+                    native_item_id: Some(call_id.clone()),
+                    parent_id: None,
+                    kind: ItemKind::ToolCall,
+                    role: Some(ItemRole::Assistant),
+                    content: vec![ContentPart::ToolCall {
+                        name: event
+                            .tool_name
+                            .clone()
+                            .unwrap_or_else(|| "unknown".to_string()),
+                        arguments,
+                        call_id,
+                    }],
+                    status: ItemStatus::Completed,
+                };
+                events.push(item_completed(item));
+            }
+            schema::StreamJsonEventType::ToolResult => {
+                events.extend(self.close_message());
+                let call_id = event
+                    .tool_id
+                    .clone()
+                    .unwrap_or_else(|| next_temp_id("tmp_gemini_tool"));
+                let failed = event.status == Some(schema::StreamJsonEventStatus::Error);
+                let output = match (&event.output, &event.error) {
+                    (Some(output), _) => output.clone(),
+                    (None, Some(error)) => error.message.clone(),
+                    (None, None) => String::new(),
+                };
+                let item = UniversalItem {
+                    item_id: next_temp_id("tmp_gemini_tool_result"),
+                    native_item_id: Some(call_id.clone()),
+                    parent_id: None,
+                    kind: ItemKind::ToolResult,
+                    role: Some(ItemRole::Tool),
+                    content: vec![ContentPart::ToolResult { call_id, output }],
+                    status: if failed {
+                        ItemStatus::Failed
+                    } else {
+                        ItemStatus::Completed
+                    },
+                };
+                events.push(item_completed(item));
+            }
+            schema::StreamJsonEventType::Error => {
+                events.push(error_event(
+                    event
+                        .message
+                        .clone()
+                        .unwrap_or_else(|| "gemini error".to_string()),
+                    event,
+                ));
+            }
+            schema::StreamJsonEventType::Result => {
+                events.extend(self.close_message());
+                let failed = event.status == Some(schema::StreamJsonEventStatus::Error);
+                if failed {
+                    let message = event
+                        .error
+                        .as_ref()
+                        .map(|error| error.message.clone())
+                        .unwrap_or_else(|| "gemini error".to_string());
+                    events.push(error_event(message, event));
+                }
+                let metadata = event
+                    .stats
+                    .as_ref()
+                    .and_then(|stats| serde_json::to_value(stats).ok())
+                    .map(|stats| serde_json::json!({ "stats": stats }));
+                events.push(turn_ended_event(None, metadata).synthetic());
+                events.push(EventConversion::new(
+                    UniversalEventType::SessionEnded,
+                    UniversalEventData::SessionEnded(SessionEndedData {
+                        reason: if failed {
+                            SessionEndReason::Error
+                        } else {
+                            SessionEndReason::Completed
+                        },
+                        terminated_by: TerminatedBy::Agent,
+                        message: None,
+                        exit_code: None,
+                        stderr: None,
+                    }),
+                ));
+            }
+        }
+
+        for conversion in &mut events {
+            conversion.raw = serde_json::to_value(event).ok();
+        }
+        Ok(events)
+    }
+
+    /// Completes the streamed assistant message, if one is open.
+    fn close_message(&mut self) -> Option<EventConversion> {
+        let open = self.open_message.take()?;
+        Some(item_completed(message_item(open.item_id, open.text, true)))
+    }
+}
+
+fn message_item(item_id: String, text: String, completed: bool) -> UniversalItem {
+    UniversalItem {
+        item_id,
+        native_item_id: None,
+        parent_id: None,
+        kind: ItemKind::Message,
+        role: Some(ItemRole::Assistant),
+        content: vec![ContentPart::Text { text }],
+        status: if completed {
+            ItemStatus::Completed
+        } else {
+            ItemStatus::InProgress
+        },
+    }
+}
+
+fn item_completed(item: UniversalItem) -> EventConversion {
+    EventConversion::new(
+        UniversalEventType::ItemCompleted,
+        UniversalEventData::Item(ItemEventData { item }),
+    )
+}
+
+fn error_event(message: String, event: &schema::StreamJsonEvent) -> EventConversion {
+    EventConversion::new(
+        UniversalEventType::Error,
+        UniversalEventData::Error(ErrorData {
+            message,
+            code: Some("gemini".to_string()),
+            details: serde_json::to_value(event).ok(),
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(stream: &mut GeminiStream, line: &str) -> Vec<EventConversion> {
+        let event: schema::StreamJsonEvent = serde_json::from_str(line).expect("event");
+        stream.event_to_universal(&event).expect("convert")
+    }
+
+    #[test]
+    fn streams_chunks_into_one_message_closed_by_tool_use() {
+        let mut stream = GeminiStream::new();
+        let init = convert(
+            &mut stream,
+            r#"{"type":"init","session_id":"abc","model":"gemini-2.5-pro"}"#,
+        );
+        assert_eq!(init[0].native_session_id.as_deref(), Some("abc"));
+        assert!(convert(
+            &mut stream,
+            r#"{"type":"message","role":"user","content":"hi"}"#
+        )
+        .is_empty());
+
+        let first = convert(
+            &mut stream,
+            r#"{"type":"message","role":"assistant","content":"Hel","delta":true}"#,
+        );
+        let types: Vec<_> = first.iter().map(|e| e.event_type.clone()).collect();
+        assert_eq!(
+            types,
+            [
+                UniversalEventType::ItemStarted,
+                UniversalEventType::ItemDelta
+            ]
+        );
+        let second = convert(
+            &mut stream,
+            r#"{"type":"message","role":"assistant","content":"lo","delta":true}"#,
+        );
+        assert_eq!(second.len(), 1);
+
+        let tool = convert(
+            &mut stream,
+            r#"{"type":"tool_use","tool_name":"ls","tool_id":"t1","parameters":{}}"#,
+        );
+        let UniversalEventData::Item(message) = &tool[0].data else {
+            panic!("expected completed message");
+        };
+        assert!(matches!(message.item.status, ItemStatus::Completed));
+        assert!(matches!(
+            &message.item.content[0],
+            ContentPart::Text { text } if text == "Hello"
+        ));
+        assert_eq!(tool[1].event_type, UniversalEventType::ItemCompleted);
+
+        let result = convert(
+            &mut stream,
+            r#"{"type":"result","status":"error","error":{"type":"FatalError","message":"quota"}}"#,
+        );
+        let types: Vec<_> = result.iter().map(|e| e.event_type.clone()).collect();
+        assert_eq!(
+            types,
+            [
+                UniversalEventType::Error,
+                UniversalEventType::TurnEnded,
+                UniversalEventType::SessionEnded
+            ]
+        );
+    }
+}
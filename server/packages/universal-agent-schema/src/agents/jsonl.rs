@@ -0,0 +1,380 @@
+//! Conversion for agents that print one JSON object per line in their own format,
+//! driven by a field mapping instead of extracted types.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    turn_ended_event, ContentPart, ErrorData, EventConversion, ItemDeltaData, ItemEventData,
+    ItemKind, ItemRole, ItemStatus, ReasoningVisibility, UniversalEventData, UniversalEventType,
+    UniversalItem,
+};
+
+static TEMP_ID: AtomicU64 = AtomicU64::new(1);
+
+fn next_temp_id(prefix: &str) -> String {
+    let id = TEMP_ID.fetch_add(1, Ordering::Relaxed);
+    format!("{prefix}_{id}")
+}
+
+/// How an agent's JSON lines map onto universal events. Fields are dotted paths into
+/// the event, with numeric segments indexing arrays (`content.0.text`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonlEventMap {
+    /// Field holding the event type that `types` is keyed by.
+    #[serde(default = "default_type_field")]
+    pub type_field: String,
+    /// Field holding the agent's session id, read from any event that has it.
+    #[serde(default)]
+    pub session_id: Option<String>,
+    #[serde(default)]
+    pub types: BTreeMap<String, JsonlEventRule>,
+}
+
+impl Default for JsonlEventMap {
+    fn default() -> Self {
+        Self {
+            type_field: default_type_field(),
+            session_id: None,
+            types: BTreeMap::new(),
+        }
+    }
+}
+
+fn default_type_field() -> String {
+    "type".to_string()
+}
+
+/// What one event type becomes, and where its fields are.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonlEventRule {
+    pub kind: JsonlEventKind,
+    /// Message, delta, reasoning or error text, or a tool result's output.
+    #[serde(default)]
+    pub text: Option<String>,
+    /// Tool call id, shared by a call and its result.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Tool name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Tool arguments; non-string values are passed on as JSON.
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JsonlEventKind {
+    /// Complete assistant message. Closes a message streamed by `delta` events,
+    /// replacing its text when the event has one.
+    Message,
+    /// Chunk of the assistant message, streamed as `item.delta`.
+    Delta,
+    Reasoning,
+    ToolCall,
+    ToolResult,
+    Error,
+    TurnEnded,
+    Ignore,
+}
+
+/// Conversion state for one agent process: the assistant message being streamed.
+#[derive(Debug, Default)]
+pub struct JsonlStream {
+    open_message: Option<OpenMessage>,
+}
+
+#[derive(Debug)]
+struct OpenMessage {
+    item_id: String,
+    text: String,
+}
+
+impl JsonlStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn event_to_universal(
+        &mut self,
+        map: &JsonlEventMap,
+        event: &Value,
+    ) -> Result<Vec<EventConversion>, String> {
+        let event_type = field(event, &map.type_field)
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("missing event type field {}", map.type_field))?;
+        let rule = map
+            .types
+            .get(event_type)
+            .ok_or_else(|| format!("unmapped event type {event_type}"))?;
+        let text = rule
+            .text
+            .as_deref()
+            .and_then(|path| field(event, path))
+            .map(value_text);
+        let id = rule
+            .id
+            .as_deref()
+            .and_then(|path| field(event, path))
+            .map(value_text);
+
+        let mut events = Vec::new();
+        match rule.kind {
+            JsonlEventKind::Ignore => {}
+            JsonlEventKind::Delta => {
+                let delta = text.unwrap_or_default();
+                let open = match self.open_message.as_mut() {
+                    Some(open) => open,
+                    None => {
+                        let item_id = next_temp_id("tmp_jsonl_message");
+                        events.push(EventConversion::new(
+                            UniversalEventType::ItemStarted,
+                            UniversalEventData::Item(ItemEventData {
+                                item: message_item(item_id.clone(), String::new(), false),
+                            }),
+                        ));
+                        self.open_message.insert(OpenMessage {
+                            item_id,
+                            text: String::new(),
+                        })
+                    }
+                };
+                open.text.push_str(&delta);
+                if !delta.is_empty() {
+                    events.push(EventConversion::new(
+                        UniversalEventType::ItemDelta,
+                        UniversalEventData::ItemDelta(ItemDeltaData {
+                            item_id: open.item_id.clone(),
+                            native_item_id: None,
+                            delta,
+                        }),
+                    ));
+                }
+            }
+            JsonlEventKind::Message => {
+                let (item_id, streamed) = match self.open_message.take() {
+                    Some(open) => (open.item_id, open.text),
+                    None => {
+                        let item_id = next_temp_id("tmp_jsonl_message");
+                        events.push(EventConversion::new(
+                            UniversalEventType::ItemStarted,
+                            UniversalEventData::Item(ItemEventData {
+                                item: message_item(item_id.clone(), String::new(), false),
+                            }),
+                        ));
+                        (item_id, String::new())
+                    }
+                };
+                let text = text.unwrap_or(streamed);
+                events.push(item_completed(message_item(item_id, text, true)));
+            }
+            JsonlEventKind::Reasoning => {
+                events.extend(self.close_message());
+                events.push(item_completed(UniversalItem {
+                    item_id: String::new(),
+                    native_item_id: None,
+                    parent_id: None,
+                    kind: ItemKind::Message,
+                    role: Some(ItemRole::Assistant),
+                    content: vec![ContentPart::Reasoning {
+                        text: text.unwrap_or_default(),
+                        visibility: ReasoningVisibility::Public,
+                    }],
+                    status: ItemStatus::Completed,
+                }));
+            }
+            JsonlEventKind::ToolCall => {
+                events.extend(self.close_message());
+                let call_id = id.unwrap_or_else(|| next_temp_id("tmp_jsonl_tool"));
+                let name = rule
+                    .name
+                    .as_deref()
+                    .and_then(|path| field(event, path))
+                    .map(value_text)
+                    .unwrap_or_else(|| "unknown".to_string());
+                let arguments = rule
+                    .arguments
+                    .as_deref()
+                    .and_then(|path| field(event, path))
+                    .map(value_text)
+                    .unwrap_or_else(|| "{}".to_string());
+                events.push(item_completed(UniversalItem {
+                    item_id: next_temp_id("tmp_jsonl_tool_call"),
+                    native_item_id: Some(call_id.clone()),
+                    parent_id: None,
+                    kind: ItemKind::ToolCall,
+                    role: Some(ItemRole::Assistant),
+                    content: vec![ContentPart::ToolCall {
+                        name,
+                        arguments,
+                        call_id,
+                    }],
+                    status: ItemStatus::Completed,
+                }));
+            }
+            JsonlEventKind::ToolResult => {
+                events.extend(self.close_message());
+                let call_id = id.unwrap_or_else(|| next_temp_id("tmp_jsonl_tool"));
+                events.push(item_completed(UniversalItem {
+                    item_id: next_temp_id("tmp_jsonl_tool_result"),
+                    native_item_id: Some(call_id.clone()),
+                    parent_id: None,
+                    kind: ItemKind::ToolResult,
+                    role: Some(ItemRole::Tool),
+                    content: vec![ContentPart::ToolResult {
+                        call_id,
+                        output: text.unwrap_or_default(),
+                    }],
+                    status: ItemStatus::Completed,
+                }));
+            }
+            JsonlEventKind::Error => {
+                events.push(EventConversion::new(
+                    UniversalEventType::Error,
+                    UniversalEventData::Error(ErrorData {
+                        message: text.unwrap_or_else(|| event_type.to_string()),
+                        code: Some(event_type.to_string()),
+                        details: Some(event.clone()),
+                    }),
+                ));
+            }
+            JsonlEventKind::TurnEnded => {
+                events.extend(self.close_message());
+                events.push(turn_ended_event(None, None));
+            }
+        }
+
+        let session_id = map
+            .session_id
+            .as_deref()
+            .and_then(|path| field(event, path))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        for conversion in &mut events {
+            conversion.native_session_id = session_id.clone();
+            conversion.raw = Some(event.clone());
+        }
+        Ok(events)
+    }
+
+    /// Completes the streamed assistant message, if one is open.
+    fn close_message(&mut self) -> Option<EventConversion> {
+        let open = self.open_message.take()?;
+        Some(item_completed(message_item(open.item_id, open.text, true)))
+    }
+}
+
+/// Looks up a dotted path such as `message.content.0.text`.
+fn field<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |current, segment| match current {
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => current.get(segment),
+        })
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn message_item(item_id: String, text: String, completed: bool) -> UniversalItem {
+    UniversalItem {
+        item_id,
+        native_item_id: None,
+        parent_id: None,
+        kind: ItemKind::Message,
+        role: Some(ItemRole::Assistant),
+        content: vec![ContentPart::Text { text }],
+        status: if completed {
+            ItemStatus::Completed
+        } else {
+            ItemStatus::InProgress
+        },
+    }
+}
+
+fn item_completed(item: UniversalItem) -> EventConversion {
+    EventConversion::new(
+        UniversalEventType::ItemCompleted,
+        UniversalEventData::Item(ItemEventData { item }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn maps_fields_to_items_and_session_id() {
+        let map: JsonlEventMap = serde_json::from_value(json!({
+            "sessionId": "meta.session",
+            "types": {
+                "chunk": { "kind": "delta", "text": "text" },
+                "done": { "kind": "message" },
+                "tool": { "kind": "toolCall", "id": "call.id", "name": "call.name", "arguments": "call.input" },
+                "end": { "kind": "turnEnded" },
+                "ping": { "kind": "ignore" },
+            },
+        }))
+        .expect("map");
+        let mut stream = JsonlStream::new();
+        let mut convert = |event: Value| stream.event_to_universal(&map, &event);
+
+        let first = convert(json!({"type": "chunk", "text": "Hel", "meta": {"session": "s1"}}))
+            .expect("chunk");
+        let types: Vec<_> = first.iter().map(|e| e.event_type.clone()).collect();
+        assert_eq!(
+            types,
+            [
+                UniversalEventType::ItemStarted,
+                UniversalEventType::ItemDelta
+            ]
+        );
+        assert_eq!(first[0].native_session_id.as_deref(), Some("s1"));
+        assert_eq!(
+            convert(json!({"type": "chunk", "text": "lo"}))
+                .expect("chunk")
+                .len(),
+            1
+        );
+        let done = convert(json!({"type": "done"})).expect("done");
+        let UniversalEventData::Item(message) = &done[0].data else {
+            panic!("expected completed message");
+        };
+        assert!(matches!(
+            &message.item.content[0],
+            ContentPart::Text { text } if text == "Hello"
+        ));
+
+        let tool = convert(json!({
+            "type": "tool",
+            "call": {"id": "c1", "name": "ls", "input": {"path": "."}},
+        }))
+        .expect("tool");
+        let UniversalEventData::Item(call) = &tool[0].data else {
+            panic!("expected tool call");
+        };
+        assert!(matches!(
+            &call.item.content[0],
+            ContentPart::ToolCall { name, arguments, call_id }
+                if name == "ls" && arguments == r#"{"path":"."}"# && call_id == "c1"
+        ));
+
+        assert!(convert(json!({"type": "ping"})).expect("ping").is_empty());
+        assert_eq!(
+            convert(json!({"type": "end"})).expect("end")[0].event_type,
+            UniversalEventType::TurnEnded
+        );
+        assert!(convert(json!({"type": "other"})).is_err());
+    }
+}
@@ -1,4 +1,7 @@
+pub mod acp;
 pub mod amp;
 pub mod claude;
 pub mod codex;
+pub mod gemini;
+pub mod jsonl;
 pub mod opencode;
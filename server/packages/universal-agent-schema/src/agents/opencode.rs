@@ -8,6 +8,36 @@ use crate::{
     UniversalItem,
 };
 
+/// Parses an SSE payload into the variant named by its `type`. The generated `Event`
+/// is untagged and several variants share the same shape, so deserializing it directly
+/// picks the first variant that fits (usually `ServerConnected`).
+pub fn parse_event(value: Value) -> Result<schema::Event, String> {
+    fn typed<T: serde::de::DeserializeOwned>(
+        value: Value,
+        variant: fn(T) -> schema::Event,
+    ) -> Result<schema::Event, String> {
+        serde_json::from_value(value)
+            .map(variant)
+            .map_err(|err| err.to_string())
+    }
+    let event_type = value
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+    match event_type.as_str() {
+        "message.updated" => typed(value, schema::Event::MessageUpdated),
+        "message.part.updated" => typed(value, schema::Event::MessagePartUpdated),
+        "question.asked" => typed(value, schema::Event::QuestionAsked),
+        "permission.asked" => typed(value, schema::Event::PermissionAsked),
+        "session.created" => typed(value, schema::Event::SessionCreated),
+        "session.status" => typed(value, schema::Event::SessionStatus),
+        "session.idle" => typed(value, schema::Event::SessionIdle),
+        "session.error" => typed(value, schema::Event::SessionError),
+        _ => serde_json::from_value(value).map_err(|err| err.to_string()),
+    }
+}
+
 pub fn event_to_universal(event: &schema::Event) -> Result<Vec<EventConversion>, String> {
     let raw = serde_json::to_value(event).ok();
     match event {
@@ -618,4 +648,35 @@ mod tests {
                 if text == "Preparing friendly brief response"
         ));
     }
+
+    #[test]
+    fn parse_event_uses_the_type_field() {
+        let event = parse_event(serde_json::json!({
+            "type": "session.idle",
+            "properties": { "sessionID": "ses_1" }
+        }))
+        .expect("parses");
+        assert!(matches!(event, schema::Event::SessionIdle(_)));
+
+        let err = parse_event(serde_json::json!({
+            "type": "message.part.updated",
+            "properties": { "part": { "id": "prt_1" } }
+        }))
+        .unwrap_err();
+        assert!(err.contains("enum Part"), "{err}");
+    }
+
+    #[test]
+    fn parsed_session_status_converts() {
+        let value = serde_json::json!({
+            "type": "session.status",
+            "properties": { "sessionID": "ses_1", "status": { "type": "busy" } }
+        });
+        let untagged: schema::Event = serde_json::from_value(value.clone()).expect("untagged");
+        assert!(event_to_universal(&untagged).is_err());
+
+        let event = parse_event(value).expect("parses");
+        assert!(matches!(event, schema::Event::SessionStatus(_)));
+        event_to_universal(&event).expect("conversion succeeds");
+    }
 }